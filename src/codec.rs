@@ -20,6 +20,7 @@ use std::fmt::{Debug, Display};
 use tremor_script::Value;
 pub(crate) mod binary;
 pub(crate) mod binflux;
+pub(crate) mod columnar;
 pub(crate) mod csv;
 pub(crate) mod dogstatsd;
 pub(crate) mod influx;
@@ -86,6 +87,23 @@ pub trait Codec: Send + Sync {
     /// otherwise we cannot use this type inside structs that need to be `Clone`.
     /// See: `crate::codec::rest::BoxedCodec`
     fn boxed_clone(&self) -> Box<dyn Codec>;
+
+    /// Returns `true` if this codec buffers encoded data and only returns it from time to time,
+    /// like columnar file formats do. Empty data returned from `encode` is not postprocessed for those codecs.
+    fn is_buffering(&self) -> bool {
+        false
+    }
+
+    /// Finishes the current output, returning all data still buffered, e.g. the last row group and the footer of a file.
+    /// Encoding after this starts a new output.
+    ///
+    /// Returns `None` if there is nothing to finish.
+    ///
+    /// # Errors
+    ///  * If the buffered data can't be encoded
+    fn finish(&self) -> Result<Option<Vec<u8>>> {
+        Ok(None)
+    }
 }
 
 impl Display for dyn Codec {
@@ -117,8 +135,18 @@ pub fn resolve(config: &config::Codec) -> Result<Box<dyn Codec>> {
         "dogstatsd" => Ok(Box::new(dogstatsd::DogStatsD {})),
         "yaml" => Ok(Box::new(yaml::Yaml {})),
         "binary" => Ok(Box::new(binary::Binary {})),
-        "syslog" => Ok(Box::new(syslog::Syslog::utcnow())),
+        "syslog" => Ok(Box::new(syslog::Syslog::from_config(
+            config.config.as_ref(),
+        )?)),
         "csv" => Ok(Box::new(csv::Csv {})),
+        "parquet" => Ok(Box::new(columnar::Columnar::from_config(
+            columnar::Format::Parquet,
+            config.config.as_ref(),
+        )?)),
+        "arrow" => Ok(Box::new(columnar::Columnar::from_config(
+            columnar::Format::Arrow,
+            config.config.as_ref(),
+        )?)),
         s => Err(ErrorKind::CodecNotFound(s.into()).into()),
     }
 }
@@ -139,6 +167,8 @@ mod test {
         assert!(super::resolve(&"dogstatsd".into()).is_ok());
         assert!(super::resolve(&"yaml".into()).is_ok());
         assert!(super::resolve(&"syslog".into()).is_ok());
+        assert!(super::resolve(&"parquet".into()).is_ok());
+        assert!(super::resolve(&"arrow".into()).is_ok());
         assert!(super::resolve(&"snot".into()).is_err(),);
     }
 }
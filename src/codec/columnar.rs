@@ -0,0 +1,496 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Columnar codecs, writing records into `parquet` or Arrow IPC files
+//!
+//! Records are buffered and written as a row group (or record batch) every `row_group_size` records.
+//! `encode` returns the bytes of the file written so far, `Codec::finish` writes the remaining
+//! records and the file footer. Sinks finish the file when the stream, object or file is finished.
+//!
+//! The schema is either declared in the codec config or inferred from the first row group,
+//! columns are sorted by name. Values not matching the type of their column are written as `null`.
+
+use crate::codec::prelude::*;
+use arrow::array::{
+    ArrayRef, BooleanBuilder, Float64Builder, Int64Builder, StringBuilder, UInt64Builder,
+};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use arrow::ipc::writer::FileWriter;
+use arrow::record_batch::RecordBatch;
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+
+/// The file format to write
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Format {
+    Parquet,
+    Arrow,
+}
+
+/// Type of a column
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum ColumnType {
+    Boolean,
+    Int64,
+    Uint64,
+    Float64,
+    String,
+}
+
+impl From<ColumnType> for DataType {
+    fn from(t: ColumnType) -> Self {
+        match t {
+            ColumnType::Boolean => DataType::Boolean,
+            ColumnType::Int64 => DataType::Int64,
+            ColumnType::Uint64 => DataType::UInt64,
+            ColumnType::Float64 => DataType::Float64,
+            ColumnType::String => DataType::Utf8,
+        }
+    }
+}
+
+/// Compression of parquet column chunks
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+enum ParquetCompression {
+    None,
+    #[default]
+    Snappy,
+    Zstd,
+}
+
+impl From<ParquetCompression> for Compression {
+    fn from(c: ParquetCompression) -> Self {
+        match c {
+            ParquetCompression::None => Compression::UNCOMPRESSED,
+            ParquetCompression::Snappy => Compression::SNAPPY,
+            ParquetCompression::Zstd => Compression::ZSTD,
+        }
+    }
+}
+
+fn default_row_group_size() -> usize {
+    10_000
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+struct Config {
+    /// column name to column type (`boolean`, `int64`, `uint64`, `float64` or `string`),
+    /// inferred from the first row group if not set
+    #[serde(default = "Default::default")]
+    schema: Option<BTreeMap<String, ColumnType>>,
+    /// number of records per row group
+    #[serde(default = "default_row_group_size")]
+    row_group_size: usize,
+    /// compression of column chunks, only used for `parquet`
+    #[serde(default = "Default::default")]
+    compression: ParquetCompression,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            schema: None,
+            row_group_size: default_row_group_size(),
+            compression: ParquetCompression::default(),
+        }
+    }
+}
+
+/// A writer target shared between the codec and the file writer, so written bytes can be taken out
+#[derive(Clone, Default)]
+struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl SharedBuffer {
+    fn take(&self) -> Vec<u8> {
+        self.0
+            .lock()
+            .map(|mut buf| std::mem::take(&mut *buf))
+            .unwrap_or_default()
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0
+            .lock()
+            .map_err(|_| io::Error::new(io::ErrorKind::Other, "buffer lock poisoned"))?
+            .extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+enum Writer {
+    Parquet(ArrowWriter<SharedBuffer>),
+    Arrow(FileWriter<SharedBuffer>),
+}
+
+#[derive(Default)]
+struct State {
+    schema: Option<SchemaRef>,
+    rows: Vec<Value<'static>>,
+    writer: Option<Writer>,
+    buffer: SharedBuffer,
+}
+
+pub(crate) struct Columnar {
+    format: Format,
+    config: Config,
+    state: Mutex<State>,
+}
+
+impl Columnar {
+    pub(crate) fn from_config(format: Format, config: Option<&Value>) -> Result<Self> {
+        let config: Config = if let Some(config) = config {
+            tremor_value::structurize(config.clone()).map_err(|e| {
+                let kind = ErrorKind::InvalidConfiguration(
+                    format!("\"{}\" codec", format.name()),
+                    e.to_string(),
+                );
+                Error::with_chain(e, kind)
+            })?
+        } else {
+            Config::default()
+        };
+        if config.row_group_size == 0 {
+            return Err(ErrorKind::InvalidConfiguration(
+                format!("\"{}\" codec", format.name()),
+                "`row_group_size` must be > 0".to_string(),
+            )
+            .into());
+        }
+        Ok(Self::new(format, config))
+    }
+
+    fn new(format: Format, config: Config) -> Self {
+        let schema = config.schema.as_ref().map(|columns| {
+            Arc::new(Schema::new(
+                columns
+                    .iter()
+                    .map(|(name, t)| Field::new(name, DataType::from(*t), true))
+                    .collect(),
+            ))
+        });
+        Self {
+            format,
+            config,
+            state: Mutex::new(State {
+                schema,
+                ..State::default()
+            }),
+        }
+    }
+
+    fn writer(&self, buffer: SharedBuffer, schema: &SchemaRef) -> Result<Writer> {
+        Ok(match self.format {
+            Format::Parquet => {
+                let props = WriterProperties::builder()
+                    .set_max_row_group_size(self.config.row_group_size)
+                    .set_compression(self.config.compression.into())
+                    .build();
+                Writer::Parquet(ArrowWriter::try_new(buffer, schema.clone(), Some(props))?)
+            }
+            Format::Arrow => Writer::Arrow(FileWriter::try_new(buffer, schema)?),
+        })
+    }
+
+    /// Write the buffered rows as a row group, starting a new file if none is in progress
+    fn write_rows(&self, state: &mut State) -> Result<()> {
+        let schema = if let Some(schema) = state.schema.clone() {
+            schema
+        } else {
+            let schema = Arc::new(infer_schema(&state.rows));
+            state.schema = Some(schema.clone());
+            schema
+        };
+        if state.writer.is_none() {
+            state.writer = Some(self.writer(state.buffer.clone(), &schema)?);
+        }
+        if state.rows.is_empty() {
+            return Ok(());
+        }
+        let columns = schema
+            .fields()
+            .iter()
+            .map(|field| column(field, &state.rows))
+            .collect();
+        let batch = RecordBatch::try_new(schema, columns)?;
+        state.rows.clear();
+        match state.writer.as_mut() {
+            Some(Writer::Parquet(writer)) => {
+                writer.write(&batch)?;
+                writer.flush()?;
+            }
+            Some(Writer::Arrow(writer)) => writer.write(&batch)?,
+            None => (),
+        }
+        Ok(())
+    }
+}
+
+impl Format {
+    fn name(self) -> &'static str {
+        match self {
+            Format::Parquet => "parquet",
+            Format::Arrow => "arrow",
+        }
+    }
+}
+
+impl Codec for Columnar {
+    fn name(&self) -> &str {
+        self.format.name()
+    }
+
+    fn mime_types(&self) -> Vec<&'static str> {
+        match self.format {
+            Format::Parquet => vec!["application/vnd.apache.parquet"],
+            Format::Arrow => vec!["application/vnd.apache.arrow.file"],
+        }
+    }
+
+    fn decode<'input>(
+        &mut self,
+        _data: &'input mut [u8],
+        _ingest_ns: u64,
+    ) -> Result<Option<Value<'input>>> {
+        Err(format!("The {} codec does not support decoding", self.name()).into())
+    }
+
+    fn encode(&self, data: &Value) -> Result<Vec<u8>> {
+        if !data.is_object() {
+            return Err(format!("The {} codec can only encode records", self.name()).into());
+        }
+        let mut state = self
+            .state
+            .lock()
+            .map_err(|_| format!("The {} codec state is poisoned", self.name()))?;
+        state.rows.push(data.clone_static());
+        if state.rows.len() >= self.config.row_group_size {
+            self.write_rows(&mut state)?;
+        }
+        Ok(state.buffer.take())
+    }
+
+    fn is_buffering(&self) -> bool {
+        true
+    }
+
+    fn finish(&self) -> Result<Option<Vec<u8>>> {
+        let mut state = self
+            .state
+            .lock()
+            .map_err(|_| format!("The {} codec state is poisoned", self.name()))?;
+        if state.writer.is_none() && state.rows.is_empty() {
+            return Ok(None);
+        }
+        self.write_rows(&mut state)?;
+        match state.writer.take() {
+            Some(Writer::Parquet(writer)) => {
+                writer.close()?;
+            }
+            Some(Writer::Arrow(mut writer)) => writer.finish()?,
+            None => (),
+        }
+        if self.config.schema.is_none() {
+            // the next file gets its own inferred schema
+            state.schema = None;
+        }
+        Ok(Some(state.buffer.take()))
+    }
+
+    fn boxed_clone(&self) -> Box<dyn Codec> {
+        Box::new(Self::new(self.format, self.config.clone()))
+    }
+}
+
+/// Infer the column type from the first non-null value of every field
+fn infer_schema(rows: &[Value<'static>]) -> Schema {
+    let mut columns: BTreeMap<String, Option<DataType>> = BTreeMap::new();
+    for row in rows.iter().filter_map(ValueAccess::as_object) {
+        for (name, value) in row.iter() {
+            let column = columns.entry(name.to_string()).or_default();
+            if column.is_none() {
+                *column = data_type(value);
+            }
+        }
+    }
+    Schema::new(
+        columns
+            .into_iter()
+            .map(|(name, t)| Field::new(&name, t.unwrap_or(DataType::Utf8), true))
+            .collect(),
+    )
+}
+
+fn data_type(value: &Value) -> Option<DataType> {
+    match value.value_type() {
+        ValueType::Null => None,
+        ValueType::Bool => Some(DataType::Boolean),
+        ValueType::I64 => Some(DataType::Int64),
+        ValueType::U64 => Some(DataType::UInt64),
+        ValueType::F64 => Some(DataType::Float64),
+        _ => Some(DataType::Utf8),
+    }
+}
+
+fn column(field: &Field, rows: &[Value<'static>]) -> ArrayRef {
+    let values = rows.iter().map(|row| row.get(field.name().as_str()));
+    match field.data_type() {
+        DataType::Boolean => {
+            let mut builder = BooleanBuilder::with_capacity(rows.len());
+            for value in values {
+                builder.append_option(value.and_then(ValueAccess::as_bool));
+            }
+            Arc::new(builder.finish())
+        }
+        DataType::Int64 => {
+            let mut builder = Int64Builder::with_capacity(rows.len());
+            for value in values {
+                builder.append_option(value.and_then(ValueAccess::as_i64));
+            }
+            Arc::new(builder.finish())
+        }
+        DataType::UInt64 => {
+            let mut builder = UInt64Builder::with_capacity(rows.len());
+            for value in values {
+                builder.append_option(value.and_then(ValueAccess::as_u64));
+            }
+            Arc::new(builder.finish())
+        }
+        DataType::Float64 => {
+            let mut builder = Float64Builder::with_capacity(rows.len());
+            for value in values {
+                builder.append_option(value.and_then(ValueAccess::cast_f64));
+            }
+            Arc::new(builder.finish())
+        }
+        _ => {
+            let mut builder = StringBuilder::new();
+            for value in values {
+                // non-string values are written as their JSON representation
+                builder.append_option(
+                    value
+                        .filter(|v| !v.is_null())
+                        .map(|v| v.as_str().map_or_else(|| v.encode(), ToString::to_string)),
+                );
+            }
+            Arc::new(builder.finish())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{Array, Int64Array, StringArray};
+    use arrow::ipc::reader::FileReader;
+    use bytes::Bytes;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+    use tremor_value::literal;
+
+    fn encode_all(codec: &dyn Codec, values: &[Value]) -> Result<Vec<u8>> {
+        let mut data = Vec::new();
+        for value in values {
+            data.append(&mut codec.encode(value)?);
+        }
+        data.append(&mut codec.finish()?.unwrap_or_default());
+        Ok(data)
+    }
+
+    #[test]
+    fn parquet_inferred_schema() -> Result<()> {
+        let codec = Columnar::from_config(Format::Parquet, Some(&literal!({"row_group_size": 2})))?;
+        let data = encode_all(
+            &codec,
+            &[
+                literal!({"id": 1, "name": "snot"}),
+                literal!({"id": 2, "name": null}),
+                literal!({"id": 3, "name": "badger", "tags": ["a"]}),
+            ],
+        )?;
+        let reader = ParquetRecordBatchReaderBuilder::try_new(Bytes::from(data))?;
+        assert_eq!(2, reader.metadata().num_row_groups());
+        let batches = reader
+            .build()?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        let rows: usize = batches.iter().map(RecordBatch::num_rows).sum();
+        assert_eq!(3, rows);
+        let schema = batches[0].schema();
+        let names: Vec<_> = schema.fields().iter().map(Field::name).collect();
+        assert_eq!(vec!["id", "name"], names);
+        let ids = batches[0]
+            .column(0)
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .ok_or("not an int64 column")?;
+        assert_eq!(vec![Some(1), Some(2)], ids.iter().collect::<Vec<_>>());
+
+        // nothing buffered, nothing to finish
+        assert_eq!(None, codec.finish()?);
+        Ok(())
+    }
+
+    #[test]
+    fn arrow_declared_schema() -> Result<()> {
+        let codec = Columnar::from_config(
+            Format::Arrow,
+            Some(&literal!({"schema": {"name": "string", "value": "float64"}})),
+        )?;
+        let data = encode_all(
+            &codec,
+            &[
+                literal!({"name": "snot", "value": 1.5, "ignored": true}),
+                literal!({"name": {"nested": 1}, "value": 2}),
+            ],
+        )?;
+        let reader = FileReader::try_new(io::Cursor::new(data), None)?;
+        let batches = reader.collect::<std::result::Result<Vec<_>, _>>()?;
+        assert_eq!(1, batches.len());
+        let batch = &batches[0];
+        assert_eq!(2, batch.num_columns());
+        let names = batch
+            .column(0)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .ok_or("not a string column")?;
+        assert_eq!("snot", names.value(0));
+        assert_eq!(r#"{"nested":1}"#, names.value(1));
+        assert_eq!(0, batch.column(1).null_count());
+        Ok(())
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(
+            Columnar::from_config(Format::Parquet, Some(&literal!({"row_group_size": 0}))).is_err()
+        );
+        assert!(
+            Columnar::from_config(Format::Parquet, Some(&literal!({"schema": {"a": "snot"}})))
+                .is_err()
+        );
+        let codec = Columnar::new(Format::Arrow, Config::default());
+        assert!(codec.encode(&literal!([1, 2, 3])).is_err());
+    }
+}
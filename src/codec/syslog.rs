@@ -13,12 +13,48 @@
 // limitations under the License.
 
 use super::prelude::*;
-use chrono::{DateTime, Datelike, Offset, TimeZone, Utc};
+use beef::Cow;
+use chrono::{DateTime, Datelike, FixedOffset, Offset, TimeZone, Utc};
 use syslog_loose::{IncompleteDate, ProcId, Protocol, SyslogFacility, SyslogSeverity};
 use tremor_value::Value;
 
 const DEFAULT_PRI: i32 = 13;
 
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Config {
+    /// UTC offset of RFC 3164 timestamps, which don't carry one, e.g. `+02:00`. Defaults to UTC.
+    /// Only fixed offsets are supported, no time zone names like `Europe/Berlin` and no daylight saving time.
+    #[serde(default)]
+    timezone: Option<String>,
+    /// Year of RFC 3164 timestamps, which don't carry one.
+    /// If not set, the current year is used, or the previous one for December messages received in January.
+    #[serde(default)]
+    year: Option<i32>,
+}
+
+/// parse a UTC offset like `+02:00`, `-0530`, `Z` or `UTC`
+fn parse_offset(s: &str) -> Option<FixedOffset> {
+    if s == "Z" || s.eq_ignore_ascii_case("UTC") {
+        return FixedOffset::east_opt(0);
+    }
+    let (sign, rest) = match s.as_bytes().first()? {
+        b'+' => (1, s.get(1..)?),
+        b'-' => (-1, s.get(1..)?),
+        _ => return None,
+    };
+    let digits = rest.replace(':', "");
+    if digits.len() != 4 || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let hours: i32 = digits.get(..2)?.parse().ok()?;
+    let minutes: i32 = digits.get(2..)?.parse().ok()?;
+    if minutes >= 60 {
+        return None;
+    }
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+}
+
 pub trait Now: Send + Sync + Clone {
     fn now(&self) -> DateTime<Utc>;
 }
@@ -37,13 +73,35 @@ where
     N: Now,
 {
     now: N,
+    /// offset of RFC 3164 timestamps
+    tz: FixedOffset,
+    /// fixed year of RFC 3164 timestamps
+    year: Option<i32>,
 }
 
 impl Syslog<UtcNow> {
-    /// construct a Syslog codec
-    /// that adds the current time in UTC during encoding if none was provided in the event payload
-    pub fn utcnow() -> Self {
-        Self { now: UtcNow {} }
+    /// construct a Syslog codec from its config
+    /// that adds the current time during encoding if none was provided in the event payload
+    pub(crate) fn from_config(config: Option<&Value>) -> Result<Self> {
+        let config: Config = if let Some(config) = config {
+            tremor_value::structurize(config.clone()).map_err(|e| {
+                let kind =
+                    ErrorKind::InvalidConfiguration("\"syslog\" codec".to_string(), e.to_string());
+                Error::with_chain(e, kind)
+            })?
+        } else {
+            Config::default()
+        };
+        let tz = match config.timezone.as_deref() {
+            Some(timezone) => parse_offset(timezone).ok_or_else(|| {
+                ErrorKind::InvalidConfiguration(
+                    "\"syslog\" codec".to_string(),
+                    format!("Invalid `timezone` {timezone}, expected an offset like `+02:00`"),
+                )
+            })?,
+            None => Utc.fix(),
+        };
+        Ok(Self::new(UtcNow {}, tz, config.year))
     }
 }
 
@@ -51,6 +109,10 @@ impl<N> Syslog<N>
 where
     N: Now,
 {
+    fn new(now: N, tz: FixedOffset, year: Option<i32>) -> Self {
+        Self { now, tz, year }
+    }
+
     /// encode structured data `sd` into `result`
     fn encode_sd(sd: &Value, result: &mut Vec<String>) -> Result<()> {
         let sd = sd.as_object().ok_or_else(|| {
@@ -83,7 +145,7 @@ where
                     elem.push_str(k);
                     elem.push('=');
                     elem.push('"');
-                    elem.push_str(value);
+                    escape_param_value(value, &mut elem);
                     elem.push('"');
                 }
             }
@@ -105,7 +167,8 @@ where
         };
         let datetime = data
             .get_i64("timestamp")
-            .map_or_else(|| self.now.now(), |t| Utc.timestamp_nanos(t));
+            .map_or_else(|| self.now.now(), |t| Utc.timestamp_nanos(t))
+            .with_timezone(&self.tz);
         result.push(format!("<{}>{}", pri, datetime.format("%b %e %H:%M:%S")));
 
         result.push(
@@ -198,10 +261,12 @@ where
         _ingest_ns: u64,
     ) -> Result<Option<Value<'input>>> {
         let line: &str = std::str::from_utf8(data)?;
+        let now = self.now.now().with_timezone(&self.tz);
+        let year = self.year;
         let parsed = syslog_loose::parse_message_with_year_tz(
             line,
-            resolve_year,
-            Some(chrono::offset::Utc.fix()),
+            move |date| year.unwrap_or_else(|| resolve_year(date, now)),
+            Some(self.tz),
         );
 
         let mut decoded = Value::object_with_capacity(11);
@@ -240,7 +305,7 @@ where
                 let mut e = Vec::with_capacity(element.params.len());
                 for (name, value) in element.params {
                     let mut param = Value::object_with_capacity(1);
-                    param.try_insert(name, value);
+                    param.try_insert(name, unescape_param_value(value));
                     e.push(param);
                 }
                 temp.try_insert(element.id, Value::from(e));
@@ -288,8 +353,10 @@ where
 // Function used to resolve the year for syslog messages that don't include the year.
 // If the current month is January, and the syslog message is for December, it will take the previous year.
 // Otherwise, take the current year.
-fn resolve_year((month, _date, _hour, _min, _sec): IncompleteDate) -> i32 {
-    let now = Utc::now();
+fn resolve_year(
+    (month, _date, _hour, _min, _sec): IncompleteDate,
+    now: DateTime<FixedOffset>,
+) -> i32 {
     if now.month() == 1 && month == 12 {
         now.year() - 1
     } else {
@@ -297,6 +364,32 @@ fn resolve_year((month, _date, _hour, _min, _sec): IncompleteDate) -> i32 {
     }
 }
 
+// Escape `"`, `\` and `]` in structured data param values, as in RFC 5424 section 6.3.3
+fn escape_param_value(value: &str, result: &mut String) {
+    for c in value.chars() {
+        if matches!(c, '"' | '\\' | ']') {
+            result.push('\\');
+        }
+        result.push(c);
+    }
+}
+
+// Unescape structured data param values, other backslashes are kept as they are.
+fn unescape_param_value(value: &str) -> Cow<str> {
+    if !value.contains('\\') {
+        return Cow::borrowed(value);
+    }
+    let mut result = String::with_capacity(value.len());
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\\' && matches!(chars.peek(), Some('"' | '\\' | ']')) {
+            continue;
+        }
+        result.push(c);
+    }
+    Cow::owned(result)
+}
+
 // Convert string to its respective syslog severity representation.
 fn to_severity(s: &str) -> Result<SyslogSeverity> {
     match s {
@@ -368,7 +461,7 @@ mod test {
     }
 
     fn test_codec() -> Syslog<TestNow> {
-        Syslog { now: TestNow {} }
+        Syslog::new(TestNow {}, Utc.fix(), None)
     }
 
     #[test]
@@ -383,6 +476,74 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn sd_escaping() -> Result<()> {
+        let mut s = br#"<165>1 2003-10-11T22:14:15.003Z mymachine.example.com evntslog - ID47 [exampleSDID@32473 quote="a \"b\"" path="c:\\snot" bracket="[x\]" other="\n"] message"#.to_vec();
+        let mut codec = test_codec();
+        let decoded = codec.decode(s.as_mut_slice(), 0)?.unwrap_or_default();
+        assert_eq!(
+            literal!({
+                "exampleSDID@32473": [
+                    {"quote": "a \"b\""},
+                    {"path": "c:\\snot"},
+                    {"bracket": "[x]"},
+                    {"other": "\\n"}
+                ]
+            }),
+            decoded["structured_data"]
+        );
+        let mut encoded = codec.encode(&decoded)?;
+        assert_eq!(
+            r#"<165>1 2003-10-11T22:14:15.003+00:00 mymachine.example.com evntslog - ID47 [exampleSDID@32473 quote="a \"b\"" path="c:\\snot" bracket="[x\]" other="\\n"] message"#,
+            std::str::from_utf8(&encoded)?
+        );
+        let decoded2 = codec.decode(&mut encoded, 0)?.unwrap_or_default();
+        assert_eq!(decoded, decoded2);
+        Ok(())
+    }
+
+    #[test]
+    fn rfc3164_timezone_and_year() -> Result<()> {
+        let config = literal!({"timezone": "+02:00", "year": 2019});
+        let mut codec = Syslog::from_config(Some(&config))?;
+        let mut s = b"<34>Oct 11 22:14:15 mymachine su: 'su root' failed".to_vec();
+        let decoded = codec.decode(s.as_mut_slice(), 0)?.unwrap_or_default();
+        let expected = chrono::DateTime::parse_from_rfc3339("2019-10-11T20:14:15Z")?;
+        assert_eq!(
+            Some(expected.timestamp_nanos()),
+            decoded.get_i64("timestamp")
+        );
+        // encoded in the same timezone
+        let encoded = codec.encode(&decoded)?;
+        assert_eq!(
+            "<34>Oct 11 22:14:15 mymachine su: 'su root' failed",
+            std::str::from_utf8(&encoded)?
+        );
+
+        // december messages received in january are from the previous year
+        let january = FixedOffset::east_opt(0)
+            .and_then(|tz| tz.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).single())
+            .ok_or("invalid date")?;
+        assert_eq!(2019, resolve_year((12, 31, 23, 59, 59), january));
+        assert_eq!(2020, resolve_year((1, 1, 0, 0, 0), january));
+
+        assert!(Syslog::from_config(Some(&literal!({"timezone": "CEST"}))).is_err());
+        assert!(Syslog::from_config(Some(&literal!({"snot": "badger"}))).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn offsets() {
+        assert_eq!(FixedOffset::east_opt(0), parse_offset("Z"));
+        assert_eq!(FixedOffset::east_opt(0), parse_offset("utc"));
+        assert_eq!(FixedOffset::east_opt(7200), parse_offset("+02:00"));
+        assert_eq!(FixedOffset::west_opt(19800), parse_offset("-0530"));
+        assert_eq!(None, parse_offset("+2"));
+        assert_eq!(None, parse_offset("02:00"));
+        assert_eq!(None, parse_offset("+02:60"));
+        assert_eq!(None, parse_offset("+ab:cd"));
+    }
+
     #[test]
     fn test_decode_empty() -> Result<()> {
         let mut s = b"<191>1 2021-03-18T20:30:00.123Z - - - - - message".to_vec();
@@ -22,8 +22,9 @@ pub(crate) mod sink;
 /// source parts
 pub(crate) mod source;
 
+/// utilities shared by connectors
 #[macro_use]
-pub(crate) mod utils;
+pub mod utils;
 
 mod google;
 #[cfg(test)]
@@ -37,6 +38,7 @@ pub(crate) use crate::config::Connector as ConnectorConfig;
 use crate::pipeline;
 use crate::system::flow;
 use crate::system::{KillSwitch, World};
+use crate::tap::Tap;
 use crate::{errors::connector_send_err, instance::State};
 use crate::{
     errors::{Error, Kind as ErrorKind, Result},
@@ -50,10 +52,14 @@ use async_std::{
 use beef::Cow;
 use futures::Future;
 use halfbrown::HashMap;
-use std::{fmt::Display, sync::atomic::Ordering, time::Duration};
+use std::{
+    fmt::Display,
+    sync::{atomic::Ordering, Arc},
+    time::Duration,
+};
 use tremor_common::ids::{ConnectorId, ConnectorIdGen, SourceId};
 use tremor_common::ports::{ERR, IN, OUT};
-use tremor_pipeline::METRICS_CHANNEL;
+use tremor_pipeline::{trace::Tracer, METRICS_CHANNEL};
 use tremor_script::ast::DeployEndpoint;
 use tremor_value::Value;
 use utils::reconnect::{Attempt, ConnectionLostNotifier, ReconnectRuntime};
@@ -65,6 +71,12 @@ pub(crate) use utils::{metrics, reconnect};
 /// Accept timeout
 pub(crate) const ACCEPT_TIMEOUT: Duration = Duration::from_millis(100);
 
+/// Pause after a failed accept, e.g. when running out of file descriptors
+pub(crate) const ACCEPT_ERROR_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Time the TLS or protocol handshake with a newly accepted connection may take
+pub(crate) const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
 /// connector address
 #[derive(Clone, Debug)]
 pub struct Addr {
@@ -131,11 +143,20 @@ impl Addr {
     }
     /// starts the connector
     ///
+    /// After the start result, `sender` receives an error whenever the connector fails.
+    ///
     /// # Errors
     ///   * if sending failed
     pub(crate) async fn start(&self, sender: Sender<ConnectorResult<()>>) -> Result<()> {
         self.send(Msg::Start(sender)).await
     }
+    /// restarts the connector by reconnecting with a fresh reconnect strategy
+    ///
+    /// # Errors
+    ///   * if sending failed
+    pub(crate) async fn restart(&self) -> Result<()> {
+        self.send(Msg::Restart).await
+    }
     /// drains the connector
     ///
     /// # Errors
@@ -195,6 +216,8 @@ pub(crate) enum Msg {
     ConnectionLost,
     /// initiate a reconnect attempt
     Reconnect,
+    /// reset the reconnect strategy and reconnect, also if the connector failed
+    Restart,
     // TODO: fill as needed
     /// start the connector
     Start(Sender<ConnectorResult<()>>),
@@ -220,6 +243,13 @@ pub(crate) enum Msg {
     Stop(Sender<ConnectorResult<()>>),
     /// request a status report
     Report(Sender<StatusReport>),
+    /// tap an input port of the sink part or an output port of the source part
+    Tap {
+        /// the tap, its port decides where it is attached
+        tap: Tap,
+        /// result receiver
+        result_tx: Sender<Result<()>>,
+    },
 }
 
 #[derive(Debug)]
@@ -429,10 +459,12 @@ pub(crate) async fn spawn(
     builder: &dyn ConnectorBuilder,
     config: ConnectorConfig,
     kill_switch: &KillSwitch,
+    tracer: Arc<Tracer>,
 ) -> Result<Addr> {
     // instantiate connector
     let connector = builder.build(alias, &config, kill_switch).await?;
-    let r = connector_task(alias.clone(), connector, config, connector_id_gen.next_id()).await?;
+    let uid = connector_id_gen.next_id();
+    let r = connector_task(alias.clone(), connector, config, uid, tracer).await?;
 
     Ok(r)
 }
@@ -444,6 +476,7 @@ async fn connector_task(
     mut connector: Box<dyn Connector>,
     config: ConnectorConfig,
     uid: ConnectorId,
+    tracer: Arc<Tracer>,
 ) -> Result<Addr> {
     let qsize = crate::QSIZE.load(Ordering::Relaxed);
     // channel for connector-level control plane communication
@@ -473,6 +506,7 @@ async fn connector_task(
         codec_requirement,
         qsize,
         source_metrics_reporter,
+        tracer.clone(),
     )?;
     let source_ctx = SourceContext {
         alias: alias.clone(),
@@ -493,6 +527,7 @@ async fn connector_task(
         &alias,
         qsize,
         sink_metrics_reporter,
+        tracer,
     )?;
     let sink_ctx = SinkContext {
         uid: uid.into(),
@@ -529,6 +564,8 @@ async fn connector_task(
     let mut connector_state = State::Initializing;
     let mut drainage = None;
     let mut start_sender: Option<Sender<ConnectorResult<()>>> = None;
+    // for notifying the flow about failures after the start
+    let mut supervisor: Option<Sender<ConnectorResult<()>>> = None;
 
     // TODO: add connector metrics reporter (e.g. for reconnect attempts, cb's received, uptime, etc.)
     task::spawn::<_, Result<()>>(async move {
@@ -568,6 +605,27 @@ async fn connector_task(
                         error!("{ctx} Error sending status report {e}.");
                     }
                 }
+                Msg::Tap { tap, result_tx } => {
+                    let port = tap.port().to_string();
+                    let res = match (connector_addr.sink.as_ref(), connector_addr.source.as_ref()) {
+                        (Some(sink), _) if connector.is_valid_input_port(&port) => {
+                            sink.addr.send(SinkMsg::Tap(tap)).await.map_err(Into::into)
+                        }
+                        (_, Some(source)) if connector.is_valid_output_port(&port) => source
+                            .addr
+                            .send(SourceMsg::Tap(tap))
+                            .await
+                            .map_err(Into::into),
+                        _ => Err(ErrorKind::InvalidTap(
+                            format!("connector {}", alias.connector_alias()),
+                            format!("unknown port {port}"),
+                        )
+                        .into()),
+                    };
+                    if let Err(e) = result_tx.send(res).await {
+                        error!("{ctx} Error sending tap result: {e}");
+                    }
+                }
                 Msg::LinkInput {
                     port,
                     pipelines: pipelines_to_link,
@@ -670,8 +728,27 @@ async fn connector_task(
                     if connector_state == State::Running {
                         // ensure we don't reconnect in a hot loop
                         // ensure we adhere to the reconnect strategy, waiting and possibly not reconnecting at all
-                        reconnect.enqueue_retry(&ctx).await;
+                        if !reconnect.enqueue_retry(&ctx).await {
+                            connector_state = State::Failed;
+                            notify_failure(&ctx, supervisor.as_ref(), "Reconnect failed.").await;
+                        }
+                    }
+                }
+                Msg::Restart if matches!(connector_state, State::Running | State::Failed) => {
+                    info!("{ctx} Restarting...");
+                    if connectivity == Connectivity::Connected {
+                        connectivity = Connectivity::Disconnected;
+                        connector_addr.send_sink(SinkMsg::ConnectionLost).await?;
+                        connector_addr
+                            .send_source(SourceMsg::ConnectionLost)
+                            .await?;
                     }
+                    connector_state = State::Running;
+                    reconnect.restart();
+                    connector_addr.send(Msg::Reconnect).await?;
+                }
+                Msg::Restart => {
+                    info!("{ctx} Ignoring Restart Msg. Current state: {connector_state}",);
                 }
                 Msg::Reconnect => {
                     // reconnect if we are below max_retries, otherwise bail out and fail the connector
@@ -716,12 +793,15 @@ async fn connector_task(
                                     .await,
                                 "Error sending start response",
                             );
+                        } else {
+                            notify_failure(&ctx, supervisor.as_ref(), "Reconnect failed.").await;
                         }
                     }
                     connectivity = new;
                 }
                 Msg::Start(sender) if connector_state == State::Initializing => {
                     info!("{ctx} Starting...");
+                    supervisor = Some(sender.clone());
                     start_sender = Some(sender);
 
                     // start connector
@@ -910,6 +990,21 @@ async fn connector_task(
     Ok(send_addr)
 }
 
+/// notify the flow that the connector failed after it started
+async fn notify_failure(
+    ctx: &ConnectorContext,
+    supervisor: Option<&Sender<ConnectorResult<()>>>,
+    msg: &'static str,
+) {
+    error!("{ctx} {msg}");
+    if let Some(supervisor) = supervisor {
+        ctx.swallow_err(
+            supervisor.send(ConnectorResult::err(ctx, msg)).await,
+            "Error sending failure notification",
+        );
+    }
+}
+
 #[derive(Debug, PartialEq)]
 enum DrainState {
     None,
@@ -18,14 +18,17 @@ use std::{fmt::Display, sync::atomic::AtomicBool};
 use crate::system::KillSwitch;
 use crate::{
     connectors::{
-        impls::http::utils::Header, prelude::*, sink::concurrency_cap::ConcurrencyCap,
-        utils::tls::TLSClientConfig,
+        impls::http::utils::Header,
+        prelude::*,
+        sink::concurrency_cap::{AdaptiveConfig, ConcurrencyCap},
+        utils::{metrics::make_concurrency_metrics_payload, tls::TLSClientConfig},
     },
     errors::{err_connector_def, Error, Result},
 };
 use async_std::{
     channel::{bounded, Receiver, Sender},
     sync::Arc,
+    task::JoinHandle,
 };
 use either::Either;
 use elasticsearch::{
@@ -45,7 +48,10 @@ use tremor_script::utils::sorted_serialize;
 use tremor_value::value::StaticValue;
 use value_trait::Mutable;
 
-use super::http::auth::Auth;
+use super::http::auth::{Auth, AuthProvider};
+use search::{SearchConfig, Searcher};
+
+mod search;
 
 #[derive(Deserialize, Debug, Clone)]
 #[serde(deny_unknown_fields)]
@@ -60,6 +66,10 @@ pub(crate) struct Config {
     #[serde(default = "default_concurrency")]
     concurrency: usize,
 
+    /// adapt the concurrency limit to the observed latency and errors, starting at `concurrency`
+    #[serde(default = "Default::default")]
+    adaptive_concurrency: Option<AdaptiveConfig>,
+
     /// if true, ES success and error responses will contain the whole event payload they are based upon
     #[serde(default = "default_false")]
     include_payload_in_response: bool,
@@ -79,6 +89,10 @@ pub(crate) struct Config {
     /// request timeout in nanoseconds for each request against elasticsearch
     #[serde(default = "Default::default")]
     timeout: Option<u64>,
+
+    /// run a search against the first node and emit its hits via the source
+    #[serde(default = "Default::default")]
+    search: Option<SearchConfig>,
 }
 impl ConfigImpl for Config {}
 
@@ -104,6 +118,16 @@ impl ConnectorBuilder for Builder {
         _kill_switch: &KillSwitch,
     ) -> Result<Box<dyn Connector>> {
         let config = Config::new(raw_config)?;
+        if let Some(adaptive) = config.adaptive_concurrency.as_ref() {
+            adaptive
+                .validate()
+                .map_err(|e| err_connector_def(id, &e.to_string()))?;
+        }
+        if let Some(search) = config.search.as_ref() {
+            search
+                .validate()
+                .map_err(|e| err_connector_def(id, &e.to_string()))?;
+        }
         if config.nodes.is_empty() {
             Err(err_connector_def(id, "empty nodes provided"))
         } else {
@@ -139,7 +163,8 @@ impl ConnectorBuilder for Builder {
                         Some(Credentials::ApiKey(id.clone(), api_key.clone()))
                     }
                     // Gcp Auth is handled in sink connect
-                    Auth::Gcp | Auth::None => None,
+                    // OAuth2 tokens are fetched and added to each request in the sink
+                    Auth::Gcp | Auth::OAuth2(_) | Auth::None => None,
                 }
             };
             let cert_validation =
@@ -188,7 +213,12 @@ impl Connector for Elastic {
     ) -> Result<Option<SourceAddr>> {
         let source = ElasticSource {
             source_is_connected: self.source_is_connected.clone(),
+            response_tx: self.response_tx.clone(),
             response_rx: self.response_rx.clone(),
+            config: self.config.clone(),
+            credentials: self.credentials.clone(),
+            cert_validation: self.cert_validation.clone(),
+            search_task: None,
         };
         builder.spawn(source, source_context).map(Some)
     }
@@ -210,17 +240,65 @@ impl Connector for Elastic {
     }
 }
 
+/// emits responses from the sink and hits of the configured search
 struct ElasticSource {
     source_is_connected: Arc<AtomicBool>,
+    response_tx: Sender<SourceReply>,
     response_rx: Receiver<SourceReply>,
+    config: Config,
+    credentials: Option<Credentials>,
+    cert_validation: CertValidation,
+    search_task: Option<JoinHandle<()>>,
 }
 
 #[async_trait::async_trait]
 impl Source for ElasticSource {
+    async fn connect(&mut self, ctx: &SourceContext, _attempt: &Attempt) -> Result<bool> {
+        if let Some(search) = self.config.search.clone() {
+            // stop searching with the previous client
+            if let Some(search_task) = self.search_task.take() {
+                search_task.cancel().await;
+            }
+            let node = self
+                .config
+                .nodes
+                .first()
+                .ok_or_else(|| err_connector_def(&ctx.alias, "empty nodes provided"))?;
+            let client = build_client(
+                node,
+                &self.config,
+                self.credentials.as_ref(),
+                &self.cert_validation,
+            )?;
+            let origin_uri = EventOriginUri {
+                scheme: String::from("elastic"),
+                host: node.host_or_local().to_string(),
+                port: Some(node.port_or_dflt()),
+                path: vec![],
+            };
+            let searcher = Searcher::new(
+                search,
+                client,
+                AuthProvider::new(self.config.auth.clone(), self.config.timeout),
+                origin_uri,
+                self.response_tx.clone(),
+            );
+            self.search_task = Some(spawn_task(ctx.clone(), searcher.run(ctx.clone())));
+        }
+        Ok(true)
+    }
+
     async fn pull_data(&mut self, _pull_id: &mut u64, _ctx: &SourceContext) -> Result<SourceReply> {
         Ok(self.response_rx.recv().await?)
     }
 
+    async fn on_stop(&mut self, _ctx: &SourceContext) -> Result<()> {
+        if let Some(search_task) = self.search_task.take() {
+            search_task.cancel().await;
+        }
+        Ok(())
+    }
+
     async fn on_cb_open(&mut self, _ctx: &SourceContext) -> Result<()> {
         // we will only know if we are connected to some pipelines if we receive a CBAction::Restore contraflow event
         // we will not send responses to out/err if we are not connected and this is determined by this variable
@@ -292,6 +370,7 @@ impl ElasticClients {
 
 struct ElasticSink {
     clients: ElasticClients,
+    auth: AuthProvider,
     response_tx: Sender<SourceReply>,
     reply_tx: Sender<AsyncSinkReply>,
     concurrency_cap: ConcurrencyCap,
@@ -313,9 +392,14 @@ impl ElasticSink {
     ) -> Self {
         Self {
             clients: ElasticClients::new(vec![]),
+            auth: AuthProvider::new(config.auth.clone(), config.timeout),
             response_tx,
             reply_tx: reply_tx.clone(),
-            concurrency_cap: ConcurrencyCap::new(config.concurrency, reply_tx),
+            concurrency_cap: if let Some(adaptive) = config.adaptive_concurrency.clone() {
+                ConcurrencyCap::adaptive(config.concurrency, adaptive, reply_tx)
+            } else {
+                ConcurrencyCap::new(config.concurrency, reply_tx)
+            },
             source_is_connected,
             config: config.clone(),
             es_credentials,
@@ -334,51 +418,37 @@ impl ElasticSink {
 impl Sink for ElasticSink {
     async fn connect(&mut self, ctx: &SinkContext, _attempt: &Attempt) -> Result<bool> {
         let mut clients = Vec::with_capacity(self.config.nodes.len());
+        let auth_header = self.auth.header_value().await?;
         for node in &self.config.nodes {
-            let conn_pool = SingleNodeConnectionPool::new(node.url().clone());
-            let mut transport_builder = TransportBuilder::new(conn_pool).enable_meta_header(false); // no meta header, that's just overhead
-            if let Some(timeout_ns) = self.config.timeout.as_ref() {
-                let duration = Duration::from_nanos(*timeout_ns);
-                transport_builder = transport_builder.timeout(duration);
+            let client = build_client(
+                node,
+                &self.config,
+                self.es_credentials.as_ref(),
+                &self.cert_validation,
+            )?;
+            // we use the cluster health endpoint, as the ping endpoint is not reliable
+            let mut health = client.cluster().health(ClusterHealthParts::None);
+            if let Some(auth_header) = auth_header.as_deref() {
+                health = health.header(
+                    reqwest::header::AUTHORIZATION,
+                    reqwest::header::HeaderValue::from_str(auth_header)?,
+                );
             }
-            if !self.config.headers.is_empty() {
-                let mut headermap = reqwest::header::HeaderMap::new();
-                for (k, v) in &self.config.headers {
-                    match v {
-                        Header(Either::Left(values)) => {
-                            for value in values {
-                                headermap.append(
-                                    reqwest::header::HeaderName::from_bytes(k.as_bytes())?,
-                                    reqwest::header::HeaderValue::from_str(value.as_str())?,
-                                );
-                            }
-                        }
-                        Header(Either::Right(value)) => {
-                            headermap.append(
-                                reqwest::header::HeaderName::from_bytes(k.as_bytes())?,
-                                reqwest::header::HeaderValue::from_str(value.as_str())?,
-                            );
-                        }
+            let res = match health
+                .send()
+                .await
+                .and_then(Response::error_for_status_code)
+            {
+                Ok(res) => res,
+                Err(e) => {
+                    let e = Error::from(e);
+                    if is_unauthorized(&e) {
+                        // make sure the next connection attempt uses fresh credentials
+                        self.auth.invalidate().await;
                     }
+                    return Err(e);
                 }
-                transport_builder = transport_builder.headers(headermap);
-            }
-            // client auth credentials
-            if let Some(credentials) = self.es_credentials.as_ref() {
-                transport_builder = transport_builder.auth(credentials.clone());
-            }
-            // server certificate validation
-            if let Some(cert_validation) = self.cert_validation.as_certificate_validation()? {
-                transport_builder = transport_builder.cert_validation(cert_validation);
-            }
-
-            let client = Elasticsearch::new(transport_builder.build()?);
-            // we use the cluster health endpoint, as the ping endpoint is not reliable
-            let res = client
-                .cluster()
-                .health(ClusterHealthParts::None)
-                .send()
-                .await?;
+            };
             let json = res.json::<StaticValue>().await?.into_value();
             let cluster_name = json.get_str("cluster_name").unwrap_or("").to_string();
             info!(
@@ -392,10 +462,15 @@ impl Sink for ElasticSink {
         Ok(true)
     }
 
-    async fn metrics(&mut self, _timestamp: u64, _ctx: &SinkContext) -> Vec<EventPayload> {
+    async fn metrics(&mut self, timestamp: u64, ctx: &SinkContext) -> Vec<EventPayload> {
         // TODO: use the /_cluster/stats/nodes/ or /<index>/_stats/_all and expose them here
         // TODO: which are the important metrics to expose?
-        vec![]
+        vec![make_concurrency_metrics_payload(
+            timestamp,
+            self.concurrency_cap.cap(),
+            self.concurrency_cap.inflight(),
+            ctx.alias(),
+        )]
     }
 
     #[allow(clippy::too_many_lines)]
@@ -428,8 +503,9 @@ impl Sink for ElasticSink {
             let reply_tx = self.reply_tx.clone();
             let include_payload = self.config.include_payload_in_response;
             let mut origin_uri = self.origin_uri.clone();
-            origin_uri.host = client.cluster_name;
+            origin_uri.host = client.cluster_name.clone();
             let default_index = self.config.index.clone();
+            let auth = self.auth.clone();
             let task_ctx = ctx.clone();
             async_std::task::Builder::new()
                 .name(format!(
@@ -438,30 +514,9 @@ impl Sink for ElasticSink {
                     guard.num()
                 ))
                 .spawn::<_, Result<()>>(async move {
-                    let r: Result<Value> = (|| async {
-                        // build bulk request (we can't do that in a separate function)
-                        let mut ops = BulkOperations::new();
-                        // per request options - extract from event metadata (ignoring batched)
-                        let event_es_meta = ESMeta::new(event.data.suffix().meta());
-
-                        for (data, meta) in event.value_meta_iter() {
-                            ESMeta::new(meta).insert_op(data, &mut ops)?;
-                        }
-
-                        let parts = event_es_meta.parts(default_index.as_deref());
-
-                        // apply request scoped options
-                        let bulk =
-                            event_es_meta.apply_to(client.client.bulk(parts).body(vec![ops]))?;
-
-                        let response = bulk
-                            .send()
-                            .await
-                            .and_then(Response::error_for_status_code)?;
-                        let value = response.json::<StaticValue>().await?;
-                        Ok(value.into_value())
-                    })()
-                    .await;
+                    let r =
+                        send_bulk_with_auth(&client, &event, default_index.as_deref(), &auth).await;
+                    let success = r.is_ok();
                     match r {
                         Err(e) => {
                             debug!("{task_ctx} Error sending Elasticsearch Bulk Request: {e}");
@@ -504,7 +559,7 @@ impl Sink for ElasticSink {
                             );
                         }
                     }
-                    drop(guard);
+                    guard.complete(success);
 
                     Ok(())
                 })?;
@@ -536,6 +591,119 @@ impl Sink for ElasticSink {
     }
 }
 
+/// Create a client for the given elasticsearch node
+fn build_client(
+    node: &Url,
+    config: &Config,
+    credentials: Option<&Credentials>,
+    cert_validation: &CertValidation,
+) -> Result<Elasticsearch> {
+    let conn_pool = SingleNodeConnectionPool::new(node.url().clone());
+    let mut transport_builder = TransportBuilder::new(conn_pool).enable_meta_header(false); // no meta header, that's just overhead
+    if let Some(timeout_ns) = config.timeout.as_ref() {
+        let duration = Duration::from_nanos(*timeout_ns);
+        transport_builder = transport_builder.timeout(duration);
+    }
+    if !config.headers.is_empty() {
+        let mut headermap = reqwest::header::HeaderMap::new();
+        for (k, v) in &config.headers {
+            match v {
+                Header(Either::Left(values)) => {
+                    for value in values {
+                        headermap.append(
+                            reqwest::header::HeaderName::from_bytes(k.as_bytes())?,
+                            reqwest::header::HeaderValue::from_str(value.as_str())?,
+                        );
+                    }
+                }
+                Header(Either::Right(value)) => {
+                    headermap.append(
+                        reqwest::header::HeaderName::from_bytes(k.as_bytes())?,
+                        reqwest::header::HeaderValue::from_str(value.as_str())?,
+                    );
+                }
+            }
+        }
+        transport_builder = transport_builder.headers(headermap);
+    }
+    // client auth credentials
+    if let Some(credentials) = credentials {
+        transport_builder = transport_builder.auth(credentials.clone());
+    }
+    // server certificate validation
+    if let Some(cert_validation) = cert_validation.as_certificate_validation()? {
+        transport_builder = transport_builder.cert_validation(cert_validation);
+    }
+
+    Ok(Elasticsearch::new(transport_builder.build()?))
+}
+
+/// Send the given event as a bulk request.
+///
+/// Static credentials are configured on the transport, refreshable ones like OAuth2 tokens are
+/// added to each request. If those get rejected, the request is retried once with fresh credentials.
+async fn send_bulk_with_auth(
+    client: &ElasticClient,
+    event: &Event,
+    default_index: Option<&str>,
+    auth: &AuthProvider,
+) -> Result<Value<'static>> {
+    if !auth.can_refresh() {
+        return send_bulk(client, event, default_index, None).await;
+    }
+    let auth_header = auth.header_value().await?;
+    match send_bulk(client, event, default_index, auth_header).await {
+        Err(e) if is_unauthorized(&e) => {
+            debug!("Bulk request rejected as unauthorized. Retrying with fresh credentials.");
+            auth.invalidate().await;
+            let auth_header = auth.header_value().await?;
+            send_bulk(client, event, default_index, auth_header).await
+        }
+        r => r,
+    }
+}
+
+async fn send_bulk(
+    client: &ElasticClient,
+    event: &Event,
+    default_index: Option<&str>,
+    auth_header: Option<String>,
+) -> Result<Value<'static>> {
+    let mut ops = BulkOperations::new();
+    // per request options - extract from event metadata (ignoring batched)
+    let event_es_meta = ESMeta::new(event.data.suffix().meta());
+
+    for (data, meta) in event.value_meta_iter() {
+        ESMeta::new(meta).insert_op(data, &mut ops)?;
+    }
+
+    let parts = event_es_meta.parts(default_index);
+
+    // apply request scoped options
+    let mut bulk = event_es_meta.apply_to(client.client.bulk(parts).body(vec![ops]))?;
+    if let Some(auth_header) = auth_header {
+        bulk = bulk.header(
+            reqwest::header::AUTHORIZATION,
+            reqwest::header::HeaderValue::from_str(&auth_header)?,
+        );
+    }
+
+    let response = bulk
+        .send()
+        .await
+        .and_then(Response::error_for_status_code)?;
+    let value = response.json::<StaticValue>().await?;
+    Ok(value.into_value())
+}
+
+/// Returns `true` if the error was caused by ES rejecting our credentials
+fn is_unauthorized(e: &Error) -> bool {
+    matches!(
+        e.kind(),
+        ErrorKind::ElasticError(e) if e.status_code() == Some(reqwest::StatusCode::UNAUTHORIZED)
+    )
+}
+
 /// Handle successful response from ES
 ///
 /// send event to OUT
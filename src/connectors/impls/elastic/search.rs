@@ -0,0 +1,339 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Search mode for the `elastic` connector
+//!
+//! Runs the configured query against a point in time, paging through the results with `search_after`
+//! and emitting every hit as an event. The sort values of the last emitted hit are remembered,
+//! so following searches continue after it.
+
+use crate::connectors::impls::http::auth::AuthProvider;
+use crate::connectors::prelude::*;
+use crate::connectors::utils::poll::{load_state, save_state, Interval};
+use async_std::channel::Sender;
+use async_std::path::Path;
+use elasticsearch::{
+    http::response::Response, ClosePointInTimeParts, Elasticsearch, OpenPointInTimeParts,
+    SearchParts,
+};
+use tremor_value::value::StaticValue;
+use value_trait::Mutable;
+
+fn default_size() -> usize {
+    1000
+}
+
+fn default_keep_alive() -> String {
+    "1m".to_string()
+}
+
+/// Configuration for searching
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct SearchConfig {
+    /// index, comma-separated list of indices or index pattern to search
+    index: String,
+    /// search request body, e.g. `{"query": {"match_all": {}}, "sort": [{"@timestamp": "asc"}]}`
+    ///
+    /// `pit`, `search_after` and `size` are set by the connector.
+    /// Without a `sort` hits are sorted by `_shard_doc`, which only allows resuming within a single search,
+    /// so `interval` and `state_file` require a `sort`, e.g. by an ever increasing field like an ingest timestamp.
+    #[serde(default = "Default::default")]
+    query: Option<simd_json::OwnedValue>,
+    /// number of hits to fetch per page
+    #[serde(default = "default_size")]
+    size: usize,
+    /// how long to keep the point in time alive between two pages, e.g. `1m`
+    #[serde(default = "default_keep_alive")]
+    keep_alive: String,
+    /// interval in nanoseconds to search again for new hits, searches only once if not set
+    #[serde(default = "Default::default")]
+    pub(crate) interval: Option<u64>,
+    /// file to persist the sort values of the last emitted hit in, so searching continues after it after a restart
+    #[serde(default = "Default::default")]
+    state_file: Option<String>,
+}
+
+impl SearchConfig {
+    pub(crate) fn validate(&self) -> Result<()> {
+        let sorted = self
+            .query
+            .as_ref()
+            .map_or(false, |query| query.get("sort").is_some());
+        if !sorted && (self.interval.is_some() || self.state_file.is_some()) {
+            // `_shard_doc` sort values are only valid within a single point in time
+            return Err(
+                "search `interval` and `state_file` require a `sort` in the `query`".into(),
+            );
+        }
+        Ok(())
+    }
+
+    /// Build the request body for the next page
+    fn body(&self, pit_id: &str, search_after: Option<&Value<'static>>) -> Result<Value<'static>> {
+        let mut body = match self.query.as_ref() {
+            Some(query) => tremor_value::to_value(query)?,
+            None => Value::object(),
+        };
+        if body.get("sort").is_none() {
+            body.try_insert("sort", literal!([{"_shard_doc": "asc"}]));
+        }
+        body.try_insert("size", self.size);
+        body.try_insert(
+            "pit",
+            literal!({
+                "id": pit_id.to_string(),
+                "keep_alive": self.keep_alive.clone()
+            }),
+        );
+        if let Some(search_after) = search_after {
+            body.try_insert("search_after", search_after.clone());
+        }
+        Ok(body)
+    }
+}
+
+/// The search position
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+struct SearchState {
+    /// sort values of the last emitted hit
+    search_after: Option<simd_json::OwnedValue>,
+}
+
+/// Runs the configured search and sends the hits to the source
+pub(crate) struct Searcher {
+    config: SearchConfig,
+    client: Elasticsearch,
+    auth: AuthProvider,
+    origin_uri: EventOriginUri,
+    tx: Sender<SourceReply>,
+    search_after: Option<Value<'static>>,
+}
+
+impl Searcher {
+    pub(crate) fn new(
+        config: SearchConfig,
+        client: Elasticsearch,
+        auth: AuthProvider,
+        origin_uri: EventOriginUri,
+        tx: Sender<SourceReply>,
+    ) -> Self {
+        Self {
+            config,
+            client,
+            auth,
+            origin_uri,
+            tx,
+            search_after: None,
+        }
+    }
+
+    /// Search once, or periodically until cancelled
+    pub(crate) async fn run(mut self, ctx: SourceContext) -> Result<()> {
+        if let Some(state_file) = self.config.state_file.as_ref() {
+            let state: SearchState = load_state(Path::new(state_file)).await?;
+            if let Some(search_after) = state.search_after {
+                self.search_after = Some(tremor_value::to_value(search_after)?);
+            }
+        }
+        if let Some(interval) = self.config.interval {
+            let mut interval = Interval::new(interval);
+            loop {
+                interval.tick().await;
+                if let Err(e) = self.search(&ctx).await {
+                    warn!("{ctx} Error searching {}: {e}", self.config.index);
+                }
+            }
+        } else {
+            self.search(&ctx).await?;
+            info!("{ctx} Search of {} finished.", self.config.index);
+            Ok(())
+        }
+    }
+
+    /// Emit all hits after the last emitted one
+    async fn search(&mut self, ctx: &SourceContext) -> Result<()> {
+        let auth_header = self.auth_header().await?;
+        let mut open = self
+            .client
+            .open_point_in_time(OpenPointInTimeParts::Index(&[self.config.index.as_str()]))
+            .keep_alive(&self.config.keep_alive);
+        if let Some(auth_header) = auth_header.clone() {
+            open = open.header(reqwest::header::AUTHORIZATION, auth_header);
+        }
+        let mut pit_id = json(open.send().await?)
+            .await?
+            .get_str("id")
+            .ok_or("Invalid response from ES: missing point in time `id`")?
+            .to_string();
+
+        let res = self
+            .search_pages(ctx, &mut pit_id, auth_header.as_ref())
+            .await;
+
+        let mut close = self
+            .client
+            .close_point_in_time(ClosePointInTimeParts::None)
+            .body(literal!({ "id": pit_id }));
+        if let Some(auth_header) = auth_header {
+            close = close.header(reqwest::header::AUTHORIZATION, auth_header);
+        }
+        ctx.swallow_err(
+            close.send().await.and_then(Response::error_for_status_code),
+            "Error closing point in time",
+        );
+        res
+    }
+
+    async fn search_pages(
+        &mut self,
+        ctx: &SourceContext,
+        pit_id: &mut String,
+        auth_header: Option<&reqwest::header::HeaderValue>,
+    ) -> Result<()> {
+        loop {
+            let body = self.config.body(pit_id, self.search_after.as_ref())?;
+            let mut search = self.client.search(SearchParts::None).body(body);
+            if let Some(auth_header) = auth_header {
+                search = search.header(reqwest::header::AUTHORIZATION, auth_header.clone());
+            }
+            let mut response = json(search.send().await?).await?;
+            // the point in time id might change with every response
+            if let Some(id) = response.get_str("pit_id") {
+                *pit_id = id.to_string();
+            }
+            let hits = response
+                .get_mut("hits")
+                .and_then(|hits| hits.get_mut("hits"))
+                .and_then(Mutable::as_array_mut)
+                .map(std::mem::take)
+                .unwrap_or_default();
+            let count = hits.len();
+            for hit in hits {
+                let meta = literal!({
+                    "elastic": {
+                        "_index": hit.get("_index").map(Value::clone_static),
+                        "_id": hit.get("_id").map(Value::clone_static),
+                        "_score": hit.get("_score").map(Value::clone_static),
+                        "sort": hit.get("sort").map(Value::clone_static)
+                    }
+                });
+                self.search_after = hit.get("sort").map(Value::clone_static);
+                let data = hit
+                    .get("_source")
+                    .map_or_else(Value::null, Value::clone_static);
+                self.tx
+                    .send(SourceReply::Structured {
+                        origin_uri: self.origin_uri.clone(),
+                        payload: (data, meta).into(),
+                        stream: DEFAULT_STREAM_ID,
+                        port: Some(OUT),
+                    })
+                    .await?;
+            }
+            if count > 0 {
+                if let Some(state_file) = self.config.state_file.as_ref() {
+                    let state = SearchState {
+                        search_after: self.search_after.clone().map(simd_json::OwnedValue::from),
+                    };
+                    save_state(Path::new(state_file), &state).await?;
+                }
+            }
+            if count < self.config.size {
+                debug!("{ctx} Reached the last page of hits.");
+                return Ok(());
+            }
+        }
+    }
+
+    async fn auth_header(&self) -> Result<Option<reqwest::header::HeaderValue>> {
+        Ok(match self.auth.header_value().await? {
+            Some(header) => Some(reqwest::header::HeaderValue::from_str(&header)?),
+            None => None,
+        })
+    }
+}
+
+async fn json(response: Response) -> Result<Value<'static>> {
+    let response = response.error_for_status_code()?;
+    Ok(response.json::<StaticValue>().await?.into_value())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn search_body() -> Result<()> {
+        let config: SearchConfig = tremor_value::structurize(literal!({
+            "index": "snot",
+            "size": 10,
+            "query": {
+                "query": {"match_all": {}}
+            }
+        }))?;
+        assert_eq!(
+            literal!({
+                "query": {"match_all": {}},
+                "sort": [{"_shard_doc": "asc"}],
+                "size": 10,
+                "pit": {"id": "badger", "keep_alive": "1m"}
+            }),
+            config.body("badger", None)?
+        );
+
+        let config: SearchConfig = tremor_value::structurize(literal!({
+            "index": "snot",
+            "query": {
+                "sort": [{"@timestamp": "asc"}]
+            }
+        }))?;
+        assert_eq!(
+            literal!({
+                "sort": [{"@timestamp": "asc"}],
+                "size": 1000,
+                "pit": {"id": "badger", "keep_alive": "1m"},
+                "search_after": [1_667_000_000_000_u64, 42]
+            }),
+            config.body("badger", Some(&literal!([1_667_000_000_000_u64, 42])))?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn incremental_search_requires_sort() -> Result<()> {
+        let config =
+            |config: Value| -> Result<SearchConfig> { Ok(tremor_value::structurize(config)?) };
+        assert!(config(literal!({"index": "snot"}))?.validate().is_ok());
+        assert!(
+            config(literal!({"index": "snot", "interval": 1_000_000_000}))?
+                .validate()
+                .is_err()
+        );
+        assert!(
+            config(literal!({"index": "snot", "state_file": "badger.json"}))?
+                .validate()
+                .is_err()
+        );
+        assert!(config(literal!({
+            "index": "snot",
+            "interval": 1_000_000_000,
+            "state_file": "badger.json",
+            "query": {"sort": [{"@timestamp": "asc"}]}
+        }))?
+        .validate()
+        .is_ok());
+        Ok(())
+    }
+}
@@ -21,7 +21,11 @@ use async_std::{
     io::BufReader,
 };
 use futures::{AsyncRead, AsyncReadExt, AsyncWriteExt};
-use tremor_common::asy::file;
+use rotate::{Durability, RotateConfig, Rotator};
+use tremor_common::{asy::file, time::nanotime};
+use tremor_pipeline::SignalKind;
+
+mod rotate;
 
 const URL_SCHEME: &str = "tremor-file";
 
@@ -73,6 +77,9 @@ pub(crate) struct Config {
     /// chunk_size to read from the file
     #[serde(default = "default_buf_size")]
     pub(crate) chunk_size: usize,
+    /// rotate the written file by size or time
+    #[serde(default = "Default::default")]
+    pub(crate) rotate: Option<RotateConfig>,
 }
 
 impl ConfigImpl for Config {}
@@ -96,12 +103,23 @@ impl ConnectorBuilder for Builder {
 
     async fn build_cfg(
         &self,
-        _: &Alias,
+        alias: &Alias,
         _: &ConnectorConfig,
         config: &Value,
         _kill_switch: &KillSwitch,
     ) -> Result<Box<dyn Connector>> {
         let config = Config::new(config)?;
+        if let Some(rotate) = config.rotate.as_ref() {
+            if config.mode == Mode::Read {
+                return Err(err_connector_def(
+                    alias,
+                    "`rotate` is only supported for writing files",
+                ));
+            }
+            rotate
+                .validate()
+                .map_err(|e| err_connector_def(alias, &format!("Invalid `rotate`: {e}")))?;
+        }
         Ok(Box::new(File { config }))
     }
 }
@@ -116,7 +134,7 @@ impl Connector for File {
         if self.config.mode == Mode::Read {
             Ok(None)
         } else {
-            let sink = FileSink::new(self.config.clone());
+            let sink = FileSink::new(self.config.clone())?;
             builder.spawn(sink, sink_context).map(Some)
         }
     }
@@ -246,11 +264,61 @@ impl Source for FileSource {
 struct FileSink {
     config: Config,
     file: Option<FSFile>,
+    rotator: Option<Rotator>,
 }
 
 impl FileSink {
-    fn new(config: Config) -> Self {
-        Self { config, file: None }
+    fn new(config: Config) -> Result<Self> {
+        let rotator = config
+            .rotate
+            .clone()
+            .map(|rotate| Rotator::new(rotate, &config.path))
+            .transpose()?;
+        Ok(Self {
+            config,
+            file: None,
+            rotator,
+        })
+    }
+
+    /// Write out data buffered in the serializer, completing the current output of e.g. the `parquet` codec
+    async fn write_flushed(&mut self, serializer: &mut EventSerializer) -> Result<()> {
+        let data = serializer.flush(nanotime())?;
+        if let Some(file) = self.file.as_mut() {
+            for chunk in data {
+                file.write_all(&chunk).await?;
+                if let Some(rotator) = self.rotator.as_mut() {
+                    rotator.track(chunk.len());
+                }
+            }
+            file.flush().await?;
+        }
+        Ok(())
+    }
+
+    /// Rotate the current file and continue writing to a new one
+    async fn rotate(&mut self, ctx: &SinkContext, serializer: &mut EventSerializer) -> Result<()> {
+        ctx.swallow_err(
+            self.write_flushed(serializer).await,
+            "Error writing buffered data before rotating",
+        );
+        if let (Some(rotator), Some(file)) = (self.rotator.as_mut(), self.file.take()) {
+            let mode = match rotator.rotate(file, ctx).await {
+                Ok(rotated) => {
+                    info!("{ctx} Rotated file to {}", rotated.display());
+                    Mode::Truncate
+                }
+                Err(e) => {
+                    // keep on writing to the current file
+                    error!("{ctx} Error rotating file: {e}");
+                    Mode::Append
+                }
+            };
+            let file = file::open_with(&self.config.path, &mut mode.as_open_options()).await?;
+            rotator.opened(file.metadata().await?.len());
+            self.file = Some(file);
+        }
+        Ok(())
     }
 }
 
@@ -272,6 +340,9 @@ impl Sink for FileSink {
             mode
         );
         let file = file::open_with(&self.config.path, &mut mode.as_open_options()).await?;
+        if let Some(rotator) = self.rotator.as_mut() {
+            rotator.opened(file.metadata().await?.len());
+        }
         self.file = Some(file);
         Ok(true)
     }
@@ -298,6 +369,9 @@ impl Sink for FileSink {
                     ctx.notifier().connection_lost().await?;
                     return Err(e.into());
                 }
+                if let Some(rotator) = self.rotator.as_mut() {
+                    rotator.track(chunk.len());
+                }
             }
             if let Err(e) = file.flush().await {
                 error!("{ctx} Error flushing file: {e}");
@@ -306,6 +380,37 @@ impl Sink for FileSink {
                 return Err(e.into());
             }
         }
+        if let Some(rotator) = self.rotator.as_ref() {
+            if rotator.durability() == Durability::Event {
+                if let Err(e) = file.sync_data().await {
+                    error!("{ctx} Error syncing file: {e}");
+                    self.file = None;
+                    ctx.notifier().connection_lost().await?;
+                    return Err(e.into());
+                }
+            }
+            if rotator.should_rotate(nanotime()) {
+                self.rotate(ctx, serializer).await?;
+            }
+        }
+        Ok(SinkReply::NONE)
+    }
+
+    async fn on_signal(
+        &mut self,
+        signal: Event,
+        ctx: &SinkContext,
+        serializer: &mut EventSerializer,
+    ) -> Result<SinkReply> {
+        // rotate by `interval` even if no events arrive
+        if signal.kind == Some(SignalKind::Tick)
+            && self
+                .rotator
+                .as_ref()
+                .map_or(false, |rotator| rotator.should_rotate(nanotime()))
+        {
+            self.rotate(ctx, serializer).await?;
+        }
         Ok(SinkReply::NONE)
     }
 
@@ -317,10 +422,24 @@ impl Sink for FileSink {
         false
     }
 
+    async fn on_flush(
+        &mut self,
+        _ctx: &SinkContext,
+        serializer: &mut EventSerializer,
+    ) -> Result<()> {
+        self.write_flushed(serializer).await
+    }
+
     async fn on_stop(&mut self, ctx: &SinkContext) -> Result<()> {
+        let sync = self
+            .rotator
+            .as_ref()
+            .map_or(true, |rotator| rotator.durability() != Durability::None);
         if let Some(file) = self.file.take() {
-            if let Err(e) = file.sync_all().await {
-                error!("{} Error flushing file: {}", &ctx, e);
+            if sync {
+                if let Err(e) = file.sync_all().await {
+                    error!("{} Error flushing file: {}", &ctx, e);
+                }
             }
         }
         Ok(())
@@ -0,0 +1,364 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Rotation of files written by the `file` connector
+//!
+//! The sink always writes to the configured `path`. Once that file reached `max_bytes` or is older than `interval`,
+//! it is renamed according to `pattern` and optionally compressed. Afterwards the oldest rotated files
+//! are deleted until at most `max_files` files and `max_total_bytes` bytes remain.
+
+use crate::connectors::prelude::*;
+use crate::connectors::utils::template::{Part, Template, Values};
+use crate::postprocessor::{compress, Compress};
+use async_std::{fs::File as FSFile, task};
+use regex::Regex;
+use std::path::{Path, PathBuf};
+use tremor_common::time::nanotime;
+
+/// When to fsync written data to disk
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Durability {
+    /// never fsync, leave it to the operating system
+    None,
+    /// fsync before a file is rotated and when stopping
+    #[default]
+    Rotate,
+    /// fsync after every event
+    Event,
+}
+
+fn default_pattern() -> String {
+    "{name}.{yyyy}{MM}{dd}{HH}{mm}{ss}.{n}".to_string()
+}
+
+/// Rotation config
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct RotateConfig {
+    /// file name for rotated files, they are kept in the directory of `path`
+    ///
+    /// Placeholders: `{name}` is the file name of `path`, `{yyyy}`, `{MM}`, `{dd}`, `{HH}`, `{mm}` and `{ss}`
+    /// the rotation time in UTC and `{n}` a number making the file name unique.
+    #[serde(default = "default_pattern")]
+    pattern: String,
+    /// rotate once the file contains at least this many bytes
+    #[serde(default = "Default::default")]
+    max_bytes: Option<u64>,
+    /// rotate once the file is older than this many nanoseconds
+    #[serde(default = "Default::default")]
+    interval: Option<u64>,
+    /// compress rotated files with this algorithm of the `compress` postprocessor, e.g. `gzip`
+    #[serde(default = "Default::default")]
+    compression: Option<String>,
+    /// compression level, for algorithms supporting it
+    #[serde(default = "Default::default")]
+    compression_level: Option<i64>,
+    /// keep at most this many rotated files
+    #[serde(default = "Default::default")]
+    max_files: Option<usize>,
+    /// keep at most this many bytes in rotated files
+    #[serde(default = "Default::default")]
+    max_total_bytes: Option<u64>,
+    /// when to fsync written data to disk
+    #[serde(default = "Default::default")]
+    pub(crate) durability: Durability,
+}
+
+impl RotateConfig {
+    pub(crate) fn validate(&self) -> Result<()> {
+        let pattern = Template::parse(&self.pattern)?;
+        if !pattern.contains(&Part::N) {
+            return Err("`pattern` needs a `{n}` placeholder".into());
+        }
+        if pattern.contains(&Part::Uuid) || pattern.has_meta() {
+            return Err(
+                "`pattern` doesn't support the `{uuid}` and `{meta.<path>}` placeholders".into(),
+            );
+        }
+        if self.pattern.contains(std::path::MAIN_SEPARATOR) || self.pattern.contains('/') {
+            return Err("`pattern` must be a file name, not a path".into());
+        }
+        if self.max_bytes.is_none() && self.interval.is_none() {
+            return Err("Either `max_bytes` or `interval` is required".into());
+        }
+        if self.max_bytes == Some(0)
+            || self.interval == Some(0)
+            || self.max_files == Some(0)
+            || self.max_total_bytes == Some(0)
+        {
+            return Err(
+                "`max_bytes`, `interval`, `max_files` and `max_total_bytes` need to be greater than 0"
+                    .into(),
+            );
+        }
+        self.compressor()?;
+        Ok(())
+    }
+
+    fn compressor(&self) -> Result<Option<Compress>> {
+        self.compression
+            .as_ref()
+            .map(|algorithm| {
+                let mut config = literal!({ "algorithm": algorithm.clone() });
+                if let Some(level) = self.compression_level {
+                    config.try_insert("level", level);
+                }
+                Compress::from_config(Some(&config))
+            })
+            .transpose()
+    }
+
+    /// file extension added to compressed files
+    fn extension(&self) -> Option<&'static str> {
+        self.compression
+            .as_deref()
+            .map(|algorithm| match algorithm {
+                "gzip" => "gz",
+                "xz2" => "xz",
+                "zstd" => "zst",
+                "snappy" => "sz",
+                "lz4" => "lz4",
+                _ => "zz",
+            })
+    }
+}
+
+/// Decides when to rotate the file written by the sink and rotates it
+pub(crate) struct Rotator {
+    config: RotateConfig,
+    pattern: Template,
+    matcher: Regex,
+    path: PathBuf,
+    dir: PathBuf,
+    name: String,
+    bytes: u64,
+    opened_at: u64,
+    n: u64,
+}
+
+impl Rotator {
+    pub(crate) fn new(config: RotateConfig, path: &Path) -> Result<Self> {
+        let pattern = Template::parse(&config.pattern)?;
+        let name = path
+            .file_name()
+            .ok_or_else(|| format!("Invalid file path: {}", path.display()))?
+            .to_string_lossy()
+            .to_string();
+        let matcher = pattern.matcher(&name, config.extension())?;
+        let dir = path
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .map_or_else(|| PathBuf::from("."), Path::to_path_buf);
+        Ok(Self {
+            config,
+            pattern,
+            matcher,
+            path: path.to_path_buf(),
+            dir,
+            name,
+            bytes: 0,
+            opened_at: nanotime(),
+            n: 0,
+        })
+    }
+
+    pub(crate) fn durability(&self) -> Durability {
+        self.config.durability
+    }
+
+    /// Start tracking a freshly opened file, already containing `bytes`
+    pub(crate) fn opened(&mut self, bytes: u64) {
+        self.bytes = bytes;
+        self.opened_at = nanotime();
+    }
+
+    /// Track `bytes` written to the current file
+    pub(crate) fn track(&mut self, bytes: usize) {
+        self.bytes += bytes as u64;
+    }
+
+    /// Returns `true` if the current file should be rotated, empty files are never rotated
+    pub(crate) fn should_rotate(&self, now: u64) -> bool {
+        self.bytes > 0
+            && (self
+                .config
+                .max_bytes
+                .map_or(false, |max_bytes| self.bytes >= max_bytes)
+                || self.config.interval.map_or(false, |interval| {
+                    now.saturating_sub(self.opened_at) >= interval
+                }))
+    }
+
+    /// Close `file`, move it to its rotated name, compress it and delete rotated files exceeding the retention limits
+    ///
+    /// Returns the path of the rotated file.
+    pub(crate) async fn rotate(&mut self, file: FSFile, ctx: &SinkContext) -> Result<PathBuf> {
+        if self.config.durability != Durability::None {
+            file.sync_all().await?;
+        }
+        drop(file);
+
+        let target = self.next_target(nanotime())?;
+        debug!(
+            "{ctx} Rotating {} to {}",
+            self.path.display(),
+            target.display()
+        );
+        let path = self.path.clone();
+        let compression = self.config.compression.clone();
+        let level = self.config.compression_level;
+        let durability = self.config.durability;
+        let extension = self.config.extension();
+        let rotated = task::spawn_blocking(move || -> Result<PathBuf> {
+            std::fs::rename(&path, &target)?;
+            if let (Some(algorithm), Some(extension)) = (compression, extension) {
+                let mut compressed_path = target.clone().into_os_string();
+                compressed_path.push(".");
+                compressed_path.push(extension);
+                let compressed_path = PathBuf::from(compressed_path);
+                let mut rotated = std::io::BufReader::new(std::fs::File::open(&target)?);
+                let compressed = std::io::BufWriter::new(std::fs::File::create(&compressed_path)?);
+                let compressed =
+                    compress::compress_stream(&algorithm, level, &mut rotated, compressed)?;
+                let compressed = compressed.into_inner().map_err(|e| e.into_error())?;
+                if durability != Durability::None {
+                    compressed.sync_all()?;
+                }
+                std::fs::remove_file(&target)?;
+                Ok(compressed_path)
+            } else {
+                Ok(target)
+            }
+        })
+        .await?;
+
+        ctx.swallow_err(
+            self.apply_retention().await,
+            "Error deleting old rotated files",
+        );
+        Ok(rotated)
+    }
+
+    /// The first unused file name for rotating
+    fn next_target(&mut self, now: u64) -> Result<PathBuf> {
+        let extension = self.config.extension();
+        loop {
+            let values = Values {
+                name: &self.name,
+                n: self.n,
+                ..Values::default()
+            };
+            let target = self.dir.join(self.pattern.render(now, &values)?);
+            self.n += 1;
+            let compressed_exists = extension.map_or(false, |extension| {
+                let mut compressed = target.clone().into_os_string();
+                compressed.push(".");
+                compressed.push(extension);
+                Path::new(&compressed).exists()
+            });
+            if !target.exists() && !compressed_exists {
+                return Ok(target);
+            }
+        }
+    }
+
+    /// Delete the oldest rotated files until the retention limits are met
+    async fn apply_retention(&self) -> Result<()> {
+        if self.config.max_files.is_none() && self.config.max_total_bytes.is_none() {
+            return Ok(());
+        }
+        let dir = self.dir.clone();
+        let matcher = self.matcher.clone();
+        let max_files = self.config.max_files;
+        let max_total_bytes = self.config.max_total_bytes;
+        task::spawn_blocking(move || -> Result<()> {
+            let mut rotated = Vec::new();
+            for entry in std::fs::read_dir(&dir)? {
+                let entry = entry?;
+                let metadata = entry.metadata()?;
+                if metadata.is_file() && matcher.is_match(&entry.file_name().to_string_lossy()) {
+                    rotated.push((metadata.modified()?, entry.path(), metadata.len()));
+                }
+            }
+            // oldest first
+            rotated.sort();
+            let mut files = rotated.len();
+            let mut total_bytes: u64 = rotated.iter().map(|(_, _, len)| len).sum();
+            for (_, path, len) in rotated {
+                let too_many = max_files.map_or(false, |max_files| files > max_files);
+                let too_big =
+                    max_total_bytes.map_or(false, |max_total_bytes| total_bytes > max_total_bytes);
+                if !too_many && !too_big {
+                    break;
+                }
+                debug!("Deleting rotated file {}", path.display());
+                std::fs::remove_file(&path)?;
+                files -= 1;
+                total_bytes -= len;
+            }
+            Ok(())
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate() -> Result<()> {
+        let config: RotateConfig = tremor_value::structurize(literal!({"max_bytes": 1024}))?;
+        assert!(config.validate().is_ok());
+        let config: RotateConfig = tremor_value::structurize(literal!({}))?;
+        assert!(config.validate().is_err());
+        let config: RotateConfig =
+            tremor_value::structurize(literal!({"max_bytes": 1024, "pattern": "{name}.old"}))?;
+        assert!(config.validate().is_err());
+        let config: RotateConfig =
+            tremor_value::structurize(literal!({"interval": 1, "pattern": "old/{name}.{n}"}))?;
+        assert!(config.validate().is_err());
+        let config: RotateConfig =
+            tremor_value::structurize(literal!({"interval": 1, "pattern": "{name}.{uuid}.{n}"}))?;
+        assert!(config.validate().is_err());
+        let config: RotateConfig =
+            tremor_value::structurize(literal!({"interval": 1, "compression": "snot"}))?;
+        assert!(config.validate().is_err());
+        let config: RotateConfig = tremor_value::structurize(
+            literal!({"interval": 1, "compression": "xz2", "compression_level": 3}),
+        )?;
+        assert!(config.validate().is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn should_rotate() -> Result<()> {
+        let config: RotateConfig =
+            tremor_value::structurize(literal!({"max_bytes": 10, "interval": 1_000_000_000}))?;
+        let mut rotator = Rotator::new(config, Path::new("out.log"))?;
+        assert_eq!(Path::new("."), rotator.dir);
+        let now = nanotime();
+        rotator.opened(0);
+        assert!(!rotator.should_rotate(now + 2_000_000_000)); // empty
+        rotator.track(9);
+        assert!(!rotator.should_rotate(now));
+        assert!(rotator.should_rotate(now + 2_000_000_000));
+        rotator.track(1);
+        assert!(rotator.should_rotate(now));
+        rotator.opened(0);
+        assert!(!rotator.should_rotate(now));
+        Ok(())
+    }
+}
@@ -89,6 +89,7 @@ mod tests {
     use crate::connectors::reconnect::ConnectionLostNotifier;
     use crate::connectors::sink::builder;
     use crate::connectors::{metrics::SinkReporter, utils::quiescence::QuiescenceBeacon};
+    use std::sync::Arc;
 
     #[async_std::test]
     pub async fn can_spawn_sink() -> Result<()> {
@@ -116,6 +117,7 @@ mod tests {
                     &Alias::new("a", "b"),
                     128,
                     SinkReporter::new(Alias::new("a", "b"), async_broadcast::broadcast(1).0, None),
+                    Arc::default(),
                 )?,
             )
             .await?;
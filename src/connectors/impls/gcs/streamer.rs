@@ -24,7 +24,7 @@ use crate::{
             },
             object_storage::{
                 BufferPart, ConsistentSink, Mode, ObjectId, ObjectStorageCommon,
-                ObjectStorageSinkImpl, ObjectStorageUpload, YoloSink,
+                ObjectStorageSinkImpl, ObjectStorageUpload, RolloverConfig, YoloSink,
             },
         },
         prelude::*,
@@ -60,6 +60,9 @@ pub(super) struct Config {
     pub(super) max_retries: u32,
     #[serde(default = "default_backoff_base_time")]
     pub(super) backoff_base_time: u64,
+    /// Roll over to new objects automatically, instead of naming them via `$gcs_streamer.name`
+    #[serde(default = "Default::default")]
+    pub(super) rollover: Option<RolloverConfig>,
 }
 
 #[allow(clippy::unwrap_used)]
@@ -126,6 +129,11 @@ impl ConnectorBuilder for Builder {
     ) -> Result<Box<dyn Connector>> {
         let mut config = Config::new(connector_config)?;
         config.normalize(alias);
+        if let Some(rollover) = config.rollover.as_ref() {
+            rollover
+                .validate()
+                .map_err(|e| err_connector_def(alias, &format!("Invalid `rollover`: {e}")))?;
+        }
 
         Ok(Box::new(GCSStreamerConnector { config }))
     }
@@ -274,6 +282,10 @@ impl<Client: ResumableUploadClient> ObjectStorageCommon for GCSObjectStorageSink
     fn default_bucket(&self) -> Option<&String> {
         self.config.bucket.as_ref()
     }
+
+    fn rollover(&self) -> Option<&RolloverConfig> {
+        self.config.rollover.as_ref()
+    }
 }
 
 #[async_trait::async_trait]
@@ -415,13 +427,13 @@ pub(crate) mod tests {
     use async_std::channel::bounded;
     use halfbrown::HashMap;
     use tremor_common::ids::{ConnectorIdGen, SinkId};
-    use tremor_pipeline::EventId;
+    use tremor_pipeline::{EventId, SignalKind};
     use tremor_value::literal;
 
     use crate::connectors::impls::gcs::resumable_upload_client::ResumableUploadClient;
     use crate::connectors::impls::object_storage::{BufferPart, ObjectId};
     use crate::errors::err_gcs;
-    use std::sync::atomic::AtomicUsize;
+    use std::sync::{atomic::AtomicUsize, Arc};
 
     #[derive(Debug, Default)]
     pub(crate) struct TestUploadClient {
@@ -557,6 +569,7 @@ pub(crate) mod tests {
             buffer_size: 10,
             max_retries: 3,
             backoff_base_time: 1,
+            rollover: None,
         };
 
         let sink_impl = GCSObjectStorageSinkImpl::yolo(config, upload_client_factory);
@@ -742,6 +755,7 @@ pub(crate) mod tests {
             buffer_size: 10,
             max_retries: 3,
             backoff_base_time: 1,
+            rollover: None,
         };
 
         let sink_impl = GCSObjectStorageSinkImpl::yolo(config, upload_client_factory);
@@ -915,6 +929,7 @@ pub(crate) mod tests {
             buffer_size: 10,
             max_retries: 3,
             backoff_base_time: 1,
+            rollover: None,
         };
 
         let sink_impl = GCSObjectStorageSinkImpl::yolo(config, upload_client_factory);
@@ -1009,6 +1024,7 @@ pub(crate) mod tests {
             buffer_size: 10,
             max_retries: 3,
             backoff_base_time: 1,
+            rollover: None,
         };
 
         let sink_impl = GCSObjectStorageSinkImpl::yolo(config, upload_client_factory);
@@ -1061,6 +1077,7 @@ pub(crate) mod tests {
             buffer_size: 10,
             max_retries: 3,
             backoff_base_time: 1,
+            rollover: None,
         };
 
         let sink_impl =
@@ -1286,6 +1303,7 @@ pub(crate) mod tests {
             buffer_size: 10,
             max_retries: 3,
             backoff_base_time: 1,
+            rollover: None,
         };
 
         let sink_impl =
@@ -1429,6 +1447,270 @@ pub(crate) mod tests {
         Ok(())
     }
 
+    #[async_std::test]
+    async fn consistent_rollover() -> Result<()> {
+        _ = env_logger::try_init();
+        let (reply_tx, reply_rx) = bounded(10);
+        let upload_client_factory = Box::new(|_config: &Config| Ok(TestUploadClient::default()));
+        let config = Config::new(&literal!({
+            "bucket": "woah",
+            "mode": "consistent",
+            "buffer_size": 1024,
+            "rollover": {
+                "key": "logs/{yyyy}/{MM}/{dd}/{meta.tenant}/{uuid}.json",
+                "max_events": 2
+            }
+        }))?;
+
+        let sink_impl =
+            GCSObjectStorageSinkImpl::consistent(config, upload_client_factory, reply_tx);
+        let mut sink: ConsistentSink<
+            GCSObjectStorageSinkImpl<TestUploadClient>,
+            GCSUpload,
+            ChunkedBuffer,
+        > = ConsistentSink::new(sink_impl);
+
+        let (connection_lost_tx, _) = bounded(10);
+
+        let alias = Alias::new("a", "b");
+        let context = SinkContext {
+            uid: SinkId::default(),
+            alias: alias.clone(),
+            connector_type: "gcs_streamer".into(),
+            quiescence_beacon: QuiescenceBeacon::default(),
+            notifier: ConnectionLostNotifier::new(connection_lost_tx),
+        };
+        let mut serializer = EventSerializer::new(
+            Some(CodecConfig::from("json")),
+            CodecReq::Required,
+            vec![],
+            &"gcs_streamer".into(),
+            &alias,
+        )?;
+
+        sink.on_start(&context).await?;
+        sink.connect(&context, &Attempt::default()).await?;
+
+        // 2022-10-18T00:00:00Z
+        let ingest_ns = 1_666_051_200_000_000_000;
+        let event = |id: u64, tenant: &'static str| Event {
+            id: EventId::from_id(0, 0, id),
+            ingest_ns,
+            data: (
+                literal!({ "id": id }),
+                literal!({
+                    "gcs_streamer": {
+                        "tenant": tenant
+                    }
+                }),
+            )
+                .into(),
+            transactional: true,
+            ..Event::default()
+        };
+
+        sink.on_event("", event(1, "snot"), &context, &mut serializer, 0)
+            .await?;
+        sink.on_event("", event(2, "snot"), &context, &mut serializer, 0)
+            .await?;
+        assert_eq!(1, test_client(&mut sink).count());
+        assert!(test_client(&mut sink).finished_uploads().is_empty());
+
+        // the third event rolls over to a new object
+        sink.on_event("", event(3, "snot"), &context, &mut serializer, 0)
+            .await?;
+        assert_eq!(2, test_client(&mut sink).count());
+        let mut finished = test_client(&mut sink).finished_uploads();
+        assert_eq!(1, finished.len());
+        let (object_id, _) = finished.pop().ok_or("no data")?;
+        assert_eq!("woah", object_id.bucket());
+        assert!(object_id.name().starts_with("logs/2022/10/18/snot/"));
+        assert!(object_id.name().ends_with(".json"));
+        let reply = reply_rx.try_recv();
+        if let Ok(AsyncSinkReply::Ack(cf, _duration)) = reply {
+            let id = cf.event_id();
+            assert!(id.is_tracking(&EventId::from_id(0, 0, 1)));
+            assert!(id.is_tracking(&EventId::from_id(0, 0, 2)));
+            assert!(!id.is_tracking(&EventId::from_id(0, 0, 3)));
+        } else {
+            panic!("Expected an ack, got {reply:?}");
+        }
+
+        // ticks don't finish uploads without `max_age`
+        let tick = Event {
+            kind: Some(SignalKind::Tick),
+            ..Event::default()
+        };
+        sink.on_signal(tick, &context, &mut serializer).await?;
+        assert_eq!(1, test_client(&mut sink).running_uploads().len());
+
+        // a different key rolls over as well
+        sink.on_event("", event(4, "badger"), &context, &mut serializer, 0)
+            .await?;
+        assert_eq!(3, test_client(&mut sink).count());
+        assert_eq!(2, test_client(&mut sink).finished_uploads().len());
+        let mut running = test_client(&mut sink).running_uploads();
+        let (object_id, _) = running.pop().ok_or("no data")?;
+        assert!(object_id.name().starts_with("logs/2022/10/18/badger/"));
+        let reply = reply_rx.try_recv();
+        if let Ok(AsyncSinkReply::Ack(cf, _duration)) = reply {
+            assert!(cf.event_id().is_tracking(&EventId::from_id(0, 0, 3)));
+        } else {
+            panic!("Expected an ack, got {reply:?}");
+        }
+
+        sink.on_stop(&context).await?;
+        assert_eq!(3, test_client(&mut sink).finished_uploads().len());
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn yolo_rollover_max_age() -> Result<()> {
+        _ = env_logger::try_init();
+        let upload_client_factory = Box::new(|_config: &Config| Ok(TestUploadClient::default()));
+        let config = Config::new(&literal!({
+            "bucket": "yolo",
+            "mode": "yolo",
+            "buffer_size": 1024,
+            "rollover": {
+                "key": "{uuid}.json",
+                "max_age": 1
+            }
+        }))?;
+        let sink_impl = GCSObjectStorageSinkImpl::yolo(config, upload_client_factory);
+        let mut sink: YoloSink<
+            GCSObjectStorageSinkImpl<TestUploadClient>,
+            GCSUpload,
+            ChunkedBuffer,
+        > = YoloSink::new(sink_impl);
+
+        let (connection_lost_tx, _) = bounded(10);
+
+        let alias = Alias::new("a", "b");
+        let context = SinkContext {
+            uid: SinkId::default(),
+            alias: alias.clone(),
+            connector_type: "gcs_streamer".into(),
+            quiescence_beacon: QuiescenceBeacon::default(),
+            notifier: ConnectionLostNotifier::new(connection_lost_tx),
+        };
+        let mut serializer = EventSerializer::new(
+            Some(CodecConfig::from("json")),
+            CodecReq::Required,
+            vec![],
+            &"gcs_streamer".into(),
+            &alias,
+        )?;
+
+        sink.on_start(&context).await?;
+        sink.connect(&context, &Attempt::default()).await?;
+
+        let event = Event {
+            data: (literal!({"snot": "badger"}), literal!({})).into(),
+            ..Event::default()
+        };
+        sink.on_event("", event, &context, &mut serializer, 0)
+            .await?;
+        assert_eq!(1, upload_client(&mut sink).running_uploads().len());
+
+        // the next tick finishes the expired upload
+        let tick = Event {
+            kind: Some(SignalKind::Tick),
+            ..Event::default()
+        };
+        sink.on_signal(tick, &context, &mut serializer).await?;
+        assert!(upload_client(&mut sink).running_uploads().is_empty());
+        let mut finished = upload_client(&mut sink).finished_uploads();
+        assert_eq!(1, finished.len());
+        let (object_id, buffers) = finished.pop().ok_or("no data")?;
+        assert!(object_id.name().ends_with(".json"));
+        let data: Vec<u8> = buffers.into_iter().flat_map(|b| b.data).collect();
+        assert_eq!(r#"{"snot":"badger"}"#.as_bytes(), &data);
+
+        sink.on_stop(&context).await?;
+        assert_eq!(1, upload_client(&mut sink).finished_uploads().len());
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn consistent_parquet() -> Result<()> {
+        _ = env_logger::try_init();
+        let (reply_tx, _reply_rx) = bounded(10);
+        let upload_client_factory = Box::new(|_config: &Config| Ok(TestUploadClient::default()));
+        let config = Config::new(&literal!({
+            "bucket": "woah",
+            "mode": "consistent",
+            "buffer_size": 1024,
+            "rollover": {
+                "key": "{uuid}.parquet",
+                "max_events": 2
+            }
+        }))?;
+
+        let sink_impl =
+            GCSObjectStorageSinkImpl::consistent(config, upload_client_factory, reply_tx);
+        let mut sink: ConsistentSink<
+            GCSObjectStorageSinkImpl<TestUploadClient>,
+            GCSUpload,
+            ChunkedBuffer,
+        > = ConsistentSink::new(sink_impl);
+
+        let (connection_lost_tx, _) = bounded(10);
+
+        let alias = Alias::new("a", "b");
+        let context = SinkContext {
+            uid: SinkId::default(),
+            alias: alias.clone(),
+            connector_type: "gcs_streamer".into(),
+            quiescence_beacon: QuiescenceBeacon::default(),
+            notifier: ConnectionLostNotifier::new(connection_lost_tx),
+        };
+        let mut serializer = EventSerializer::new(
+            Some(CodecConfig::from("parquet")),
+            CodecReq::Required,
+            vec![],
+            &"gcs_streamer".into(),
+            &alias,
+        )?;
+
+        sink.on_start(&context).await?;
+        sink.connect(&context, &Attempt::default()).await?;
+
+        let event = |id: u64| Event {
+            id: EventId::from_id(0, 0, id),
+            data: (literal!({ "id": id }), literal!({})).into(),
+            transactional: true,
+            ..Event::default()
+        };
+        // concatenate all uploaded parts of an object
+        let object_data = |parts: Vec<BufferPart>| -> Vec<u8> {
+            parts.into_iter().flat_map(|part| part.data).collect()
+        };
+        for id in 1..=3 {
+            sink.on_event("", event(id), &context, &mut serializer, 0)
+                .await?;
+        }
+        // the first object has been rolled over and contains a complete parquet file
+        let mut finished = test_client(&mut sink).finished_uploads();
+        assert_eq!(1, finished.len());
+        let (_, parts) = finished.pop().ok_or("no data")?;
+        let data = object_data(parts);
+        assert!(data.starts_with(b"PAR1"));
+        assert!(data.ends_with(b"PAR1"));
+
+        // the buffered rows of the second object are written on flush
+        sink.on_flush(&context, &mut serializer).await?;
+        sink.on_stop(&context).await?;
+        let finished = test_client(&mut sink).finished_uploads();
+        assert_eq!(2, finished.len());
+        for (_, parts) in finished {
+            let data = object_data(parts);
+            assert!(data.starts_with(b"PAR1"));
+            assert!(data.ends_with(b"PAR1"));
+        }
+        Ok(())
+    }
+
     #[async_std::test]
     async fn connector_yolo_mode() -> Result<()> {
         _ = env_logger::try_init();
@@ -1450,9 +1732,15 @@ pub(crate) mod tests {
         let mut connector_id_gen = ConnectorIdGen::default();
 
         // lets cover create-sink here
-        let addr =
-            crate::connectors::spawn(&alias, &mut connector_id_gen, &builder, cfg, &kill_switch)
-                .await?;
+        let addr = crate::connectors::spawn(
+            &alias,
+            &mut connector_id_gen,
+            &builder,
+            cfg,
+            &kill_switch,
+            Arc::default(),
+        )
+        .await?;
         let (tx, rx) = bounded(1);
         addr.stop(tx).await?;
         assert!(rx.recv().await?.res.is_ok());
@@ -1480,9 +1768,15 @@ pub(crate) mod tests {
         let mut connector_id_gen = ConnectorIdGen::default();
 
         // lets cover create-sink here
-        let addr =
-            crate::connectors::spawn(&alias, &mut connector_id_gen, &builder, cfg, &kill_switch)
-                .await?;
+        let addr = crate::connectors::spawn(
+            &alias,
+            &mut connector_id_gen,
+            &builder,
+            cfg,
+            &kill_switch,
+            Arc::default(),
+        )
+        .await?;
         let (tx, rx) = bounded(1);
         addr.stop(tx).await?;
         assert!(rx.recv().await?.res.is_ok());
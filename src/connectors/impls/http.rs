@@ -15,5 +15,7 @@
 pub(crate) mod auth;
 pub(crate) mod client;
 pub(crate) mod meta;
+pub(crate) mod poll;
+pub(crate) mod routes;
 pub(crate) mod server;
 pub(crate) mod utils;
@@ -13,6 +13,13 @@
 // limitations under the License.
 
 use std::io::Write;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_std::prelude::FutureExt;
+use async_std::sync::Mutex;
+use http_types::{headers, StatusCode};
+use tremor_common::time::nanotime;
 
 use crate::errors::Result;
 
@@ -28,6 +35,8 @@ pub(crate) enum Auth {
     ElasticsearchApiKey { id: String, api_key: String },
     #[serde(alias = "gcp")]
     Gcp,
+    #[serde(alias = "oauth2")]
+    OAuth2(OAuth2),
     #[serde(alias = "none")]
     None,
 }
@@ -57,6 +66,9 @@ impl Auth {
                 writer.into_inner(); // release the reference, so header-value is accessible again
                 Ok(Some(header_value))
             }
+            Auth::OAuth2(_) => {
+                Err("OAuth2 access tokens need to be fetched via an `AuthProvider`".into())
+            }
             Auth::None => Ok(None),
         }
     }
@@ -68,6 +80,176 @@ impl Default for Auth {
     }
 }
 
+const DEFAULT_REFRESH_MARGIN: u64 = 30_000_000_000; // 30 seconds
+/// timeout for fetching a token, if the connector has no `timeout` configured
+const DEFAULT_TOKEN_TIMEOUT: Duration = Duration::from_secs(10);
+
+fn default_refresh_margin() -> u64 {
+    DEFAULT_REFRESH_MARGIN
+}
+
+/// OAuth2 client credentials grant
+///
+/// See <https://www.rfc-editor.org/rfc/rfc6749#section-4.4>
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct OAuth2 {
+    /// URL of the token endpoint
+    pub(crate) token_url: String,
+    pub(crate) client_id: String,
+    pub(crate) client_secret: String,
+    /// scopes to request, sent as space separated `scope` parameter
+    #[serde(default = "Default::default")]
+    pub(crate) scopes: Vec<String>,
+    /// optional `audience` parameter, required by some providers
+    #[serde(default = "Default::default")]
+    pub(crate) audience: Option<String>,
+    /// refresh the token this many nanoseconds before it expires
+    #[serde(default = "default_refresh_margin")]
+    pub(crate) refresh_margin: u64,
+}
+
+/// Response of an OAuth2 token endpoint
+#[derive(Deserialize, Debug)]
+struct TokenResponse {
+    access_token: String,
+    #[serde(default = "default_token_type")]
+    token_type: String,
+    /// lifetime of the token in seconds
+    expires_in: Option<u64>,
+}
+
+fn default_token_type() -> String {
+    "Bearer".to_string()
+}
+
+/// A cached access token
+#[derive(Clone, Debug)]
+struct AccessToken {
+    header_value: String,
+    /// expiry timestamp in nanoseconds, `None` if the token doesn't expire
+    expires_at: Option<u64>,
+}
+
+impl AccessToken {
+    fn new(response: TokenResponse, now: u64) -> Self {
+        // the token type is case insensitive, but some servers are picky about `Bearer`
+        let token_type = if response.token_type.eq_ignore_ascii_case("bearer") {
+            "Bearer"
+        } else {
+            response.token_type.as_str()
+        };
+        Self {
+            header_value: format!("{token_type} {}", response.access_token),
+            expires_at: response
+                .expires_in
+                .map(|secs| now.saturating_add(secs.saturating_mul(1_000_000_000))),
+        }
+    }
+
+    /// returns `true` if this token expires within `margin` nanoseconds from `now`
+    fn expires_within(&self, margin: u64, now: u64) -> bool {
+        self.expires_at
+            .map_or(false, |expires_at| now.saturating_add(margin) >= expires_at)
+    }
+}
+
+impl OAuth2 {
+    /// fetch a new access token from the token endpoint, within `timeout`
+    async fn fetch_token(&self, timeout: Duration) -> Result<AccessToken> {
+        self.request_token()
+            .timeout(timeout)
+            .await
+            .map_err(|_| format!("Timeout fetching an OAuth2 token from {}", self.token_url))?
+    }
+
+    async fn request_token(&self) -> Result<AccessToken> {
+        let mut form = vec![("grant_type", "client_credentials".to_string())];
+        if !self.scopes.is_empty() {
+            form.push(("scope", self.scopes.join(" ")));
+        }
+        if let Some(audience) = self.audience.as_ref() {
+            form.push(("audience", audience.clone()));
+        }
+        let credentials = base64::encode(format!("{}:{}", self.client_id, self.client_secret));
+        let mut response = surf::post(&self.token_url)
+            .header(headers::AUTHORIZATION, format!("Basic {credentials}"))
+            .header(headers::ACCEPT, "application/json")
+            .body(surf::Body::from_form(&form)?)
+            .await?;
+        if !response.status().is_success() {
+            return Err(format!(
+                "OAuth2 token endpoint {} responded with {}",
+                self.token_url,
+                response.status()
+            )
+            .into());
+        }
+        let token_response: TokenResponse = response.body_json().await?;
+        Ok(AccessToken::new(token_response, nanotime()))
+    }
+}
+
+/// Provides the authorization header value for an [`Auth`] method.
+///
+/// Credentials that need to be fetched from elsewhere first, like OAuth2 access tokens,
+/// are cached and refreshed before they expire. Clones share the same cache.
+#[derive(Clone, Debug)]
+pub(crate) struct AuthProvider {
+    auth: Auth,
+    /// timeout for fetching credentials
+    timeout: Duration,
+    token: Arc<Mutex<Option<AccessToken>>>,
+}
+
+impl AuthProvider {
+    /// `timeout` in nanoseconds for fetching credentials, usually the request timeout of the connector
+    pub(crate) fn new(auth: Auth, timeout: Option<u64>) -> Self {
+        Self {
+            auth,
+            timeout: timeout.map_or(DEFAULT_TOKEN_TIMEOUT, Duration::from_nanos),
+            token: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Prepare a HTTP authorization header value, fetching or refreshing credentials if necessary
+    pub(crate) async fn header_value(&self) -> Result<Option<String>> {
+        if let Auth::OAuth2(oauth2) = &self.auth {
+            // we hold the lock while fetching, so concurrent requests don't all hit the token endpoint
+            let mut token = self.token.lock().await;
+            match token.as_ref() {
+                Some(cached) if !cached.expires_within(oauth2.refresh_margin, nanotime()) => {
+                    Ok(Some(cached.header_value.clone()))
+                }
+                _ => {
+                    let fresh = oauth2.fetch_token(self.timeout).await?;
+                    let header_value = fresh.header_value.clone();
+                    *token = Some(fresh);
+                    Ok(Some(header_value))
+                }
+            }
+        } else {
+            self.auth.as_header_value()
+        }
+    }
+
+    /// Returns `true` if the credentials can be fetched again after being rejected
+    pub(crate) fn can_refresh(&self) -> bool {
+        matches!(self.auth, Auth::OAuth2(_))
+    }
+
+    /// Returns `true` if a request rejected with the given `status`
+    /// should be retried once with fresh credentials
+    pub(crate) fn should_retry(&self, status: StatusCode) -> bool {
+        status == StatusCode::Unauthorized && self.can_refresh()
+    }
+
+    /// Drop any cached credentials, so they are fetched again for the next request
+    pub(crate) async fn invalidate(&self) {
+        self.token.lock().await.take();
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -101,4 +283,91 @@ mod tests {
             auth.as_header_value()
         );
     }
+
+    #[test]
+    fn oauth2_config() -> Result<()> {
+        let auth: Auth = tremor_value::structurize(tremor_value::literal!({
+            "oauth2": {
+                "token_url": "http://localhost:8080/token",
+                "client_id": "snot",
+                "client_secret": "badger",
+                "scopes": ["read", "write"]
+            }
+        }))?;
+        assert_eq!(
+            Auth::OAuth2(OAuth2 {
+                token_url: "http://localhost:8080/token".to_string(),
+                client_id: "snot".to_string(),
+                client_secret: "badger".to_string(),
+                scopes: vec!["read".to_string(), "write".to_string()],
+                audience: None,
+                refresh_margin: DEFAULT_REFRESH_MARGIN,
+            }),
+            auth
+        );
+        assert!(auth.as_header_value().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn access_token_expiry() {
+        let token = AccessToken::new(
+            TokenResponse {
+                access_token: "snot".to_string(),
+                token_type: "bearer".to_string(),
+                expires_in: Some(60),
+            },
+            0,
+        );
+        assert_eq!("Bearer snot", token.header_value);
+        assert!(!token.expires_within(DEFAULT_REFRESH_MARGIN, 0));
+        assert!(token.expires_within(DEFAULT_REFRESH_MARGIN, 30_000_000_000));
+        assert!(token.expires_within(0, 60_000_000_000));
+
+        let eternal = AccessToken::new(
+            TokenResponse {
+                access_token: "badger".to_string(),
+                token_type: "MAC".to_string(),
+                expires_in: None,
+            },
+            0,
+        );
+        assert_eq!("MAC badger", eternal.header_value);
+        assert!(!eternal.expires_within(DEFAULT_REFRESH_MARGIN, u64::MAX));
+    }
+
+    #[async_std::test]
+    async fn provider_static_auth() -> Result<()> {
+        let provider = AuthProvider::new(Auth::Bearer("token".to_string()), None);
+        assert_eq!(
+            Some("Bearer token".to_string()),
+            provider.header_value().await?
+        );
+        assert!(!provider.should_retry(StatusCode::Unauthorized));
+        Ok(())
+    }
+    #[async_std::test]
+    async fn provider_token_timeout() -> Result<()> {
+        // a token endpoint that accepts connections but never responds
+        let listener = async_std::net::TcpListener::bind("127.0.0.1:0").await?;
+        let token_url = format!("http://{}/token", listener.local_addr()?);
+        let server = async_std::task::spawn(async move {
+            let (stream, _) = listener.accept().await?;
+            async_std::task::sleep(Duration::from_secs(10)).await;
+            drop(stream);
+            Ok::<(), std::io::Error>(())
+        });
+        let auth = Auth::OAuth2(OAuth2 {
+            token_url,
+            client_id: "snot".to_string(),
+            client_secret: "badger".to_string(),
+            scopes: vec![],
+            audience: None,
+            refresh_margin: DEFAULT_REFRESH_MARGIN,
+        });
+        let provider = AuthProvider::new(auth, Some(100_000_000));
+        assert!(provider.header_value().await.is_err());
+        server.cancel().await;
+        Ok(())
+    }
 }
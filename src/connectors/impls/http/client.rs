@@ -17,20 +17,26 @@ use std::sync::Arc;
 use std::time::Duration;
 
 use async_std::channel::{bounded, Receiver, Sender};
+use async_std::task::JoinHandle;
 use either::Either;
 use halfbrown::HashMap;
 use http_client::h1::H1Client;
 use http_client::HttpClient;
-use http_types::Method;
+use http_types::{headers, Method, Request, Response, StatusCode};
 use tremor_common::time::nanotime;
 
-use super::auth::Auth;
+use super::auth::{Auth, AuthProvider};
 use super::meta::{extract_request_meta, extract_response_meta, HttpRequestBuilder};
+use super::poll::{PollConfig, Poller};
 use super::utils::{Header, RequestId};
-use crate::connectors::sink::concurrency_cap::ConcurrencyCap;
+use crate::connectors::sink::concurrency_cap::{AdaptiveConfig, ConcurrencyCap};
+use crate::connectors::utils::metrics::make_concurrency_metrics_payload;
 use crate::connectors::utils::mime::MimeCodecMap;
-use crate::connectors::utils::tls::{tls_client_config, TLSClientConfig};
-use crate::{connectors::prelude::*, errors::err_connector_def};
+use crate::connectors::utils::tls::{Reloadable, TLSClientConfig};
+use crate::{
+    connectors::{prelude::*, spawn_task},
+    errors::err_connector_def,
+};
 
 const CONNECTOR_TYPE: &str = "http_client";
 #[derive(Deserialize, Debug, Clone)]
@@ -45,6 +51,9 @@ pub(crate) struct Config {
     /// Concurrency capacity limits ( in flight requests )
     #[serde(default = "default_concurrency")]
     pub(super) concurrency: usize,
+    /// Adapt the concurrency limit to the observed latency and errors, starting at `concurrency`
+    #[serde(default = "Default::default")]
+    pub(super) adaptive_concurrency: Option<AdaptiveConfig>,
     /// Default HTTP headers
     #[serde(default = "Default::default")]
     pub(super) headers: HashMap<String, Header>,
@@ -61,6 +70,9 @@ pub(crate) struct Config {
     /// e.g. for handling `application/json` with the `binary` codec, if desired
     /// the mime type of `*/*` serves as a default / fallback
     mime_mapping: Option<HashMap<String, String>>,
+    /// periodically poll `url` and emit the responses via the source
+    #[serde(default = "Default::default")]
+    poll: Option<PollConfig>,
 }
 
 const DEFAULT_CONCURRENCY: usize = 4;
@@ -97,9 +109,9 @@ impl ConnectorBuilder for Builder {
         let tls_client_config = match config.tls.as_ref() {
             Some(Either::Right(true)) => {
                 // default config
-                Some(tls_client_config(&TLSClientConfig::default()).await?)
+                Some(Reloadable::client(&TLSClientConfig::default())?)
             }
-            Some(Either::Left(tls_config)) => Some(tls_client_config(tls_config).await?),
+            Some(Either::Left(tls_config)) => Some(Reloadable::client(tls_config)?),
             Some(Either::Right(false)) | None => None,
         };
         if config.url.scheme() == "https" && tls_client_config.is_none() {
@@ -108,6 +120,21 @@ impl ConnectorBuilder for Builder {
                     "missing tls config with 'https' url. Set 'tls' to 'true' or provide a full tls config.",
                 ));
         }
+        if let Some(adaptive) = config.adaptive_concurrency.as_ref() {
+            adaptive
+                .validate()
+                .map_err(|e| err_connector_def(id, &e.to_string()))?;
+        }
+        if config
+            .poll
+            .as_ref()
+            .map_or(false, |poll| poll.interval == 0)
+        {
+            return Err(err_connector_def(
+                id,
+                "poll interval must be greater than 0",
+            ));
+        }
         let (response_tx, response_rx) = bounded(crate::QSIZE.load(Ordering::Relaxed));
         let mime_codec_map = Arc::new(if let Some(codec_map) = config.mime_mapping.clone() {
             MimeCodecMap::from_custom(codec_map)
@@ -131,7 +158,7 @@ pub(crate) struct Client {
     response_tx: Sender<SourceReply>,
     response_rx: Receiver<SourceReply>,
     config: Config,
-    tls_client_config: Option<rustls::ClientConfig>,
+    tls_client_config: Option<Reloadable<rustls::ClientConfig>>,
     // this is basically an immutable map, we use arc to share it across tasks (e.g. for each request sending)
     mime_codec_map: Arc<MimeCodecMap>,
     source_is_connected: Arc<AtomicBool>,
@@ -151,6 +178,11 @@ impl Connector for Client {
         let source = HttpRequestSource {
             is_connected: self.source_is_connected.clone(),
             rx: self.response_rx.clone(),
+            tx: self.response_tx.clone(),
+            config: self.config.clone(),
+            tls_client_config: self.tls_client_config.clone(),
+            codec_map: self.mime_codec_map.clone(),
+            poll_task: None,
         };
         builder.spawn(source, source_context).map(Some)
     }
@@ -172,24 +204,124 @@ impl Connector for Client {
     }
 }
 
+/// A HTTP client that is built again once the TLS config got reloaded,
+/// so new connections use the reloaded certificates
+pub(crate) struct ReloadingClient {
+    config: Config,
+    tls_client_config: Option<Reloadable<rustls::ClientConfig>>,
+    client: Arc<H1Client>,
+    generation: u64,
+}
+
+impl ReloadingClient {
+    fn new(
+        config: &Config,
+        tls_client_config: Option<Reloadable<rustls::ClientConfig>>,
+    ) -> Result<Self> {
+        let generation = tls_client_config.as_ref().map_or(0, Reloadable::generation);
+        let client = Arc::new(build_client(config, tls_client_config.as_ref())?);
+        Ok(Self {
+            config: config.clone(),
+            tls_client_config,
+            client,
+            generation,
+        })
+    }
+
+    /// The client for the next request, using the current TLS config
+    ///
+    /// Requests in flight keep their connections of the previous client.
+    pub(crate) fn get(&mut self) -> Arc<H1Client> {
+        if let Some(tls_client_config) = self.tls_client_config.as_ref() {
+            let generation = tls_client_config.generation();
+            if generation != self.generation {
+                match build_client(&self.config, Some(tls_client_config)) {
+                    Ok(client) => self.client = Arc::new(client),
+                    Err(e) => {
+                        error!("Error building HTTP client with the reloaded TLS config: {e}")
+                    }
+                }
+                self.generation = generation;
+            }
+        }
+        self.client.clone()
+    }
+}
+
+/// Create a HTTP client from the connector config, using the current TLS config
+fn build_client(
+    config: &Config,
+    tls_client_config: Option<&Reloadable<rustls::ClientConfig>>,
+) -> Result<H1Client> {
+    let timeout = config.timeout.map(Duration::from_nanos);
+    let tls_config = tls_client_config.map(Reloadable::current);
+    let client_config = http_client::Config::new()
+        .set_http_keep_alive(true) // TODO: make configurable, maybe some people don't want that
+        .set_tcp_no_delay(true)
+        .set_timeout(timeout)
+        .set_max_connections_per_host(
+            config
+                .adaptive_concurrency
+                .as_ref()
+                .map_or(config.concurrency, |adaptive| adaptive.max),
+        )
+        .set_tls_config(tls_config);
+
+    Ok(H1Client::try_from(client_config)
+        .map_err(|e| format!("Invalid HTTP Client config: {e}."))?)
+}
+
 struct HttpRequestSource {
     is_connected: Arc<AtomicBool>,
     rx: Receiver<SourceReply>,
+    tx: Sender<SourceReply>,
+    config: Config,
+    tls_client_config: Option<Reloadable<rustls::ClientConfig>>,
+    codec_map: Arc<MimeCodecMap>,
+    poll_task: Option<JoinHandle<()>>,
 }
 
 #[async_trait::async_trait()]
 impl Source for HttpRequestSource {
+    async fn connect(&mut self, ctx: &SourceContext, _attempt: &Attempt) -> Result<bool> {
+        if let Some(poll) = self.config.poll.clone() {
+            // stop polling with the previous client
+            if let Some(poll_task) = self.poll_task.take() {
+                poll_task.cancel().await;
+            }
+            let client = ReloadingClient::new(&self.config, self.tls_client_config.clone())?;
+            let poller = Poller::new(
+                poll,
+                self.config.url.url().clone(),
+                self.config.headers.clone(),
+                AuthProvider::new(self.config.auth.clone(), self.config.timeout),
+                client,
+                self.codec_map.clone(),
+                self.tx.clone(),
+            );
+            self.poll_task = Some(spawn_task(ctx.clone(), poller.run(ctx.clone())));
+        }
+        Ok(true)
+    }
+
     async fn pull_data(&mut self, _pull_id: &mut u64, _ctx: &SourceContext) -> Result<SourceReply> {
         Ok(self.rx.recv().await?)
     }
 
+    async fn on_stop(&mut self, _ctx: &SourceContext) -> Result<()> {
+        if let Some(poll_task) = self.poll_task.take() {
+            poll_task.cancel().await;
+        }
+        Ok(())
+    }
+
     fn is_transactional(&self) -> bool {
         false
     }
 
-    /// there is no asynchronous task driving this and is being stopped by the quiescence process
+    /// without polling there is no asynchronous task driving this and is being stopped by the quiescence process
     fn asynchronous(&self) -> bool {
-        false
+        self.config.poll.is_some()
     }
 
     async fn on_cb_open(&mut self, _ctx: &SourceContext) -> Result<()> {
@@ -202,11 +334,12 @@ impl Source for HttpRequestSource {
 
 struct HttpRequestSink {
     request_counter: u64,
-    client: Option<Arc<H1Client>>,
+    client: Option<ReloadingClient>,
+    auth: AuthProvider,
     response_tx: Sender<SourceReply>,
     reply_tx: Sender<AsyncSinkReply>,
     config: Config,
-    tls_client_config: Option<rustls::ClientConfig>,
+    tls_client_config: Option<Reloadable<rustls::ClientConfig>>,
     // reply_tx: Sender<AsyncSinkReply>,
     concurrency_cap: ConcurrencyCap,
     origin_uri: EventOriginUri,
@@ -222,14 +355,20 @@ impl HttpRequestSink {
         response_tx: Sender<SourceReply>,
         reply_tx: Sender<AsyncSinkReply>,
         config: Config,
-        tls_client_config: Option<rustls::ClientConfig>,
+        tls_client_config: Option<Reloadable<rustls::ClientConfig>>,
         codec_map: Arc<MimeCodecMap>,
         source_is_connected: Arc<AtomicBool>,
     ) -> Self {
-        let concurrency_cap = ConcurrencyCap::new(config.concurrency, reply_tx.clone());
+        let concurrency_cap = if let Some(adaptive) = config.adaptive_concurrency.clone() {
+            ConcurrencyCap::adaptive(config.concurrency, adaptive, reply_tx.clone())
+        } else {
+            ConcurrencyCap::new(config.concurrency, reply_tx.clone())
+        };
+        let auth = AuthProvider::new(config.auth.clone(), config.timeout);
         Self {
             request_counter: 1, // always start by 1, 0 is DEFAULT_STREAM_ID and this might interfere with custom codecs
             client: None,
+            auth,
             response_tx,
             reply_tx,
             config,
@@ -250,18 +389,8 @@ impl HttpRequestSink {
 #[async_trait::async_trait()]
 impl Sink for HttpRequestSink {
     async fn connect(&mut self, _ctx: &SinkContext, _attempt: &Attempt) -> Result<bool> {
-        let timeout = self.config.timeout.map(Duration::from_nanos);
-        let tls_config = self.tls_client_config.as_ref().cloned().map(Arc::new);
-        let client_config = http_client::Config::new()
-            .set_http_keep_alive(true) // TODO: make configurable, maybe some people don't want that
-            .set_tcp_no_delay(true)
-            .set_timeout(timeout)
-            .set_max_connections_per_host(self.config.concurrency)
-            .set_tls_config(tls_config);
-
-        let client = H1Client::try_from(client_config)
-            .map_err(|e| format!("Invalid HTTP Client config: {e}."))?;
-        self.client = Some(Arc::new(client));
+        let client = ReloadingClient::new(&self.config, self.tls_client_config.clone())?;
+        self.client = Some(client);
 
         Ok(true)
     }
@@ -278,7 +407,7 @@ impl Sink for HttpRequestSink {
         // constrain to max concurrency - propagate CB close on hitting limit
         let guard = self.concurrency_cap.inc_for(&event).await?;
 
-        if let Some(client) = self.client.as_ref().cloned() {
+        if let Some(client) = self.client.as_mut().map(ReloadingClient::get) {
             let send_ctx = ctx.clone();
             let response_tx = self
                 .source_is_connected
@@ -302,8 +431,18 @@ impl Sink for HttpRequestSink {
             self.request_counter = self.request_counter.wrapping_add(1).max(1);
 
             let http_meta = event_meta.and_then(|meta| ctx.extract_meta(meta));
+            let auth_header = ctx.bail_err(
+                self.auth.header_value().await,
+                "Error fetching authorization credentials",
+            )?;
             let mut builder = ctx.bail_err(
-                HttpRequestBuilder::new(request_id, http_meta, &self.codec_map, &self.config),
+                HttpRequestBuilder::new(
+                    request_id,
+                    http_meta,
+                    &self.codec_map,
+                    &self.config,
+                    auth_header,
+                ),
                 "Error turning event into an HTTP Request",
             )?;
             let codec_map = self.codec_map.clone();
@@ -325,12 +464,23 @@ impl Sink for HttpRequestSink {
                 )?;
             }
 
-            if let Some(request) = request {
+            if let Some(mut request) = request {
+                let auth = self.auth.clone();
                 // spawn the sending task
                 async_std::task::spawn::<_, Result<()>>(async move {
+                    // keep a copy of the request around, so we can retry it with fresh credentials
+                    // chunked requests cannot be retried, as their body has been streamed already
+                    let retry_request = if auth.can_refresh() && !request_is_chunked {
+                        Some(send_ctx.bail_err(
+                            copy_request(&mut request).await,
+                            "Error preparing HTTP request",
+                        )?)
+                    } else {
+                        None
+                    };
                     // extract request meta for the response metadata from the finally prepared request
                     // the actual sent request might differ from the metadata used to create this request
-                    let req_meta = extract_request_meta(&request);
+                    let mut req_meta = extract_request_meta(&request);
                     if let Some(host) = request.host() {
                         origin_uri.host = host.to_string();
                     }
@@ -340,7 +490,35 @@ impl Sink for HttpRequestSink {
                         .path_segments()
                         .map(|iter| iter.map(ToString::to_string).collect::<Vec<_>>())
                         .unwrap_or_default();
-                    match client.send(request).await {
+                    let mut result = client.send(request).await;
+                    let rejected = result
+                        .as_ref()
+                        .ok()
+                        .map(Response::status)
+                        .filter(|status| auth.should_retry(*status));
+                    if let Some((status, mut retry_request)) = rejected.zip(retry_request) {
+                        debug!("{send_ctx} Request rejected with {status}. Retrying with fresh credentials.");
+                        auth.invalidate().await;
+                        match auth.header_value().await {
+                            Ok(auth_header) => {
+                                if let Some(auth_header) = auth_header {
+                                    retry_request
+                                        .insert_header(headers::AUTHORIZATION, auth_header);
+                                }
+                                req_meta = extract_request_meta(&retry_request);
+                                result = client.send(retry_request).await;
+                            }
+                            Err(e) => {
+                                error!("{send_ctx} Error fetching authorization credentials: {e}");
+                            }
+                        }
+                    }
+                    // server errors and rate limiting are signs of overload
+                    let success = result.as_ref().map_or(false, |response| {
+                        !response.status().is_server_error()
+                            && response.status() != StatusCode::TooManyRequests
+                    });
+                    match result {
                         Ok(mut response) => {
                             let response_meta = extract_response_meta(&response);
                             let mut meta = send_ctx.meta(literal!({
@@ -398,7 +576,7 @@ impl Sink for HttpRequestSink {
                             }
                         }
                     }
-                    drop(guard);
+                    guard.complete(success);
                     Ok(())
                 });
             } else {
@@ -428,6 +606,21 @@ impl Sink for HttpRequestSink {
         Ok(SinkReply::NONE)
     }
 
+    async fn metrics(&mut self, timestamp: u64, ctx: &SinkContext) -> Vec<EventPayload> {
+        let mut metrics = vec![make_concurrency_metrics_payload(
+            timestamp,
+            self.concurrency_cap.cap(),
+            self.concurrency_cap.inflight(),
+            ctx.alias(),
+        )];
+        metrics.extend(
+            self.tls_client_config
+                .iter()
+                .map(|tls| tls.metrics(timestamp, ctx.alias())),
+        );
+        metrics
+    }
+
     fn asynchronous(&self) -> bool {
         true
     }
@@ -437,3 +630,12 @@ impl Sink for HttpRequestSink {
         false
     }
 }
+
+/// Buffer the body of `request` and return a copy of it, to be sent again later.
+async fn copy_request(request: &mut Request) -> Result<Request> {
+    let body = request.take_body().into_bytes().await?;
+    let mut copy = request.clone();
+    copy.set_body(body.clone());
+    request.set_body(body);
+    Ok(copy)
+}
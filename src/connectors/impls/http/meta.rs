@@ -108,6 +108,7 @@ impl HttpRequestBuilder {
         meta: Option<&Value>,
         codec_map: &MimeCodecMap,
         config: &client::Config,
+        auth_header: Option<String>,
     ) -> Result<Self> {
         let request_meta = meta.get("request");
         let method = if let Some(method_v) = request_meta.get("method") {
@@ -167,7 +168,7 @@ impl HttpRequestBuilder {
             }
         }
         // handle AUTH
-        if let Some(auth_header) = config.auth.as_header_value()? {
+        if let Some(auth_header) = auth_header {
             request.insert_header(headers::AUTHORIZATION, auth_header);
         }
 
@@ -394,7 +395,7 @@ mod test {
         )?;
         let config = client::Config::new(&c)?;
 
-        let mut b = HttpRequestBuilder::new(request_id, meta, &codec_map, &config)?;
+        let mut b = HttpRequestBuilder::new(request_id, meta, &codec_map, &config, None)?;
 
         let r = b.finalize(&mut s).await?.ok_or("no data")?;
         assert_eq!(
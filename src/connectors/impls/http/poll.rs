@@ -0,0 +1,409 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Polling mode for the `http_client` connector
+//!
+//! Periodically requests the configured url and emits the responses via the source,
+//! following pagination and remembering the page to continue at.
+
+use super::auth::AuthProvider;
+use super::client::ReloadingClient;
+use super::meta::{extract_request_meta, extract_response_meta};
+use super::utils::Header;
+use crate::connectors::prelude::*;
+use crate::connectors::utils::{
+    mime::MimeCodecMap,
+    poll::{load_state, save_state, Interval},
+};
+use async_std::channel::Sender;
+use async_std::path::Path;
+use either::Either;
+use halfbrown::HashMap;
+use http_client::HttpClient;
+use http_types::{headers, Method, Request, StatusCode};
+use std::sync::Arc;
+
+fn default_poll_method() -> Method {
+    Method::Get
+}
+
+fn default_conditional() -> bool {
+    true
+}
+
+fn default_max_pages() -> usize {
+    100
+}
+
+/// Configuration for polling
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct PollConfig {
+    /// interval between the start of two polls in nanoseconds
+    pub(crate) interval: u64,
+    /// HTTP method to poll with
+    #[serde(default = "default_poll_method")]
+    method: Method,
+    /// send `If-None-Match` and `If-Modified-Since` headers from the `ETag` and `Last-Modified` of the last response
+    #[serde(default = "default_conditional")]
+    conditional: bool,
+    /// how to get from one page of results to the next
+    #[serde(default = "Default::default")]
+    pagination: Pagination,
+    /// maximum number of pages to fetch in a single poll
+    #[serde(default = "default_max_pages")]
+    max_pages: usize,
+    /// file to persist the polling position in, so polling continues where it left off after a restart
+    #[serde(default = "Default::default")]
+    state_file: Option<String>,
+}
+
+/// Pagination strategy
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Pagination {
+    /// only ever request the configured url
+    None,
+    /// follow `Link` headers with `rel="next"` (RFC 8288)
+    Link,
+    /// take the cursor of the next page from the json response body and send it as query parameter
+    Cursor {
+        /// dot-separated path to the cursor within the response body, e.g. `meta.next_cursor`
+        field: String,
+        /// name of the query parameter to send the cursor with
+        param: String,
+    },
+}
+
+impl Default for Pagination {
+    fn default() -> Self {
+        Self::None
+    }
+}
+
+impl Pagination {
+    /// Determine the url of the page after the one at `current` from its response.
+    ///
+    /// Returns `None` if there is no next page.
+    pub(crate) fn next_url(
+        &self,
+        current: &url::Url,
+        link: Option<&str>,
+        body: &[u8],
+    ) -> Option<url::Url> {
+        match self {
+            Pagination::None => None,
+            Pagination::Link => link
+                .and_then(next_link)
+                .and_then(|next| current.join(next).ok()),
+            Pagination::Cursor { field, param } => {
+                let mut buf = body.to_vec();
+                let body = tremor_value::parse_to_value(&mut buf).ok()?;
+                let cursor = field
+                    .split('.')
+                    .try_fold(&body, |value, key| value.get(key))?;
+                let cursor = cursor
+                    .as_str()
+                    .map(ToString::to_string)
+                    .or_else(|| cursor.as_u64().map(|cursor| cursor.to_string()))?;
+                if cursor.is_empty() {
+                    return None;
+                }
+                let mut next = current.clone();
+                let query: Vec<(String, String)> = current
+                    .query_pairs()
+                    .filter(|(name, _)| name != param.as_str())
+                    .map(|(name, value)| (name.to_string(), value.to_string()))
+                    .collect();
+                next.query_pairs_mut()
+                    .clear()
+                    .extend_pairs(query)
+                    .append_pair(param, &cursor);
+                Some(next)
+            }
+        }
+    }
+}
+
+/// Extract the target of the `rel="next"` link from a `Link` header value
+fn next_link(header: &str) -> Option<&str> {
+    header.split(',').find_map(|link| {
+        let mut parts = link.split(';');
+        let target = parts.next()?.trim().strip_prefix('<')?.strip_suffix('>')?;
+        parts
+            .filter_map(|param| param.split_once('='))
+            .filter(|(name, _)| name.trim().eq_ignore_ascii_case("rel"))
+            .any(|(_, value)| {
+                value
+                    .trim()
+                    .trim_matches('"')
+                    .split_whitespace()
+                    .any(|rel| rel.eq_ignore_ascii_case("next"))
+            })
+            .then_some(target)
+    })
+}
+
+/// The polling position
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub(crate) struct PollState {
+    /// url of the page to continue polling at, the configured url if not set
+    url: Option<String>,
+    /// `ETag` of the last response for `url`
+    etag: Option<String>,
+    /// `Last-Modified` of the last response for `url`
+    last_modified: Option<String>,
+}
+
+/// Periodically polls the configured url and sends the responses to the source
+pub(crate) struct Poller {
+    config: PollConfig,
+    url: url::Url,
+    headers: HashMap<String, Header>,
+    auth: AuthProvider,
+    client: ReloadingClient,
+    codec_map: Arc<MimeCodecMap>,
+    tx: Sender<SourceReply>,
+    state: PollState,
+}
+
+impl Poller {
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn new(
+        config: PollConfig,
+        url: url::Url,
+        headers: HashMap<String, Header>,
+        auth: AuthProvider,
+        client: ReloadingClient,
+        codec_map: Arc<MimeCodecMap>,
+        tx: Sender<SourceReply>,
+    ) -> Self {
+        Self {
+            config,
+            url,
+            headers,
+            auth,
+            client,
+            codec_map,
+            tx,
+            state: PollState::default(),
+        }
+    }
+
+    /// Poll until cancelled
+    pub(crate) async fn run(mut self, ctx: SourceContext) -> Result<()> {
+        if let Some(state_file) = self.config.state_file.as_ref() {
+            self.state = load_state(Path::new(state_file)).await?;
+        }
+        let mut interval = Interval::new(self.config.interval);
+        loop {
+            interval.tick().await;
+            if let Err(e) = self.poll(&ctx).await {
+                warn!("{ctx} Error polling {}: {e}", self.url);
+            }
+        }
+    }
+
+    /// Fetch all new pages, starting at the persisted position
+    async fn poll(&mut self, ctx: &SourceContext) -> Result<()> {
+        for page in 0..self.config.max_pages {
+            let url = match self.state.url.as_deref() {
+                Some(url) => url::Url::parse(url)?,
+                None => self.url.clone(),
+            };
+            let request = self.request(url.clone()).await?;
+            let mut req_meta = extract_request_meta(&request);
+            let mut response = self.client.get().send(request).await?;
+            if self.auth.should_retry(response.status()) {
+                debug!("{ctx} Poll of {url} rejected as unauthorized. Retrying with fresh credentials.");
+                self.auth.invalidate().await;
+                let request = self.request(url.clone()).await?;
+                req_meta = extract_request_meta(&request);
+                response = self.client.get().send(request).await?;
+            }
+            let status = response.status();
+            if status == StatusCode::NotModified {
+                debug!("{ctx} {url} not modified.");
+                return Ok(());
+            }
+            let data = response.body_bytes().await?;
+            let next_url = if status.is_success() {
+                let link = response
+                    .header(headers::LINK)
+                    .map(|values| values.last().as_str());
+                self.config.pagination.next_url(&url, link, &data)
+            } else {
+                None
+            };
+
+            let meta = ctx.meta(literal!({
+                "request": req_meta,
+                "response": extract_response_meta(&response),
+                "poll": {
+                    "page": page
+                }
+            }));
+            let codec_overwrite = response
+                .content_type()
+                .and_then(|mime| self.codec_map.get_codec_name(mime.essence()))
+                .cloned();
+            let origin_uri = EventOriginUri {
+                scheme: "http_client".to_string(),
+                host: url.host_str().unwrap_or_default().to_string(),
+                port: url.port_or_known_default(),
+                path: url
+                    .path_segments()
+                    .map(|iter| iter.map(ToString::to_string).collect::<Vec<_>>())
+                    .unwrap_or_default(),
+            };
+            self.tx
+                .send(SourceReply::Data {
+                    origin_uri,
+                    data,
+                    meta: Some(meta),
+                    stream: None, // every response is a discrete unit and not part of a stream
+                    port: None,
+                    codec_overwrite,
+                })
+                .await?;
+            if !status.is_success() {
+                // try again at the same position with the next poll
+                return Ok(());
+            }
+
+            if let Some(next_url) = next_url.as_ref() {
+                self.state = PollState {
+                    url: Some(next_url.to_string()),
+                    etag: None,
+                    last_modified: None,
+                };
+            } else {
+                // stay on the last page, new data will show up there
+                self.state.etag = response
+                    .header(headers::ETAG)
+                    .map(|values| values.last().to_string());
+                self.state.last_modified = response
+                    .header(headers::LAST_MODIFIED)
+                    .map(|values| values.last().to_string());
+            }
+            if let Some(state_file) = self.config.state_file.as_ref() {
+                save_state(Path::new(state_file), &self.state).await?;
+            }
+            if next_url.is_none() {
+                return Ok(());
+            }
+        }
+        debug!(
+            "{ctx} Fetched {} pages, continuing with the next poll.",
+            self.config.max_pages
+        );
+        Ok(())
+    }
+
+    /// Build the request for `url`
+    async fn request(&self, url: url::Url) -> Result<Request> {
+        let mut request = Request::new(self.config.method, url);
+        for (name, values) in &self.headers {
+            match &values.0 {
+                Either::Left(values) => {
+                    for value in values {
+                        request.append_header(name.as_str(), value.as_str());
+                    }
+                }
+                Either::Right(value) => {
+                    request.append_header(name.as_str(), value.as_str());
+                }
+            }
+        }
+        if let Some(auth_header) = self.auth.header_value().await? {
+            request.insert_header(headers::AUTHORIZATION, auth_header);
+        }
+        if self.config.conditional {
+            if let Some(etag) = self.state.etag.as_ref() {
+                request.insert_header(headers::IF_NONE_MATCH, etag.as_str());
+            }
+            if let Some(last_modified) = self.state.last_modified.as_ref() {
+                request.insert_header(headers::IF_MODIFIED_SINCE, last_modified.as_str());
+            }
+        }
+        Ok(request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn link_pagination() -> Result<()> {
+        let current = url::Url::parse("https://api.example.com/items?page=1")?;
+        let link = r#"<https://api.example.com/items?page=1>; rel="prev", </items?page=3>; rel="next last""#;
+        assert_eq!(
+            Some(url::Url::parse("https://api.example.com/items?page=3")?),
+            Pagination::Link.next_url(&current, Some(link), b"")
+        );
+        assert_eq!(
+            None,
+            Pagination::Link.next_url(&current, Some(r#"</items?page=1>; rel="prev""#), b"")
+        );
+        assert_eq!(None, Pagination::Link.next_url(&current, None, b""));
+        Ok(())
+    }
+
+    #[test]
+    fn cursor_pagination() -> Result<()> {
+        let pagination = Pagination::Cursor {
+            field: "meta.next".to_string(),
+            param: "cursor".to_string(),
+        };
+        let current = url::Url::parse("https://api.example.com/items?limit=10&cursor=abc")?;
+        assert_eq!(
+            Some(url::Url::parse(
+                "https://api.example.com/items?limit=10&cursor=def"
+            )?),
+            pagination.next_url(&current, None, br#"{"meta": {"next": "def"}}"#)
+        );
+        assert_eq!(
+            Some(url::Url::parse(
+                "https://api.example.com/items?limit=10&cursor=42"
+            )?),
+            pagination.next_url(&current, None, br#"{"meta": {"next": 42}}"#)
+        );
+        assert_eq!(
+            None,
+            pagination.next_url(&current, None, br#"{"meta": {"next": ""}}"#)
+        );
+        assert_eq!(
+            None,
+            pagination.next_url(&current, None, br#"{"meta": {"next": null}}"#)
+        );
+        assert_eq!(None, pagination.next_url(&current, None, b"snot"));
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn state_roundtrip() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = async_std::path::PathBuf::from(dir.path().join("poll.json"));
+        assert_eq!(PollState::default(), load_state::<PollState>(&path).await?);
+        let state = PollState {
+            url: Some("http://localhost/items?page=2".to_string()),
+            etag: Some(r#""snot""#.to_string()),
+            last_modified: None,
+        };
+        save_state(&path, &state).await?;
+        assert_eq!(state, load_state::<PollState>(&path).await?);
+        Ok(())
+    }
+}
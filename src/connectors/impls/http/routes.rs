@@ -0,0 +1,514 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Route table for the `http_server` connector
+//!
+//! Routes are matched in the order they are configured, the first one matching the request path wins.
+
+use crate::connectors::utils::tls::PeerCertificates;
+use crate::errors::{Error, Result};
+use beef::Cow;
+use http_types::{
+    headers::{self, HeaderValue},
+    Method, StatusCode,
+};
+use std::str::FromStr;
+use tide::security::{CorsMiddleware, Origin};
+use tremor_common::ports::{ERR, OUT};
+use tremor_value::{prelude::*, Value};
+
+/// Authentication required for requests
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum ServerAuth {
+    /// HTTP basic auth with the given credentials
+    Basic {
+        username: String,
+        password: String,
+    },
+    /// Bearer token auth with the given token
+    Bearer(String),
+    /// requires a client certificate, verified against the `cafile` of the tls config
+    Mtls,
+    None,
+}
+
+impl Default for ServerAuth {
+    fn default() -> Self {
+        Self::None
+    }
+}
+
+impl ServerAuth {
+    /// Check the given request headers and peer certificates against this auth method.
+    ///
+    /// Returns the status code to reject the request with, if it is not authorized.
+    pub(crate) fn check(
+        &self,
+        authorization: Option<&str>,
+        peer_certificates: Option<&PeerCertificates>,
+    ) -> Option<StatusCode> {
+        let authorized = match self {
+            ServerAuth::Basic { username, password } => authorization
+                .and_then(|auth| auth.strip_prefix("Basic "))
+                .and_then(|encoded| base64::decode(encoded.trim()).ok())
+                .and_then(|decoded| String::from_utf8(decoded).ok())
+                .map_or(false, |credentials| {
+                    constant_time_eq(
+                        credentials.as_bytes(),
+                        format!("{username}:{password}").as_bytes(),
+                    )
+                }),
+            ServerAuth::Bearer(token) => authorization
+                .and_then(|auth| auth.strip_prefix("Bearer "))
+                .map_or(false, |given| {
+                    constant_time_eq(given.trim().as_bytes(), token.as_bytes())
+                }),
+            ServerAuth::Mtls => {
+                return peer_certificates
+                    .map_or(true, |certs| certs.0.is_empty())
+                    .then_some(StatusCode::Forbidden);
+            }
+            ServerAuth::None => true,
+        };
+        (!authorized).then_some(StatusCode::Unauthorized)
+    }
+
+    /// The `WWW-Authenticate` challenge to send along with a `401 Unauthorized`
+    pub(crate) fn challenge(&self) -> Option<&'static str> {
+        match self {
+            ServerAuth::Basic { .. } => Some(r#"Basic realm="tremor""#),
+            ServerAuth::Bearer(_) => Some("Bearer"),
+            ServerAuth::Mtls | ServerAuth::None => None,
+        }
+    }
+}
+
+/// compare two byte slices without short-circuiting, so the time taken doesn't leak how much matched
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0_u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn default_route_port() -> String {
+    OUT.to_string()
+}
+
+/// A single entry in the route table
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct RouteConfig {
+    /// path pattern, e.g. `/api/:version/users/:id` or `/static/*path`
+    ///
+    /// Segments starting with `:` capture a single path segment,
+    /// a last segment starting with `*` captures the rest of the path.
+    pub(crate) path: String,
+    /// HTTP methods this route accepts, accepts all methods if empty
+    #[serde(default = "Default::default")]
+    pub(crate) methods: Vec<Method>,
+    /// output port to emit requests matching this route on
+    #[serde(default = "default_route_port")]
+    pub(crate) port: String,
+    /// authentication required for this route, overrides the connector wide `auth`
+    #[serde(default = "Default::default")]
+    pub(crate) auth: Option<ServerAuth>,
+    /// maximum request body size in bytes, overrides the connector wide `max_body_size`
+    #[serde(default = "Default::default")]
+    pub(crate) max_body_size: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Literal(String),
+    Capture(String),
+    Rest(String),
+}
+
+/// A parsed route path pattern
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct PathPattern {
+    segments: Vec<Segment>,
+}
+
+impl FromStr for PathPattern {
+    type Err = Error;
+
+    fn from_str(pattern: &str) -> Result<Self> {
+        if !pattern.starts_with('/') {
+            return Err(format!("Invalid route path \"{pattern}\": must start with '/'").into());
+        }
+        let mut segments = Vec::new();
+        let mut parts = pattern.split('/').filter(|s| !s.is_empty()).peekable();
+        while let Some(part) = parts.next() {
+            let segment = if let Some(name) = part.strip_prefix(':') {
+                Segment::Capture(name.to_string())
+            } else if let Some(name) = part.strip_prefix('*') {
+                if parts.peek().is_some() {
+                    return Err(format!(
+                        "Invalid route path \"{pattern}\": `*{name}` must be the last segment"
+                    )
+                    .into());
+                }
+                Segment::Rest(name.to_string())
+            } else {
+                Segment::Literal(part.to_string())
+            };
+            if let Segment::Capture(name) | Segment::Rest(name) = &segment {
+                if name.is_empty() {
+                    return Err(format!("Invalid route path \"{pattern}\": unnamed capture").into());
+                }
+            }
+            segments.push(segment);
+        }
+        Ok(Self { segments })
+    }
+}
+
+impl PathPattern {
+    /// Match the given request path against this pattern.
+    ///
+    /// Returns the captured path segments if it matches.
+    /// Empty segments and thus trailing slashes are ignored.
+    pub(crate) fn matches(&self, path: &str) -> Option<Value<'static>> {
+        let mut captures = Value::object_with_capacity(self.segments.len());
+        let mut parts = path.split('/').filter(|s| !s.is_empty());
+        for segment in &self.segments {
+            match segment {
+                Segment::Literal(literal) => {
+                    if parts.next()? != literal {
+                        return None;
+                    }
+                }
+                Segment::Capture(name) => {
+                    captures.try_insert(name.clone(), parts.next()?.to_string());
+                }
+                Segment::Rest(name) => {
+                    let rest: Vec<&str> = parts.by_ref().collect();
+                    captures.try_insert(name.clone(), rest.join("/"));
+                }
+            }
+        }
+        if parts.next().is_some() {
+            None
+        } else {
+            Some(captures)
+        }
+    }
+}
+
+/// A compiled route
+#[derive(Debug, Clone)]
+pub(crate) struct Route {
+    /// the configured pattern, `None` matches every path
+    pattern: Option<(String, PathPattern)>,
+    methods: Vec<Method>,
+    pub(crate) port: Cow<'static, str>,
+    pub(crate) auth: ServerAuth,
+    pub(crate) max_body_size: Option<usize>,
+}
+
+impl Route {
+    /// The route metadata to add to `$http_server.request`, if this route has been configured
+    pub(crate) fn meta(&self, params: Value<'static>) -> Option<Value<'static>> {
+        self.pattern.as_ref().map(|(raw, _)| {
+            literal!({
+                "path": raw.clone(),
+                "params": params
+            })
+        })
+    }
+}
+
+/// Outcome of routing a request
+#[derive(Debug)]
+pub(crate) enum RouteMatch<'table> {
+    /// the route to use and the captured path segments
+    Found(&'table Route, Value<'static>),
+    /// some routes match the path, but none of them accepts the method
+    MethodNotAllowed(Vec<Method>),
+    NotFound,
+}
+
+/// Ordered list of routes
+#[derive(Debug, Clone)]
+pub(crate) struct RouteTable {
+    routes: Vec<Route>,
+}
+
+impl RouteTable {
+    /// Compile the route table.
+    ///
+    /// Without any configured routes, every request is emitted via the `out` port.
+    pub(crate) fn new(
+        routes: &[RouteConfig],
+        auth: &ServerAuth,
+        max_body_size: Option<usize>,
+    ) -> Result<Self> {
+        let routes = if routes.is_empty() {
+            vec![Route {
+                pattern: None,
+                methods: vec![],
+                port: OUT,
+                auth: auth.clone(),
+                max_body_size,
+            }]
+        } else {
+            routes
+                .iter()
+                .map(|route| {
+                    if route.port.eq_ignore_ascii_case(ERR.as_ref()) {
+                        return Err(Error::from(format!(
+                            "Invalid port for route \"{}\": `err` is reserved for errors",
+                            route.path
+                        )));
+                    }
+                    Ok(Route {
+                        pattern: Some((route.path.clone(), route.path.parse()?)),
+                        methods: route.methods.clone(),
+                        port: Cow::owned(route.port.to_ascii_lowercase()),
+                        auth: route.auth.clone().unwrap_or_else(|| auth.clone()),
+                        max_body_size: route.max_body_size.or(max_body_size),
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?
+        };
+        Ok(Self { routes })
+    }
+
+    /// All output ports requests can be emitted on, including `out` and `err`
+    pub(crate) fn output_ports(&self) -> Vec<Cow<'static, str>> {
+        let mut ports = vec![OUT, ERR];
+        for route in &self.routes {
+            if !ports.contains(&route.port) {
+                ports.push(route.port.clone());
+            }
+        }
+        ports
+    }
+
+    /// Whether any route requires a verified client certificate
+    pub(crate) fn requires_mtls(&self) -> bool {
+        self.routes
+            .iter()
+            .any(|route| route.auth == ServerAuth::Mtls)
+    }
+
+    /// Find the route for the given request
+    pub(crate) fn route(&self, method: Method, path: &str) -> RouteMatch {
+        let mut allowed = Vec::new();
+        for route in &self.routes {
+            let params = match route.pattern.as_ref() {
+                Some((_, pattern)) => pattern.matches(path),
+                None => Some(Value::object()),
+            };
+            if let Some(params) = params {
+                if route.methods.is_empty() || route.methods.contains(&method) {
+                    return RouteMatch::Found(route, params);
+                }
+                for method in &route.methods {
+                    if !allowed.contains(method) {
+                        allowed.push(*method);
+                    }
+                }
+            }
+        }
+        if allowed.is_empty() {
+            RouteMatch::NotFound
+        } else {
+            RouteMatch::MethodNotAllowed(allowed)
+        }
+    }
+}
+
+fn default_cors_origins() -> Vec<String> {
+    vec!["*".to_string()]
+}
+
+fn default_cors_methods() -> Vec<Method> {
+    vec![Method::Get, Method::Post, Method::Options]
+}
+
+/// CORS handling, preflight requests are answered directly and not emitted as events
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct CorsConfig {
+    /// allowed origins, `*` allows any origin
+    #[serde(default = "default_cors_origins")]
+    allow_origins: Vec<String>,
+    /// allowed methods
+    #[serde(default = "default_cors_methods")]
+    allow_methods: Vec<Method>,
+    /// allowed request headers, allows all headers if empty
+    #[serde(default = "Default::default")]
+    allow_headers: Vec<String>,
+    /// response headers exposed to the client
+    #[serde(default = "Default::default")]
+    expose_headers: Vec<String>,
+    /// allow requests with credentials
+    #[serde(default = "Default::default")]
+    allow_credentials: bool,
+    /// number of seconds clients may cache preflight responses
+    #[serde(default = "Default::default")]
+    max_age: Option<u64>,
+}
+
+impl CorsConfig {
+    pub(crate) fn middleware(&self) -> Result<CorsMiddleware> {
+        let methods = self
+            .allow_methods
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(", ");
+        let mut cors = CorsMiddleware::new()
+            .allow_origin(Origin::from(self.allow_origins.clone()))
+            .allow_methods(HeaderValue::from_str(&methods)?)
+            .allow_credentials(self.allow_credentials);
+        if !self.allow_headers.is_empty() {
+            cors = cors.allow_headers(HeaderValue::from_str(&self.allow_headers.join(", "))?);
+        }
+        if !self.expose_headers.is_empty() {
+            cors = cors.expose_headers(HeaderValue::from_str(&self.expose_headers.join(", "))?);
+        }
+        if let Some(max_age) = self.max_age {
+            cors = cors.max_age(HeaderValue::from_str(&max_age.to_string())?);
+        }
+        Ok(cors)
+    }
+}
+
+/// Build a response rejecting a request with the given `status`
+pub(crate) fn reject(status: StatusCode, auth: &ServerAuth) -> tide::Response {
+    let mut response = tide::Response::new(status);
+    if status == StatusCode::Unauthorized {
+        if let Some(challenge) = auth.challenge() {
+            response.insert_header(headers::WWW_AUTHENTICATE, challenge);
+        }
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn path_pattern() -> Result<()> {
+        let pattern: PathPattern = "/api/:version/users/:id".parse()?;
+        assert_eq!(
+            Some(literal!({"version": "v1", "id": "42"})),
+            pattern.matches("/api/v1/users/42/")
+        );
+        assert_eq!(None, pattern.matches("/api/v1/users"));
+        assert_eq!(None, pattern.matches("/api/v1/users/42/posts"));
+        assert_eq!(None, pattern.matches("/api/v1/groups/42"));
+
+        let pattern: PathPattern = "/static/*path".parse()?;
+        assert_eq!(
+            Some(literal!({"path": "css/main.css"})),
+            pattern.matches("/static/css/main.css")
+        );
+        assert_eq!(Some(literal!({"path": ""})), pattern.matches("/static"));
+
+        let pattern: PathPattern = "/".parse()?;
+        assert_eq!(Some(Value::object()), pattern.matches("/"));
+        assert_eq!(None, pattern.matches("/snot"));
+
+        assert!("snot".parse::<PathPattern>().is_err());
+        assert!("/*rest/snot".parse::<PathPattern>().is_err());
+        assert!("/snot/:".parse::<PathPattern>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn route_table() -> Result<()> {
+        let routes: Vec<RouteConfig> = tremor_value::structurize(literal!([
+            {"path": "/users/:id", "methods": ["GET"], "port": "users"},
+            {"path": "/users/:id", "methods": ["PUT", "DELETE"], "port": "Admin"},
+            {"path": "/*rest"}
+        ]))?;
+        let table = RouteTable::new(&routes, &ServerAuth::None, Some(1024))?;
+        assert_eq!(
+            vec![OUT, ERR, Cow::from("users"), Cow::from("admin")],
+            table.output_ports()
+        );
+        match table.route(Method::Delete, "/users/42") {
+            RouteMatch::Found(route, params) => {
+                assert_eq!("admin", route.port);
+                assert_eq!(Some(1024), route.max_body_size);
+                assert_eq!(literal!({"id": "42"}), params);
+            }
+            other => panic!("Unexpected route match: {other:?}"),
+        }
+        match table.route(Method::Post, "/snot/badger") {
+            RouteMatch::Found(route, params) => {
+                assert_eq!(OUT, route.port);
+                assert_eq!(literal!({"rest": "snot/badger"}), params);
+            }
+            other => panic!("Unexpected route match: {other:?}"),
+        }
+
+        let routes: Vec<RouteConfig> = tremor_value::structurize(literal!([
+            {"path": "/users/:id", "methods": ["GET"]},
+            {"path": "/users/:id", "methods": ["PUT"]},
+            {"path": "/users/:id", "methods": ["GET"]}
+        ]))?;
+        let table = RouteTable::new(&routes, &ServerAuth::None, None)?;
+        assert!(matches!(
+            table.route(Method::Post, "/users/42"),
+            RouteMatch::MethodNotAllowed(methods) if methods == vec![Method::Get, Method::Put]
+        ));
+        assert!(matches!(
+            table.route(Method::Get, "/groups/42"),
+            RouteMatch::NotFound
+        ));
+
+        let routes: Vec<RouteConfig> =
+            tremor_value::structurize(literal!([{"path": "/", "port": "err"}]))?;
+        assert!(RouteTable::new(&routes, &ServerAuth::None, None).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn auth() {
+        let basic = ServerAuth::Basic {
+            username: "snot".to_string(),
+            password: "badger".to_string(),
+        };
+        assert_eq!(None, basic.check(Some("Basic c25vdDpiYWRnZXI="), None));
+        assert_eq!(
+            Some(StatusCode::Unauthorized),
+            basic.check(Some("Basic YmFkZ2VyOnNub3Q="), None)
+        );
+        assert_eq!(Some(StatusCode::Unauthorized), basic.check(None, None));
+
+        let bearer = ServerAuth::Bearer("token".to_string());
+        assert_eq!(None, bearer.check(Some("Bearer token"), None));
+        assert_eq!(
+            Some(StatusCode::Unauthorized),
+            bearer.check(Some("Bearer nekot"), None)
+        );
+        assert_eq!(
+            Some(StatusCode::Unauthorized),
+            bearer.check(Some("Basic token"), None)
+        );
+
+        let certs = PeerCertificates(vec![rustls::Certificate(vec![1, 2, 3])]);
+        assert_eq!(None, ServerAuth::Mtls.check(None, Some(&certs)));
+        assert_eq!(
+            Some(StatusCode::Forbidden),
+            ServerAuth::Mtls.check(None, None)
+        );
+        assert_eq!(None, ServerAuth::None.check(None, None));
+    }
+}
@@ -14,19 +14,28 @@
 
 use super::{
     meta::{consolidate_mime, extract_request_meta, BodyData, HeaderValueValue},
+    routes::{reject, CorsConfig, RouteConfig, RouteMatch, RouteTable, ServerAuth},
     utils::{FixedBodyReader, RequestId, StreamingBodyReader},
 };
 use crate::{
     connectors::{
         prelude::*,
         spawn_task,
-        utils::{mime::MimeCodecMap, tls::TLSServerConfig},
+        utils::{
+            mime::MimeCodecMap,
+            tls::{
+                peer_identity, serve_tls, ClientAuth, PeerCertificates, Reloadable, TLSServerConfig,
+            },
+        },
     },
     errors::err_connector_def,
 };
+use async_rustls::TlsAcceptor;
 use async_std::{
     channel::{bounded, unbounded, Receiver, Sender},
-    task::JoinHandle,
+    io::ReadExt,
+    net::TcpListener,
+    task::{self, JoinHandle},
 };
 use dashmap::DashMap;
 use halfbrown::{Entry, HashMap};
@@ -34,13 +43,13 @@ use http_types::{
     headers::{self, HeaderValues},
     StatusCode,
 };
+use rustls::ServerConfig;
 use simd_json::ValueAccess;
 use std::sync::Arc;
 use tide::{
     listener::{Listener, ToListener},
     Response,
 };
-use tide_rustls::TlsListener;
 use tremor_common::ids::Id;
 
 #[derive(Deserialize, Debug, Clone)]
@@ -55,6 +64,18 @@ pub(crate) struct Config {
     /// e.g. for handling `application/json` with the `binary` codec, if desired
     /// the mime type of `*/*` serves as a default / fallback
     mime_mapping: Option<HashMap<String, String>>,
+    /// route table, requests are emitted via the `out` port if no routes are configured
+    #[serde(default = "Default::default")]
+    routes: Vec<RouteConfig>,
+    /// authentication required for all routes not configuring their own
+    #[serde(default = "Default::default")]
+    auth: ServerAuth,
+    /// maximum request body size in bytes for all routes not configuring their own
+    #[serde(default = "Default::default")]
+    max_body_size: Option<usize>,
+    /// CORS handling, if not configured no CORS headers are added
+    #[serde(default = "Default::default")]
+    cors: Option<CorsConfig>,
 }
 
 impl ConfigImpl for Config {}
@@ -65,6 +86,8 @@ pub(crate) struct Builder {}
 impl Builder {
     const HTTPS_REQUIRED: &'static str =
         "Using SSL certificates requires setting up a https endpoint";
+    const CAFILE_REQUIRED: &'static str =
+        "Using `mtls` auth requires a `cafile` and a `client_auth` other than `none` in the `tls` config to verify client certificates";
 }
 
 #[async_trait::async_trait]
@@ -86,6 +109,16 @@ impl ConnectorBuilder for Builder {
         if tls_server_config.is_some() && config.url.scheme() != "https" {
             return Err(err_connector_def(id, Self::HTTPS_REQUIRED));
         }
+        let routes = RouteTable::new(&config.routes, &config.auth, config.max_body_size)
+            .map_err(|e| err_connector_def(id, &e.to_string()))?;
+        if routes.requires_mtls()
+            && tls_server_config
+                .as_ref()
+                .map_or(true, |tls| tls.client_auth() == ClientAuth::None)
+        {
+            return Err(err_connector_def(id, Self::CAFILE_REQUIRED));
+        }
+        let output_ports = routes.output_ports();
         let origin_uri = EventOriginUri {
             scheme: "http-server".to_string(),
             host: "localhost".to_string(),
@@ -99,12 +132,18 @@ impl ConnectorBuilder for Builder {
             MimeCodecMap::new()
         };
 
+        let tls_server_config = tls_server_config
+            .as_ref()
+            .map(Reloadable::server)
+            .transpose()?;
         Ok(Box::new(HttpServer {
             config,
             origin_uri,
             tls_server_config,
             inflight,
             codec_map,
+            routes: Arc::new(routes),
+            output_ports,
         }))
     }
 }
@@ -113,9 +152,11 @@ impl ConnectorBuilder for Builder {
 pub(crate) struct HttpServer {
     config: Config,
     origin_uri: EventOriginUri,
-    tls_server_config: Option<TLSServerConfig>,
+    tls_server_config: Option<Reloadable<ServerConfig>>,
     inflight: Arc<DashMap<RequestId, Sender<Response>>>,
     codec_map: MimeCodecMap,
+    routes: Arc<RouteTable>,
+    output_ports: Vec<Cow<'static, str>>,
 }
 
 impl HttpServer {
@@ -128,6 +169,10 @@ impl HttpServer {
 
 #[async_trait::async_trait()]
 impl Connector for HttpServer {
+    fn output_ports(&self) -> &[Cow<'static, str>] {
+        &self.output_ports
+    }
+
     fn codec_requirements(&self) -> CodecReq {
         CodecReq::Structured
     }
@@ -147,7 +192,8 @@ impl Connector for HttpServer {
             origin_uri: self.origin_uri.clone(),
             server_task: None,
             tls_server_config: self.tls_server_config.clone(),
-
+            routes: self.routes.clone(),
+            cors: self.config.cors.clone(),
             codec_map: self.codec_map.clone(),
         };
         builder.spawn(source, source_context).map(Some)
@@ -171,7 +217,9 @@ struct HttpServerSource {
     request_rx: Receiver<RawRequestData>,
     request_tx: Sender<RawRequestData>,
     server_task: Option<JoinHandle<()>>,
-    tls_server_config: Option<TLSServerConfig>,
+    tls_server_config: Option<Reloadable<ServerConfig>>,
+    routes: Arc<RouteTable>,
+    cors: Option<CorsConfig>,
     codec_map: MimeCodecMap,
 }
 
@@ -203,7 +251,13 @@ impl Source for HttpServerSource {
         // TODO: clear out the inflight map. Q: How to drain the map without losing responses?
         // Answer all pending requests with a 503 status?
 
-        let tx = self.request_tx.clone();
+        let state = HttpServerState::new(self.request_tx.clone(), ctx.clone(), self.routes.clone());
+        let mut endpoint = tide::Server::with_state(state);
+        if let Some(cors) = self.cors.as_ref() {
+            endpoint.with(cors.middleware()?);
+        }
+        endpoint.at("/").all(handle_request);
+        endpoint.at("/*").all(handle_request);
 
         let ctx = ctx.clone();
         let tls_server_config = self.tls_server_config.clone();
@@ -211,27 +265,32 @@ impl Source for HttpServerSource {
         // Server task - this is the main receive loop for http server instances
         self.server_task = Some(spawn_task(ctx.clone(), async move {
             if let Some(tls_server_config) = tls_server_config {
-                let mut endpoint = tide::Server::with_state(HttpServerState::new(tx, ctx.clone()));
-                endpoint.at("/").all(handle_request);
-                endpoint.at("/*").all(handle_request);
-
-                let mut listener = TlsListener::build()
-                    .addrs(&hostport)
-                    .cert(&tls_server_config.cert)
-                    .key(&tls_server_config.key)
-                    .finish()?;
-                listener.bind(endpoint).await?;
-                if let Some(info) = listener.info().into_iter().next() {
-                    info!(
-                        "{ctx} Listening for HTTPS requests on {}",
-                        info.connection()
-                    );
+                let listener = TcpListener::bind(&hostport).await?;
+                info!(
+                    "{ctx} Listening for HTTPS requests on {}",
+                    listener.local_addr()?
+                );
+                loop {
+                    let (stream, peer_addr) = match listener.accept().await {
+                        Ok(accepted) => accepted,
+                        Err(e) => {
+                            // e.g. running out of file descriptors, keep serving the existing connections
+                            error!("{ctx} Error Accepting: {e}");
+                            task::sleep(ACCEPT_ERROR_BACKOFF).await;
+                            continue;
+                        }
+                    };
+                    // new connections pick up reloaded certificates
+                    let acceptor = TlsAcceptor::from(tls_server_config.current());
+                    let endpoint = endpoint.clone();
+                    let ctx = ctx.clone();
+                    task::spawn(async move {
+                        if let Err(e) = serve_tls(stream, peer_addr, acceptor, endpoint).await {
+                            debug!("{ctx} Error serving HTTPS connection from {peer_addr}: {e}");
+                        }
+                    });
                 }
-                listener.accept().await?;
             } else {
-                let mut endpoint = tide::Server::with_state(HttpServerState::new(tx, ctx.clone()));
-                endpoint.at("/").all(handle_request);
-                endpoint.at("/*").all(handle_request);
                 let mut listener = (&hostport).to_listener()?;
                 listener.bind(endpoint).await?;
                 if let Some(info) = listener.info().into_iter().next() {
@@ -250,6 +309,7 @@ impl Source for HttpServerSource {
             data,
             request_meta,
             content_type,
+            port,
             response_channel,
         } = self.request_rx.recv().await?;
 
@@ -274,7 +334,7 @@ impl Source for HttpServerSource {
                 origin_uri: self.origin_uri.clone(),
                 payload: EventPayload::from(ValueAndMeta::from_parts(Value::const_null(), meta)),
                 stream: DEFAULT_STREAM_ID, // a http request is a discrete unit and not part of any stream
-                port: None,
+                port: Some(port),
             }
         } else {
             // codec overwrite, depending on requests content-type
@@ -290,12 +350,19 @@ impl Source for HttpServerSource {
                 data,
                 meta: Some(meta),
                 stream: None, // a http request is a discrete unit and not part of any stream
-                port: None,
+                port: Some(port),
                 codec_overwrite: Some(codec_overwrite),
             }
         })
     }
 
+    fn metrics(&mut self, timestamp: u64, ctx: &SourceContext) -> Vec<EventPayload> {
+        self.tls_server_config
+            .iter()
+            .map(|tls| tls.metrics(timestamp, ctx.alias()))
+            .collect()
+    }
+
     fn is_transactional(&self) -> bool {
         //TODO: add ack/fail handling when not using custom_responses
         false
@@ -642,11 +709,12 @@ impl SinkResponse {
 struct HttpServerState {
     tx: Sender<RawRequestData>,
     ctx: SourceContext,
+    routes: Arc<RouteTable>,
 }
 
 impl HttpServerState {
-    fn new(tx: Sender<RawRequestData>, ctx: SourceContext) -> Self {
-        Self { tx, ctx }
+    fn new(tx: Sender<RawRequestData>, ctx: SourceContext, routes: Arc<RouteTable>) -> Self {
+        Self { tx, ctx, routes }
     }
 }
 
@@ -656,6 +724,8 @@ struct RawRequestData {
     // metadata about the request, not the ready event meta, still needs to be wrapped
     request_meta: Value<'static>,
     content_type: Option<String>,
+    // the output port of the matched route
+    port: Cow<'static, str>,
     response_channel: Sender<Response>,
 }
 
@@ -673,9 +743,56 @@ async fn handle_request(mut req: tide::Request<HttpServerState>) -> tide::Result
     }
 }
 async fn _handle_request(req: &mut tide::Request<HttpServerState>) -> tide::Result<tide::Response> {
-    let request_meta = extract_request_meta(req.as_ref());
+    let routes = req.state().routes.clone();
+    let (route, params) = match routes.route(req.method(), req.url().path()) {
+        RouteMatch::Found(route, params) => (route, params),
+        RouteMatch::MethodNotAllowed(allowed) => {
+            let mut response = tide::Response::new(StatusCode::MethodNotAllowed);
+            let allowed: Vec<String> = allowed.iter().map(ToString::to_string).collect();
+            response.insert_header(headers::ALLOW, allowed.join(", "));
+            return Ok(response);
+        }
+        RouteMatch::NotFound => return Ok(tide::Response::new(StatusCode::NotFound)),
+    };
+
+    let authorization = req
+        .header(headers::AUTHORIZATION)
+        .map(|values| values.last().as_str());
+    if let Some(status) = route
+        .auth
+        .check(authorization, req.ext::<PeerCertificates>())
+    {
+        return Ok(reject(status, &route.auth));
+    }
+
+    let mut request_meta = extract_request_meta(req.as_ref());
+    if let Some(identity) = req
+        .ext::<PeerCertificates>()
+        .and_then(|certs| peer_identity(&certs.0))
+    {
+        request_meta.try_insert("peer", literal!({ "tls": identity }));
+    }
+    if let Some(route_meta) = route.meta(params) {
+        request_meta.try_insert("route", route_meta);
+    }
     let content_type = req.content_type().map(|mime| mime.essence().to_string());
-    let data = req.body_bytes().await?;
+    let data = if let Some(max_body_size) = route.max_body_size {
+        if req.len().map_or(false, |len| len > max_body_size) {
+            return Ok(reject(StatusCode::PayloadTooLarge, &route.auth));
+        }
+        // don't trust the content-length, read at most one byte more than allowed
+        let mut data = Vec::new();
+        req.take_body()
+            .take(max_body_size as u64 + 1)
+            .read_to_end(&mut data)
+            .await?;
+        if data.len() > max_body_size {
+            return Ok(reject(StatusCode::PayloadTooLarge, &route.auth));
+        }
+        data
+    } else {
+        req.body_bytes().await?
+    };
 
     // Dispatch
     let (response_tx, response_rx) = bounded(1);
@@ -685,6 +802,7 @@ async fn _handle_request(req: &mut tide::Request<HttpServerState>) -> tide::Resu
             data,
             request_meta,
             content_type,
+            port: route.port.clone(),
             response_channel: response_tx,
         })
         .await?;
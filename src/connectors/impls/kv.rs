@@ -12,6 +12,15 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+//! Key value store connector
+//!
+//! Commands are received via the sink and responses are emitted via the source.
+//! Commands address the default tree or the tree named in `$kv.tree`, `put`, `swap` and `cas`
+//! can set an expiry with `$kv.ttl` in nanoseconds. Values and their expiry are changed in one transaction.
+//! Expired keys are removed on every tick.
+//! Changes to the prefixes configured in `watch` are emitted via the source as well. Changes happening
+//! while the source is not connected are not emitted, they are not buffered until it reconnects.
+
 // #![cfg_attr(coverage, no_coverage)]
 use crate::{
     codec::{
@@ -25,10 +34,17 @@ use async_std::{
     channel::{bounded, Receiver, Sender},
     path::PathBuf,
     sync::Arc,
+    task::JoinHandle,
 };
 use serde::Deserialize;
-use sled::{CompareAndSwapError, Db, IVec};
+use sled::{Db, IVec, Tree};
 use std::{boxed::Box, convert::TryFrom, sync::atomic::AtomicBool};
+use tremor_common::time::nanotime;
+
+mod ttl;
+
+use tremor_pipeline::SignalKind;
+use ttl::Ttl;
 
 #[derive(Debug)]
 enum Command<'v> {
@@ -163,10 +179,24 @@ fn oks(
     vec![ok(op_name, k, v)]
 }
 
+/// Emit changes to keys starting with `prefix` in `tree`
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct WatchConfig {
+    /// name of the tree to watch, the default tree if not set
+    #[serde(default = "Default::default")]
+    tree: Option<String>,
+    /// prefix of the keys to watch, all keys if empty
+    #[serde(default = "Default::default")]
+    prefix: String,
+}
+
 #[derive(Deserialize, Debug, Clone)]
 #[serde(deny_unknown_fields)]
 pub(crate) struct Config {
     path: String,
+    #[serde(default = "Default::default")]
+    watch: Vec<WatchConfig>,
 }
 
 impl ConfigImpl for Config {}
@@ -176,7 +206,14 @@ pub(crate) struct Builder {}
 
 impl Builder {
     const INVALID_DIR: &'static str = "Invalid `dir`. Not a directory or not accessible.";
+    const RESERVED_TREE: &'static str = "Tree names starting with `__` are reserved.";
 }
+
+/// Returns `true` if the tree `name` is used internally by sled or this connector
+fn is_reserved(name: &str) -> bool {
+    name.starts_with("__")
+}
+
 #[async_trait::async_trait]
 impl ConnectorBuilder for Builder {
     fn connector_type(&self) -> ConnectorType {
@@ -193,13 +230,30 @@ impl ConnectorBuilder for Builder {
         if !PathBuf::from(&config.path).is_dir().await {
             return Err(err_connector_def(id, Builder::INVALID_DIR));
         }
+        if config
+            .watch
+            .iter()
+            .filter_map(|w| w.tree.as_deref())
+            .any(is_reserved)
+        {
+            return Err(err_connector_def(id, Builder::RESERVED_TREE));
+        }
+        let db = sled::open(&config.path)?;
 
         let (tx, rx) = bounded(crate::QSIZE.load(Ordering::Relaxed));
         Ok(Box::new(Kv {
+            origin_uri: EventOriginUri {
+                scheme: "tremor-kv".to_string(),
+                host: hostname(),
+                port: None,
+                path: config.path.split('/').map(ToString::to_string).collect(),
+            },
             config,
+            db,
             rx,
             tx,
             source_is_connected: Arc::default(),
+            watch_tasks: Vec::new(),
         }))
     }
 }
@@ -209,13 +263,49 @@ impl ConnectorBuilder for Builder {
 /// Receiving commands via its sink and emitting responses to those commands via its source.
 pub(crate) struct Kv {
     config: Config,
+    origin_uri: EventOriginUri,
+    db: Db,
     rx: Receiver<SourceReply>,
     tx: Sender<SourceReply>,
     source_is_connected: Arc<AtomicBool>,
+    watch_tasks: Vec<JoinHandle<()>>,
+}
+
+impl Kv {
+    async fn stop_watching(&mut self) {
+        for task in self.watch_tasks.drain(..) {
+            task.cancel().await;
+        }
+    }
 }
 
 #[async_trait::async_trait]
 impl Connector for Kv {
+    async fn connect(&mut self, ctx: &ConnectorContext, _attempt: &Attempt) -> Result<bool> {
+        self.stop_watching().await;
+        for watch in &self.config.watch {
+            let tree = match watch.tree.as_deref() {
+                Some(name) => self.db.open_tree(name)?,
+                None => Tree::clone(&self.db),
+            };
+            let watcher = watch_prefix(
+                tree,
+                watch.tree.clone(),
+                watch.prefix.as_bytes().to_vec(),
+                self.tx.clone(),
+                self.origin_uri.clone(),
+                self.source_is_connected.clone(),
+            );
+            self.watch_tasks.push(spawn_task(ctx.clone(), watcher));
+        }
+        Ok(true)
+    }
+
+    async fn on_stop(&mut self, _ctx: &ConnectorContext) -> Result<()> {
+        self.stop_watching().await;
+        Ok(())
+    }
+
     async fn create_source(
         &mut self,
         source_context: SourceContext,
@@ -234,24 +324,12 @@ impl Connector for Kv {
         sink_context: SinkContext,
         builder: SinkManagerBuilder,
     ) -> Result<Option<SinkAddr>> {
-        let db = sled::open(&self.config.path)?;
-        let codec = Json::default();
-        let origin_uri = EventOriginUri {
-            scheme: "tremor-kv".to_string(),
-            host: hostname(),
-            port: None,
-            path: self
-                .config
-                .path
-                .split('/')
-                .map(ToString::to_string)
-                .collect(),
-        };
         let s = KvSink {
-            db,
+            ttl: Ttl::open(&self.db)?,
+            db: self.db.clone(),
             tx: self.tx.clone(),
-            codec,
-            origin_uri,
+            codec: Json::default(),
+            origin_uri: self.origin_uri.clone(),
             source_is_connected: self.source_is_connected.clone(),
         };
         builder.spawn(s, sink_context).map(Some)
@@ -262,8 +340,62 @@ impl Connector for Kv {
     }
 }
 
+fn decode(codec: &mut Json<Sorted>, mut v: Option<IVec>, ingest_ns: u64) -> Result<Value<'static>> {
+    if let Some(v) = v.as_mut() {
+        let data: &mut [u8] = v;
+        // TODO: We could optimize this
+        Ok(codec
+            .decode(data, ingest_ns)?
+            .unwrap_or_default()
+            .into_static())
+    } else {
+        Ok(Value::null())
+    }
+}
+
+/// Emit every change to keys starting with `prefix` in `tree` via the source
+async fn watch_prefix(
+    tree: Tree,
+    tree_name: Option<String>,
+    prefix: Vec<u8>,
+    tx: Sender<SourceReply>,
+    origin_uri: EventOriginUri,
+    source_is_connected: Arc<AtomicBool>,
+) -> Result<()> {
+    let mut codec = Json::<Sorted>::default();
+    let mut subscriber = tree.watch_prefix(prefix);
+    while let Some(event) = (&mut subscriber).await {
+        // keep draining the subscriber, sled blocks writes if it falls behind,
+        // changes are lost until the source is connected again
+        if !source_is_connected.load(Ordering::Acquire) {
+            continue;
+        }
+        let (change, key, value) = match event {
+            sled::Event::Insert { key, value } => ("insert", key, Some(value)),
+            sled::Event::Remove { key } => ("remove", key, None),
+        };
+        let data = decode(&mut codec, value, nanotime())?;
+        let meta = literal!({
+            "kv": {
+                "change": change,
+                "key": Value::Bytes(key.to_vec().into()),
+                "tree": tree_name.clone()
+            }
+        });
+        let reply = SourceReply::Structured {
+            origin_uri: origin_uri.clone(),
+            payload: (data, meta).into(),
+            stream: DEFAULT_STREAM_ID,
+            port: Some(OUT),
+        };
+        tx.send(reply).await?;
+    }
+    Ok(())
+}
+
 struct KvSink {
     db: Db,
+    ttl: Ttl,
     tx: Sender<SourceReply>,
     codec: Json<Sorted>,
     origin_uri: EventOriginUri,
@@ -271,51 +403,99 @@ struct KvSink {
 }
 
 impl KvSink {
-    fn decode(&mut self, mut v: Option<IVec>, ingest_ns: u64) -> Result<Value<'static>> {
-        if let Some(v) = v.as_mut() {
-            let data: &mut [u8] = v;
-            // TODO: We could optimize this
-            Ok(self
-                .codec
-                .decode(data, ingest_ns)?
-                .unwrap_or_default()
-                .into_static())
-        } else {
-            Ok(Value::null())
-        }
+    fn decode(&mut self, v: Option<IVec>, ingest_ns: u64) -> Result<Value<'static>> {
+        decode(&mut self.codec, v, ingest_ns)
     }
     fn encode(&self, v: &Value) -> Result<Vec<u8>> {
         self.codec.encode(v)
     }
+    /// The tree `name` or the default tree
+    fn tree(&self, name: &str) -> Result<Tree> {
+        if name.is_empty() {
+            Ok(Tree::clone(&self.db))
+        } else if is_reserved(name) {
+            Err(Builder::RESERVED_TREE.into())
+        } else {
+            Ok(self.db.open_tree(name)?)
+        }
+    }
+    /// Remove all keys expired at `now`
+    fn sweep(&self, now: u64) -> Result<()> {
+        for (tree_name, key) in self.ttl.expired(now)? {
+            let tree = self.tree(&tree_name)?;
+            // the key might have been updated since
+            self.ttl
+                .transaction(&tree, &tree_name, |tx| tx.expire(&key, now))?;
+        }
+        Ok(())
+    }
     fn execute(
         &mut self,
         cmd: Command,
         op_name: &'static str,
+        tree_name: &str,
+        ttl: Option<u64>,
         value: &Value,
         ingest_ns: u64,
     ) -> Result<Vec<(Value<'static>, Value<'static>)>> {
+        let tree = self.tree(tree_name)?;
+        let expiry = ttl.map(|ttl| ingest_ns.saturating_add(ttl));
         match cmd {
-            Command::Get { key } => self
-                .decode(self.db.get(&key)?, ingest_ns)
-                .map(|v| oks(op_name, key, v)),
-            Command::Put { key } => self
-                .decode(self.db.insert(&key, self.encode(value)?)?, ingest_ns)
-                .map(|_old_value| oks(op_name, key, value.clone_static())), // return the new value
-            Command::Swap { key } => self
-                .decode(self.db.insert(&key, self.encode(value)?)?, ingest_ns)
-                .map(|old_value| oks(op_name, key, old_value)), // return the old value
-            Command::Delete { key } => self
-                .decode(self.db.remove(&key)?, ingest_ns)
-                .map(|v| oks(op_name, key, v)),
+            Command::Get { key } => {
+                let value = self.ttl.transaction(&tree, tree_name, |tx| {
+                    tx.expire(&key, ingest_ns)?;
+                    Ok(tx.tree.get(&key)?)
+                })?;
+                self.decode(value, ingest_ns).map(|v| oks(op_name, key, v))
+            }
+            Command::Put { key } => {
+                let encoded = self.encode(value)?;
+                self.ttl.transaction(&tree, tree_name, |tx| {
+                    tx.tree.insert(key.as_slice(), encoded.as_slice())?;
+                    tx.set_expiry(&key, expiry)
+                })?;
+                // return the new value
+                Ok(oks(op_name, key, value.clone_static()))
+            }
+            Command::Swap { key } => {
+                let encoded = self.encode(value)?;
+                let old_value = self.ttl.transaction(&tree, tree_name, |tx| {
+                    tx.expire(&key, ingest_ns)?;
+                    let old_value = tx.tree.insert(key.as_slice(), encoded.as_slice())?;
+                    tx.set_expiry(&key, expiry)?;
+                    Ok(old_value)
+                })?;
+                // return the old value
+                self.decode(old_value, ingest_ns)
+                    .map(|old_value| oks(op_name, key, old_value))
+            }
+            Command::Delete { key } => {
+                let old_value = self.ttl.transaction(&tree, tree_name, |tx| {
+                    tx.expire(&key, ingest_ns)?;
+                    let old_value = tx.tree.remove(key.as_slice())?;
+                    tx.set_expiry(&key, None)?;
+                    Ok(old_value)
+                })?;
+                self.decode(old_value, ingest_ns)
+                    .map(|v| oks(op_name, key, v))
+            }
             Command::Cas { key, old } => {
-                if let Err(CompareAndSwapError { current, proposed }) = self.db.compare_and_swap(
-                    &key,
-                    old.map(|v| self.encode(v)).transpose()?,
-                    Some(self.encode(value)?),
-                )? {
+                let old = old.map(|v| self.encode(v)).transpose()?;
+                let encoded = self.encode(value)?;
+                let swapped = self.ttl.transaction(&tree, tree_name, |tx| {
+                    tx.expire(&key, ingest_ns)?;
+                    let current = tx.tree.get(&key)?;
+                    if current.as_deref() != old.as_deref() {
+                        return Ok(Err(current));
+                    }
+                    tx.tree.insert(key.as_slice(), encoded.as_slice())?;
+                    tx.set_expiry(&key, expiry)?;
+                    Ok(Ok(()))
+                })?;
+                if let Err(current) = swapped {
                     Err(format!(
                         "CAS error: expected {} but found {}.",
-                        self.decode(proposed, ingest_ns)?,
+                        self.decode(old.map(IVec::from), ingest_ns)?,
                         self.decode(current, ingest_ns)?,
                     )
                     .into())
@@ -325,14 +505,17 @@ impl KvSink {
             }
             Command::Scan { start, end } => {
                 let i = match end {
-                    None => self.db.range(start..),
-                    Some(end) => self.db.range(start..end),
+                    None => tree.range(start..),
+                    Some(end) => tree.range(start..end),
                 };
                 let mut res = Vec::with_capacity(i.size_hint().0);
                 for e in i {
                     let (key, e) = e?;
                     let key: &[u8] = &key;
-
+                    // not swept yet
+                    if self.ttl.is_expired(tree_name, key, ingest_ns)? {
+                        continue;
+                    }
                     res.push(ok(op_name, key.to_vec(), self.decode(Some(e), ingest_ns)?));
                 }
                 Ok(res)
@@ -351,17 +534,19 @@ impl Sink for KvSink {
         _serializer: &mut EventSerializer,
         _start: u64,
     ) -> Result<SinkReply> {
-        let ingest_ns = tremor_common::time::nanotime();
+        let ingest_ns = nanotime();
         let send_replies = self.source_is_connected.load(Ordering::Acquire);
 
         let mut r = SinkReply::ACK;
         for (v, m) in event.value_meta_iter() {
             let correlation = m.get("correlation");
+            let tree = m.get("kv").and_then(|kv| kv.get_str("tree"));
             let executed = match Command::try_from(m) {
                 Ok(cmd) => {
                     let name = cmd.op_name();
                     let key = cmd.key();
-                    self.execute(cmd, name, v, ingest_ns)
+                    let ttl = m.get("kv").and_then(|kv| kv.get_u64("ttl"));
+                    self.execute(cmd, name, tree.unwrap_or_default(), ttl, v, ingest_ns)
                         .map_err(|e| (Some(name), key, e))
                 }
                 Err(e) => {
@@ -376,6 +561,9 @@ impl Sink for KvSink {
                             if let Some(correlation) = correlation {
                                 meta.try_insert("correlation", correlation.clone_static());
                             }
+                            if let (Some(tree), Some(kv)) = (tree, meta.get_mut("kv")) {
+                                kv.try_insert("tree", tree.to_string());
+                            }
                             let reply = SourceReply::Structured {
                                 origin_uri: self.origin_uri.clone(),
                                 payload: (data, meta).into(),
@@ -415,7 +603,97 @@ impl Sink for KvSink {
         Ok(r)
     }
 
+    async fn on_signal(
+        &mut self,
+        signal: Event,
+        ctx: &SinkContext,
+        _serializer: &mut EventSerializer,
+    ) -> Result<SinkReply> {
+        if signal.kind == Some(SignalKind::Tick) {
+            ctx.swallow_err(self.sweep(nanotime()), "Failed to remove expired keys");
+        }
+        Ok(SinkReply::NONE)
+    }
+
     fn auto_ack(&self) -> bool {
         false
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sink(db: Db) -> Result<KvSink> {
+        let (tx, _rx) = bounded(1);
+        Ok(KvSink {
+            ttl: Ttl::open(&db)?,
+            db,
+            tx,
+            codec: Json::default(),
+            origin_uri: EventOriginUri {
+                scheme: "tremor-kv".to_string(),
+                host: "localhost".to_string(),
+                port: None,
+                path: vec![],
+            },
+            source_is_connected: Arc::default(),
+        })
+    }
+
+    fn get(sink: &mut KvSink, tree: &str, key: &str, now: u64) -> Result<Value<'static>> {
+        let mut res = sink.execute(
+            Command::Get {
+                key: key.as_bytes().to_vec(),
+            },
+            "get",
+            tree,
+            None,
+            &Value::null(),
+            now,
+        )?;
+        Ok(res.pop().map(|(v, _)| v).unwrap_or_default())
+    }
+
+    fn put(sink: &mut KvSink, tree: &str, key: &str, ttl: Option<u64>, now: u64) -> Result<()> {
+        sink.execute(
+            Command::Put {
+                key: key.as_bytes().to_vec(),
+            },
+            "put",
+            tree,
+            ttl,
+            &Value::from(key),
+            now,
+        )?;
+        Ok(())
+    }
+
+    #[test]
+    fn trees_and_ttl() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let mut sink = sink(sled::open(dir.path())?)?;
+
+        put(&mut sink, "", "snot", None, 0)?;
+        put(&mut sink, "tree", "badger", Some(10), 0)?;
+        assert_eq!(Value::from("snot"), get(&mut sink, "", "snot", 0)?);
+        assert_eq!(Value::null(), get(&mut sink, "", "badger", 0)?);
+        assert_eq!(Value::from("badger"), get(&mut sink, "tree", "badger", 9)?);
+        // expired keys are gone, even before they are swept
+        assert_eq!(Value::null(), get(&mut sink, "tree", "badger", 10)?);
+        assert!(sink.ttl.expired(u64::MAX)?.is_empty());
+
+        // a put without ttl removes the expiry
+        put(&mut sink, "tree", "snot", Some(10), 0)?;
+        put(&mut sink, "tree", "snot", None, 5)?;
+        sink.sweep(20)?;
+        assert_eq!(Value::from("snot"), get(&mut sink, "tree", "snot", 20)?);
+
+        put(&mut sink, "tree", "badger", Some(10), 0)?;
+        sink.sweep(20)?;
+        assert!(!sink.db.open_tree("tree")?.contains_key("badger")?);
+
+        assert!(sink.tree("__tremor_kv_ttl").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,220 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Expiry of keys
+//!
+//! Expiry timestamps are kept in two internal trees: one ordered by expiry, to find expired keys,
+//! and one by key, to update or remove the expiry of a key.
+//! Keys are identified by their tree name and key, the default tree has the empty name.
+//! Values and their expiry are changed together in a [`Transaction`].
+
+use crate::errors::{Error, Result};
+use sled::transaction::{
+    ConflictableTransactionError, ConflictableTransactionResult, TransactionError,
+    TransactionalTree,
+};
+use sled::{Db, Transactional, Tree};
+
+const BY_EXPIRY: &str = "__tremor_kv_ttl";
+const BY_KEY: &str = "__tremor_kv_ttl_keys";
+
+pub(crate) struct Ttl {
+    by_expiry: Tree,
+    by_key: Tree,
+}
+
+/// identify `key` in `tree` as the length of the tree name, the tree name and the key
+fn id(tree: &str, key: &[u8]) -> Result<Vec<u8>> {
+    let mut id = Vec::with_capacity(4 + tree.len() + key.len());
+    id.extend_from_slice(&u32::try_from(tree.len())?.to_be_bytes());
+    id.extend_from_slice(tree.as_bytes());
+    id.extend_from_slice(key);
+    Ok(id)
+}
+
+fn expiry_key(expiry: u64, id: &[u8]) -> Vec<u8> {
+    let mut k = Vec::with_capacity(8 + id.len());
+    k.extend_from_slice(&expiry.to_be_bytes());
+    k.extend_from_slice(id);
+    k
+}
+
+fn be_u64(data: &[u8]) -> Option<u64> {
+    let bytes: [u8; 8] = data.get(..8)?.try_into().ok()?;
+    Some(u64::from_be_bytes(bytes))
+}
+
+/// split an expiry key into the tree name and key
+fn parse(expiry_key: &[u8]) -> Option<(String, Vec<u8>)> {
+    let id = expiry_key.get(8..)?;
+    let len: [u8; 4] = id.get(..4)?.try_into().ok()?;
+    let len = usize::try_from(u32::from_be_bytes(len)).ok()?;
+    let tree = std::str::from_utf8(id.get(4..4 + len)?).ok()?;
+    Some((tree.to_string(), id.get(4 + len..)?.to_vec()))
+}
+
+pub(crate) type TxResult<T> = ConflictableTransactionResult<T, Error>;
+
+/// A transaction across a tree and the expiry of its keys
+pub(crate) struct Transaction<'t> {
+    tree_name: &'t str,
+    pub(crate) tree: &'t TransactionalTree,
+    by_expiry: &'t TransactionalTree,
+    by_key: &'t TransactionalTree,
+}
+
+impl Transaction<'_> {
+    /// Set the expiry timestamp in nanoseconds of `key`, `None` removes it
+    pub(crate) fn set_expiry(&self, key: &[u8], expiry: Option<u64>) -> TxResult<()> {
+        let id = id(self.tree_name, key).map_err(ConflictableTransactionError::Abort)?;
+        if let Some(old) = self
+            .by_key
+            .remove(id.as_slice())?
+            .as_deref()
+            .and_then(be_u64)
+        {
+            self.by_expiry.remove(expiry_key(old, &id))?;
+        }
+        if let Some(expiry) = expiry {
+            self.by_key.insert(id.as_slice(), &expiry.to_be_bytes())?;
+            self.by_expiry.insert(expiry_key(expiry, &id), &[])?;
+        }
+        Ok(())
+    }
+
+    /// Returns `true` if `key` has expired at `now`
+    pub(crate) fn is_expired(&self, key: &[u8], now: u64) -> TxResult<bool> {
+        let id = id(self.tree_name, key).map_err(ConflictableTransactionError::Abort)?;
+        Ok(self
+            .by_key
+            .get(id)?
+            .as_deref()
+            .and_then(be_u64)
+            .map_or(false, |expiry| expiry <= now))
+    }
+
+    /// Remove `key` if it has expired at `now`
+    pub(crate) fn expire(&self, key: &[u8], now: u64) -> TxResult<()> {
+        if self.is_expired(key, now)? {
+            self.tree.remove(key)?;
+            self.set_expiry(key, None)?;
+        }
+        Ok(())
+    }
+}
+
+impl Ttl {
+    pub(crate) fn open(db: &Db) -> Result<Self> {
+        Ok(Self {
+            by_expiry: db.open_tree(BY_EXPIRY)?,
+            by_key: db.open_tree(BY_KEY)?,
+        })
+    }
+
+    /// Run `f` in a transaction across `tree` named `tree_name` and the expiry of its keys
+    ///
+    /// `f` is run again if the transaction conflicts with another one.
+    pub(crate) fn transaction<T, F>(&self, tree: &Tree, tree_name: &str, f: F) -> Result<T>
+    where
+        F: Fn(&Transaction) -> TxResult<T>,
+    {
+        (tree, &self.by_expiry, &self.by_key)
+            .transaction(|(tree, by_expiry, by_key)| {
+                f(&Transaction {
+                    tree_name,
+                    tree,
+                    by_expiry,
+                    by_key,
+                })
+            })
+            .map_err(|e| match e {
+                TransactionError::Abort(e) => e,
+                TransactionError::Storage(e) => e.into(),
+            })
+    }
+
+    /// Returns `true` if `key` in `tree` has expired at `now`
+    pub(crate) fn is_expired(&self, tree: &str, key: &[u8], now: u64) -> Result<bool> {
+        Ok(self
+            .by_key
+            .get(id(tree, key)?)?
+            .as_deref()
+            .and_then(be_u64)
+            .map_or(false, |expiry| expiry <= now))
+    }
+
+    /// All keys expired at `now`, as tree name and key
+    pub(crate) fn expired(&self, now: u64) -> Result<Vec<(String, Vec<u8>)>> {
+        let mut expired = Vec::new();
+        for entry in self
+            .by_expiry
+            .range(..now.saturating_add(1).to_be_bytes().to_vec())
+        {
+            let (k, _) = entry?;
+            if let Some(tree_key) = parse(&k) {
+                expired.push(tree_key);
+            }
+        }
+        Ok(expired)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set(ttl: &Ttl, db: &Db, tree_name: &str, key: &[u8], expiry: Option<u64>) -> Result<()> {
+        let tree = if tree_name.is_empty() {
+            Tree::clone(db)
+        } else {
+            db.open_tree(tree_name)?
+        };
+        ttl.transaction(&tree, tree_name, |tx| tx.set_expiry(key, expiry))
+    }
+
+    #[test]
+    fn expiry() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let db = sled::open(dir.path())?;
+        let ttl = Ttl::open(&db)?;
+        set(&ttl, &db, "", b"snot", Some(10))?;
+        set(&ttl, &db, "tree", b"badger", Some(20))?;
+        set(&ttl, &db, "tree", b"forever", None)?;
+        assert!(!ttl.is_expired("", b"snot", 9)?);
+        assert!(ttl.is_expired("", b"snot", 10)?);
+        assert!(!ttl.is_expired("tree", b"snot", 10)?);
+        assert!(!ttl.is_expired("tree", b"forever", u64::MAX)?);
+        assert_eq!(vec![(String::new(), b"snot".to_vec())], ttl.expired(15)?);
+        assert_eq!(2, ttl.expired(20)?.len());
+
+        // updating the expiry replaces the old one
+        set(&ttl, &db, "", b"snot", Some(30))?;
+        assert!(!ttl.is_expired("", b"snot", 20)?);
+        assert_eq!(
+            vec![("tree".to_string(), b"badger".to_vec())],
+            ttl.expired(20)?
+        );
+        set(&ttl, &db, "tree", b"badger", None)?;
+        assert!(ttl.expired(20)?.is_empty());
+
+        // the value and its expiry are removed together
+        let tree = db.open_tree("tree")?;
+        tree.insert("snot", "badger")?;
+        set(&ttl, &db, "tree", b"snot", Some(10))?;
+        ttl.transaction(&tree, "tree", |tx| tx.expire(b"snot", 10))?;
+        assert!(!tree.contains_key("snot")?);
+        assert!(ttl.expired(u64::MAX)?.iter().all(|(_, key)| key != b"snot"));
+        Ok(())
+    }
+}
@@ -15,10 +15,13 @@
 //! Common abstractions for object storage connectors
 //!
 //! Currently home of two kinds of sinks implementing different modes: `yolo` and `consistent` See docs on `Mode`.
+//! Both sinks can roll over to new objects automatically, see `RolloverConfig`.
 
 use crate::connectors::prelude::*;
-use crate::errors::{err_object_storage, Result};
-use tremor_pipeline::Event;
+use crate::connectors::utils::template::{Part, Template, Values};
+use crate::errors::{err_object_storage, Error, Result};
+use tremor_common::time::nanotime;
+use tremor_pipeline::{Event, SignalKind};
 use tremor_value::Value;
 use value_trait::ValueAccess;
 
@@ -88,6 +91,7 @@ pub(crate) const BUCKET: &str = "bucket";
 pub(crate) trait ObjectStorageCommon {
     fn connector_type(&self) -> &str;
     fn default_bucket(&self) -> Option<&String>;
+    fn rollover(&self) -> Option<&RolloverConfig>;
 
     fn get_bucket_name(&self, meta: Option<&Value>) -> Result<String> {
         let res = match (meta.get(BUCKET), self.default_bucket()) {
@@ -128,6 +132,185 @@ pub(crate) trait ObjectStorageCommon {
     }
 }
 
+/// Automatic rollover to new objects, instead of taking object names from the event metadata
+///
+/// A new object is started whenever the key rendered without `{uuid}` changes,
+/// or the current object reached one of the configured limits.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct RolloverConfig {
+    /// template for object keys, e.g. `logs/{yyyy}/{MM}/{dd}/{HH}/{uuid}.json.gz`
+    ///
+    /// Date and time placeholders are taken from the event ingest time.
+    key: Template,
+    /// start a new object once the current one contains at least this many bytes
+    #[serde(default = "Default::default")]
+    max_bytes: Option<usize>,
+    /// start a new object once the current one contains this many events
+    #[serde(default = "Default::default")]
+    max_events: Option<u64>,
+    /// finish the current object once it is older than this many nanoseconds
+    #[serde(default = "Default::default")]
+    max_age: Option<u64>,
+}
+
+impl RolloverConfig {
+    pub(crate) fn validate(&self) -> Result<()> {
+        if self.max_bytes == Some(0) || self.max_events == Some(0) || self.max_age == Some(0) {
+            return Err(err_object_storage(
+                "`max_bytes`, `max_events` and `max_age` need to be greater than 0",
+            ));
+        }
+        if self.key.contains(&Part::Name) || self.key.contains(&Part::N) {
+            return Err(err_object_storage(
+                "`key` doesn't support the `{name}` and `{n}` placeholders",
+            ));
+        }
+        let has_limits =
+            self.max_bytes.is_some() || self.max_events.is_some() || self.max_age.is_some();
+        if has_limits && !self.key.contains(&Part::Uuid) {
+            return Err(err_object_storage(
+                "`key` needs a `{uuid}` placeholder to roll over by `max_bytes`, `max_events` or `max_age`",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// The object currently rolled into
+#[derive(Debug)]
+struct RolloverObject {
+    /// key rendered without `{uuid}`
+    partition: String,
+    object_id: ObjectId,
+    started: u64,
+    bytes: usize,
+    events: u64,
+}
+
+/// Tracks the current object for automatic rollover
+#[derive(Debug)]
+pub(crate) struct Rollover {
+    config: RolloverConfig,
+    current: Option<RolloverObject>,
+}
+
+impl Rollover {
+    pub(crate) fn new(config: RolloverConfig) -> Self {
+        Self {
+            config,
+            current: None,
+        }
+    }
+
+    /// Get the object id for the next event, rolling over to a new object if necessary
+    fn object_id(
+        &mut self,
+        bucket: String,
+        meta: Option<&Value>,
+        ingest_ns: u64,
+        connector_type: &str,
+    ) -> Result<ObjectId> {
+        let values = Values {
+            meta,
+            connector_type,
+            ..Values::default()
+        };
+        let partition = self.render(ingest_ns, &values)?;
+        let now = nanotime();
+        if let Some(current) = self.current.as_ref() {
+            if current.object_id.bucket() == bucket
+                && current.partition == partition
+                && !self.limit_reached(current, now)
+            {
+                return Ok(current.object_id.clone());
+            }
+        }
+        let uuid = uuid::Uuid::new_v4().to_string();
+        let name = self.render(
+            ingest_ns,
+            &Values {
+                uuid: &uuid,
+                ..values
+            },
+        )?;
+        let object_id = ObjectId::new(bucket, name);
+        self.current = Some(RolloverObject {
+            partition,
+            object_id: object_id.clone(),
+            started: now,
+            bytes: 0,
+            events: 0,
+        });
+        Ok(object_id)
+    }
+
+    fn render(&self, ingest_ns: u64, values: &Values) -> Result<String> {
+        self.config
+            .key
+            .render(ingest_ns, values)
+            .map_err(|e| err_object_storage(e.to_string()))
+    }
+
+    fn limit_reached(&self, current: &RolloverObject, now: u64) -> bool {
+        self.config
+            .max_bytes
+            .map_or(false, |max_bytes| current.bytes >= max_bytes)
+            || self
+                .config
+                .max_events
+                .map_or(false, |max_events| current.events >= max_events)
+            || self.is_expired(current, now)
+    }
+
+    fn is_expired(&self, current: &RolloverObject, now: u64) -> bool {
+        self.config.max_age.map_or(false, |max_age| {
+            now.saturating_sub(current.started) >= max_age
+        })
+    }
+
+    /// Track an event with `bytes` serialized bytes written to the current object
+    fn track(&mut self, bytes: usize) {
+        if let Some(current) = self.current.as_mut() {
+            current.bytes += bytes;
+            current.events += 1;
+        }
+    }
+
+    /// Returns `true` if the current object should be finished because of its age
+    fn should_finish(&self, now: u64) -> bool {
+        self.current
+            .as_ref()
+            .map_or(false, |current| self.is_expired(current, now))
+    }
+
+    /// Forget the current object, the next event will start a new one
+    fn reset(&mut self) {
+        self.current = None;
+    }
+}
+
+/// Get the object id for an event, either from its metadata or from the rollover
+fn next_object_id<Impl: ObjectStorageCommon>(
+    sink_impl: &Impl,
+    rollover: Option<&mut Rollover>,
+    meta: Option<&Value>,
+    ingest_ns: u64,
+) -> Result<ObjectId> {
+    if let Some(rollover) = rollover {
+        let bucket = sink_impl.get_bucket_name(meta)?;
+        rollover.object_id(bucket, meta, ingest_ns, sink_impl.connector_type())
+    } else {
+        sink_impl.get_object_id(meta)
+    }
+}
+
+/// Returns `true` if `signal` is a tick and the current rolled over object is expired
+fn is_rollover_tick(signal: &Event, rollover: Option<&Rollover>) -> bool {
+    signal.kind == Some(SignalKind::Tick)
+        && rollover.map_or(false, |rollover| rollover.should_finish(nanotime()))
+}
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub(crate) struct BufferPart {
     pub(crate) data: Vec<u8>,
@@ -204,6 +387,7 @@ where
     pub(crate) sink_impl: Impl,
     current_upload: Option<Upload>,
     buffers: Buffer,
+    rollover: Option<Rollover>,
 }
 
 impl<Impl, Upload, Buffer> ConsistentSink<Impl, Upload, Buffer>
@@ -214,10 +398,12 @@ where
 {
     pub(crate) fn new(sink_impl: Impl) -> Self {
         let buffers = Buffer::new(sink_impl.buffer_size());
+        let rollover = sink_impl.rollover().cloned().map(Rollover::new);
         Self {
             sink_impl,
             current_upload: None,
             buffers,
+            rollover,
         }
     }
 }
@@ -247,6 +433,9 @@ where
                 "Error failing previous upload",
             );
         }
+        if let Some(rollover) = self.rollover.as_mut() {
+            rollover.reset();
+        }
 
         Ok(bucket_exists)
     }
@@ -277,6 +466,42 @@ where
         Ok(SinkReply::NONE)
     }
 
+    async fn on_signal(
+        &mut self,
+        signal: Event,
+        ctx: &SinkContext,
+        serializer: &mut EventSerializer,
+    ) -> Result<SinkReply> {
+        if is_rollover_tick(&signal, self.rollover.as_ref()) {
+            debug!("{ctx} Finishing upload after reaching rollover `max_age`");
+            if let Err(e) = self.flush_serializer(serializer, ctx).await {
+                error!("{ctx} Error writing buffered data: {e}");
+                if let Some(current_upload) = self.current_upload.as_mut() {
+                    current_upload.mark_as_failed();
+                }
+            }
+            self.fail_or_finish_upload(ctx).await?;
+            if let Some(rollover) = self.rollover.as_mut() {
+                rollover.reset();
+            }
+        }
+        Ok(SinkReply::NONE)
+    }
+
+    async fn on_flush(
+        &mut self,
+        ctx: &SinkContext,
+        serializer: &mut EventSerializer,
+    ) -> Result<()> {
+        let res = self.flush_serializer(serializer, ctx).await;
+        if res.is_err() {
+            if let Some(current_upload) = self.current_upload.as_mut() {
+                current_upload.mark_as_failed();
+            }
+        }
+        res
+    }
+
     async fn on_stop(&mut self, ctx: &SinkContext) -> Result<()> {
         // Commit the final upload.
         self.fail_or_finish_upload(ctx).await?;
@@ -308,7 +533,12 @@ where
 
         for (value, meta) in event.value_meta_iter() {
             let meta = ctx.extract_meta(meta);
-            let new_object_id: ObjectId = self.sink_impl.get_object_id(meta)?;
+            let new_object_id: ObjectId = next_object_id(
+                &self.sink_impl,
+                self.rollover.as_mut(),
+                meta,
+                event.ingest_ns,
+            )?;
 
             // ignore current event (or part of it if batched) if current upload with same file_id is failed
             if let Some(current_upload) = self.current_upload.as_mut() {
@@ -330,6 +560,7 @@ where
                     // new file_id, current upload is failed  ==> FAIL & DELETE UPLOAD \
                     //                                                                 |--> START NEW UPLOAD
                     // new file_id, current upload not failed ==> FINISH UPLOAD ______/
+                    self.flush_serializer(serializer, ctx).await?;
                     self.fail_or_finish_upload(ctx).await?;
 
                     self.current_upload = Some(
@@ -350,27 +581,53 @@ where
             // At this point we defo have a healthy upload
             // accumulate event payload for the current upload
             let serialized_data = serializer.serialize(value, event.ingest_ns)?;
-            for item in serialized_data {
-                self.buffers.write(item);
-                // upload some data if necessary
-                while let Some(data) = self.buffers.read_current_block() {
-                    let done_until = self
-                        .sink_impl
-                        .upload_data(
-                            data,
-                            self.current_upload
-                                .as_mut()
-                                .ok_or_else(|| err_object_storage("No upload available"))?,
-                            ctx,
-                        )
-                        .await?;
-                    self.buffers.mark_done_until(done_until)?;
-                }
+            if let Some(rollover) = self.rollover.as_mut() {
+                rollover.track(serialized_data.iter().map(Vec::len).sum());
+            }
+            self.write_data(serialized_data, ctx).await?;
+        }
+        Ok(())
+    }
+
+    async fn write_data(&mut self, serialized_data: Vec<Vec<u8>>, ctx: &SinkContext) -> Result<()> {
+        for item in serialized_data {
+            self.buffers.write(item);
+            // upload some data if necessary
+            while let Some(data) = self.buffers.read_current_block() {
+                let done_until = self
+                    .sink_impl
+                    .upload_data(
+                        data,
+                        self.current_upload
+                            .as_mut()
+                            .ok_or_else(|| err_object_storage("No upload available"))?,
+                        ctx,
+                    )
+                    .await?;
+                self.buffers.mark_done_until(done_until)?;
             }
         }
         Ok(())
     }
 
+    /// write out data buffered in the serializer to the current upload, e.g. the footer of a `parquet` file
+    async fn flush_serializer(
+        &mut self,
+        serializer: &mut EventSerializer,
+        ctx: &SinkContext,
+    ) -> Result<()> {
+        // always flush, so the next upload starts with a clean codec
+        let data = serializer.flush(nanotime())?;
+        if self
+            .current_upload
+            .as_ref()
+            .map_or(false, |upload| !upload.is_failed())
+        {
+            self.write_data(data, ctx).await?;
+        }
+        Ok(())
+    }
+
     async fn fail_or_finish_upload(&mut self, ctx: &SinkContext) -> Result<()> {
         if let Some(upload) = self.current_upload.take() {
             let final_part = self.buffers.reset();
@@ -395,6 +652,7 @@ where
     pub(crate) sink_impl: Impl,
     current_upload: Option<Upload>,
     buffer: Buffer,
+    rollover: Option<Rollover>,
 }
 
 impl<Impl, Upload, Buffer> YoloSink<Impl, Upload, Buffer>
@@ -405,10 +663,12 @@ where
 {
     pub(crate) fn new(sink_impl: Impl) -> Self {
         let buffer = Buffer::new(sink_impl.buffer_size());
+        let rollover = sink_impl.rollover().cloned().map(Rollover::new);
         Self {
             sink_impl,
             current_upload: None,
             buffer,
+            rollover,
         }
     }
 }
@@ -424,6 +684,9 @@ where
         // clear out the previous upload
         self.current_upload = None;
         self.buffer = Buffer::new(self.sink_impl.buffer_size());
+        if let Some(rollover) = self.rollover.as_mut() {
+            rollover.reset();
+        }
         self.sink_impl.connect(ctx).await?;
 
         // if we have a default bucket, check that it is accessible
@@ -444,10 +707,19 @@ where
     ) -> Result<SinkReply> {
         for (value, meta) in event.value_meta_iter() {
             let meta = ctx.extract_meta(meta);
-            let object_id = self.sink_impl.get_object_id(meta)?;
+            let object_id = next_object_id(
+                &self.sink_impl,
+                self.rollover.as_mut(),
+                meta,
+                event.ingest_ns,
+            )?;
 
             if let Some(current_upload) = self.current_upload.as_mut() {
                 if object_id != *current_upload.object_id() {
+                    ctx.swallow_err(
+                        self.flush_serializer(serializer, ctx).await,
+                        "Error writing buffered data of previous upload",
+                    );
                     // if finishing the previous one failed, we continue anyways
                     if let Some(current_upload) = self.current_upload.take() {
                         let final_part = self.buffer.reset();
@@ -472,38 +744,50 @@ where
                 self.current_upload = Some(upload);
             }
             let serialized_data = serializer.serialize(value, event.ingest_ns)?;
-            for item in serialized_data {
-                self.buffer.write(item);
-                while let Some(data) = self.buffer.read_current_block() {
-                    let start = data.start;
-                    let end = data.start + data.len();
-                    if let Ok(done_until) = ctx.bail_err(
-                        self.sink_impl
-                            .upload_data(
-                                data,
-                                self.current_upload
-                                    .as_mut()
-                                    .ok_or_else(|| err_object_storage("No upload available"))?,
-                                ctx,
-                            )
-                            .await,
-                        &format!(
-                            "Error uploading data for bytes {}-{} for {}",
-                            start, end, object_id
-                        ),
-                    ) {
-                        // if this fails, we corrupted our internal state somehow
-                        self.buffer.mark_done_until(done_until)?;
-                    } else {
-                        // stop attempting more writes on error
-                        break;
-                    }
-                }
+            if let Some(rollover) = self.rollover.as_mut() {
+                rollover.track(serialized_data.iter().map(Vec::len).sum());
             }
+            self.write_data(serialized_data, ctx).await?;
         }
         Ok(SinkReply::ack_or_none(event.transactional))
     }
 
+    async fn on_signal(
+        &mut self,
+        signal: Event,
+        ctx: &SinkContext,
+        serializer: &mut EventSerializer,
+    ) -> Result<SinkReply> {
+        if is_rollover_tick(&signal, self.rollover.as_ref()) {
+            debug!("{ctx} Finishing upload after reaching rollover `max_age`");
+            ctx.swallow_err(
+                self.flush_serializer(serializer, ctx).await,
+                "Error writing buffered data of expired upload",
+            );
+            if let Some(current_upload) = self.current_upload.take() {
+                let final_part = self.buffer.reset();
+                ctx.swallow_err(
+                    self.sink_impl
+                        .finish_upload(current_upload, final_part, ctx)
+                        .await,
+                    "Error finishing expired upload",
+                );
+            }
+            if let Some(rollover) = self.rollover.as_mut() {
+                rollover.reset();
+            }
+        }
+        Ok(SinkReply::NONE)
+    }
+
+    async fn on_flush(
+        &mut self,
+        ctx: &SinkContext,
+        serializer: &mut EventSerializer,
+    ) -> Result<()> {
+        self.flush_serializer(serializer, ctx).await
+    }
+
     async fn on_stop(&mut self, ctx: &SinkContext) -> Result<()> {
         if let Some(current_upload) = self.current_upload.take() {
             let final_part = self.buffer.reset();
@@ -521,3 +805,142 @@ where
         false // we always ack
     }
 }
+
+impl<Impl, Upload, Buffer> YoloSink<Impl, Upload, Buffer>
+where
+    Impl: ObjectStorageSinkImpl<Upload>,
+    Upload: ObjectStorageUpload,
+    Buffer: ObjectStorageBuffer,
+{
+    async fn write_data(&mut self, serialized_data: Vec<Vec<u8>>, ctx: &SinkContext) -> Result<()> {
+        for item in serialized_data {
+            self.buffer.write(item);
+            while let Some(data) = self.buffer.read_current_block() {
+                let current_upload = self
+                    .current_upload
+                    .as_mut()
+                    .ok_or_else(|| err_object_storage("No upload available"))?;
+                let msg = format!(
+                    "Error uploading data for bytes {}-{} for {}",
+                    data.start,
+                    data.start + data.len(),
+                    current_upload.object_id()
+                );
+                if let Ok(done_until) = ctx.bail_err(
+                    self.sink_impl.upload_data(data, current_upload, ctx).await,
+                    &msg,
+                ) {
+                    // if this fails, we corrupted our internal state somehow
+                    self.buffer.mark_done_until(done_until)?;
+                } else {
+                    // stop attempting more writes on error
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// write out data buffered in the serializer to the current upload, e.g. the footer of a `parquet` file
+    async fn flush_serializer(
+        &mut self,
+        serializer: &mut EventSerializer,
+        ctx: &SinkContext,
+    ) -> Result<()> {
+        // always flush, so the next upload starts with a clean codec
+        let data = serializer.flush(nanotime())?;
+        if self.current_upload.is_some() {
+            self.write_data(data, ctx).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tremor_value::literal;
+
+    // 2022-10-18T13:37:42Z
+    const INGEST_NS: u64 = 1_666_100_262_000_000_000;
+
+    #[test]
+    fn rollover_config() -> Result<()> {
+        let config: RolloverConfig =
+            tremor_value::structurize(literal!({"key": "{yyyy}/{MM}/{dd}.json"}))?;
+        assert!(config.validate().is_ok());
+        let config: RolloverConfig = tremor_value::structurize(
+            literal!({"key": "{yyyy}/{MM}/{dd}.json", "max_events": 10}),
+        )?;
+        assert!(config.validate().is_err());
+        let config: RolloverConfig =
+            tremor_value::structurize(literal!({"key": "{uuid}.json", "max_bytes": 0}))?;
+        assert!(config.validate().is_err());
+        let config: RolloverConfig =
+            tremor_value::structurize(literal!({"key": "{uuid}.json", "max_age": 1_000}))?;
+        assert!(config.validate().is_ok());
+        let config: RolloverConfig =
+            tremor_value::structurize(literal!({"key": "{name}-{uuid}.json"}))?;
+        assert!(config.validate().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn rollover() -> Result<()> {
+        let config: RolloverConfig = tremor_value::structurize(
+            literal!({"key": "{HH}/{uuid}", "max_bytes": 10, "max_events": 3}),
+        )?;
+        let mut rollover = Rollover::new(config);
+        let bucket = || "bucket".to_string();
+
+        let first = rollover.object_id(bucket(), None, INGEST_NS, "s3_streamer")?;
+        assert!(first.name().starts_with("13/"));
+        rollover.track(4);
+        assert_eq!(
+            first,
+            rollover.object_id(bucket(), None, INGEST_NS, "s3_streamer")?
+        );
+        rollover.track(4);
+        assert_eq!(
+            first,
+            rollover.object_id(bucket(), None, INGEST_NS, "s3_streamer")?
+        );
+        // max_bytes reached
+        rollover.track(4);
+        let second = rollover.object_id(bucket(), None, INGEST_NS, "s3_streamer")?;
+        assert_ne!(first, second);
+
+        // max_events reached
+        rollover.track(1);
+        rollover.track(1);
+        rollover.track(1);
+        let third = rollover.object_id(bucket(), None, INGEST_NS, "s3_streamer")?;
+        assert_ne!(second, third);
+
+        // a new hour starts a new object
+        let fourth =
+            rollover.object_id(bucket(), None, INGEST_NS + 3_600_000_000_000, "s3_streamer")?;
+        assert_ne!(third, fourth);
+        assert!(fourth.name().starts_with("14/"));
+
+        // so does another bucket
+        let fifth = rollover.object_id(
+            "other".to_string(),
+            None,
+            INGEST_NS + 3_600_000_000_000,
+            "s3_streamer",
+        )?;
+        assert_eq!("other", fifth.bucket());
+
+        assert!(!rollover.should_finish(nanotime()));
+        rollover.reset();
+        let sixth = rollover.object_id(
+            "other".to_string(),
+            None,
+            INGEST_NS + 3_600_000_000_000,
+            "s3_streamer",
+        )?;
+        assert_ne!(fifth, sixth);
+        Ok(())
+    }
+}
@@ -12,12 +12,12 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-mod common;
+pub(crate) mod common;
 mod id;
 mod logs;
 mod metrics;
 mod resource;
-mod trace;
+pub(crate) mod trace;
 
 pub(crate) mod client;
 pub(crate) mod server;
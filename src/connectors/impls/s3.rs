@@ -15,3 +15,4 @@
 mod auth;
 pub(crate) mod reader;
 pub(crate) mod streamer;
+mod watch;
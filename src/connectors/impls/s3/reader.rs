@@ -11,15 +11,23 @@
 // WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.
-use crate::connectors::prelude::*;
+use crate::connectors::{
+    prelude::*,
+    utils::poll::{load_state, save_state, Interval},
+};
+use crate::preprocessor::decompress;
+use async_std::path::Path;
 use futures::stream::TryStreamExt;
+use std::collections::BTreeSet;
 use std::error::Error as StdError;
+use std::io::Read;
 
 use async_std::channel::{self, Receiver, Sender};
 use async_std::sync::Arc;
 use async_std::task::{self, JoinHandle};
 
 use super::auth;
+use super::watch::{self, ObjectDone, WatchConfig, WatchState};
 use aws_sdk_s3 as s3;
 use s3::model::Object;
 use s3::types::ByteStream;
@@ -27,6 +35,9 @@ use s3::Client as S3Client;
 
 const MINCHUNKSIZE: i64 = 8 * 1024 * 1024; // 8 MBs
 
+/// chunks buffered between downloading, decompressing and sending an object
+const DECOMPRESS_QSIZE: usize = 4;
+
 pub(crate) const CONNECTOR_TYPE: &str = "s3_reader";
 const URL_SCHEME: &str = "tremor-s3";
 
@@ -51,6 +62,14 @@ pub(crate) struct Config {
 
     #[serde(default = "Config::default_max_connections")]
     max_connections: usize,
+
+    /// list the bucket periodically and only read objects not read before, lists only once if not set
+    #[serde(default = "Default::default")]
+    watch: Option<WatchConfig>,
+
+    /// decompress objects with a known compression extension, like `.gz` or `.zst`, before decoding
+    #[serde(default = "Default::default")]
+    decompress: bool,
 }
 
 struct KeyPayload {
@@ -86,12 +105,22 @@ impl ConnectorBuilder for Builder {
 
     async fn build_cfg(
         &self,
-        _: &Alias,
+        alias: &Alias,
         _: &ConnectorConfig,
         config: &Value,
         _kill_switch: &KillSwitch,
     ) -> Result<Box<dyn Connector>> {
         let config = Config::new(config)?;
+        if config
+            .watch
+            .as_ref()
+            .map_or(false, |watch| watch.interval == 0)
+        {
+            return Err(err_connector_def(
+                alias,
+                "watch interval must be greater than 0",
+            ));
+        }
 
         // TODO: display a warning if chunksize lesser than some quantity
         Ok(Box::new(S3Reader {
@@ -151,6 +180,13 @@ impl Connector for S3Reader {
             })?;
 
         let (tx_key, rx_key) = channel::bounded(QSIZE.load(Ordering::Relaxed));
+        // fetchers report read objects back to the watcher
+        let (tx_done, rx_done) = if self.config.watch.is_some() {
+            let (tx, rx) = channel::unbounded();
+            (Some(tx), Some(rx))
+        } else {
+            (None, None)
+        };
 
         // spawn object fetcher tasks
         for i in 0..self.config.max_connections {
@@ -177,6 +213,8 @@ impl Connector for S3Reader {
                 multipart_threshold: self.config.multipart_threshold,
                 part_size: self.config.multipart_chunksize,
                 origin_uri,
+                decompress: self.config.decompress,
+                done: tx_done.clone(),
             };
             let handle = task::Builder::new()
                 .name(format!("fetch_obj_task{}", i))
@@ -187,9 +225,23 @@ impl Connector for S3Reader {
         // spawn key fetcher task
         let bucket = self.config.bucket.clone();
         let prefix = self.config.prefix.clone();
-        task::Builder::new()
-            .name("fetch_key_task".to_owned())
-            .spawn(fetch_keys_task(client, bucket, prefix, tx_key))?;
+        if let (Some(watch), Some(rx_done)) = (self.config.watch.clone(), rx_done) {
+            let ctx = ctx.clone();
+            let handle = task::Builder::new()
+                .name("watch_key_task".to_owned())
+                .spawn(async move {
+                    let res = watch_keys_task(client, bucket, prefix, watch, tx_key, rx_done).await;
+                    if let Err(e) = &res {
+                        error!("{ctx} Error watching bucket: {e}");
+                    }
+                    res
+                })?;
+            self.handles.push(handle);
+        } else {
+            task::Builder::new()
+                .name("fetch_key_task".to_owned())
+                .spawn(fetch_keys_task(client, bucket, prefix, tx_key))?;
+        }
 
         Ok(true)
     }
@@ -255,6 +307,152 @@ async fn fetch_keys_task(
     Ok(())
 }
 
+/// List all objects under `prefix`
+async fn list_objects(
+    client: &S3Client,
+    bucket: &str,
+    prefix: Option<&String>,
+) -> Result<Vec<Object>> {
+    let mut objects = Vec::new();
+    let mut continuation_token: Option<String> = None;
+    loop {
+        let mut resp = client
+            .list_objects_v2()
+            .bucket(bucket)
+            .set_prefix(prefix.cloned())
+            .set_continuation_token(continuation_token.take())
+            .send()
+            .await?;
+        if let Some(mut entries) = resp.contents.take() {
+            objects.append(&mut entries);
+        }
+        if resp.is_truncated {
+            continuation_token = resp.next_continuation_token().map(ToString::to_string);
+        } else {
+            return Ok(objects);
+        }
+    }
+}
+
+#[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
+fn last_modified_ns(object: &Object) -> u64 {
+    object.last_modified().map_or(0, |dt| dt.as_nanos() as u64)
+}
+
+/// Periodically list the bucket and send objects not read before to the fetchers
+async fn watch_keys_task(
+    client: S3Client,
+    bucket: String,
+    prefix: Option<String>,
+    watch: WatchConfig,
+    sender: Sender<KeyPayload>,
+    done: Receiver<ObjectDone>,
+) -> Result<()> {
+    let state_file = watch.state_file.as_ref().map(Path::new);
+    let mut state = if let Some(state_file) = state_file {
+        load_state(state_file).await?
+    } else {
+        WatchState::default()
+    };
+    let mut stream = 0; // for the Channel Source
+    let mut interval = Interval::new(watch.interval);
+    loop {
+        interval.tick().await;
+
+        let objects = match list_objects(&client, &bucket, prefix.as_ref()).await {
+            Ok(objects) => objects,
+            Err(e) => {
+                warn!("Error listing bucket {bucket}: {e}");
+                continue;
+            }
+        };
+        let listed: BTreeSet<String> = objects
+            .iter()
+            .filter_map(|object| object.key().map(ToString::to_string))
+            .collect();
+        let mut new: Vec<Object> = objects
+            .into_iter()
+            .filter(|object| {
+                object.key().map_or(false, |key| {
+                    state.is_new(watch.track, key, last_modified_ns(object))
+                })
+            })
+            .collect();
+        new.sort_by_key(last_modified_ns);
+        debug!("Found {} new objects in bucket {bucket}.", new.len());
+
+        let count = new.len();
+        for object_data in new {
+            sender
+                .send(KeyPayload {
+                    object_data,
+                    stream,
+                })
+                .await?;
+            stream += 1;
+        }
+        let mut read = Vec::with_capacity(count);
+        for _ in 0..count {
+            read.push(done.recv().await?);
+        }
+        watch::advance(&mut state, watch.track, read);
+        state.retain(watch.track, &listed);
+        if let Some(state_file) = state_file {
+            save_state(state_file, &state).await?;
+        }
+    }
+}
+
+/// Compression algorithm of `preprocessor::decompress` for the extension of `key`, if known
+fn compression(key: &str) -> Option<&'static str> {
+    let (_, ext) = key.rsplit_once('.')?;
+    match ext.to_ascii_lowercase().as_str() {
+        "gz" | "gzip" => Some("gzip"),
+        "zz" | "zlib" => Some("zlib"),
+        "xz" => Some("xz2"),
+        "sz" | "snappy" => Some("snappy"),
+        "lz4" => Some("lz4"),
+        "zst" | "zstd" => Some("zstd"),
+        _ => None,
+    }
+}
+
+/// Blocking reader over the chunks of an object while it is downloaded
+struct ChunkReader {
+    rx: Receiver<Vec<u8>>,
+    chunk: Vec<u8>,
+    pos: usize,
+}
+
+impl ChunkReader {
+    fn new(rx: Receiver<Vec<u8>>) -> Self {
+        Self {
+            rx,
+            chunk: Vec::new(),
+            pos: 0,
+        }
+    }
+}
+
+impl Read for ChunkReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.pos == self.chunk.len() {
+            match task::block_on(self.rx.recv()) {
+                Ok(chunk) => {
+                    self.chunk = chunk;
+                    self.pos = 0;
+                }
+                // the download finished
+                Err(_) => return Ok(0),
+            }
+        }
+        let len = buf.len().min(self.chunk.len() - self.pos);
+        buf[..len].copy_from_slice(&self.chunk[self.pos..self.pos + len]);
+        self.pos += len;
+        Ok(len)
+    }
+}
+
 struct S3Instance {
     ctx: ConnectorContext,
     client: S3Client,
@@ -264,6 +462,8 @@ struct S3Instance {
     multipart_threshold: i64,
     part_size: i64,
     origin_uri: EventOriginUri,
+    decompress: bool,
+    done: Option<Sender<ObjectDone>>,
 }
 impl S3Instance {
     fn event_from(&self, data: Vec<u8>, meta: Value<'static>, stream: u64) -> SourceReply {
@@ -304,7 +504,13 @@ impl S3Instance {
         }) = self.rx.recv().await
         {
             debug!("{} Fetching key {:?}...", self.ctx, object_data.key());
-            let err = if object_data.size() <= self.multipart_threshold {
+            let key = object_data.key().unwrap_or_default().to_string();
+            let last_modified = last_modified_ns(&object_data);
+            let algorithm = compression(&key).filter(|_| self.decompress);
+            let err = if let Some(algorithm) = algorithm {
+                self.fetch_decompressed(stream, object_data, algorithm)
+                    .await
+            } else if object_data.size() <= self.multipart_threshold {
                 // Perform a single fetch.
                 self.fetch_no_multipart(stream, object_data).await
             } else {
@@ -321,11 +527,74 @@ impl S3Instance {
                     meta: None,
                 }
             };
+            let success = err.is_ok();
+            if let Err(e) = err {
+                error!("{} Error reading {key}: {e}", self.ctx);
+            }
             self.tx.send(stream_finish_reply).await?;
+            if let Some(done) = self.done.as_ref() {
+                done.send(ObjectDone {
+                    key,
+                    last_modified,
+                    success,
+                })
+                .await?;
+            }
         }
         Ok(())
     }
 
+    /// Fetch the whole object and send it decompressed in chunks of `part_size`
+    async fn fetch_decompressed(
+        &self,
+        stream: u64,
+        object_data: Object,
+        algorithm: &str,
+    ) -> Result<()> {
+        let key = object_data.key().map(ToString::to_string);
+        let mut obj_stream = self.fetch_object_stream(key.clone(), None).await?;
+        debug!("{} Decompressing {algorithm} for key {key:?}.", self.ctx);
+        let part_size = u64::try_from(self.part_size.max(1))?;
+        let (compressed_tx, compressed_rx) = channel::bounded(DECOMPRESS_QSIZE);
+        let (decompressed_tx, decompressed_rx) = channel::bounded(DECOMPRESS_QSIZE);
+
+        // the decoders are blocking, they decompress the object on their own thread while it is downloaded
+        let algorithm = algorithm.to_string();
+        let decoder = task::spawn_blocking(move || -> Result<()> {
+            let mut decoder = decompress::decoder(&algorithm, ChunkReader::new(compressed_rx))?;
+            loop {
+                let mut part = Vec::new();
+                decoder.by_ref().take(part_size).read_to_end(&mut part)?;
+                // stop if we are done or the object isn't sent anymore
+                if part.is_empty() || task::block_on(decompressed_tx.send(part)).is_err() {
+                    return Ok(());
+                }
+            }
+        });
+        let download = async move {
+            while let Some(chunk) = obj_stream.try_next().await? {
+                if compressed_tx.send(chunk.to_vec()).await.is_err() {
+                    // the decoder failed
+                    break;
+                }
+            }
+            Ok::<(), Error>(())
+        };
+        let meta = self.to_object_meta(object_data, None);
+        let send = async move {
+            while let Ok(part) = decompressed_rx.recv().await {
+                self.tx
+                    .send(self.event_from(part, meta.clone(), stream))
+                    .await?;
+            }
+            Ok::<(), Error>(())
+        };
+        let (downloaded, sent) = futures::future::join(download, send).await;
+        downloaded?;
+        decoder.await?;
+        sent
+    }
+
     async fn fetch_no_multipart(&self, stream: u64, object_data: Object) -> Result<()> {
         let key = object_data.key().map(ToString::to_string);
         let mut obj_stream = self.fetch_object_stream(key.clone(), None).await?;
@@ -409,3 +678,38 @@ impl S3Instance {
         self.ctx.meta(meta)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compression_by_extension() {
+        assert_eq!(Some("gzip"), compression("logs/2022/10/18/snot.json.gz"));
+        assert_eq!(Some("zstd"), compression("snot.ZST"));
+        assert_eq!(Some("xz2"), compression("snot.xz"));
+        assert_eq!(None, compression("snot.json"));
+        assert_eq!(None, compression("snot"));
+    }
+
+    #[async_std::test]
+    async fn decompress_while_downloading() -> Result<()> {
+        let data = "snot badger ".repeat(1024);
+        let mut encoder = libflate::gzip::Encoder::new(Vec::new())?;
+        std::io::Write::write_all(&mut encoder, data.as_bytes())?;
+        let compressed = encoder.finish().into_result()?;
+
+        let (tx, rx) = channel::bounded(DECOMPRESS_QSIZE);
+        let decoder = task::spawn_blocking(move || -> Result<Vec<u8>> {
+            let mut decompressed = Vec::new();
+            decompress::decoder("gzip", ChunkReader::new(rx))?.read_to_end(&mut decompressed)?;
+            Ok(decompressed)
+        });
+        for chunk in compressed.chunks(7) {
+            tx.send(chunk.to_vec()).await?;
+        }
+        drop(tx);
+        assert_eq!(data.as_bytes(), decoder.await?.as_slice());
+        Ok(())
+    }
+}
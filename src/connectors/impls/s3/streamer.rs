@@ -15,7 +15,7 @@
 use crate::connectors::impls::{
     object_storage::{
         BufferPart, ObjectId, ObjectStorageBuffer, ObjectStorageCommon, ObjectStorageSinkImpl,
-        ObjectStorageUpload,
+        ObjectStorageUpload, RolloverConfig,
     },
     s3::auth,
 };
@@ -46,6 +46,10 @@ pub(crate) struct Config {
 
     #[serde(default = "Config::fivembs")]
     buffer_size: usize,
+
+    /// roll over to new objects automatically, instead of naming them via `$s3.name`
+    #[serde(default = "Default::default")]
+    rollover: Option<RolloverConfig>,
 }
 
 // Defaults for the config.
@@ -82,6 +86,11 @@ impl ConnectorBuilder for Builder {
     ) -> Result<Box<dyn Connector>> {
         let mut config = Config::new(config)?;
         config.normalize(id);
+        if let Some(rollover) = config.rollover.as_ref() {
+            rollover
+                .validate()
+                .map_err(|e| err_connector_def(id, &format!("Invalid `rollover`: {e}")))?;
+        }
         Ok(Box::new(S3Connector { config }))
     }
 }
@@ -132,6 +141,10 @@ impl ObjectStorageCommon for S3ObjectStorageSinkImpl {
         self.config.bucket.as_ref()
     }
 
+    fn rollover(&self) -> Option<&RolloverConfig> {
+        self.config.rollover.as_ref()
+    }
+
     fn connector_type(&self) -> &str {
         CONNECTOR_TYPE
     }
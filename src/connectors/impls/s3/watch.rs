@@ -0,0 +1,193 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Watching a bucket for new objects
+//!
+//! The bucket is listed every `interval` and only objects not read before are fetched.
+//! The position only advances once the objects of a listing have been read, so after a restart
+//! objects might be read again, but none are skipped.
+
+use std::cmp::Ordering;
+use std::collections::BTreeSet;
+
+/// How to tell new objects from already read ones
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Track {
+    /// Remember the `last_modified` timestamp of the newest read object, objects are new if they are newer.
+    ///
+    /// Objects landing with an older timestamp, e.g. multipart uploads started before, are missed.
+    #[default]
+    Watermark,
+    /// Remember the keys of all read objects still present in the bucket
+    Keys,
+}
+
+/// Configuration for watching a bucket
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct WatchConfig {
+    /// interval between the start of two listings in nanoseconds
+    pub(crate) interval: u64,
+    /// how to tell new objects from already read ones
+    #[serde(default = "Default::default")]
+    pub(crate) track: Track,
+    /// file to persist the watch position in, so watching continues where it left off after a restart
+    #[serde(default = "Default::default")]
+    pub(crate) state_file: Option<String>,
+}
+
+/// The watch position
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub(crate) struct WatchState {
+    /// `last_modified` of the newest read object in nanoseconds
+    watermark: u64,
+    /// keys of read objects, for `watermark` tracking only those at the watermark,
+    /// as S3 timestamps only have a resolution of seconds
+    keys: BTreeSet<String>,
+}
+
+impl WatchState {
+    /// Returns `true` if the object has not been read yet
+    pub(crate) fn is_new(&self, track: Track, key: &str, last_modified: u64) -> bool {
+        match track {
+            Track::Watermark => match last_modified.cmp(&self.watermark) {
+                Ordering::Greater => true,
+                Ordering::Equal => !self.keys.contains(key),
+                Ordering::Less => false,
+            },
+            Track::Keys => !self.keys.contains(key),
+        }
+    }
+
+    /// Record the object as read
+    pub(crate) fn advance(&mut self, track: Track, key: &str, last_modified: u64) {
+        match track {
+            Track::Watermark => match last_modified.cmp(&self.watermark) {
+                Ordering::Greater => {
+                    self.watermark = last_modified;
+                    self.keys.clear();
+                    self.keys.insert(key.to_string());
+                }
+                Ordering::Equal => {
+                    self.keys.insert(key.to_string());
+                }
+                Ordering::Less => (),
+            },
+            Track::Keys => {
+                self.keys.insert(key.to_string());
+            }
+        }
+    }
+
+    /// Forget keys no longer present in the bucket
+    pub(crate) fn retain(&mut self, track: Track, listed: &BTreeSet<String>) {
+        if track == Track::Keys {
+            self.keys.retain(|key| listed.contains(key));
+        }
+    }
+}
+
+/// Outcome of reading an object, reported back to the watcher
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ObjectDone {
+    pub(crate) key: String,
+    pub(crate) last_modified: u64,
+    pub(crate) success: bool,
+}
+
+/// Record the read objects of a listing in `state`.
+///
+/// With `watermark` tracking the watermark doesn't advance past the oldest failed object,
+/// so it is read again with the next listing.
+pub(crate) fn advance(state: &mut WatchState, track: Track, mut done: Vec<ObjectDone>) {
+    let oldest_failed = done
+        .iter()
+        .filter(|d| !d.success)
+        .map(|d| d.last_modified)
+        .min();
+    done.sort_by_key(|d| d.last_modified);
+    for d in done.iter().filter(|d| d.success) {
+        if track == Track::Watermark && oldest_failed.map_or(false, |f| d.last_modified >= f) {
+            break;
+        }
+        state.advance(track, &d.key, d.last_modified);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn done(key: &str, last_modified: u64, success: bool) -> ObjectDone {
+        ObjectDone {
+            key: key.to_string(),
+            last_modified,
+            success,
+        }
+    }
+
+    #[test]
+    fn watermark() {
+        let mut state = WatchState::default();
+        let track = Track::Watermark;
+        assert!(state.is_new(track, "a", 1));
+        advance(
+            &mut state,
+            track,
+            vec![done("b", 2, true), done("a", 1, true), done("c", 2, true)],
+        );
+        assert_eq!(2, state.watermark);
+        assert!(!state.is_new(track, "a", 1));
+        assert!(!state.is_new(track, "b", 2));
+        assert!(!state.is_new(track, "c", 2));
+        // same second, different key
+        assert!(state.is_new(track, "d", 2));
+        assert!(state.is_new(track, "e", 3));
+        // landed late with an old timestamp
+        assert!(!state.is_new(track, "f", 1));
+
+        // failures hold back the watermark
+        advance(
+            &mut state,
+            track,
+            vec![done("d", 2, true), done("e", 3, false), done("g", 4, true)],
+        );
+        assert_eq!(2, state.watermark);
+        assert!(!state.is_new(track, "d", 2));
+        assert!(state.is_new(track, "e", 3));
+        assert!(state.is_new(track, "g", 4));
+    }
+
+    #[test]
+    fn keys() {
+        let mut state = WatchState::default();
+        let track = Track::Keys;
+        advance(
+            &mut state,
+            track,
+            vec![done("b", 2, true), done("a", 1, false), done("c", 3, true)],
+        );
+        assert!(state.is_new(track, "a", 1));
+        assert!(!state.is_new(track, "b", 2));
+        assert!(!state.is_new(track, "c", 3));
+        // landed late with an old timestamp
+        assert!(state.is_new(track, "d", 0));
+
+        // deleted objects are forgotten
+        state.retain(track, &["c".to_string()].into_iter().collect());
+        assert!(state.is_new(track, "b", 2));
+        assert!(!state.is_new(track, "c", 3));
+    }
+}
@@ -24,7 +24,7 @@ use crate::{
         prelude::*,
         utils::{
             socket::{tcp_client_socket, TcpSocketOptions},
-            tls::{tls_client_connector, TLSClientConfig},
+            tls::{Reloadable, TLSClientConfig},
         },
     },
     errors::err_connector_def,
@@ -38,6 +38,7 @@ use async_std::{
 use async_tls::TlsConnector;
 use either::Either;
 use futures::io::AsyncReadExt;
+use rustls::ClientConfig;
 
 const URL_SCHEME: &str = "tremor-tcp-client";
 
@@ -59,7 +60,7 @@ impl ConfigImpl for Config {}
 
 pub(crate) struct TcpClient {
     config: Config,
-    tls_connector: Option<TlsConnector>,
+    tls_client_config: Option<Reloadable<ClientConfig>>,
     tls_domain: Option<String>,
     source_tx: Sender<SourceReply>,
     source_rx: Receiver<SourceReply>,
@@ -92,16 +93,16 @@ impl ConnectorBuilder for Builder {
             Some(host) => host.to_string(),
             None => return Err(err_connector_def(id, Self::MISSING_HOST)),
         };
-        let (tls_connector, tls_domain) = match config.tls.as_ref() {
+        let (tls_client_config, tls_domain) = match config.tls.as_ref() {
             Some(Either::Right(true)) => {
                 // default config
                 (
-                    Some(tls_client_connector(&TLSClientConfig::default()).await?),
+                    Some(Reloadable::client(&TLSClientConfig::default())?),
                     Some(host),
                 )
             }
             Some(Either::Left(tls_config)) => (
-                Some(tls_client_connector(tls_config).await?),
+                Some(Reloadable::client(tls_config)?),
                 tls_config.domain.clone(),
             ),
             Some(Either::Right(false)) | None => (None, None),
@@ -109,7 +110,7 @@ impl ConnectorBuilder for Builder {
         let (source_tx, source_rx) = bounded(crate::QSIZE.load(Ordering::Relaxed));
         Ok(Box::new(TcpClient {
             config,
-            tls_connector,
+            tls_client_config,
             tls_domain,
             source_tx,
             source_rx,
@@ -124,9 +125,9 @@ impl Connector for TcpClient {
         sink_context: SinkContext,
         builder: SinkManagerBuilder,
     ) -> Result<Option<SinkAddr>> {
-        if let Some(tls_connector) = self.tls_connector.as_ref() {
+        if let Some(tls_client_config) = self.tls_client_config.as_ref() {
             let sink = TcpClientSink::tls(
-                tls_connector.clone(),
+                tls_client_config.clone(),
                 self.tls_domain.clone(),
                 self.config.clone(),
                 self.source_tx.clone(),
@@ -144,11 +145,18 @@ impl Connector for TcpClient {
         builder: SourceManagerBuilder,
     ) -> Result<Option<SourceAddr>> {
         // this source is wired up to the ending channel that is forwarding data received from the TCP (or TLS) connection
+        let tls_client_config = self.tls_client_config.clone();
         let source = ChannelSource::from_channel(
             self.source_tx.clone(),
             self.source_rx.clone(),
             Arc::default(), // we don't need to know if the source is connected. Worst case if nothing is connected is that the receiving task is blocked.
-        );
+        )
+        .with_metrics(move |timestamp, alias| {
+            tls_client_config
+                .iter()
+                .map(|tls| tls.metrics(timestamp, alias))
+                .collect()
+        });
         builder.spawn(source, source_context).map(Some)
     }
 
@@ -159,7 +167,7 @@ impl Connector for TcpClient {
 
 /// TCP/TLS client sink implementation
 struct TcpClientSink {
-    tls_connector: Option<TlsConnector>,
+    tls_client_config: Option<Reloadable<ClientConfig>>,
     tls_domain: Option<String>,
     config: Config,
     wrapped_stream: Option<
@@ -178,7 +186,7 @@ impl TcpClientSink {
     fn plain(config: Config, source_tx: Sender<SourceReply>) -> Self {
         let source_runtime = ChannelSourceRuntime::new(source_tx);
         Self {
-            tls_connector: None,
+            tls_client_config: None,
             tls_domain: None,
             config,
             wrapped_stream: None,
@@ -187,14 +195,14 @@ impl TcpClientSink {
         }
     }
     fn tls(
-        tls_connector: TlsConnector,
+        tls_client_config: Reloadable<ClientConfig>,
         tls_domain: Option<String>,
         config: Config,
         source_tx: Sender<SourceReply>,
     ) -> Self {
         let source_runtime = ChannelSourceRuntime::new(source_tx);
         Self {
-            tls_connector: Some(tls_connector),
+            tls_client_config: Some(tls_client_config),
             tls_domain,
             config,
             wrapped_stream: None,
@@ -238,8 +246,9 @@ impl Sink for TcpClientSink {
             port: self.config.url.port(),
             path: vec![local_addr.port().to_string()], // local port
         };
-        if let Some(tls_connector) = self.tls_connector.as_ref() {
-            // TLS
+        if let Some(tls_client_config) = self.tls_client_config.as_ref() {
+            // TLS, every connection picks up reloaded certificates
+            let tls_connector = TlsConnector::from(tls_client_config.current());
             let tls_stream = tls_connector
                 .connect(
                     self.tls_domain
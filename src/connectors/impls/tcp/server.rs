@@ -18,20 +18,20 @@ use crate::{
         sink::channel_sink::ChannelSinkMsg,
         utils::{
             socket::{tcp_server_socket, TcpSocketOptions},
-            tls::{load_server_config, TLSServerConfig},
+            tls::{peer_identity, Reloadable, TLSServerConfig},
             ConnectionMeta,
         },
     },
     errors::err_connector_def,
 };
+use async_rustls::TlsAcceptor;
 use async_std::{
     channel::{bounded, Receiver, Sender},
     prelude::*,
-    task::JoinHandle,
+    task::{self, JoinHandle},
 };
-use async_tls::TlsAcceptor;
 use futures::io::AsyncReadExt;
-use rustls::ServerConfig;
+use rustls::{ServerConfig, Session};
 use simd_json::ValueAccess;
 use std::sync::{atomic::AtomicBool, Arc};
 
@@ -59,7 +59,7 @@ impl ConfigImpl for Config {}
 #[allow(clippy::module_name_repetitions)]
 pub(crate) struct TcpServer {
     config: Config,
-    tls_server_config: Option<ServerConfig>,
+    tls_server_config: Option<Reloadable<ServerConfig>>,
     sink_tx: Sender<ChannelSinkMsg<ConnectionMeta>>,
     sink_rx: Receiver<ChannelSinkMsg<ConnectionMeta>>,
     /// marker that the sink is connected
@@ -86,7 +86,7 @@ impl ConnectorBuilder for Builder {
             return Err(err_connector_def(id, "Missing port for TCP server"));
         }
         let tls_server_config = if let Some(tls_config) = config.tls.as_ref() {
-            Some(load_server_config(tls_config)?)
+            Some(Reloadable::server(tls_config)?)
         } else {
             None
         };
@@ -153,7 +153,7 @@ impl Connector for TcpServer {
 
 struct TcpServerSource {
     config: Config,
-    tls_server_config: Option<ServerConfig>,
+    tls_server_config: Option<Reloadable<ServerConfig>>,
     accept_task: Option<JoinHandle<()>>,
     connection_rx: Receiver<SourceReply>,
     runtime: ChannelSourceRuntime,
@@ -164,7 +164,7 @@ struct TcpServerSource {
 impl TcpServerSource {
     fn new(
         config: Config,
-        tls_server_config: Option<ServerConfig>,
+        tls_server_config: Option<Reloadable<ServerConfig>>,
         sink_runtime: ChannelSinkRuntime<ConnectionMeta>,
         sink_is_connected: Arc<AtomicBool>,
     ) -> Self {
@@ -227,47 +227,76 @@ impl Source for TcpServerSource {
                             path: path.clone(), // captures server port
                         };
 
-                        let tls_acceptor: Option<TlsAcceptor> = tls_server_config
-                            .clone()
-                            .map(|sc| TlsAcceptor::from(Arc::new(sc)));
-                        if let Some(acceptor) = tls_acceptor {
-                            let tls_stream = acceptor.accept(stream.clone()).await?;
-                            let (tls_read_stream, tls_write_sink) = tls_stream.split();
-                            let meta = ctx.meta(literal!({
-                                "tls": true,
-                                "peer": {
+                        if let Some(tls_server_config) = tls_server_config.as_ref() {
+                            // new connections pick up reloaded certificates
+                            let acceptor = TlsAcceptor::from(tls_server_config.current());
+                            let ctx = ctx.clone();
+                            let runtime = runtime.clone();
+                            let sink_runtime = sink_runtime.clone();
+                            let sink_is_connected = sink_is_connected.clone();
+                            // handshake in a separate task, so a stalled client doesn't keep us from accepting others
+                            task::spawn(async move {
+                                let tls_stream = match acceptor
+                                    .accept(stream.clone())
+                                    .timeout(HANDSHAKE_TIMEOUT)
+                                    .await
+                                {
+                                    Ok(Ok(s)) => s,
+                                    Ok(Err(e)) => {
+                                        warn!("{ctx} TLS handshake with {peer_addr} failed: {e}");
+                                        return;
+                                    }
+                                    Err(_) => {
+                                        warn!("{ctx} TLS handshake with {peer_addr} timed out");
+                                        return;
+                                    }
+                                };
+                                let mut peer = literal!({
                                     "host": peer_addr.ip().to_string(),
                                     "port": peer_addr.port()
+                                });
+                                if let Some(identity) = tls_stream
+                                    .get_ref()
+                                    .1
+                                    .get_peer_certificates()
+                                    .and_then(|certs| peer_identity(&certs))
+                                {
+                                    peer.try_insert("tls", identity);
                                 }
-                            }));
+                                let (tls_read_stream, tls_write_sink) = tls_stream.split();
+                                let meta = ctx.meta(literal!({
+                                    "tls": true,
+                                    "peer": peer
+                                }));
 
-                            // we only register a writer when we actually have something connected to the sink
-                            // the connected sink will not be driven by the sink task anyways (no calls to on_event/on_signal)
-                            let reader_runtime = if sink_is_connected.load(Ordering::Acquire) {
-                                sink_runtime
-                                    .register_stream_writer(
-                                        stream_id,
-                                        Some(connection_meta.clone()),
-                                        &ctx,
-                                        TcpWriter::tls_server(tls_write_sink, stream.clone()),
-                                    )
-                                    .await;
-                                Some(sink_runtime.clone())
-                            } else {
-                                debug!("{ctx} Sink not connected, not offering writing to TCP connections.");
-                                None
-                            };
-                            let tls_reader = TcpReader::tls_server(
-                                tls_read_stream,
-                                stream,
-                                vec![0; buf_size],
-                                ctx.alias.clone(),
-                                origin_uri.clone(),
-                                meta,
-                                reader_runtime,
-                            );
+                                // we only register a writer when we actually have something connected to the sink
+                                // the connected sink will not be driven by the sink task anyways (no calls to on_event/on_signal)
+                                let reader_runtime = if sink_is_connected.load(Ordering::Acquire) {
+                                    sink_runtime
+                                        .register_stream_writer(
+                                            stream_id,
+                                            Some(connection_meta.clone()),
+                                            &ctx,
+                                            TcpWriter::tls_server(tls_write_sink, stream.clone()),
+                                        )
+                                        .await;
+                                    Some(sink_runtime.clone())
+                                } else {
+                                    debug!("{ctx} Sink not connected, not offering writing to TCP connections.");
+                                    None
+                                };
+                                let tls_reader = TcpReader::tls_server(
+                                    tls_read_stream,
+                                    stream,
+                                    vec![0; buf_size],
+                                    ctx.alias.clone(),
+                                    origin_uri.clone(),
+                                    meta,
+                                    reader_runtime,
+                                );
 
-                            runtime.register_stream_reader(stream_id, &ctx, tls_reader);
+                                runtime.register_stream_reader(stream_id, &ctx, tls_reader);
+                            });
                         } else {
                             let meta = ctx.meta(literal!({
                                 "tls": false,
@@ -306,8 +335,9 @@ impl Source for TcpServerSource {
                         }
                     }
                     Ok(Err(e)) => {
+                        // e.g. running out of file descriptors, keep serving the existing connections
                         error!("{ctx} Error Accepting: {e}");
-                        return Err(e.into());
+                        task::sleep(ACCEPT_ERROR_BACKOFF).await;
                     }
                     Err(_) => continue, // timeout accepting
                 };
@@ -323,6 +353,13 @@ impl Source for TcpServerSource {
         Ok(self.connection_rx.recv().await?)
     }
 
+    fn metrics(&mut self, timestamp: u64, ctx: &SourceContext) -> Vec<EventPayload> {
+        self.tls_server_config
+            .iter()
+            .map(|tls| tls.metrics(timestamp, ctx.alias()))
+            .collect()
+    }
+
     async fn on_stop(&mut self, _ctx: &SourceContext) -> Result<()> {
         if let Some(accept_task) = self.accept_task.take() {
             // stop acceptin' new connections
@@ -13,9 +13,12 @@
 // limitations under the License.
 
 ///! The UDP server will close the udp spcket on stop
-use crate::connectors::{
-    prelude::*,
-    utils::socket::{udp_socket, UdpSocketOptions},
+use crate::{
+    connectors::{
+        prelude::*,
+        utils::socket::{udp_socket, MulticastGroup, UdpSocketOptions},
+    },
+    errors::err_connector_def,
 };
 use async_std::net::UdpSocket;
 
@@ -33,6 +36,10 @@ pub(crate) struct Config {
     /// so the incoming load can be shared scross multiple connectors
     #[serde(default)]
     socket_options: UdpSocketOptions,
+
+    /// multicast groups to join, the `url` needs to bind to the port of the groups
+    #[serde(default)]
+    multicast_groups: Vec<MulticastGroup>,
 }
 
 impl ConfigImpl for Config {}
@@ -51,12 +58,17 @@ impl ConnectorBuilder for Builder {
     }
     async fn build_cfg(
         &self,
-        _: &Alias,
+        id: &Alias,
         _: &ConnectorConfig,
         raw: &Value,
         _kill_switch: &KillSwitch,
     ) -> Result<Box<dyn Connector>> {
         let config = Config::new(raw)?;
+        for group in &config.multicast_groups {
+            group
+                .validate()
+                .map_err(|e| err_connector_def(id, &e.to_string()))?;
+        }
         Ok(Box::new(UdpServer { config }))
     }
 }
@@ -106,6 +118,9 @@ impl UdpServerSource {
 impl Source for UdpServerSource {
     async fn connect(&mut self, _ctx: &SourceContext, _attempt: &Attempt) -> Result<bool> {
         let listener = udp_socket(&self.config.url, &self.config.socket_options).await?;
+        for group in &self.config.multicast_groups {
+            group.join(&listener)?;
+        }
         self.listener = Some(listener);
         Ok(true)
     }
@@ -115,8 +130,8 @@ impl Source for UdpServerSource {
             .listener
             .as_ref()
             .ok_or_else(|| Error::from(ErrorKind::NoSocket))?;
-        match socket.recv(&mut self.buffer).await {
-            Ok(bytes_read) => {
+        match socket.recv_from(&mut self.buffer).await {
+            Ok((bytes_read, peer)) => {
                 if bytes_read == 0 {
                     Ok(SourceReply::EndStream {
                         origin_uri: self.origin_uri.clone(),
@@ -124,10 +139,17 @@ impl Source for UdpServerSource {
                         stream: DEFAULT_STREAM_ID,
                     })
                 } else {
+                    // the peer `host` is its IP address, it is not resolved to a hostname
+                    let meta = ctx.meta(literal!({
+                        "peer": {
+                            "host": peer.ip().to_string(),
+                            "port": peer.port()
+                        }
+                    }));
                     Ok(SourceReply::Data {
                         origin_uri: self.origin_uri.clone(),
                         stream: Some(DEFAULT_STREAM_ID),
-                        meta: None,
+                        meta: Some(meta),
                         // ALLOW: we know bytes_read is smaller than or equal buf_size
                         data: self.buffer[0..bytes_read].to_vec(),
                         port: None,
@@ -12,27 +12,95 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+//! Write-ahead log connector
+//!
+//! Events sent to the sink are appended to the log, the source reads them with a named cursor,
+//! whose position only advances on `ack`. Connectors with the same `path` share the log,
+//! so multiple flows can read it independently with different cursors.
+//! Cursors of flows that are gone for good need to be listed in `remove_cursors`,
+//! otherwise they keep the entries they didn't read yet until the log is full.
+//!
+//! The log is local to the node running the connector, it is not replicated to other nodes.
+
 // #![cfg_attr(coverage, no_coverage)]
-use std::{sync::Arc, time::Duration};
+use std::collections::{BTreeSet, HashMap};
+use std::sync::{Arc, Weak};
+use std::time::Duration;
 
 use crate::connectors::prelude::*;
+use crate::connectors::utils::metrics::make_wal_metrics_payload;
+use async_std::path::PathBuf;
 use async_std::{sync::Mutex, task};
 
 use simd_json_derive::{Deserialize, Serialize};
 
+mod storage;
+
+use storage::{Log, Seek};
+
+fn default_cursor() -> String {
+    "default".to_string()
+}
+
+fn default_fsync() -> bool {
+    true
+}
+
 #[derive(serde::Deserialize, Debug, Clone)]
 #[serde(deny_unknown_fields)]
 pub(crate) struct Config {
     path: String,
     chunk_size: u64,
     max_chunks: usize,
+    /// name of the cursor to read with, connectors sharing a log need to use different cursors
+    #[serde(default = "default_cursor")]
+    cursor: String,
+    /// where to start reading when the connector starts, continues at the position of the cursor if not set
+    #[serde(default = "Default::default")]
+    seek: Option<Seek>,
+    /// cursors no longer used by any connector, removed when the connector starts so the entries they
+    /// didn't read yet can be deleted
+    #[serde(default = "Default::default")]
+    remove_cursors: Vec<String>,
+    /// sync every appended entry and cursor update to disk, so they survive a power loss,
+    /// connectors sharing a log use the setting of the one that opened it first
+    #[serde(default = "default_fsync")]
+    fsync: bool,
 }
 
 impl ConfigImpl for Config {}
 
+lazy_static! {
+    /// logs opened by `wal` connectors, so connectors with the same `path` share the log
+    static ref LOGS: Mutex<HashMap<PathBuf, Weak<Mutex<Log>>>> = Mutex::new(HashMap::new());
+}
+
+/// Open the log at `path` or get the one already opened by another connector
+async fn open_log(
+    path: &str,
+    chunk_size: u64,
+    max_chunks: usize,
+    fsync: bool,
+) -> Result<Arc<Mutex<Log>>> {
+    async_std::fs::create_dir_all(path).await?;
+    let path = async_std::fs::canonicalize(path).await?;
+    let mut logs = LOGS.lock().await;
+    if let Some(log) = logs.get(&path).and_then(Weak::upgrade) {
+        return Ok(log);
+    }
+    let log = Arc::new(Mutex::new(
+        Log::open(&path, chunk_size, max_chunks, fsync).await?,
+    ));
+    logs.retain(|_, log| log.strong_count() > 0);
+    logs.insert(path, Arc::downgrade(&log));
+    Ok(log)
+}
+
 struct Wal {
     event_origin_uri: EventOriginUri,
-    wal: Arc<Mutex<qwal::Wal>>,
+    wal: Arc<Mutex<Log>>,
+    cursor: String,
+    seek: Option<Seek>,
 }
 
 #[derive(Debug, Default)]
@@ -45,12 +113,23 @@ impl ConnectorBuilder for Builder {
     }
     async fn build_cfg(
         &self,
-        _: &Alias,
+        alias: &Alias,
         _: &ConnectorConfig,
         config: &Value,
         _kill_switch: &KillSwitch,
     ) -> Result<Box<dyn Connector>> {
         let config: Config = Config::new(config)?;
+        if config.cursor.is_empty()
+            || !config
+                .cursor
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        {
+            return Err(err_connector_def(
+                alias,
+                "`cursor` must only contain alphanumeric characters, `_` and `-`",
+            ));
+        }
 
         let event_origin_uri = EventOriginUri {
             scheme: "tremor-kv".to_string(),
@@ -58,44 +137,79 @@ impl ConnectorBuilder for Builder {
             port: None,
             path: config.path.split('/').map(ToString::to_string).collect(),
         };
-        let wal = qwal::Wal::open(&config.path, config.chunk_size, config.max_chunks).await?;
+        if config.remove_cursors.contains(&config.cursor) {
+            return Err(err_connector_def(
+                alias,
+                "`remove_cursors` must not contain the `cursor` of the connector",
+            ));
+        }
+        let wal = open_log(
+            &config.path,
+            config.chunk_size,
+            config.max_chunks,
+            config.fsync,
+        )
+        .await?;
+        for cursor in &config.remove_cursors {
+            if wal.lock().await.remove_cursor(cursor).await? {
+                info!("[Connector::{alias}] Removed WAL cursor `{cursor}`");
+            }
+        }
 
         Ok(Box::new(Wal {
             event_origin_uri,
-            wal: Arc::new(Mutex::new(wal)),
+            wal,
+            cursor: config.cursor,
+            seek: config.seek,
         }))
     }
 }
 
 struct WalSource {
     origin_uri: EventOriginUri,
-    wal: Arc<Mutex<qwal::Wal>>,
-}
-
-struct Payload(Event);
-
-impl qwal::Entry for Payload {
-    type Output = Event;
-    type Error = simd_json::Error;
-
-    fn serialize(self) -> std::result::Result<Vec<u8>, Self::Error> {
-        Ok(self.0.json_vec()?)
-    }
-
-    fn deserialize(mut data: Vec<u8>) -> std::result::Result<Self::Output, Self::Error> {
-        Event::from_slice(&mut data)
-    }
+    wal: Arc<Mutex<Log>>,
+    cursor: String,
+    seek: Option<Seek>,
+    /// offset of the next entry to read
+    read: u64,
+    /// offset up to which all entries have been acked
+    committed: u64,
+    /// read entries not yet acked
+    pending: BTreeSet<u64>,
 }
 
 #[async_trait::async_trait]
 impl Source for WalSource {
+    async fn connect(&mut self, _ctx: &SourceContext, attempt: &Attempt) -> Result<bool> {
+        let mut wal = self.wal.lock().await;
+        let position = match self.seek {
+            Some(seek) if attempt.is_first() => {
+                let position = wal.seek(seek);
+                wal.commit(&self.cursor, position).await?;
+                position
+            }
+            _ => wal
+                .cursor(&self.cursor)
+                .unwrap_or_else(|| wal.first_offset())
+                .max(wal.first_offset()),
+        };
+        self.read = position;
+        self.committed = position;
+        self.pending.clear();
+        Ok(true)
+    }
+
     async fn pull_data(&mut self, pull_id: &mut u64, _ctx: &SourceContext) -> Result<SourceReply> {
         // This is a busy loop until we get data to avoid hogging the cpu
         // TODO: improve this by adding  notifyer on write
         loop {
-            if let Some((id, event)) = self.wal.lock().await.pop::<Payload>().await? {
-                // the wal is creating its own ids, we take over here
-                *pull_id = id;
+            let data = self.wal.lock().await.read(self.read).await?;
+            if let Some(mut data) = data {
+                let event = Event::from_slice(&mut data)?;
+                // the offset in the wal is the id, we take over here
+                *pull_id = self.read;
+                self.pending.insert(self.read);
+                self.read += 1;
                 return Ok(SourceReply::Structured {
                     origin_uri: self.origin_uri.clone(),
                     payload: event.data,
@@ -108,15 +222,50 @@ impl Source for WalSource {
     }
 
     async fn ack(&mut self, _stream_id: u64, pull_id: u64, _ctx: &SourceContext) -> Result<()> {
-        self.wal.lock().await.ack(pull_id).await?;
+        self.pending.remove(&pull_id);
+        // everything before the oldest pending entry has been acked
+        let committed = self.pending.iter().next().copied().unwrap_or(self.read);
+        if committed > self.committed {
+            self.committed = committed;
+            self.wal
+                .lock()
+                .await
+                .commit(&self.cursor, committed)
+                .await?;
+        }
         Ok(())
     }
 
     async fn fail(&mut self, _stream_id: u64, _pull_id: u64, _ctx: &SourceContext) -> Result<()> {
-        self.wal.lock().await.revert().await?;
+        // read everything again that has not been acked yet
+        self.pending.clear();
+        self.read = self.committed;
         Ok(())
     }
 
+    fn metrics(&mut self, timestamp: u64, ctx: &SourceContext) -> Vec<EventPayload> {
+        // don't wait for readers and writers, we will report again next time
+        if let Some(wal) = self.wal.try_lock() {
+            let size = wal.size();
+            let next = wal.next_offset();
+            let entries = next - wal.first_offset();
+            wal.cursors()
+                .map(|(cursor, offset)| {
+                    make_wal_metrics_payload(
+                        timestamp,
+                        cursor,
+                        size,
+                        entries,
+                        next.saturating_sub(*offset),
+                        ctx.alias(),
+                    )
+                })
+                .collect()
+        } else {
+            vec![]
+        }
+    }
+
     fn is_transactional(&self) -> bool {
         true
     }
@@ -127,7 +276,7 @@ impl Source for WalSource {
 }
 
 struct WalSink {
-    wal: Arc<Mutex<qwal::Wal>>,
+    wal: Arc<Mutex<Log>>,
 }
 
 #[async_trait::async_trait]
@@ -144,7 +293,8 @@ impl Sink for WalSink {
         _serializer: &mut EventSerializer,
         _start: u64,
     ) -> Result<SinkReply> {
-        self.wal.lock().await.push(Payload(event)).await?;
+        let data = event.json_vec()?;
+        self.wal.lock().await.append(event.ingest_ns, &data).await?;
         Ok(SinkReply::NONE)
     }
 }
@@ -159,6 +309,11 @@ impl Connector for Wal {
         let s = WalSource {
             wal: self.wal.clone(),
             origin_uri: self.event_origin_uri.clone(),
+            cursor: self.cursor.clone(),
+            seek: self.seek,
+            read: 0,
+            committed: 0,
+            pending: BTreeSet::new(),
         };
         builder.spawn(s, source_context).map(Some)
     }
@@ -174,11 +329,6 @@ impl Connector for Wal {
         builder.spawn(s, sink_context).map(Some)
     }
 
-    async fn on_stop(&mut self, _ctx: &ConnectorContext) -> Result<()> {
-        self.wal.lock().await.preserve_ack().await?;
-        Ok(())
-    }
-
     fn codec_requirements(&self) -> CodecReq {
         CodecReq::Structured
     }
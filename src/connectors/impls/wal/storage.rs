@@ -0,0 +1,637 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Segmented, append-only log with named cursors
+//!
+//! Entries are appended to segment files in `<path>/segments` of about `chunk_size` bytes,
+//! named after the offset of their first entry. Every entry is stored as its length,
+//! its ingest timestamp and its data, a partially written entry at the end of a segment is
+//! truncated when opening the log.
+//!
+//! Cursors persist the offset of the next entry to read in `<path>/cursors/<name>`.
+//! Segments are deleted once all cursors are past them, the last segment is always kept.
+//! Cursors that are no longer used need to be removed, or they keep their segments forever.
+//!
+//! With `fsync` every appended entry and every cursor update is synced to disk before it is confirmed.
+//!
+//! Logs written by the `qwal` based versions of the `wal` connector are migrated when opening them:
+//! all entries not yet acked are appended to a new log, which every cursor starts reading at its beginning.
+
+use crate::errors::Result;
+use async_std::fs::{self, File, OpenOptions};
+use async_std::io::prelude::{ReadExt, SeekExt, WriteExt};
+use async_std::io::SeekFrom;
+use async_std::path::{Path, PathBuf};
+use async_std::stream::StreamExt;
+use simd_json_derive::{Deserialize as _, Serialize as _};
+use std::collections::{BTreeMap, VecDeque};
+use tremor_pipeline::Event;
+
+/// length (`u32`) and ingest timestamp (`u64`) of an entry
+const HEADER_LEN: u64 = 12;
+
+const SEGMENTS: &str = "segments";
+const CURSORS: &str = "cursors";
+/// files of a log written by `qwal` are moved here for the migration
+const LEGACY: &str = "qwal";
+/// the migrated log is built here and moved in place once complete
+const MIGRATING: &str = "migrating";
+
+/// Where to start reading
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Seek {
+    /// the oldest entry still in the log
+    Earliest,
+    /// the next entry written to the log
+    Latest,
+    /// the entry at the given offset
+    Offset(u64),
+    /// the first entry ingested at or after the given timestamp in nanoseconds
+    Time(u64),
+}
+
+struct EntryIndex {
+    /// position of the entry within the segment file
+    pos: u64,
+    ingest_ns: u64,
+}
+
+struct Segment {
+    /// offset of the first entry
+    first: u64,
+    path: PathBuf,
+    size: u64,
+    entries: Vec<EntryIndex>,
+}
+
+impl Segment {
+    /// offset after the last entry
+    fn end(&self) -> u64 {
+        self.first + self.entries.len() as u64
+    }
+
+    async fn load(path: PathBuf, first: u64) -> Result<Self> {
+        let data = fs::read(&path).await?;
+        let len = data.len() as u64;
+        let mut entries = Vec::new();
+        let mut pos = 0_u64;
+        while pos + HEADER_LEN <= len {
+            let header = usize::try_from(pos)?;
+            let mut entry_len = [0_u8; 4];
+            entry_len.copy_from_slice(&data[header..header + 4]);
+            let mut ingest_ns = [0_u8; 8];
+            ingest_ns.copy_from_slice(&data[header + 4..header + 12]);
+            let entry_len = u64::from(u32::from_le_bytes(entry_len));
+            if pos + HEADER_LEN + entry_len > len {
+                break;
+            }
+            entries.push(EntryIndex {
+                pos,
+                ingest_ns: u64::from_le_bytes(ingest_ns),
+            });
+            pos += HEADER_LEN + entry_len;
+        }
+        if pos < len {
+            warn!(
+                "[WAL] Truncating partially written entry at {pos} in {}",
+                path.display()
+            );
+            OpenOptions::new()
+                .write(true)
+                .open(&path)
+                .await?
+                .set_len(pos)
+                .await?;
+        }
+        Ok(Self {
+            first,
+            path,
+            size: pos,
+            entries,
+        })
+    }
+}
+
+/// The log
+pub(crate) struct Log {
+    dir: PathBuf,
+    chunk_size: u64,
+    max_chunks: usize,
+    segments: VecDeque<Segment>,
+    /// file of the last segment, opened for appending
+    writer: Option<File>,
+    /// last read segment, identified by its first offset
+    reader: Option<(u64, File)>,
+    cursors: BTreeMap<String, u64>,
+    fsync: bool,
+}
+
+/// Entries of a log written by `qwal`
+struct LegacyEntry(Event);
+
+impl qwal::Entry for LegacyEntry {
+    type Output = Event;
+    type Error = simd_json::Error;
+
+    fn serialize(self) -> std::result::Result<Vec<u8>, Self::Error> {
+        Ok(self.0.json_vec()?)
+    }
+
+    fn deserialize(mut data: Vec<u8>) -> std::result::Result<Self::Output, Self::Error> {
+        Event::from_slice(&mut data)
+    }
+}
+
+/// Migrate a log written by `qwal` in `dir`, if there is one
+async fn migrate_legacy(dir: &Path, chunk_size: u64, max_chunks: usize) -> Result<()> {
+    let legacy_dir = dir.join(LEGACY);
+    let migrating_dir = dir.join(MIGRATING);
+    if dir.join(SEGMENTS).exists().await {
+        // a migration finished before cleaning up
+        if legacy_dir.exists().await {
+            fs::remove_dir_all(&legacy_dir).await?;
+        }
+        if migrating_dir.exists().await {
+            fs::remove_dir_all(&migrating_dir).await?;
+        }
+        return Ok(());
+    }
+    // all files in `dir` belong to `qwal`, our own files live in sub directories
+    let mut legacy_files = Vec::new();
+    let mut entries = fs::read_dir(dir).await?;
+    while let Some(entry) = entries.next().await {
+        let entry = entry?;
+        if entry.file_type().await?.is_file() {
+            legacy_files.push(entry.path());
+        }
+    }
+    if legacy_files.is_empty() && !legacy_dir.exists().await {
+        return Ok(());
+    }
+    info!(
+        "[WAL] Migrating log written by an earlier version in {}",
+        dir.display()
+    );
+    fs::create_dir_all(&legacy_dir).await?;
+    for path in legacy_files {
+        if let Some(name) = path.file_name() {
+            fs::rename(&path, legacy_dir.join(name)).await?;
+        }
+    }
+    if migrating_dir.exists().await {
+        fs::remove_dir_all(&migrating_dir).await?;
+    }
+    fs::create_dir_all(&migrating_dir).await?;
+    let mut legacy = qwal::Wal::open(&legacy_dir, chunk_size, max_chunks).await?;
+    let mut log = Log::load(&migrating_dir, chunk_size, usize::MAX, true).await?;
+    let mut migrated = 0_u64;
+    while let Some((_, event)) = legacy.pop::<LegacyEntry>().await? {
+        log.append(event.ingest_ns, &event.json_vec()?).await?;
+        migrated += 1;
+    }
+    drop(log);
+    drop(legacy);
+    fs::rename(migrating_dir.join(SEGMENTS), dir.join(SEGMENTS)).await?;
+    fs::remove_dir_all(&migrating_dir).await?;
+    fs::remove_dir_all(&legacy_dir).await?;
+    info!("[WAL] Migrated {migrated} entries in {}", dir.display());
+    Ok(())
+}
+
+impl Log {
+    /// Open the log in `dir`, creating it if it doesn't exist
+    ///
+    /// With `fsync` appended entries and cursor updates are synced to disk.
+    pub(crate) async fn open(
+        dir: &Path,
+        chunk_size: u64,
+        max_chunks: usize,
+        fsync: bool,
+    ) -> Result<Self> {
+        fs::create_dir_all(dir).await?;
+        migrate_legacy(dir, chunk_size, max_chunks).await?;
+        Self::load(dir, chunk_size, max_chunks, fsync).await
+    }
+
+    async fn load(dir: &Path, chunk_size: u64, max_chunks: usize, fsync: bool) -> Result<Self> {
+        let segments_dir = dir.join(SEGMENTS);
+        let cursors_dir = dir.join(CURSORS);
+        fs::create_dir_all(&segments_dir).await?;
+        fs::create_dir_all(&cursors_dir).await?;
+
+        let mut files = Vec::new();
+        let mut entries = fs::read_dir(&segments_dir).await?;
+        while let Some(entry) = entries.next().await {
+            let path = entry?.path();
+            let first = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(|stem| stem.parse::<u64>().ok());
+            if let Some(first) = first {
+                files.push((first, path));
+            }
+        }
+        files.sort_by_key(|(first, _)| *first);
+        let mut segments: VecDeque<Segment> = VecDeque::with_capacity(files.len());
+        for (first, path) in files {
+            let segment = Segment::load(path, first).await?;
+            if let Some(last) = segments.back() {
+                if last.end() != segment.first {
+                    return Err(format!(
+                        "Invalid WAL: missing entries {}-{} in {}",
+                        last.end(),
+                        segment.first,
+                        dir.display()
+                    )
+                    .into());
+                }
+            }
+            segments.push_back(segment);
+        }
+
+        let mut cursors = BTreeMap::new();
+        let mut entries = fs::read_dir(&cursors_dir).await?;
+        while let Some(entry) = entries.next().await {
+            let path = entry?.path();
+            if path.extension().is_some() {
+                // leftover temporary file
+                continue;
+            }
+            if let Some(name) = path.file_name().and_then(|name| name.to_str()) {
+                let offset = fs::read_to_string(&path).await?.trim().parse::<u64>()?;
+                cursors.insert(name.to_string(), offset);
+            }
+        }
+
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            chunk_size,
+            max_chunks,
+            segments,
+            writer: None,
+            reader: None,
+            cursors,
+            fsync,
+        })
+    }
+
+    /// offset of the oldest entry in the log
+    pub(crate) fn first_offset(&self) -> u64 {
+        self.segments.front().map_or(0, |segment| segment.first)
+    }
+
+    /// offset of the next entry appended to the log
+    pub(crate) fn next_offset(&self) -> u64 {
+        self.segments.back().map_or(0, Segment::end)
+    }
+
+    /// size of all segments in bytes
+    pub(crate) fn size(&self) -> u64 {
+        self.segments.iter().map(|segment| segment.size).sum()
+    }
+
+    /// Append an entry, returning its offset
+    pub(crate) async fn append(&mut self, ingest_ns: u64, data: &[u8]) -> Result<u64> {
+        let roll = self
+            .segments
+            .back()
+            .map_or(true, |segment| segment.size >= self.chunk_size);
+        if roll {
+            if self.segments.len() >= self.max_chunks {
+                self.gc().await?;
+                if self.segments.len() >= self.max_chunks {
+                    let slowest = self
+                        .cursors
+                        .iter()
+                        .min_by_key(|(_, offset)| **offset)
+                        .map_or_else(String::new, |(name, _)| {
+                            format!(", cursor `{name}` is the furthest behind")
+                        });
+                    return Err(format!(
+                        "WAL {} is full, all {} chunks are in use{slowest}",
+                        self.dir.display(),
+                        self.max_chunks
+                    )
+                    .into());
+                }
+            }
+            let first = self.next_offset();
+            let path = self.dir.join(SEGMENTS).join(format!("{first:020}.log"));
+            let file = OpenOptions::new()
+                .create_new(true)
+                .append(true)
+                .open(&path)
+                .await?;
+            self.segments.push_back(Segment {
+                first,
+                path,
+                size: 0,
+                entries: Vec::new(),
+            });
+            self.writer = Some(file);
+        }
+        let segment = self
+            .segments
+            .back_mut()
+            .ok_or("WAL has no segment to append to")?;
+        if self.writer.is_none() {
+            self.writer = Some(OpenOptions::new().append(true).open(&segment.path).await?);
+        }
+        let writer = self.writer.as_mut().ok_or("WAL has no writer")?;
+
+        let mut buf = Vec::with_capacity(data.len() + 12);
+        buf.extend_from_slice(&u32::try_from(data.len())?.to_le_bytes());
+        buf.extend_from_slice(&ingest_ns.to_le_bytes());
+        buf.extend_from_slice(data);
+        writer.write_all(&buf).await?;
+        writer.flush().await?;
+        if self.fsync {
+            writer.sync_data().await?;
+        }
+
+        segment.entries.push(EntryIndex {
+            pos: segment.size,
+            ingest_ns,
+        });
+        segment.size += buf.len() as u64;
+        Ok(segment.end() - 1)
+    }
+
+    /// Read the entry at `offset`, `None` if it hasn't been written yet
+    pub(crate) async fn read(&mut self, offset: u64) -> Result<Option<Vec<u8>>> {
+        if offset < self.first_offset() {
+            return Err(format!(
+                "WAL entry {offset} has already been deleted, the oldest entry is {}",
+                self.first_offset()
+            )
+            .into());
+        }
+        let idx = self
+            .segments
+            .partition_point(|segment| segment.end() <= offset);
+        let (first, pos, path) = if let Some(segment) = self.segments.get(idx) {
+            let entry = usize::try_from(offset - segment.first)?;
+            (
+                segment.first,
+                segment.entries[entry].pos,
+                segment.path.clone(),
+            )
+        } else {
+            return Ok(None);
+        };
+        if self.reader.as_ref().map_or(true, |(f, _)| *f != first) {
+            self.reader = Some((first, File::open(&path).await?));
+        }
+        let file = &mut self.reader.as_mut().ok_or("WAL has no reader")?.1;
+        file.seek(SeekFrom::Start(pos)).await?;
+        let mut header = [0_u8; 12];
+        file.read_exact(&mut header).await?;
+        let mut len = [0_u8; 4];
+        len.copy_from_slice(&header[..4]);
+        let mut data = vec![0_u8; u32::from_le_bytes(len) as usize];
+        file.read_exact(&mut data).await?;
+        Ok(Some(data))
+    }
+
+    /// Resolve `seek` to an offset within the log
+    pub(crate) fn seek(&self, seek: Seek) -> u64 {
+        match seek {
+            Seek::Earliest => self.first_offset(),
+            Seek::Latest => self.next_offset(),
+            Seek::Offset(offset) => offset.clamp(self.first_offset(), self.next_offset()),
+            Seek::Time(ingest_ns) => self
+                .segments
+                .iter()
+                .find_map(|segment| {
+                    segment
+                        .entries
+                        .iter()
+                        .position(|entry| entry.ingest_ns >= ingest_ns)
+                        .map(|idx| segment.first + idx as u64)
+                })
+                .unwrap_or_else(|| self.next_offset()),
+        }
+    }
+
+    /// offset of the next entry to read for the cursor `name`
+    pub(crate) fn cursor(&self, name: &str) -> Option<u64> {
+        self.cursors.get(name).copied()
+    }
+
+    /// all cursors and the offset of the next entry they read
+    pub(crate) fn cursors(&self) -> impl Iterator<Item = (&String, &u64)> {
+        self.cursors.iter()
+    }
+
+    /// Persist the offset of the next entry to read for the cursor `name`
+    pub(crate) async fn commit(&mut self, name: &str, offset: u64) -> Result<()> {
+        if self.cursors.get(name) == Some(&offset) {
+            return Ok(());
+        }
+        let path = self.dir.join(CURSORS).join(name);
+        let tmp = path.with_extension("tmp");
+        let mut file = File::create(&tmp).await?;
+        file.write_all(offset.to_string().as_bytes()).await?;
+        file.flush().await?;
+        if self.fsync {
+            file.sync_all().await?;
+        }
+        drop(file);
+        fs::rename(&tmp, &path).await?;
+        self.cursors.insert(name.to_string(), offset);
+        self.gc().await
+    }
+
+    /// Remove the cursor `name`, so it no longer keeps segments from being deleted
+    ///
+    /// Returns `true` if the cursor existed.
+    pub(crate) async fn remove_cursor(&mut self, name: &str) -> Result<bool> {
+        if self.cursors.remove(name).is_none() {
+            return Ok(false);
+        }
+        fs::remove_file(self.dir.join(CURSORS).join(name)).await?;
+        self.gc().await?;
+        Ok(true)
+    }
+
+    /// Delete segments all cursors are past
+    async fn gc(&mut self) -> Result<()> {
+        let min = self.cursors.values().min().copied().unwrap_or_default();
+        while self.segments.len() > 1
+            && self
+                .segments
+                .front()
+                .map_or(false, |segment| segment.end() <= min)
+        {
+            if let Some(segment) = self.segments.pop_front() {
+                if self
+                    .reader
+                    .as_ref()
+                    .map_or(false, |(first, _)| *first == segment.first)
+                {
+                    self.reader = None;
+                }
+                debug!("[WAL] Deleting segment {}", segment.path.display());
+                fs::remove_file(&segment.path).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[async_std::test]
+    async fn append_read_seek() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = Path::new(dir.path());
+        let mut log = Log::open(path, 32, 10, false).await?;
+        for i in 0..10_u64 {
+            assert_eq!(
+                i,
+                log.append(i * 10, format!("entry {i}").as_bytes()).await?
+            );
+        }
+        assert_eq!(0, log.first_offset());
+        assert_eq!(10, log.next_offset());
+        assert_eq!(Some(b"entry 3".to_vec()), log.read(3).await?);
+        assert_eq!(Some(b"entry 0".to_vec()), log.read(0).await?);
+        assert_eq!(None, log.read(10).await?);
+
+        assert_eq!(0, log.seek(Seek::Earliest));
+        assert_eq!(10, log.seek(Seek::Latest));
+        assert_eq!(5, log.seek(Seek::Offset(5)));
+        assert_eq!(10, log.seek(Seek::Offset(42)));
+        assert_eq!(4, log.seek(Seek::Time(35)));
+        assert_eq!(10, log.seek(Seek::Time(1000)));
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn cursors_and_gc() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = Path::new(dir.path());
+        let mut log = Log::open(path, 16, 3, true).await?;
+        // every entry fills a segment
+        for i in 0..3_u64 {
+            log.append(i, b"0123456789").await?;
+        }
+        // full, nothing consumed yet
+        assert!(log.append(3, b"0123456789").await.is_err());
+
+        log.commit("b", 1).await?;
+        log.commit("a", 2).await?;
+        // `b` still needs the second segment
+        assert_eq!(1, log.first_offset());
+        assert_eq!(3, log.append(3, b"0123456789").await?);
+        assert!(log.read(0).await.is_err());
+        drop(log);
+
+        // positions and segments survive a restart
+        let mut log = Log::open(path, 16, 3, true).await?;
+        assert_eq!(Some(2), log.cursor("a"));
+        assert_eq!(Some(1), log.cursor("b"));
+        assert_eq!(None, log.cursor("c"));
+        assert_eq!(1, log.first_offset());
+        assert_eq!(4, log.next_offset());
+        assert_eq!(Some(b"0123456789".to_vec()), log.read(3).await?);
+
+        // `b` is abandoned, it no longer keeps the second segment
+        log.commit("a", 4).await?;
+        assert_eq!(1, log.first_offset());
+        assert!(log.remove_cursor("b").await?);
+        assert!(!log.remove_cursor("b").await?);
+        assert_eq!(3, log.first_offset());
+        drop(log);
+        let log = Log::open(path, 16, 3, true).await?;
+        assert_eq!(None, log.cursor("b"));
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn migrate_legacy() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = Path::new(dir.path());
+        let mut legacy = qwal::Wal::open(path, 1024, 10).await?;
+        for (ingest_ns, data) in [(1_u64, "snot"), (2, "badger"), (3, "grmpf")] {
+            let event = Event {
+                ingest_ns,
+                data: (
+                    tremor_value::Value::from(data),
+                    tremor_value::Value::object(),
+                )
+                    .into(),
+                ..Event::default()
+            };
+            legacy.push(LegacyEntry(event)).await?;
+        }
+        // the first entry was acked and is not migrated
+        let (id, _) = legacy
+            .pop::<LegacyEntry>()
+            .await?
+            .ok_or("no legacy entry")?;
+        legacy.ack(id).await?;
+        legacy.preserve_ack().await?;
+        drop(legacy);
+
+        let mut log = Log::open(path, 1024, 10, false).await?;
+        assert_eq!(0, log.first_offset());
+        assert_eq!(2, log.next_offset());
+        assert_eq!(1, log.seek(Seek::Time(3)));
+        let mut data = log.read(0).await?.ok_or("no migrated entry")?;
+        let event = Event::from_slice(&mut data)?;
+        assert_eq!(2, event.ingest_ns);
+        assert_eq!(
+            &tremor_value::Value::from("badger"),
+            event.data.suffix().value()
+        );
+        assert!(!path.join(LEGACY).exists().await);
+        assert!(!path.join(MIGRATING).exists().await);
+        drop(log);
+
+        // opening it again doesn't migrate anything
+        let log = Log::open(path, 1024, 10, false).await?;
+        assert_eq!(2, log.next_offset());
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn truncated_entry() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = Path::new(dir.path());
+        let mut log = Log::open(path, 1024, 10, false).await?;
+        log.append(1, b"snot").await?;
+        log.append(2, b"badger").await?;
+        drop(log);
+        // simulate a crash while writing the second entry
+        let segment = path.join(SEGMENTS).join(format!("{:020}.log", 0));
+        let len = fs::metadata(&segment).await?.len();
+        OpenOptions::new()
+            .write(true)
+            .open(&segment)
+            .await?
+            .set_len(len - 3)
+            .await?;
+
+        let mut log = Log::open(path, 1024, 10, false).await?;
+        assert_eq!(1, log.next_offset());
+        assert_eq!(Some(b"snot".to_vec()), log.read(0).await?);
+        assert_eq!(1, log.append(3, b"badger").await?);
+        assert_eq!(Some(b"badger".to_vec()), log.read(1).await?);
+        Ok(())
+    }
+}
@@ -21,7 +21,7 @@ use crate::{
         prelude::*,
         utils::{
             socket::{tcp_client_socket, TcpSocketOptions},
-            tls::{tls_client_connector, TLSClientConfig},
+            tls::{Reloadable, TLSClientConfig},
         },
     },
     errors::err_connector_def,
@@ -31,6 +31,7 @@ use async_tls::TlsConnector;
 use async_tungstenite::client_async;
 use either::Either;
 use futures::StreamExt;
+use rustls::ClientConfig;
 use std::net::SocketAddr;
 
 const URL_SCHEME: &str = "tremor-ws-client";
@@ -78,13 +79,12 @@ impl ConnectorBuilder for Builder {
             return Err(err_connector_def(id, Self::MISSING_PORT));
         };
 
-        let (tls_connector, tls_domain) = match config.tls.as_ref() {
-            Some(Either::Right(true)) => (
-                Some(tls_client_connector(&TLSClientConfig::default()).await?),
-                host,
-            ),
+        let (tls_client_config, tls_domain) = match config.tls.as_ref() {
+            Some(Either::Right(true)) => {
+                (Some(Reloadable::client(&TLSClientConfig::default())?), host)
+            }
             Some(Either::Left(tls_config)) => (
-                Some(tls_client_connector(tls_config).await?),
+                Some(Reloadable::client(tls_config)?),
                 tls_config.domain.clone().unwrap_or(host),
             ),
             Some(Either::Right(false)) | None => (None, host),
@@ -92,7 +92,7 @@ impl ConnectorBuilder for Builder {
 
         Ok(Box::new(WsClient {
             config,
-            tls_connector,
+            tls_client_config,
             tls_domain,
             source_runtime: None,
             sink_runtime: None,
@@ -102,7 +102,7 @@ impl ConnectorBuilder for Builder {
 
 pub(crate) struct WsClient {
     config: Config,
-    tls_connector: Option<TlsConnector>,
+    tls_client_config: Option<Reloadable<ClientConfig>>,
     tls_domain: String,
     source_runtime: Option<ChannelSourceRuntime>,
     sink_runtime: Option<SingleStreamSinkRuntime>,
@@ -130,10 +130,17 @@ impl Connector for WsClient {
         source_context: SourceContext,
         builder: SourceManagerBuilder,
     ) -> Result<Option<SourceAddr>> {
+        let tls_client_config = self.tls_client_config.clone();
         let source = ChannelSource::new(
             builder.qsize(),
             Arc::default(), // we don't need to know if the source is connected. Worst case if nothing is connected is that the receiving task is blocked.
-        );
+        )
+        .with_metrics(move |timestamp, alias| {
+            tls_client_config
+                .iter()
+                .map(|tls| tls.metrics(timestamp, alias))
+                .collect()
+        });
         self.source_runtime = Some(source.runtime());
         let addr = builder.spawn(source, source_context)?;
         Ok(Some(addr))
@@ -163,8 +170,9 @@ impl Connector for WsClient {
         let tcp_stream = tcp_client_socket(&self.config.url, &self.config.socket_options).await?;
         let (local_addr, peer_addr) = (tcp_stream.local_addr()?, tcp_stream.peer_addr()?);
 
-        if let Some(tls_connector) = self.tls_connector.as_ref() {
-            // TLS
+        if let Some(tls_client_config) = self.tls_client_config.as_ref() {
+            // TLS, every connection picks up reloaded certificates
+            let tls_connector = TlsConnector::from(tls_client_config.current());
             // wrap it into arcmutex, because we need to clone it in order to close it properly
             let tls_stream = tls_connector.connect(&self.tls_domain, tcp_stream).await?;
             let (ws_stream, _http_response) =
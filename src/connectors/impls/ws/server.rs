@@ -17,15 +17,18 @@ use crate::connectors::{
     prelude::*,
     utils::{
         socket::{tcp_server_socket, TcpSocketOptions},
-        tls::{load_server_config, TLSServerConfig},
+        tls::{peer_identity, Reloadable, TLSServerConfig},
         ConnectionMeta,
     },
 };
-use async_std::{prelude::FutureExt, task::JoinHandle};
-use async_tls::TlsAcceptor;
+use async_rustls::TlsAcceptor;
+use async_std::{
+    prelude::FutureExt,
+    task::{self, JoinHandle},
+};
 use async_tungstenite::accept_async;
 use futures::StreamExt;
-use rustls::ServerConfig;
+use rustls::{ServerConfig, Session};
 use simd_json::ValueAccess;
 use std::{
     net::SocketAddr,
@@ -55,7 +58,7 @@ pub(crate) struct WsServer {
     accept_task: Option<JoinHandle<()>>,
     sink_runtime: Option<ChannelSinkRuntime<ConnectionMeta>>,
     source_runtime: Option<ChannelSourceRuntime>,
-    tls_server_config: Option<ServerConfig>,
+    tls_server_config: Option<Reloadable<ServerConfig>>,
     /// marker that the sink is actually connected to some pipeline
     sink_is_connected: Arc<AtomicBool>,
 }
@@ -78,7 +81,7 @@ impl ConnectorBuilder for Builder {
         let config = Config::new(raw_config)?;
 
         let tls_server_config = if let Some(tls_config) = config.tls.as_ref() {
-            Some(load_server_config(tls_config)?)
+            Some(Reloadable::server(tls_config)?)
         } else {
             None
         };
@@ -107,16 +110,21 @@ fn resolve_connection_meta(meta: &Value) -> Option<ConnectionMeta> {
 }
 
 impl WsServer {
-    fn meta(peer: SocketAddr, has_tls: bool) -> Value<'static> {
+    /// `identity` is the identity of the peer certificate of TLS connections, if any
+    fn meta(peer: SocketAddr, has_tls: bool, identity: Option<Value<'static>>) -> Value<'static> {
         let peer_ip = peer.ip().to_string();
         let peer_port = peer.port();
 
+        let mut peer = literal!({
+            "host": peer_ip,
+            "port": peer_port
+        });
+        if let Some(identity) = identity {
+            peer.try_insert("tls", identity);
+        }
         literal!({
             "tls": has_tls,
-            "peer": {
-                "host": peer_ip,
-                "port": peer_port
-            }
+            "peer": peer
         })
     }
 }
@@ -136,10 +144,17 @@ impl Connector for WsServer {
         ctx: SourceContext,
         builder: SourceManagerBuilder,
     ) -> Result<Option<SourceAddr>> {
+        let tls_server_config = self.tls_server_config.clone();
         let source = ChannelSource::new(
             builder.qsize(),
             Arc::default(), // we don't need to know if the source is connected. Worst case if nothing is connected is that the receiving task is blocked.
-        );
+        )
+        .with_metrics(move |timestamp, alias| {
+            tls_server_config
+                .iter()
+                .map(|tls| tls.metrics(timestamp, alias))
+                .collect()
+        });
         self.source_runtime = Some(source.runtime());
         let addr = builder.spawn(source, ctx)?;
 
@@ -219,82 +234,130 @@ impl Connector for WsServer {
                             path: path.clone(), // captures server port
                         };
 
+                        // new connections pick up reloaded certificates
                         let tls_acceptor: Option<TlsAcceptor> = tls_server_config
-                            .clone()
-                            .map(|sc| TlsAcceptor::from(Arc::new(sc)));
-                        if let Some(acceptor) = tls_acceptor {
-                            let meta = ctx.meta(WsServer::meta(peer_addr, true));
-                            // TODO: this should live in its own task, as it requires rome roundtrips :()
-                            let tls_stream = acceptor.accept(tcp_stream).await?;
-                            let ws_stream = accept_async(tls_stream).await?;
-                            debug!("{ctx} new connection from {peer_addr}");
-
-                            let (ws_write, ws_read) = ws_stream.split();
-
-                            let reader_runtime = if sink_is_connected.load(Ordering::Acquire) {
-                                let ws_writer = WsWriter::new_tls_server(ws_write);
-                                sink_runtime
-                                    .register_stream_writer(
-                                        stream_id,
-                                        Some(connection_meta.clone()),
-                                        &ctx,
-                                        ws_writer,
-                                    )
-                                    .await;
-                                Some(sink_runtime.clone())
+                            .as_ref()
+                            .map(|sc| TlsAcceptor::from(sc.current()));
+                        let ctx = ctx.clone();
+                        let source_runtime = source_runtime.clone();
+                        let sink_runtime = sink_runtime.clone();
+                        let sink_is_connected = sink_is_connected.clone();
+                        // handshakes in a separate task, so a stalled client doesn't keep us from accepting others
+                        task::spawn(async move {
+                            if let Some(acceptor) = tls_acceptor {
+                                let tls_stream = match acceptor
+                                    .accept(tcp_stream)
+                                    .timeout(HANDSHAKE_TIMEOUT)
+                                    .await
+                                {
+                                    Ok(Ok(s)) => s,
+                                    Ok(Err(e)) => {
+                                        warn!("{ctx} TLS handshake with {peer_addr} failed: {e}");
+                                        return;
+                                    }
+                                    Err(_) => {
+                                        warn!("{ctx} TLS handshake with {peer_addr} timed out");
+                                        return;
+                                    }
+                                };
+                                let identity = tls_stream
+                                    .get_ref()
+                                    .1
+                                    .get_peer_certificates()
+                                    .and_then(|certs| peer_identity(&certs));
+                                let meta = ctx.meta(WsServer::meta(peer_addr, true, identity));
+                                let ws_stream =
+                                    match accept_async(tls_stream).timeout(HANDSHAKE_TIMEOUT).await
+                                    {
+                                        Ok(Ok(s)) => s,
+                                        Ok(Err(e)) => {
+                                            error!("{ctx} Websocket connection error: {e}");
+                                            return;
+                                        }
+                                        Err(_) => {
+                                            warn!("{ctx} WS handshake with {peer_addr} timed out");
+                                            return;
+                                        }
+                                    };
+                                debug!("{ctx} new connection from {peer_addr}");
+
+                                let (ws_write, ws_read) = ws_stream.split();
+
+                                let reader_runtime = if sink_is_connected.load(Ordering::Acquire) {
+                                    let ws_writer = WsWriter::new_tls_server(ws_write);
+                                    sink_runtime
+                                        .register_stream_writer(
+                                            stream_id,
+                                            Some(connection_meta.clone()),
+                                            &ctx,
+                                            ws_writer,
+                                        )
+                                        .await;
+                                    Some(sink_runtime.clone())
+                                } else {
+                                    None
+                                };
+
+                                let ws_reader = WsReader::new(
+                                    ws_read,
+                                    reader_runtime,
+                                    origin_uri.clone(),
+                                    meta,
+                                    ctx.clone(),
+                                );
+                                source_runtime.register_stream_reader(stream_id, &ctx, ws_reader);
                             } else {
-                                None
-                            };
-
-                            let ws_reader = WsReader::new(
-                                ws_read,
-                                reader_runtime,
-                                origin_uri.clone(),
-                                meta,
-                                ctx.clone(),
-                            );
-                            source_runtime.register_stream_reader(stream_id, &ctx, ws_reader);
-                        } else {
-                            let ws_stream = match accept_async(tcp_stream).await {
-                                Ok(s) => s,
-                                Err(e) => {
-                                    error!("{ctx} Websocket connection error: {e}");
-                                    continue;
-                                }
-                            };
-                            debug!("{ctx} new connection from {peer_addr}",);
-
-                            let (ws_write, ws_read) = ws_stream.split();
-
-                            let meta = ctx.meta(WsServer::meta(peer_addr, false));
-
-                            let reader_runtime = if sink_is_connected.load(Ordering::Acquire) {
-                                let ws_writer = WsWriter::new(ws_write);
-
-                                sink_runtime
-                                    .register_stream_writer(
-                                        stream_id,
-                                        Some(connection_meta.clone()),
-                                        &ctx,
-                                        ws_writer,
-                                    )
-                                    .await;
-                                Some(sink_runtime.clone())
-                            } else {
-                                None
-                            };
-
-                            let ws_reader = WsReader::new(
-                                ws_read,
-                                reader_runtime,
-                                origin_uri.clone(),
-                                meta,
-                                ctx.clone(),
-                            );
-                            source_runtime.register_stream_reader(stream_id, &ctx, ws_reader);
-                        }
+                                let ws_stream =
+                                    match accept_async(tcp_stream).timeout(HANDSHAKE_TIMEOUT).await
+                                    {
+                                        Ok(Ok(s)) => s,
+                                        Ok(Err(e)) => {
+                                            error!("{ctx} Websocket connection error: {e}");
+                                            return;
+                                        }
+                                        Err(_) => {
+                                            warn!("{ctx} WS handshake with {peer_addr} timed out");
+                                            return;
+                                        }
+                                    };
+                                debug!("{ctx} new connection from {peer_addr}",);
+
+                                let (ws_write, ws_read) = ws_stream.split();
+
+                                let meta = ctx.meta(WsServer::meta(peer_addr, false, None));
+
+                                let reader_runtime = if sink_is_connected.load(Ordering::Acquire) {
+                                    let ws_writer = WsWriter::new(ws_write);
+
+                                    sink_runtime
+                                        .register_stream_writer(
+                                            stream_id,
+                                            Some(connection_meta.clone()),
+                                            &ctx,
+                                            ws_writer,
+                                        )
+                                        .await;
+                                    Some(sink_runtime.clone())
+                                } else {
+                                    None
+                                };
+
+                                let ws_reader = WsReader::new(
+                                    ws_read,
+                                    reader_runtime,
+                                    origin_uri.clone(),
+                                    meta,
+                                    ctx.clone(),
+                                );
+                                source_runtime.register_stream_reader(stream_id, &ctx, ws_reader);
+                            }
+                        });
+                    }
+                    Ok(Err(e)) => {
+                        // e.g. running out of file descriptors, keep serving the existing connections
+                        error!("{ctx} Error Accepting: {e}");
+                        task::sleep(ACCEPT_ERROR_BACKOFF).await;
                     }
-                    Ok(Err(e)) => return Err(e.into()),
                     Err(_) => continue,
                 };
             }
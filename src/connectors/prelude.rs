@@ -28,7 +28,8 @@ pub(crate) use crate::connectors::utils::{
 };
 pub(crate) use crate::connectors::{
     metrics::make_metrics_payload, spawn_task, Alias, CodecReq, Connector, ConnectorBuilder,
-    ConnectorContext, ConnectorType, Context, StreamDone, StreamIdGen, ACCEPT_TIMEOUT,
+    ConnectorContext, ConnectorType, Context, StreamDone, StreamIdGen, ACCEPT_ERROR_BACKOFF,
+    ACCEPT_TIMEOUT, HANDSHAKE_TIMEOUT,
 };
 pub(crate) use crate::errors::{err_connector_def, Error, Kind as ErrorKind, Result};
 pub(crate) use crate::system::KillSwitch;
@@ -31,8 +31,11 @@ use crate::connectors::utils::reconnect::{Attempt, ConnectionLostNotifier};
 use crate::connectors::{Alias, ConnectorType, Context, Msg, QuiescenceBeacon, StreamDone};
 use crate::errors::Result;
 use crate::pipeline;
-use crate::postprocessor::{finish, make_postprocessors, postprocess, Postprocessors};
+use crate::postprocessor::{
+    finish, make_postprocessors, postprocess, Postprocessor, Postprocessors,
+};
 use crate::primerge::PriorityMerge;
+use crate::tap::{Tap, Taps};
 use async_std::channel::{bounded, unbounded, Receiver, Sender};
 use async_std::stream::StreamExt; // for .next() on PriorityMerge
 use async_std::task;
@@ -42,11 +45,15 @@ pub(crate) use single_stream_sink::{SingleStreamSink, SingleStreamSinkRuntime};
 use std::borrow::Borrow;
 use std::collections::{btree_map::Entry, BTreeMap, HashSet};
 use std::fmt::Display;
+use std::sync::Arc;
 use tremor_common::ids::{SinkId, SourceId};
 use tremor_common::time::nanotime;
-use tremor_pipeline::{CbAction, Event, EventId, OpMeta, SignalKind, DEFAULT_STREAM_ID};
+use tremor_pipeline::{
+    trace::Tracer, CbAction, Event, EventId, OpMeta, SignalKind, DEFAULT_STREAM_ID,
+};
 use tremor_script::{ast::DeployEndpoint, EventPayload};
-use tremor_value::Value;
+use tremor_value::{literal, Value};
+use value_trait::Mutable;
 
 /// Result for a sink function that may provide insights or response.
 ///
@@ -201,6 +208,14 @@ pub(crate) trait Sink: Send {
     async fn on_resume(&mut self, _ctx: &SinkContext) -> Result<()> {
         Ok(())
     }
+    /// called right before `on_stop`, to write out data still buffered in the serializer
+    async fn on_flush(
+        &mut self,
+        _ctx: &SinkContext,
+        _serializer: &mut EventSerializer,
+    ) -> Result<()> {
+        Ok(())
+    }
     /// called when stopped
     async fn on_stop(&mut self, _ctx: &SinkContext) -> Result<()> {
         Ok(())
@@ -324,6 +339,8 @@ pub(crate) enum SinkMsg {
     Stop(Sender<Result<()>>),
     /// drain this sink and notify the connector via the provided sender
     Drain(Sender<Msg>),
+    /// tap the input port
+    Tap(Tap),
 }
 
 /// Wrapper around all possible sink messages
@@ -348,6 +365,7 @@ pub(crate) struct SinkManagerBuilder {
     serializer: EventSerializer,
     reply_channel: (Sender<AsyncSinkReply>, Receiver<AsyncSinkReply>),
     metrics_reporter: SinkReporter,
+    tracer: Arc<Tracer>,
 }
 
 impl SinkManagerBuilder {
@@ -391,6 +409,7 @@ pub(crate) fn builder(
     alias: &Alias,
     qsize: usize,
     metrics_reporter: SinkReporter,
+    tracer: Arc<Tracer>,
 ) -> Result<SinkManagerBuilder> {
     // resolve codec and processors
     let postprocessor_configs = config.postprocessors.clone().unwrap_or_default();
@@ -409,6 +428,7 @@ pub(crate) fn builder(
         serializer,
         reply_channel,
         metrics_reporter,
+        tracer,
     })
 }
 
@@ -512,17 +532,18 @@ impl EventSerializer {
     ) -> Result<Vec<Vec<u8>>> {
         if stream_id == DEFAULT_STREAM_ID {
             // no codec_overwrite for the default stream
-            postprocess(
+            encode(
+                self.codec.as_ref(),
                 &mut self.postprocessors,
+                value,
                 ingest_ns,
-                self.codec.encode(value)?,
                 &self.alias,
             )
         } else {
             match self.streams.entry(stream_id) {
                 Entry::Occupied(mut entry) => {
                     let (codec, pps) = entry.get_mut();
-                    postprocess(pps, ingest_ns, codec.encode(value)?, &self.alias)
+                    encode(codec.as_ref(), pps, value, ingest_ns, &self.alias)
                 }
                 Entry::Vacant(entry) => {
                     // codec overwrite only considered for new streams
@@ -533,22 +554,56 @@ impl EventSerializer {
                     let pps = make_postprocessors(self.postprocessor_configs.as_slice())?;
                     // insert data for a new stream
                     let (c, pps2) = entry.insert((codec, pps));
-                    postprocess(pps2, ingest_ns, c.encode(value)?, &self.alias)
+                    encode(c.as_ref(), pps2, value, ingest_ns, &self.alias)
                 }
             }
         }
     }
 
+    /// flush out data buffered in the codec of the default stream, e.g. finishing a parquet file
+    ///
+    /// Postprocessors are not finished, the default stream is kept open.
+    ///
+    /// # Errors
+    ///   * if the codec or postprocessors failed
+    pub(crate) fn flush(&mut self, ingest_ns: u64) -> Result<Vec<Vec<u8>>> {
+        match self.codec.finish()? {
+            Some(data) => postprocess(&mut self.postprocessors, ingest_ns, data, &self.alias),
+            None => Ok(vec![]),
+        }
+    }
+
     /// remove and flush out any pending data from the stream identified by the given `stream_id`
     pub(crate) fn finish_stream(&mut self, stream_id: u64) -> Result<Vec<Vec<u8>>> {
-        if let Some((mut _codec, mut postprocessors)) = self.streams.remove(&stream_id) {
-            finish(&mut postprocessors, &self.alias)
+        if let Some((codec, mut postprocessors)) = self.streams.remove(&stream_id) {
+            let mut data = match codec.finish()? {
+                Some(data) => postprocess(&mut postprocessors, nanotime(), data, &self.alias)?,
+                None => vec![],
+            };
+            data.append(&mut finish(&mut postprocessors, &self.alias)?);
+            Ok(data)
         } else {
             Ok(vec![])
         }
     }
 }
 
+/// encode and postprocess a value, skipping postprocessing of empty data from buffering codecs
+fn encode(
+    codec: &dyn Codec,
+    postprocessors: &mut [Box<dyn Postprocessor>],
+    value: &Value,
+    ingest_ns: u64,
+    alias: &str,
+) -> Result<Vec<Vec<u8>>> {
+    let data = codec.encode(value)?;
+    if data.is_empty() && codec.is_buffering() {
+        Ok(vec![])
+    } else {
+        postprocess(postprocessors, ingest_ns, data, alias)
+    }
+}
+
 #[derive(Debug, PartialEq)]
 enum SinkState {
     Initialized,
@@ -584,6 +639,8 @@ where
     // set of connector ids we received drain signals from
     drains_received: HashSet<SourceId>, // TODO: use a bitset for both?
     drain_channel: Option<Sender<Msg>>,
+    taps: Taps,
+    tracer: Arc<Tracer>,
     state: SinkState,
 }
 
@@ -596,6 +653,7 @@ where
             serializer,
             reply_channel,
             metrics_reporter,
+            tracer,
             ..
         } = builder;
         Self {
@@ -610,6 +668,8 @@ where
             starts_received: HashSet::new(),
             drains_received: HashSet::new(),
             drain_channel: None,
+            taps: Taps::default(),
+            tracer,
             state: SinkState::Initialized,
         }
     }
@@ -626,6 +686,9 @@ where
                         SinkMsg::Link { mut pipelines } => {
                             self.pipelines.append(&mut pipelines);
                         }
+                        SinkMsg::Tap(tap) => {
+                            self.taps.add(tap);
+                        }
                         SinkMsg::Start if self.state == Initialized => {
                             self.state = Running;
                             self.ctx.swallow_err(
@@ -668,6 +731,10 @@ where
                         SinkMsg::Stop(sender) => {
                             info!("{} Stopping...", &self.ctx);
                             self.state = Stopped;
+                            self.ctx.swallow_err(
+                                self.sink.on_flush(&self.ctx, &mut self.serializer).await,
+                                "Error flushing buffered data",
+                            );
                             self.ctx.swallow_err(
                                 sender.send(self.sink.on_stop(&self.ctx).await).await,
                                 "Error sending Stop reply",
@@ -749,7 +816,9 @@ where
                             //       (hg) - I don't think we can do this w/o a clone since we need
                             //              them here and in the on_event
                             self.merged_operator_meta.merge(event.op_meta.clone());
+                            self.taps.send(&port, &event);
                             let transactional = event.transactional;
+                            let trace = event.id.trace();
                             let start = nanotime();
                             let res = self
                                 .sink
@@ -761,7 +830,20 @@ where
                                     start,
                                 )
                                 .await;
-                            let duration = nanotime() - start;
+                            let end = nanotime();
+                            let duration = end - start;
+                            if let Some(parent) = trace {
+                                let mut attributes = literal!({
+                                    "connector": self.ctx.alias.to_string(),
+                                    "port": port.to_string()
+                                });
+                                if let Err(e) = &res {
+                                    attributes.try_insert("error", e.to_string());
+                                }
+                                let name = format!("sink {}", self.ctx.alias);
+                                self.tracer
+                                    .child_span(&parent, name, start, end, attributes);
+                            }
                             match res {
                                 Ok(replies) => {
                                     // TODO: send metric for duration
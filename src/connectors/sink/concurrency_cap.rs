@@ -16,38 +16,185 @@ use crate::connectors::sink::{AsyncSinkReply, ContraflowData};
 use crate::errors::Result;
 use async_std::channel::Sender;
 use async_std::sync::Arc;
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use tremor_common::time::nanotime;
 use tremor_pipeline::{CbAction, Event};
 
+fn default_min() -> usize {
+    1
+}
+
+fn default_max() -> usize {
+    64
+}
+
+fn default_latency_tolerance() -> f64 {
+    2.0
+}
+
+fn default_backoff() -> f64 {
+    0.75
+}
+
+/// Configuration for adapting the concurrency limit to the observed latency and errors
+/// using additive increase / multiplicative decrease (AIMD)
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct AdaptiveConfig {
+    /// lower bound for the concurrency limit
+    #[serde(default = "default_min")]
+    pub(crate) min: usize,
+    /// upper bound for the concurrency limit
+    #[serde(default = "default_max")]
+    pub(crate) max: usize,
+    /// requests taking longer than this many nanoseconds count as a sign of overload.
+    /// If not set, requests taking `latency_tolerance` times the average latency do.
+    #[serde(default = "Default::default")]
+    pub(crate) latency_threshold: Option<u64>,
+    /// factor of the average latency above which requests count as a sign of overload
+    #[serde(default = "default_latency_tolerance")]
+    pub(crate) latency_tolerance: f64,
+    /// factor to multiply the limit with on overload
+    #[serde(default = "default_backoff")]
+    pub(crate) backoff: f64,
+}
+
+impl AdaptiveConfig {
+    pub(crate) fn validate(&self) -> Result<()> {
+        if self.min == 0 || self.min > self.max {
+            return Err("adaptive concurrency requires 0 < `min` <= `max`".into());
+        }
+        if self.backoff <= 0.0 || self.backoff >= 1.0 {
+            return Err("adaptive concurrency `backoff` must be between 0 and 1".into());
+        }
+        Ok(())
+    }
+}
+
+/// weight of a new latency sample in the moving average
+const LATENCY_EWMA_ALPHA: f64 = 0.05;
+
+/// AIMD state
+#[derive(Debug)]
+struct Aimd {
+    config: AdaptiveConfig,
+    limit: f64,
+    avg_latency: Option<f64>,
+    last_decrease: u64,
+}
+
+impl Aimd {
+    fn new(initial: usize, config: AdaptiveConfig) -> Self {
+        #[allow(clippy::cast_precision_loss)]
+        let limit = initial.clamp(config.min, config.max) as f64;
+        Self {
+            config,
+            limit,
+            avg_latency: None,
+            last_decrease: 0,
+        }
+    }
+
+    /// Adjust the limit for a request started at `start` and completed at `end`,
+    /// returns the new limit
+    #[allow(
+        clippy::cast_precision_loss,
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss
+    )]
+    fn observe(&mut self, start: u64, end: u64, success: bool) -> usize {
+        let latency = end.saturating_sub(start) as f64;
+        let threshold = self
+            .config
+            .latency_threshold
+            .map(|threshold| threshold as f64)
+            .or_else(|| {
+                self.avg_latency
+                    .map(|avg| avg * self.config.latency_tolerance)
+            });
+        let overloaded = !success || threshold.map_or(false, |threshold| latency > threshold);
+        self.avg_latency = Some(
+            self.avg_latency
+                .map_or(latency, |avg| avg + (latency - avg) * LATENCY_EWMA_ALPHA),
+        );
+        if overloaded {
+            // only back off once for all the requests in flight when we backed off last time
+            if start >= self.last_decrease {
+                self.limit = (self.limit * self.config.backoff).max(self.config.min as f64);
+                self.last_decrease = end;
+            }
+        } else {
+            // grow by 1 once the whole limit worth of requests succeeded
+            self.limit = (self.limit + 1.0 / self.limit).min(self.config.max as f64);
+        }
+        self.limit as usize
+    }
+}
+
 /// Utility for limiting concurrency in a sink to a certain `cap` value
 /// Issueing `CB::Close` message when the `cap` value is reached and `CB::Open` message when we fall back below it
+///
+/// The `cap` is either fixed or adapted to the latency and errors reported via `CounterGuard::complete`.
 #[derive(Debug, Clone)]
 pub(crate) struct ConcurrencyCap {
-    cap: usize,
+    cap: Arc<AtomicUsize>,
     reply_tx: Sender<AsyncSinkReply>,
     counter: Arc<AtomicUsize>,
+    triggered: Arc<AtomicBool>,
+    adaptive: Option<Arc<Mutex<Aimd>>>,
 }
 
 impl ConcurrencyCap {
     pub(crate) fn new(cap: usize, reply_tx: Sender<AsyncSinkReply>) -> Self {
         Self {
-            cap,
+            cap: Arc::new(AtomicUsize::new(cap)),
             reply_tx,
             counter: Arc::new(AtomicUsize::new(0)),
+            triggered: Arc::new(AtomicBool::new(false)),
+            adaptive: None,
         }
     }
 
+    /// Create a cap starting at `initial`, adapted within the bounds of `config`
+    pub(crate) fn adaptive(
+        initial: usize,
+        config: AdaptiveConfig,
+        reply_tx: Sender<AsyncSinkReply>,
+    ) -> Self {
+        let aimd = Aimd::new(initial, config);
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let mut cap = Self::new(aimd.limit as usize, reply_tx);
+        cap.adaptive = Some(Arc::new(Mutex::new(aimd)));
+        cap
+    }
+
     #[cfg(test)]
     fn get_counter(&self) -> usize {
         self.counter.load(Ordering::Acquire)
     }
 
+    /// the current concurrency limit
+    pub(crate) fn cap(&self) -> usize {
+        self.cap.load(Ordering::Acquire)
+    }
+
+    /// the number of requests currently in flight
+    pub(crate) fn inflight(&self) -> usize {
+        self.counter.load(Ordering::Acquire)
+    }
+
     /// increment the counter and return a guard for safely counting down
     /// wrapped inside an enum to check whether we exceeded the maximum or not
     pub(crate) async fn inc_for(&self, event: &Event) -> Result<CounterGuard> {
         let num = self.counter.fetch_add(1, Ordering::AcqRel);
-        let guard = CounterGuard(num, self.clone(), ContraflowData::from(event));
-        if num == self.cap {
+        let guard = CounterGuard {
+            num,
+            start: nanotime(),
+            cap: self.clone(),
+            cf_data: ContraflowData::from(event),
+        };
+        if num >= self.cap() && !self.triggered.swap(true, Ordering::AcqRel) {
             // we crossed max - send a close
             self.reply_tx
                 .send(AsyncSinkReply::CB(
@@ -61,7 +208,7 @@ impl ConcurrencyCap {
 
     async fn dec_with(&self, cf_data: &ContraflowData) -> Result<()> {
         let num = self.counter.fetch_sub(1, Ordering::AcqRel);
-        if num == self.cap {
+        if num <= self.cap() && self.triggered.swap(false, Ordering::AcqRel) {
             // we crossed max - send an open
             self.reply_tx
                 .send(AsyncSinkReply::CB(cf_data.clone(), CbAction::Restore))
@@ -69,20 +216,39 @@ impl ConcurrencyCap {
         }
         Ok(())
     }
+
+    fn observe(&self, start: u64, end: u64, success: bool) {
+        if let Some(aimd) = self.adaptive.as_ref() {
+            if let Ok(mut aimd) = aimd.lock() {
+                let cap = aimd.observe(start, end, success);
+                self.cap.store(cap, Ordering::Release);
+            }
+        }
+    }
 }
 
 /// ensures that we subtract 1 from the counter once this drops
-pub(crate) struct CounterGuard(usize, ConcurrencyCap, ContraflowData);
+pub(crate) struct CounterGuard {
+    num: usize,
+    start: u64,
+    cap: ConcurrencyCap,
+    cf_data: ContraflowData,
+}
 
 impl CounterGuard {
     pub(crate) fn num(&self) -> usize {
-        self.0
+        self.num
+    }
+
+    /// Report the outcome of the guarded request, so an adaptive cap can adjust, and count down
+    pub(crate) fn complete(self, success: bool) {
+        self.cap.observe(self.start, nanotime(), success);
     }
 }
 impl Drop for CounterGuard {
     fn drop(&mut self) {
         // TODO: move this out of drop here
-        if async_std::task::block_on(self.1.dec_with(&self.2)).is_err() {
+        if async_std::task::block_on(self.cap.dec_with(&self.cf_data)).is_err() {
             error!("Error sending a CB Open.");
         }
     }
@@ -131,4 +297,68 @@ mod tests {
         assert!(rx.is_empty());
         Ok(())
     }
+
+    #[test]
+    fn aimd() {
+        let config = AdaptiveConfig {
+            min: 2,
+            max: 4,
+            latency_threshold: None,
+            latency_tolerance: 2.0,
+            backoff: 0.5,
+        };
+        let mut aimd = Aimd::new(3, config);
+        // every successful request grows the limit by `1 / limit`
+        assert_eq!(3, aimd.observe(0, 100, true));
+        assert_eq!(3, aimd.observe(0, 100, true));
+        assert_eq!(3, aimd.observe(0, 100, true));
+        assert_eq!(4, aimd.observe(0, 100, true));
+        // but not beyond `max`
+        assert_eq!(4, aimd.observe(0, 100, true));
+        // errors halve the limit, only once for requests started before the last decrease
+        assert_eq!(2, aimd.observe(200, 300, false));
+        assert_eq!(2, aimd.observe(250, 350, false));
+        // not below `min`
+        assert_eq!(2, aimd.observe(400, 500, false));
+        assert_eq!(2, aimd.observe(600, 700, true));
+        assert_eq!(2, aimd.observe(600, 700, true));
+        assert_eq!(3, aimd.observe(600, 700, true));
+        // requests much slower than average count as overload
+        assert_eq!(2, aimd.observe(800, 2000, true));
+    }
+
+    #[async_std::test]
+    async fn adaptive_concurrency_cap() -> Result<()> {
+        let (tx, rx) = bounded(64);
+        let config = AdaptiveConfig {
+            min: 1,
+            max: 8,
+            latency_threshold: Some(1_000_000_000),
+            latency_tolerance: 2.0,
+            backoff: 0.5,
+        };
+        let cap = ConcurrencyCap::adaptive(2, config, tx);
+        let event = Event::default();
+        let guard1 = cap.inc_for(&event).await?;
+        let guard2 = cap.inc_for(&event).await?;
+        assert!(rx.is_empty());
+        // shrinking the cap below the in flight requests
+        guard1.complete(false);
+        assert_eq!(1, cap.cap());
+        assert_eq!(1, cap.inflight());
+        assert!(rx.is_empty());
+        // triggers with the next request
+        let guard3 = cap.inc_for(&event).await?;
+        let reply = rx.try_recv()?;
+        assert!(matches!(reply, AsyncSinkReply::CB(_, CbAction::Trigger)));
+        // growing the cap above the in flight requests restores
+        guard2.complete(true);
+        assert_eq!(2, cap.cap());
+        let reply = rx.try_recv()?;
+        assert!(matches!(reply, AsyncSinkReply::CB(_, CbAction::Restore)));
+        guard3.complete(true);
+        assert!(rx.is_empty());
+        assert_eq!(0, cap.inflight());
+        Ok(())
+    }
 }
@@ -21,11 +21,12 @@ pub(crate) use channel_source::{ChannelSource, ChannelSourceRuntime};
 
 use async_std::channel::unbounded;
 use async_std::task;
-use hashbrown::HashSet;
+use hashbrown::{HashMap, HashSet};
 use simd_json::Mutable;
 use std::collections::btree_map::Entry;
 use std::collections::BTreeMap;
 use std::fmt::Display;
+use std::sync::Arc;
 use tremor_common::{
     ids::{Id, SinkId, SourceId},
     time::nanotime,
@@ -43,6 +44,7 @@ use crate::connectors::{
 use crate::errors::{Error, Result};
 use crate::pipeline;
 use crate::preprocessor::{finish, make_preprocessors, preprocess, Preprocessors};
+use crate::tap::{Tap, Taps};
 use crate::{
     codec::{self, Codec},
     pipeline::InputTarget,
@@ -51,7 +53,7 @@ use async_std::channel::{Receiver, Sender};
 use beef::Cow;
 use tremor_common::ports::{ERR, OUT};
 use tremor_pipeline::{
-    CbAction, Event, EventId, EventIdGenerator, EventOriginUri, DEFAULT_STREAM_ID,
+    trace::Tracer, CbAction, Event, EventId, EventIdGenerator, EventOriginUri, DEFAULT_STREAM_ID,
 };
 use tremor_value::{literal, Value};
 use value_trait::Builder;
@@ -88,6 +90,8 @@ pub(crate) enum SourceMsg {
     Stop(Sender<Result<()>>),
     /// drain the source - bears a sender for sending out a SourceDrained status notification
     Drain(Sender<Msg>),
+    /// tap an output port
+    Tap(Tap),
     #[cfg(test)]
     Ping(Sender<()>),
 }
@@ -342,6 +346,7 @@ pub(crate) struct SourceManagerBuilder {
     qsize: usize,
     streams: Streams,
     source_metrics_reporter: SourceReporter,
+    tracer: Arc<Tracer>,
 }
 
 impl SourceManagerBuilder {
@@ -404,6 +409,7 @@ pub(crate) fn builder(
     connector_default_codec: CodecReq,
     qsize: usize,
     source_metrics_reporter: SourceReporter,
+    tracer: Arc<Tracer>,
 ) -> Result<SourceManagerBuilder> {
     let preprocessor_configs = config.preprocessors.clone().unwrap_or_default();
     let codec_config = match connector_default_codec {
@@ -432,6 +438,7 @@ pub(crate) fn builder(
         qsize,
         streams,
         source_metrics_reporter,
+        tracer,
     })
 }
 
@@ -562,6 +569,10 @@ where
     addr: SourceAddr,
     pipelines_out: Vec<(DeployEndpoint, pipeline::Addr)>,
     pipelines_err: Vec<(DeployEndpoint, pipeline::Addr)>,
+    /// pipelines connected to additional output ports declared by the connector via `Connector::output_ports`
+    pipelines_other: HashMap<Cow<'static, str>, Vec<(DeployEndpoint, pipeline::Addr)>>,
+    taps: Taps,
+    tracer: Arc<Tracer>,
     streams: Streams,
     metrics_reporter: SourceReporter,
     // `Paused` is used for both explicitly pausing and CB close/open
@@ -599,6 +610,7 @@ where
         let SourceManagerBuilder {
             streams,
             source_metrics_reporter,
+            tracer,
             ..
         } = builder;
         let is_transactional = source.is_transactional();
@@ -612,6 +624,9 @@ where
             metrics_reporter: source_metrics_reporter,
             pipelines_out: Vec::with_capacity(1),
             pipelines_err: Vec::with_capacity(1),
+            pipelines_other: HashMap::new(),
+            taps: Taps::default(),
+            tracer,
             state: SourceState::Initialized,
             connectivity: Connectivity::Disconnected, // we always start as disconnected until `.connect()` connects us
             is_transactional,
@@ -691,6 +706,10 @@ where
                 Ok(Control::Continue)
             }
             SourceMsg::Cb(cb, id) => Ok(self.handle_cb(cb, id).await),
+            SourceMsg::Tap(tap) => {
+                self.taps.add(tap);
+                Ok(Control::Continue)
+            }
             #[cfg(test)]
             SourceMsg::Ping(sender) => {
                 self.ctx
@@ -839,14 +858,15 @@ where
         tx: Sender<Result<()>>,
         pipeline: (DeployEndpoint, pipeline::Addr),
     ) -> Result<Control> {
+        // the connector already validated the port against its `output_ports`
         let pipes = if port.eq_ignore_ascii_case(OUT.as_ref()) {
             &mut self.pipelines_out
         } else if port.eq_ignore_ascii_case(ERR.as_ref()) {
             &mut self.pipelines_err
         } else {
-            error!("{} Tried to connect to invalid port: {}", &self.ctx, &port);
-            tx.send(Err("Connecting to invalid port".into())).await?;
-            return Ok(Control::Continue);
+            self.pipelines_other
+                .entry(Cow::owned(port.to_ascii_lowercase()))
+                .or_default()
         };
         // We can not move this to the system flow since we need to know about transactionality
         let (pipeline_url, p) = &pipeline;
@@ -869,7 +889,12 @@ where
 
     /// send a signal to all connected pipelines
     async fn send_signal(&mut self, signal: Event) -> Result<()> {
-        for (_url, addr) in self.pipelines_out.iter().chain(self.pipelines_err.iter()) {
+        for (_url, addr) in self
+            .pipelines_out
+            .iter()
+            .chain(self.pipelines_err.iter())
+            .chain(self.pipelines_other.values().flatten())
+        {
             addr.send(Box::new(pipeline::Msg::Signal(signal.clone())))
                 .await?;
         }
@@ -881,13 +906,19 @@ where
         let mut send_error = false;
 
         let ctx = &self.ctx;
-        for (port, event) in events {
+        for (port, mut event) in events {
             let pipelines = if port.eq_ignore_ascii_case(OUT.as_ref()) {
                 self.metrics_reporter.increment_out();
                 &mut self.pipelines_out
             } else if port.eq_ignore_ascii_case(ERR.as_ref()) {
                 self.metrics_reporter.increment_err();
                 &mut self.pipelines_err
+            } else if let Some(pipelines) = self
+                .pipelines_other
+                .get_mut(port.to_ascii_lowercase().as_str())
+            {
+                self.metrics_reporter.increment_port(&port);
+                pipelines
             } else {
                 error!("{ctx} Trying to send event to invalid port: {port}");
                 continue;
@@ -898,6 +929,19 @@ where
                 self.metrics_reporter
                     .send_source_metrics(self.source.metrics(t, ctx));
             }
+            // sampled events start a trace, covering the time since they were ingested
+            if self.tracer.sample() {
+                let attributes = literal!({
+                    "connector": ctx.alias.to_string(),
+                    "port": port.to_string()
+                });
+                let name = format!("source {}", ctx.alias);
+                let trace = self
+                    .tracer
+                    .root_span(name, event.ingest_ns, nanotime(), attributes);
+                event.id.set_trace(Some(trace));
+            }
+            self.taps.send(&port, &event);
 
             if let Some((last, pipelines)) = pipelines.split_last_mut() {
                 for (pipe_url, addr) in pipelines {
@@ -937,7 +981,7 @@ where
 
         // combine all the conditions
         state_should_pull
-            && !self.pipelines_out.is_empty() // we have pipelines connected
+            && (!self.pipelines_out.is_empty() || !self.pipelines_other.is_empty()) // we have pipelines connected
             && self.connectivity == Connectivity::Connected // we are connected to our thingy
             && self.cb_restore_received > 0                       // we did receive at least 1 `CbAction::Restore` in order to ensure we do not accidentally send events 
                                                                   // before we received any CbAction::SinkStart and the corresponding CbAction::Restore
@@ -1302,8 +1346,8 @@ fn build_events(
         data,
         alias,
     ) {
-        Ok(processed) => {
-            let mut res = Vec::with_capacity(processed.len());
+        Ok((processed, errors)) => {
+            let mut res = Vec::with_capacity(processed.len() + errors.len());
             for chunk in processed {
                 let line_value = EventPayload::try_new::<Option<Error>, _>(chunk, |mut_data| {
                     match stream_state.codec.decode(mut_data, *ingest_ns) {
@@ -1333,6 +1377,20 @@ fn build_events(
                 );
                 res.push((port, event));
             }
+            // data discarded by a preprocessor
+            for e in errors {
+                let err_payload =
+                    make_error(alias, &e, stream_state.stream_id, pull_id, meta.clone());
+                let event = build_event(
+                    stream_state,
+                    pull_id,
+                    *ingest_ns,
+                    err_payload,
+                    origin_uri.clone(),
+                    is_transactional,
+                );
+                res.push((ERR, event));
+            }
             res
         }
         Err(e) => {
@@ -1365,8 +1423,8 @@ fn build_last_events(
     is_transactional: bool,
 ) -> Vec<(Cow<'static, str>, Event)> {
     match finish(stream_state.preprocessors.as_mut_slice(), alias) {
-        Ok(processed) => {
-            let mut res = Vec::with_capacity(processed.len());
+        Ok((processed, errors)) => {
+            let mut res = Vec::with_capacity(processed.len() + errors.len());
             for chunk in processed {
                 let line_value = EventPayload::try_new::<Option<Error>, _>(chunk, |mut_data| {
                     match stream_state.codec.decode(mut_data, *ingest_ns) {
@@ -1396,6 +1454,20 @@ fn build_last_events(
                 );
                 res.push((port, event));
             }
+            // data discarded by a preprocessor
+            for e in errors {
+                let err_payload =
+                    make_error(alias, &e, stream_state.stream_id, pull_id, meta.clone());
+                let event = build_event(
+                    stream_state,
+                    pull_id,
+                    *ingest_ns,
+                    err_payload,
+                    origin_uri.clone(),
+                    is_transactional,
+                );
+                res.push((ERR, event));
+            }
             res
         }
         Err(e) => {
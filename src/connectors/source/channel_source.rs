@@ -15,7 +15,7 @@
 use crate::{
     connectors::{
         source::{Source, SourceContext, SourceReply, SourceReplySender, StreamDone, StreamReader},
-        Context,
+        Alias, Context,
     },
     errors::Result,
 };
@@ -27,6 +27,10 @@ use async_std::{
 };
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::Duration;
+use tremor_script::EventPayload;
+
+type MetricsFn = Box<dyn Fn(u64, &Alias) -> Vec<EventPayload> + Send + Sync>;
+
 /// A source that receives `SourceReply` messages via a channel.
 /// It does not handle acks/fails.
 ///
@@ -37,6 +41,7 @@ pub(crate) struct ChannelSource {
     rx: Receiver<SourceReply>,
     tx: SourceReplySender,
     is_connected: Arc<AtomicBool>,
+    metrics: Option<MetricsFn>,
 }
 
 impl ChannelSource {
@@ -58,9 +63,20 @@ impl ChannelSource {
             rx,
             tx,
             is_connected,
+            metrics: None,
         }
     }
 
+    /// report the metrics returned by `metrics` along with the source metrics
+    #[must_use]
+    pub fn with_metrics<F>(mut self, metrics: F) -> Self
+    where
+        F: Fn(u64, &Alias) -> Vec<EventPayload> + Send + Sync + 'static,
+    {
+        self.metrics = Some(Box::new(metrics));
+        self
+    }
+
     /// get the runtime for the source
 
     #[must_use]
@@ -77,6 +93,12 @@ impl Source for ChannelSource {
         Ok(self.rx.recv().await?)
     }
 
+    fn metrics(&mut self, timestamp: u64, ctx: &SourceContext) -> Vec<EventPayload> {
+        self.metrics
+            .as_ref()
+            .map_or_else(Vec::new, |metrics| metrics(timestamp, ctx.alias()))
+    }
+
     /// this source is not handling acks/fails
     fn is_transactional(&self) -> bool {
         false
@@ -28,6 +28,8 @@ mod file;
 #[cfg(feature = "file-integration")]
 mod file_non_existent;
 #[cfg(feature = "file-integration")]
+mod file_rotate;
+#[cfg(feature = "file-integration")]
 mod file_xz;
 #[cfg(feature = "gcp-integration")]
 mod gpubsub;
@@ -76,7 +78,10 @@ use async_std::{
 use beef::Cow;
 use log::{debug, info};
 use std::{collections::HashMap, time::Instant};
-use std::{sync::atomic::Ordering, time::Duration};
+use std::{
+    sync::{atomic::Ordering, Arc},
+    time::Duration,
+};
 use tremor_common::{
     ids::{ConnectorIdGen, Id, SourceId},
     ports::{ERR, IN, OUT},
@@ -114,6 +119,7 @@ impl ConnectorHarness {
             builder,
             raw_config,
             &kill_switch,
+            Arc::default(),
         )
         .await?;
         let mut pipes = HashMap::new();
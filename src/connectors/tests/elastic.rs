@@ -22,7 +22,8 @@ use async_std::path::Path;
 use elasticsearch::auth::{ClientCertificate, Credentials};
 use elasticsearch::cert::{Certificate, CertificateValidation};
 use elasticsearch::http::transport::{SingleNodeConnectionPool, TransportBuilder};
-use elasticsearch::{http::transport::Transport, Elasticsearch};
+use elasticsearch::params::Refresh;
+use elasticsearch::{http::transport::Transport, Elasticsearch, IndexParts};
 use futures::TryFutureExt;
 use serial_test::serial;
 use testcontainers::core::WaitFor;
@@ -893,6 +894,87 @@ async fn elastic_https_invalid_url() -> Result<()> {
     Ok(())
 }
 
+#[async_std::test]
+#[serial(elastic, timeout_ms = 6000000)]
+async fn elastic_search() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    let docker = clients::Cli::default();
+    let port = super::free_port::find_free_tcp_port().await?;
+    let image = RunnableImage::from(
+        GenericImage::new("elasticsearch", ELASTICSEARCH_VERSION)
+            .with_env_var("discovery.type", "single-node")
+            .with_env_var("ES_JAVA_OPTS", "-Xms256m -Xmx256m")
+            .with_env_var("xpack.security.enabled", "false")
+            .with_env_var("xpack.security.http.ssl.enabled", "false"),
+    )
+    .with_mapped_port((port, 9200_u16));
+
+    let container = docker.run(image);
+    let port = container.get_host_port_ipv4(9200);
+    let elastic = Elasticsearch::new(Transport::single_node(
+        format!("http://127.0.0.1:{port}").as_str(),
+    )?);
+    wait_for_es(&elastic).await?;
+
+    let index = "searchable";
+    for n in 0_u64..3 {
+        elastic
+            .index(IndexParts::IndexId(index, &n.to_string()))
+            .body(literal!({ "n": n }))
+            .refresh(Refresh::True)
+            .send()
+            .await?
+            .error_for_status_code()?;
+    }
+    let state_dir = tempfile::tempdir()?;
+    let state_file = state_dir.path().join("search.json");
+
+    let connector_config = literal!({
+        "config": {
+            "nodes": [
+                format!("http://127.0.0.1:{port}")
+            ],
+            "search": {
+                "index": index,
+                "size": 2,
+                "query": {
+                    "sort": [{"n": "asc"}]
+                },
+                "state_file": state_file.display().to_string()
+            }
+        }
+    });
+    let harness = ConnectorHarness::new(
+        function_name!(),
+        &elastic::Builder::default(),
+        &connector_config,
+    )
+    .await?;
+    let out = harness.out().expect("No pipe connected to port OUT");
+    harness.start().await?;
+    harness.wait_for_connected().await?;
+
+    for n in 0_u64..3 {
+        let event = out.get_event().await?;
+        assert_eq!(&literal!({ "n": n }), event.data.suffix().value());
+        let meta = event.data.suffix().meta();
+        assert_eq!(Some(index), meta.get("elastic").get_str("_index"));
+        assert_eq!(
+            Some(n.to_string().as_str()),
+            meta.get("elastic").get_str("_id")
+        );
+        assert_eq!(Some(&literal!([n])), meta.get("elastic").get("sort"));
+    }
+    let (out, err) = harness.stop().await?;
+    assert!(out.is_empty());
+    assert!(err.is_empty());
+
+    let state = async_std::fs::read_to_string(&state_file).await?;
+    assert_eq!(r#"{"search_after":[2]}"#, state);
+    Ok(())
+}
+
 async fn wait_for_es(elastic: &Elasticsearch) -> Result<()> {
     // wait for the image to be reachable
 
@@ -0,0 +1,73 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::ConnectorHarness;
+use crate::{connectors::impls::file, errors::Result};
+use std::io::Read;
+use tremor_common::ports::IN;
+use tremor_pipeline::{Event, EventId};
+use tremor_value::prelude::*;
+
+#[async_std::test]
+async fn file_rotate() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("out.log");
+    let defn = literal!({
+        "codec": "string",
+        "config": {
+            "path": path.display().to_string(),
+            "mode": "truncate",
+            "rotate": {
+                "pattern": "{name}.{n}",
+                "max_bytes": 10,
+                "compression": "gzip",
+                "max_files": 2
+            }
+        }
+    });
+
+    let harness = ConnectorHarness::new(function_name!(), &file::Builder::default(), &defn).await?;
+    harness.start().await?;
+    harness.wait_for_connected().await?;
+
+    for i in 0..4 {
+        let event = Event {
+            id: EventId::from_id(0, 0, i),
+            data: (Value::from(format!("snotbadge{i}")), Value::object()).into(),
+            ..Event::default()
+        };
+        harness.send_to_sink(event, IN).await?;
+    }
+    let (out_events, err_events) = harness.stop().await?;
+    assert!(out_events.is_empty());
+    assert!(err_events.is_empty());
+
+    // every event exceeded `max_bytes`, only the 2 latest rotated files are kept
+    let mut files = std::fs::read_dir(dir.path())?
+        .map(|entry| Ok(entry?.file_name().to_string_lossy().to_string()))
+        .collect::<Result<Vec<_>>>()?;
+    files.sort();
+    assert_eq!(vec!["out.log", "out.log.2.gz", "out.log.3.gz"], files);
+    assert_eq!(0, std::fs::metadata(&path)?.len());
+    for i in 2..4 {
+        let compressed = std::fs::File::open(dir.path().join(format!("out.log.{i}.gz")))?;
+        let mut decoder = libflate::gzip::Decoder::new(compressed)?;
+        let mut content = String::new();
+        decoder.read_to_string(&mut content)?;
+        assert_eq!(format!("snotbadge{i}"), content);
+    }
+    Ok(())
+}
@@ -26,10 +26,19 @@ use async_std::{
     task::{spawn, JoinHandle},
 };
 use http_types::{
-    headers::{HeaderValues, CONTENT_TYPE, TRANSFER_ENCODING},
-    Body,
+    headers::{
+        HeaderValues, AUTHORIZATION, CONTENT_TYPE, ETAG, IF_NONE_MATCH, LINK, TRANSFER_ENCODING,
+    },
+    Body, StatusCode,
 };
 use rustls::NoClientAuth;
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 use tide;
 use tide_rustls::TlsListener;
 use tremor_common::ports::IN;
@@ -552,6 +561,216 @@ async fn http_client_request_auth_basic() -> Result<()> {
     Ok(())
 }
 
+/// Stand-in OAuth2 token endpoint, issuing `token-1`, `token-2`, ...
+async fn fake_token_dispatch(
+    mut request: tide::Request<Arc<AtomicUsize>>,
+) -> tide::Result<tide::Response> {
+    let body = request.body_string().await?;
+    let credentials = request
+        .header(AUTHORIZATION)
+        .map(|values| values.last().as_str().to_string());
+    // snot:badger
+    if !body.contains("grant_type=client_credentials")
+        || credentials.as_deref() != Some("Basic c25vdDpiYWRnZXI=")
+    {
+        return Ok(tide::Response::new(StatusCode::Unauthorized));
+    }
+    let n = request.state().fetch_add(1, Ordering::AcqRel) + 1;
+    let mut res = tide::Response::new(StatusCode::Ok);
+    res.set_content_type(http_types::mime::JSON);
+    res.set_body(format!(
+        r#"{{"access_token":"token-{n}","token_type":"bearer","expires_in":3600}}"#
+    ));
+    Ok(res)
+}
+
+/// Stand-in protected API, rejecting the first token ever issued as if it got revoked
+async fn fake_protected_dispatch(
+    request: tide::Request<Arc<AtomicUsize>>,
+) -> tide::Result<tide::Response> {
+    match request
+        .header(AUTHORIZATION)
+        .map(|values| values.last().as_str())
+    {
+        Some("Bearer token-1") | None => Ok(tide::Response::new(StatusCode::Unauthorized)),
+        Some(_) => {
+            let mut res = tide::Response::new(StatusCode::Ok);
+            res.set_body("authorized");
+            Ok(res)
+        }
+    }
+}
+
+#[async_std::test]
+async fn http_client_request_auth_oauth2() -> Result<()> {
+    let _ = env_logger::try_init();
+    let target = find_free_tcp_endpoint_str().await;
+    let url = format!("http://{target}");
+    let mut endpoint = tide::with_state(Arc::new(AtomicUsize::new(0)));
+    endpoint.at("/token").post(fake_token_dispatch);
+    endpoint.at("/").all(fake_protected_dispatch);
+    let listen_url = url.clone();
+    let acceptor = spawn(async move { endpoint.listen(listen_url).await });
+
+    let defn = literal!({
+      "config": {
+        "url": format!("{url}/"),
+        "method": "get",
+        "auth": {
+          "oauth2": {
+            "token_url": format!("{url}/token"),
+            "client_id": "snot",
+            "client_secret": "badger"
+          }
+        },
+        "mime_mapping": {
+          "*/*": "string"
+        }
+      }
+    });
+    let harness =
+        ConnectorHarness::new(function_name!(), &http::client::Builder::default(), &defn).await?;
+    let out_pipeline = harness
+        .out()
+        .expect("No pipeline connected to 'out' port of connector");
+    harness.start().await?;
+    harness.wait_for_connected().await?;
+    harness.consume_initial_sink_contraflow().await?;
+
+    let event = Event {
+        data: (Value::from("snot"), literal!({})).into(),
+        ..Default::default()
+    };
+    harness.send_to_sink(event, IN).await?;
+    let event = out_pipeline.get_event().await?;
+    acceptor.cancel().await;
+    let (_out, err) = harness.stop().await?;
+    assert!(err.is_empty());
+
+    let res = ValueAndMeta::from_parts(
+        event.data.suffix().value().clone_static(),
+        event.data.suffix().meta().clone_static(),
+    );
+    // the first token got rejected, so the request has been retried with a fresh one
+    assert_with_request_headers!(res, meta, {
+        assert_eq!(
+            Some(&literal!(["Bearer token-2"])),
+            meta.get("authorization")
+        );
+    });
+    assert_eq!(
+        Some(200),
+        res.meta()
+            .get("http_client")
+            .get("response")
+            .get_u16("status")
+    );
+    assert_eq!(&Value::from("authorized"), res.value());
+    Ok(())
+}
+
+/// Stand-in paginated API, counting requests answered with `304 Not Modified`
+async fn fake_paginated_dispatch(
+    request: tide::Request<Arc<AtomicUsize>>,
+) -> tide::Result<tide::Response> {
+    let page = request.param("page").unwrap_or("1");
+    let mut res = tide::Response::new(StatusCode::Ok);
+    if page == "1" {
+        res.insert_header(LINK, r#"</items/2>; rel="next""#);
+        res.set_body("page-1");
+    } else if request
+        .header(IF_NONE_MATCH)
+        .map(|values| values.last().as_str())
+        == Some(r#""v2""#)
+    {
+        request.state().fetch_add(1, Ordering::AcqRel);
+        res.set_status(StatusCode::NotModified);
+    } else {
+        res.insert_header(ETAG, r#""v2""#);
+        res.set_body("page-2");
+    }
+    Ok(res)
+}
+
+#[async_std::test]
+async fn http_client_poll() -> Result<()> {
+    let _ = env_logger::try_init();
+    let target = find_free_tcp_endpoint_str().await;
+    let url = format!("http://{target}");
+    let not_modified = Arc::new(AtomicUsize::new(0));
+    let mut endpoint = tide::with_state(not_modified.clone());
+    endpoint.at("/items/:page").get(fake_paginated_dispatch);
+    let listen_url = url.clone();
+    let acceptor = spawn(async move { endpoint.listen(listen_url).await });
+
+    let dir = tempfile::tempdir()?;
+    let state_file = dir.path().join("poll.json");
+    let defn = literal!({
+      "config": {
+        "url": format!("{url}/items/1"),
+        "poll": {
+          "interval": 100_000_000,
+          "pagination": "link",
+          "state_file": state_file.display().to_string()
+        },
+        "mime_mapping": {
+          "*/*": "string"
+        }
+      }
+    });
+    let harness =
+        ConnectorHarness::new(function_name!(), &http::client::Builder::default(), &defn).await?;
+    let out_pipeline = harness
+        .out()
+        .expect("No pipeline connected to 'out' port of connector");
+    harness.start().await?;
+    harness.wait_for_connected().await?;
+
+    let event = out_pipeline.get_event().await?;
+    assert_eq!(&Value::from("page-1"), event.data.suffix().value());
+    let event = out_pipeline.get_event().await?;
+    assert_eq!(&Value::from("page-2"), event.data.suffix().value());
+    assert_eq!(
+        Some(1),
+        event
+            .data
+            .suffix()
+            .meta()
+            .get("http_client")
+            .get("poll")
+            .get_u64("page")
+    );
+    // following polls continue on the last page and send its etag
+    while not_modified.load(Ordering::Acquire) == 0 {
+        async_std::task::sleep(Duration::from_millis(50)).await;
+    }
+    out_pipeline
+        .expect_no_event_for(Duration::from_millis(200))
+        .await?;
+    let (_out, err) = harness.stop().await?;
+    assert!(err.is_empty());
+
+    // the position survives a restart
+    let seen = not_modified.load(Ordering::Acquire);
+    let harness =
+        ConnectorHarness::new(function_name!(), &http::client::Builder::default(), &defn).await?;
+    let out_pipeline = harness
+        .out()
+        .expect("No pipeline connected to 'out' port of connector");
+    harness.start().await?;
+    harness.wait_for_connected().await?;
+    while not_modified.load(Ordering::Acquire) == seen {
+        async_std::task::sleep(Duration::from_millis(50)).await;
+    }
+    out_pipeline
+        .expect_no_event_for(Duration::from_millis(200))
+        .await?;
+    let (_out, err) = harness.stop().await?;
+    assert!(err.is_empty());
+    acceptor.cancel().await;
+    Ok(())
+}
+
 #[async_std::test]
 async fn chunked() -> Result<()> {
     let target = find_free_tcp_endpoint_str().await;
@@ -20,6 +20,7 @@ use std::{
 use crate::{
     connectors::{
         impls::http::server,
+        prelude::KillSwitch,
         sink::SinkMsg,
         tests::{free_port, setup_for_tls, ConnectorHarness},
         utils::tls::{tls_client_config, TLSClientConfig},
@@ -34,7 +35,7 @@ use http_types::{
     Method, StatusCode, Url,
 };
 use std::str::FromStr;
-use tremor_common::ports::IN;
+use tremor_common::ports::{ERR, IN, OUT};
 use tremor_pipeline::{Event, EventId};
 use tremor_script::ValueAndMeta;
 use tremor_value::{literal, value::StaticValue, Value};
@@ -389,6 +390,7 @@ async fn https_server_test() -> Result<()> {
         domain: Some("localhost".to_string()),
         cert: None,
         key: None,
+        reload_interval: None,
     })
     .await?;
     config = config.set_tls_config(Some(Arc::new(tls_config)));
@@ -446,3 +448,131 @@ value: null
 
     Ok(())
 }
+
+#[async_std::test]
+async fn http_server_routes_test() -> Result<()> {
+    let _ = env_logger::try_init();
+    let port = free_port::find_free_tcp_port().await?;
+    let url = format!("http://localhost:{port}");
+    let defn = literal!({
+        "config": {
+            "url": url.clone(),
+            "max_body_size": 16,
+            "routes": [
+                {
+                    "path": "/users/:id",
+                    "methods": ["POST"],
+                    "port": "users",
+                    "auth": {"bearer": "snot"}
+                },
+                {
+                    "path": "/health"
+                }
+            ]
+        }
+    });
+    let connector = ConnectorHarness::new_with_ports(
+        function_name!(),
+        &server::Builder::default(),
+        &defn,
+        KillSwitch::dummy(),
+        vec![IN],
+        vec![OUT, ERR, "users".into()],
+    )
+    .await?;
+    connector.start().await?;
+    connector.wait_for_connected().await?;
+    let users = connector
+        .get_pipe("users")
+        .expect("No pipeline connected to users")
+        .clone();
+    let c_addr = connector.addr.clone();
+
+    // respond with the captured route
+    let handle = async_std::task::spawn::<_, Result<()>>(async move {
+        while let Ok(inbound) = users.get_event().await {
+            let route = inbound
+                .data
+                .suffix()
+                .meta()
+                .get("http_server")
+                .get("request")
+                .get("route")
+                .map(Value::clone_static);
+            let event = Event {
+                id: inbound.id.clone(),
+                data: (literal!({ "route": route }), Value::object()).into(),
+                ..Event::default()
+            };
+            c_addr.send_sink(SinkMsg::Event { event, port: IN }).await?;
+        }
+        Ok(())
+    });
+
+    let client = surf::client();
+    let user_url = Url::parse(&format!("{url}/users/42"))?;
+
+    // retry until the http server is actually up
+    let start = Instant::now();
+    let timeout = Duration::from_secs(30);
+    let mut res = client.get(format!("{url}/nope")).await;
+    while let Err(e) = res {
+        async_std::task::sleep(Duration::from_millis(100)).await;
+        if start.elapsed() > timeout {
+            return Err(format!("HTTP Server not listening after {timeout:?}: {e}").into());
+        }
+        res = client.get(format!("{url}/nope")).await;
+    }
+    assert_eq!(StatusCode::NotFound, res?.status());
+
+    let res = client.get(user_url.clone()).await?;
+    assert_eq!(StatusCode::MethodNotAllowed, res.status());
+    assert_eq!(
+        Some("POST"),
+        res.header(headers::ALLOW).map(|h| h.last().as_str())
+    );
+
+    let res = client
+        .post(user_url.clone())
+        .body_string("badger".into())
+        .await?;
+    assert_eq!(StatusCode::Unauthorized, res.status());
+    assert_eq!(
+        Some("Bearer"),
+        res.header(headers::WWW_AUTHENTICATE)
+            .map(|h| h.last().as_str())
+    );
+
+    let res = client
+        .post(user_url.clone())
+        .header(headers::AUTHORIZATION, "Bearer snot")
+        .body_string("badger badger badger badger".into())
+        .await?;
+    assert_eq!(StatusCode::PayloadTooLarge, res.status());
+
+    let mut res = client
+        .post(user_url)
+        .header(headers::AUTHORIZATION, "Bearer snot")
+        .body_string("badger".into())
+        .timeout(Duration::from_secs(5))
+        .await??;
+    assert_eq!(StatusCode::Created, res.status());
+    let body = res.body_json::<StaticValue>().await?.into_value();
+    assert_eq!(
+        literal!({
+            "route": {
+                "path": "/users/:id",
+                "params": {"id": "42"}
+            }
+        }),
+        body
+    );
+
+    if let Some(res) = handle.cancel().await {
+        res?;
+    }
+    let (out, err) = connector.stop().await?;
+    assert!(out.is_empty());
+    assert!(err.is_empty());
+    Ok(())
+}
@@ -258,3 +258,108 @@ async fn connector_s3_reader() -> Result<()> {
 
     Ok(())
 }
+
+#[async_std::test]
+#[serial(s3, timeout_ms = 6000000)]
+async fn connector_s3_reader_watch() -> Result<()> {
+    let _ = env_logger::try_init();
+    let bucket_name = random_bucket_name("tremor-watch");
+
+    let docker = clients::Cli::default();
+    let (_container, http_port) = spawn_docker(&docker).await;
+
+    wait_for_s3(http_port).await?;
+    create_bucket(&bucket_name, http_port).await?;
+
+    let s3_client: Client = get_client(http_port);
+    let mut encoder = libflate::gzip::Encoder::new(Vec::new())?;
+    std::io::Write::write_all(&mut encoder, b"compressed")?;
+    let compressed = encoder.finish().into_result()?;
+    s3_client
+        .put_object()
+        .key("first.txt.gz")
+        .bucket(bucket_name.as_str())
+        .body(ByteStream::from(compressed))
+        .send()
+        .await?;
+
+    let state_dir = tempfile::tempdir()?;
+    let state_file = state_dir.path().join("watch.json");
+    let mut env = EnvHelper::new();
+    env.set_var("AWS_ACCESS_KEY_ID", MINIO_ROOT_USER);
+    env.set_var("AWS_SECRET_ACCESS_KEY", MINIO_ROOT_PASSWORD);
+    let endpoint = format!("http://localhost:{http_port}");
+    let connector_yaml = literal!({
+        "codec": "binary",
+        "config": {
+            "aws_region": MINIO_REGION,
+            "bucket": bucket_name.clone(),
+            "url": endpoint,
+            "decompress": true,
+            "watch": {
+                "interval": 100_000_000,
+                "track": "keys",
+                "state_file": state_file.display().to_string()
+            }
+        }
+    });
+
+    let harness = ConnectorHarness::new(
+        function_name!(),
+        &s3::reader::Builder::default(),
+        &connector_yaml,
+    )
+    .await?;
+    let out_pipe = harness
+        .out()
+        .expect("No pipelines connected to out port of s3-reader");
+    harness.start().await?;
+
+    let event = out_pipe.get_event().await?;
+    assert_eq!(
+        Some("first.txt.gz"),
+        event.data.suffix().meta().get("s3_reader").get_str("key")
+    );
+    assert_eq!(
+        Some(b"compressed".as_slice()),
+        event.data.suffix().value().as_bytes()
+    );
+
+    // objects landing later are picked up with the next listing
+    s3_client
+        .put_object()
+        .key("second.txt")
+        .bucket(bucket_name.as_str())
+        .body(ByteStream::from_static(b"plain"))
+        .send()
+        .await?;
+    let event = out_pipe.get_event().await?;
+    assert_eq!(
+        Some("second.txt"),
+        event.data.suffix().meta().get("s3_reader").get_str("key")
+    );
+    assert_eq!(
+        Some(b"plain".as_slice()),
+        event.data.suffix().value().as_bytes()
+    );
+
+    // both keys are recorded as read, once the listing is done
+    let mut state = String::new();
+    for _ in 0..50 {
+        state = async_std::fs::read_to_string(&state_file)
+            .await
+            .unwrap_or_default();
+        if state.contains("second.txt") {
+            break;
+        }
+        async_std::task::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    assert!(state.contains("first.txt.gz"));
+    assert!(state.contains("second.txt"));
+
+    let (out, err) = harness.stop().await?;
+    assert!(out.is_empty());
+    assert!(err.is_empty());
+
+    Ok(())
+}
@@ -279,6 +279,90 @@ async fn connector_s3_consistent() -> Result<()> {
     Ok(())
 }
 
+#[async_std::test]
+#[serial(s3, timeout_ms = 6000000)]
+async fn connector_s3_rollover() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    let bucket_name = random_bucket_name("rollover");
+
+    let docker = clients::Cli::default();
+    let (_container, http_port) = spawn_docker(&docker).await;
+
+    wait_for_s3(http_port).await?;
+    create_bucket(&bucket_name, http_port).await?;
+
+    let s3_client = get_client(http_port);
+
+    let mut env = EnvHelper::new();
+    env.set_var("AWS_ACCESS_KEY_ID", MINIO_ROOT_USER);
+    env.set_var("AWS_SECRET_ACCESS_KEY", MINIO_ROOT_PASSWORD);
+    env.set_var("AWS_REGION", MINIO_REGION);
+
+    let connector_yaml = literal!({
+        "codec": "json",
+        "config":{
+            "bucket": bucket_name.clone(),
+            "url": format!("http://localhost:{http_port}"),
+            "mode": "yolo",
+            "rollover": {
+                "key": "rolled/{meta.tenant}/{uuid}.json",
+                "max_events": 2
+            }
+        }
+    });
+
+    let harness = ConnectorHarness::new(
+        function_name!(),
+        &s3::streamer::Builder::default(),
+        &connector_yaml,
+    )
+    .await?;
+    harness.start().await?;
+    harness.wait_for_connected().await?;
+    harness.consume_initial_sink_contraflow().await?;
+
+    for n in 0..3 {
+        let event = Event {
+            id: EventId::from_id(1, 1, n),
+            data: (
+                literal!({ "n": n }),
+                literal!({
+                    "s3_streamer": {
+                        "tenant": "snot"
+                    }
+                }),
+            )
+                .into(),
+            ..Event::default()
+        };
+        send_to_sink(&harness, &event).await?;
+    }
+    harness.stop().await?;
+
+    let objects = s3_client
+        .list_objects_v2()
+        .bucket(bucket_name.clone())
+        .prefix("rolled/snot/")
+        .send()
+        .await?;
+    let mut contents = Vec::new();
+    for object in objects.contents().unwrap_or_default() {
+        let key = object.key().unwrap_or_default();
+        assert!(key.ends_with(".json"));
+        contents.push(String::from_utf8(
+            get_object(&s3_client, &bucket_name, key).await,
+        )?);
+    }
+    contents.sort();
+    assert_eq!(
+        vec![r#"{"n":0}{"n":1}"#.to_string(), r#"{"n":2}"#.to_string()],
+        contents
+    );
+
+    Ok(())
+}
+
 async fn send_to_sink(harness: &ConnectorHarness, event: &Event) -> Result<()> {
     harness.send_to_sink(event.clone(), IN).await?;
     Ok(())
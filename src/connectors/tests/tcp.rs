@@ -98,6 +98,9 @@ impl EchoServer {
             Some(load_server_config(&TLSServerConfig {
                 cert: "./tests/localhost.cert".into(),
                 key: "./tests/localhost.key".into(),
+                cafile: None,
+                client_auth: None,
+                reload_interval: None,
             })?)
         } else {
             None
@@ -15,7 +15,8 @@
 use std::time::Duration;
 
 use crate::connectors::impls::tcp;
-use crate::connectors::tests::{free_port, ConnectorHarness};
+use crate::connectors::tests::{free_port, setup_for_tls, ConnectorHarness};
+use crate::connectors::utils::tls::{tls_client_connector, TLSClientConfig};
 use crate::errors::Result;
 use async_std::{io::WriteExt, net::TcpStream, prelude::*};
 use tremor_common::ports::IN;
@@ -158,3 +159,155 @@ async fn client_disconnect() -> Result<()> {
     assert!(err.is_empty());
     Ok(())
 }
+
+#[async_std::test]
+async fn tls_client_auth() -> Result<()> {
+    let _ = env_logger::try_init();
+    setup_for_tls();
+
+    let free_port = free_port::find_free_tcp_port().await?;
+    let server_addr = format!("127.0.0.1:{free_port}");
+
+    let defn = literal!({
+      "codec": "string",
+      "preprocessors": ["separate"],
+      "config": {
+        "url": format!("tcp://127.0.0.1:{free_port}"),
+        "buf_size": 4096,
+        "tls": {
+            "cert": "./tests/localhost.cert",
+            "key": "./tests/localhost.key",
+            "cafile": "./tests/localhost.cert",
+            "client_auth": "required"
+        }
+      }
+    });
+    let harness =
+        ConnectorHarness::new(function_name!(), &tcp::server::Builder::default(), &defn).await?;
+    let out_pipeline = harness
+        .out()
+        .expect("No pipeline connected to 'out' port of tcp_server connector");
+    harness.start().await?;
+    harness.wait_for_connected().await?;
+
+    // a client without certificate is rejected, without stopping the server
+    let anonymous = tls_client_connector(&TLSClientConfig {
+        cafile: Some("./tests/localhost.cert".into()),
+        domain: Some("localhost".to_string()),
+        cert: None,
+        key: None,
+        reload_interval: None,
+    })
+    .await?;
+    let socket = TcpStream::connect(&server_addr).await?;
+    if let Ok(mut stream) = anonymous.connect("localhost", socket).await {
+        // the rejection might only be noticed after the handshake
+        let _ = stream.write_all("snot\n".as_bytes()).await;
+        let _ = stream.flush().await;
+    }
+
+    let authenticated = tls_client_connector(&TLSClientConfig {
+        cafile: Some("./tests/localhost.cert".into()),
+        domain: Some("localhost".to_string()),
+        cert: Some("./tests/localhost.cert".into()),
+        key: Some("./tests/localhost.key".into()),
+        reload_interval: None,
+    })
+    .await?;
+    let socket = TcpStream::connect(&server_addr).await?;
+    let mut stream = authenticated.connect("localhost", socket).await?;
+    stream.write_all("badger\n".as_bytes()).await?;
+    stream.flush().await?;
+
+    let event = out_pipeline.get_event().await?;
+    let (data, meta) = event.data.parts();
+    assert_eq!(&Value::from("badger"), data);
+    let tcp_server_meta = meta.get("tcp_server");
+    assert_eq!(Some(true), tcp_server_meta.get_bool("tls"));
+    let identity = tcp_server_meta.get("peer").get("tls");
+    assert_eq!(Some("CN=localhost"), identity.get_str("subject"));
+    assert_eq!(
+        Some(&literal!(["localhost"])),
+        identity.get("san").get("dns")
+    );
+    assert!(identity.get_str("fingerprint").is_some());
+
+    //cleanup
+    let (_out, err) = harness.stop().await?;
+    assert!(err.is_empty());
+    Ok(())
+}
+
+#[async_std::test]
+async fn syslog_tls() -> Result<()> {
+    let _ = env_logger::try_init();
+    setup_for_tls();
+
+    let free_port = free_port::find_free_tcp_port().await?;
+    let server_addr = format!("127.0.0.1:{free_port}");
+
+    // syslog over TLS as in RFC 5425, accepting non-transparent framing as well
+    let defn = literal!({
+      "codec": "syslog",
+      "preprocessors": ["octet-counting"],
+      "config": {
+        "url": format!("tcp://127.0.0.1:{free_port}"),
+        "tls": {
+            "cert": "./tests/localhost.cert",
+            "key": "./tests/localhost.key"
+        }
+      }
+    });
+    let harness =
+        ConnectorHarness::new(function_name!(), &tcp::server::Builder::default(), &defn).await?;
+    let out_pipeline = harness
+        .out()
+        .expect("No pipeline connected to 'out' port of tcp_server connector");
+    harness.start().await?;
+    harness.wait_for_connected().await?;
+
+    let connector = tls_client_connector(&TLSClientConfig {
+        cafile: Some("./tests/localhost.cert".into()),
+        domain: Some("localhost".to_string()),
+        cert: None,
+        key: None,
+        reload_interval: None,
+    })
+    .await?;
+    let socket = TcpStream::connect(&server_addr).await?;
+    let mut stream = connector.connect("localhost", socket).await?;
+    let msg = "<165>1 2003-10-11T22:14:15.003Z mymachine.example.com evntslog - ID47 [exampleSDID@32473 iut=\"3\"] snot\nbadger";
+    stream
+        .write_all(
+            format!(
+                "{} {msg}<34>Oct 11 22:14:15 mymachine su: grmpf\n",
+                msg.len()
+            )
+            .as_bytes(),
+        )
+        .await?;
+    stream.flush().await?;
+
+    let event = out_pipeline.get_event().await?;
+    let (data, meta) = event.data.parts();
+    assert_eq!(Some("RFC5424"), data.get_str("protocol"));
+    assert_eq!(Some("mymachine.example.com"), data.get_str("hostname"));
+    assert_eq!(Some("snot\nbadger"), data.get_str("msg"));
+    assert_eq!(
+        Some(&literal!({"exampleSDID@32473": [{"iut": "3"}]})),
+        data.get("structured_data")
+    );
+    let peer = meta.get("tcp_server").get("peer");
+    assert_eq!(Some("127.0.0.1"), peer.get_str("host"));
+
+    let event = out_pipeline.get_event().await?;
+    let (data, _meta) = event.data.parts();
+    assert_eq!(Some("RFC3164"), data.get_str("protocol"));
+    assert_eq!(Some("mymachine"), data.get_str("hostname"));
+    assert_eq!(Some("grmpf"), data.get_str("msg"));
+
+    //cleanup
+    let (_out, err) = harness.stop().await?;
+    assert!(err.is_empty());
+    Ok(())
+}
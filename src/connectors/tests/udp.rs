@@ -109,6 +109,9 @@ async fn udp_bind() -> Result<()> {
     // send an event and route it via eventid to socket 2
 
     assert_eq!(server_event.data.parts().0.as_str(), Some("badger"));
+    let peer = server_event.data.parts().1.get("udp_server").get("peer");
+    assert_eq!(Some("127.0.0.1"), peer.get_str("host"));
+    assert_eq!(Some(4244), peer.get_u16("port"));
 
     let (_out, err) = server_harness.stop().await?;
     assert!(err.is_empty());
@@ -172,3 +175,148 @@ async fn connect_ipv6() -> Result<()> {
     let _ = client_harness.stop().await?;
     Ok(())
 }
+
+#[async_std::test]
+async fn udp_multicast() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    let server_defn = literal!({
+      "codec": "string",
+      "config": {
+          "url": "0.0.0.0:4245",
+          "multicast_groups": [
+              {"group": "239.255.42.1", "interface": "127.0.0.1"}
+          ]
+      }
+    });
+
+    let server_harness =
+        ConnectorHarness::new("udp_server", &udp::server::Builder::default(), &server_defn).await?;
+    let server_out = server_harness
+        .out()
+        .expect("No pipeline connected to 'out' port of udp_server connector");
+    server_harness.start().await?;
+    server_harness.wait_for_connected().await?;
+
+    let client_defn = literal!({
+      "codec": "string",
+      "config": {
+          "url": "239.255.42.1:4245",
+          "socket_options": {
+              "IP_MULTICAST_IF": "127.0.0.1",
+              "IP_MULTICAST_LOOP": true,
+              "IP_MULTICAST_TTL": 1
+          }
+      }
+    });
+
+    let client_harness =
+        ConnectorHarness::new("udp_client", &udp::client::Builder::default(), &client_defn).await?;
+    client_harness.start().await?;
+    client_harness.wait_for_connected().await?;
+
+    let event1 = Event {
+        data: (Value::String("badger".into()), literal!({})).into(),
+        ..Event::default()
+    };
+    client_harness.send_to_sink(event1, IN).await?;
+    let server_event = server_out.get_event().await?;
+    assert_eq!(server_event.data.parts().0.as_str(), Some("badger"));
+
+    let (_out, err) = server_harness.stop().await?;
+    assert!(err.is_empty());
+    let (_out, err) = client_harness.stop().await?;
+    assert!(err.is_empty());
+    Ok(())
+}
+
+#[async_std::test]
+async fn udp_multicast_invalid_group() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    let defn = literal!({
+      "codec": "string",
+      "config": {
+          "url": "0.0.0.0:4246",
+          "multicast_groups": [
+              {"group": "127.0.0.1"}
+          ]
+      }
+    });
+    let res = ConnectorHarness::new("udp_server", &udp::server::Builder::default(), &defn).await;
+    assert!(res.is_err());
+
+    let defn = literal!({
+      "codec": "string",
+      "config": {
+          "url": "[::]:4246",
+          "multicast_groups": [
+              {"group": "ff02::42", "interface": "127.0.0.1"}
+          ]
+      }
+    });
+    let res = ConnectorHarness::new("udp_server", &udp::server::Builder::default(), &defn).await;
+    assert!(res.is_err());
+    Ok(())
+}
+
+// sending to the broadcast address of the loopback network is linux specific
+#[cfg(target_os = "linux")]
+#[async_std::test]
+async fn udp_broadcast() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    let server_defn = literal!({
+      "codec": "string",
+      "config": {
+          "url": "0.0.0.0:4247",
+      }
+    });
+
+    let server_harness =
+        ConnectorHarness::new("udp_server", &udp::server::Builder::default(), &server_defn).await?;
+    let server_out = server_harness
+        .out()
+        .expect("No pipeline connected to 'out' port of udp_server connector");
+    server_harness.start().await?;
+    server_harness.wait_for_connected().await?;
+
+    // broadcasts are not permitted without SO_BROADCAST
+    let client_defn = literal!({
+      "codec": "string",
+      "config": {
+          "url": "127.255.255.255:4247",
+      }
+    });
+    let client_harness =
+        ConnectorHarness::new("udp_client", &udp::client::Builder::default(), &client_defn).await?;
+    assert!(client_harness.start().await.is_err());
+
+    let client_defn = literal!({
+      "codec": "string",
+      "config": {
+          "url": "127.255.255.255:4247",
+          "socket_options": {
+              "SO_BROADCAST": true
+          }
+      }
+    });
+    let client_harness =
+        ConnectorHarness::new("udp_client", &udp::client::Builder::default(), &client_defn).await?;
+    client_harness.start().await?;
+    client_harness.wait_for_connected().await?;
+
+    let event1 = Event {
+        data: (Value::String("badger".into()), literal!({})).into(),
+        ..Event::default()
+    };
+    client_harness.send_to_sink(event1, IN).await?;
+    let server_event = server_out.get_event().await?;
+    assert_eq!(server_event.data.parts().0.as_str(), Some("badger"));
+
+    let (_out, err) = server_harness.stop().await?;
+    assert!(err.is_empty());
+    let (_out, err) = client_harness.stop().await?;
+    assert!(err.is_empty());
+    Ok(())
+}
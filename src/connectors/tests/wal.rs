@@ -115,3 +115,90 @@ async fn wal() -> Result<()> {
 
     Ok(())
 }
+
+#[async_std::test]
+async fn wal_cursors() -> Result<()> {
+    let _ = env_logger::try_init();
+    let temp_dir = tempfile::Builder::new().tempdir()?;
+    let path = temp_dir.path().display().to_string();
+
+    let config = literal!({
+        "config": {
+            "path": path.clone(),
+            "chunk_size": 1024,
+            "max_chunks": 100,
+            "cursor": "first"
+        }
+    });
+    let harness =
+        ConnectorHarness::new(function_name!(), &wal::Builder::default(), &config).await?;
+    harness.start().await?;
+    harness.wait_for_connected().await?;
+    harness.consume_initial_sink_contraflow().await?;
+    let out = harness.out().expect("No pipeline connected to WAL out.");
+
+    let mut id_gen = EventIdGenerator::new(SourceId::new(1));
+    for value in ["snot", "badger"] {
+        let event = Event {
+            id: id_gen.next_id(),
+            data: (Value::from(value), Value::object()).into(),
+            ..Event::default()
+        };
+        harness.send_to_sink(event, IN).await?;
+    }
+    let event = out.get_event().await?;
+    assert_eq!(&Value::from("snot"), event.data.suffix().value());
+    harness.send_contraflow(CbAction::Ack, event.id).await?;
+    let event = out.get_event().await?;
+    assert_eq!(&Value::from("badger"), event.data.suffix().value());
+    harness.send_contraflow(CbAction::Ack, event.id).await?;
+
+    // a second connector on the same log reads independently, starting at the given offset
+    let config = literal!({
+        "config": {
+            "path": path.clone(),
+            "chunk_size": 1024,
+            "max_chunks": 100,
+            "cursor": "second",
+            "seek": {"offset": 1}
+        }
+    });
+    let second =
+        ConnectorHarness::new("wal_cursors_second", &wal::Builder::default(), &config).await?;
+    second.start().await?;
+    second.wait_for_connected().await?;
+    second.consume_initial_sink_contraflow().await?;
+    let second_out = second.out().expect("No pipeline connected to WAL out.");
+    let event = second_out.get_event().await?;
+    assert_eq!(&Value::from("badger"), event.data.suffix().value());
+
+    let (_out, err) = second.stop().await?;
+    assert!(err.is_empty());
+    let (_out, err) = harness.stop().await?;
+    assert!(err.is_empty());
+
+    // both cursors are persisted
+    let cursors = temp_dir.path().join("cursors");
+    assert_eq!("2", std::fs::read_to_string(cursors.join("first"))?);
+    assert_eq!("1", std::fs::read_to_string(cursors.join("second"))?);
+
+    // the second flow is gone, its cursor is removed
+    let config = literal!({
+        "config": {
+            "path": path.clone(),
+            "chunk_size": 1024,
+            "max_chunks": 100,
+            "cursor": "first",
+            "remove_cursors": ["second"]
+        }
+    });
+    let third =
+        ConnectorHarness::new("wal_cursors_third", &wal::Builder::default(), &config).await?;
+    third.start().await?;
+    third.wait_for_connected().await?;
+    let (_out, err) = third.stop().await?;
+    assert!(err.is_empty());
+    assert!(cursors.join("first").exists());
+    assert!(!cursors.join("second").exists());
+    Ok(())
+}
@@ -25,7 +25,7 @@ pub(crate) mod quiescence;
 pub(crate) mod reconnect;
 
 /// Transport Level Security facilities
-pub(crate) mod tls;
+pub mod tls;
 
 /// MIME encoding utilities
 pub(crate) mod mime;
@@ -36,6 +36,12 @@ pub(crate) mod pb;
 /// Socket utilities
 pub(crate) mod socket;
 
+/// Polling utilities
+pub(crate) mod poll;
+
+/// Name templates
+pub(crate) mod template;
+
 #[derive(Clone, Debug, Hash, PartialEq, Eq)]
 pub(crate) struct ConnectionMeta {
     pub(crate) host: String,
@@ -26,12 +26,17 @@ const FLOW: Cow<'static, str> = Cow::const_str("flow");
 const CONNECTOR: Cow<'static, str> = Cow::const_str("connector");
 const PORT: Cow<'static, str> = Cow::const_str("port");
 const CONNECTOR_EVENTS: Cow<'static, str> = Cow::const_str("connector_events");
+const CONNECTOR_CONCURRENCY: &str = "connector_concurrency";
+const CONNECTOR_WAL: &str = "connector_wal";
+const CONNECTOR_TLS: &str = "connector_tls";
 
 /// metrics reporter for connector sources
 pub(crate) struct SourceReporter {
     alias: Alias,
     metrics_out: u64,
     metrics_err: u64,
+    /// counts for additional output ports declared by the connector
+    metrics_other: HashMap<Cow<'static, str>, u64>,
     tx: MetricsSender,
     flush_interval_ns: Option<u64>,
     last_flush_ns: u64,
@@ -43,6 +48,7 @@ impl SourceReporter {
             alias,
             metrics_out: 0,
             metrics_err: 0,
+            metrics_other: HashMap::new(),
             tx,
             flush_interval_ns: flush_interval_s.map(|s| s * 1_000_000_000),
             last_flush_ns: 0,
@@ -57,6 +63,14 @@ impl SourceReporter {
         self.metrics_err += 1;
     }
 
+    pub(crate) fn increment_port(&mut self, port: &str) {
+        if let Some(count) = self.metrics_other.get_mut(port) {
+            *count += 1;
+        } else {
+            self.metrics_other.insert(Cow::owned(port.to_string()), 1);
+        }
+    }
+
     /// Flush the metrics and send them out if the flush interval is set and the time has come
     /// returns `Some(timestamp)` if it did flush the system metrics
     pub(crate) fn periodic_flush(&mut self, timestamp: u64) -> Option<u64> {
@@ -68,6 +82,15 @@ impl SourceReporter {
                     make_event_count_metrics_payload(timestamp, ERR, self.metrics_err, &self.alias);
                 send(&self.tx, payload_out, &self.alias);
                 send(&self.tx, payload_err, &self.alias);
+                for (port, count) in &self.metrics_other {
+                    let payload = make_event_count_metrics_payload(
+                        timestamp,
+                        port.clone(),
+                        *count,
+                        &self.alias,
+                    );
+                    send(&self.tx, payload, &self.alias);
+                }
                 self.last_flush_ns = timestamp;
                 return Some(timestamp);
             }
@@ -158,6 +181,64 @@ pub(crate) fn make_event_count_metrics_payload(
     (value, Value::object()).into()
 }
 
+/// metrics payload for the concurrency limit and the number of in flight requests of a sink
+#[must_use]
+pub(crate) fn make_concurrency_metrics_payload(
+    timestamp: u64,
+    limit: usize,
+    inflight: usize,
+    connector_id: &Alias,
+) -> EventPayload {
+    let mut tags: HashMap<Cow<'static, str>, Value<'static>> = HashMap::with_capacity(2);
+    tags.insert_nocheck(FLOW, Value::from(connector_id.flow_alias().to_string()));
+    tags.insert_nocheck(CONNECTOR, connector_id.to_string().into());
+
+    let mut fields: HashMap<Cow<'static, str>, Value<'static>> = HashMap::with_capacity(2);
+    fields.insert_nocheck(Cow::const_str("limit"), Value::from(limit));
+    fields.insert_nocheck(Cow::const_str("inflight"), Value::from(inflight));
+    make_metrics_payload(CONNECTOR_CONCURRENCY, fields, tags, timestamp)
+}
+
+/// metrics payload for the size of a write-ahead log and the lag of one of its cursors
+#[must_use]
+pub(crate) fn make_wal_metrics_payload(
+    timestamp: u64,
+    cursor: &str,
+    size_bytes: u64,
+    entries: u64,
+    lag: u64,
+    connector_id: &Alias,
+) -> EventPayload {
+    let mut tags: HashMap<Cow<'static, str>, Value<'static>> = HashMap::with_capacity(3);
+    tags.insert_nocheck(FLOW, Value::from(connector_id.flow_alias().to_string()));
+    tags.insert_nocheck(CONNECTOR, connector_id.to_string().into());
+    tags.insert_nocheck(Cow::const_str("cursor"), Value::from(cursor.to_string()));
+
+    let mut fields: HashMap<Cow<'static, str>, Value<'static>> = HashMap::with_capacity(3);
+    fields.insert_nocheck(Cow::const_str("size_bytes"), Value::from(size_bytes));
+    fields.insert_nocheck(Cow::const_str("entries"), Value::from(entries));
+    fields.insert_nocheck(Cow::const_str("lag"), Value::from(lag));
+    make_metrics_payload(CONNECTOR_WAL, fields, tags, timestamp)
+}
+
+/// metrics payload for the number of successful and failed reloads of a TLS config
+#[must_use]
+pub(crate) fn make_tls_reload_metrics_payload(
+    timestamp: u64,
+    reloads: u64,
+    failures: u64,
+    connector_id: &Alias,
+) -> EventPayload {
+    let mut tags: HashMap<Cow<'static, str>, Value<'static>> = HashMap::with_capacity(2);
+    tags.insert_nocheck(FLOW, Value::from(connector_id.flow_alias().to_string()));
+    tags.insert_nocheck(CONNECTOR, connector_id.to_string().into());
+
+    let mut fields: HashMap<Cow<'static, str>, Value<'static>> = HashMap::with_capacity(2);
+    fields.insert_nocheck(Cow::const_str("reloads"), Value::from(reloads));
+    fields.insert_nocheck(Cow::const_str("reload_failures"), Value::from(failures));
+    make_metrics_payload(CONNECTOR_TLS, fields, tags, timestamp)
+}
+
 // TODO: add convenience functions for creating custom metrics payloads
 #[must_use]
 pub(crate) fn make_metrics_payload(
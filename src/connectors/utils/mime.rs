@@ -28,7 +28,7 @@ const MIME_TYPES: [(&str, &str); 10] = [
 ];
 
 /// additional mapping from codec to mime-types
-const CODEC_TO_MIME_TYPES: [(&str, &str); 12] = [
+const CODEC_TO_MIME_TYPES: [(&str, &str); 14] = [
     ("json-sorted", "application/json"),
     ("json", "application/json"),
     ("csv", "text/csv"),
@@ -41,6 +41,8 @@ const CODEC_TO_MIME_TYPES: [(&str, &str); 12] = [
     ("binflux", "application/octet-stream"),
     ("statsd", "text/plain"),
     ("dogstatsd", "text/plain"),
+    ("parquet", "application/vnd.apache.parquet"),
+    ("arrow", "application/vnd.apache.arrow.file"),
 ];
 
 /// Map from mime-type / content-type to codec name
@@ -0,0 +1,103 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Facilities for sources polling an external system periodically,
+//! remembering where they left off across restarts
+
+use crate::errors::Result;
+use async_std::{path::Path, task};
+use serde::{de::DeserializeOwned, Serialize};
+use std::time::Duration;
+use tremor_common::{file, time::nanotime};
+
+/// Ticks at a fixed rate, measured from the start of one tick to the start of the next
+pub(crate) struct Interval {
+    /// nanoseconds between two ticks
+    interval: u64,
+    next: u64,
+}
+
+impl Interval {
+    /// ticks every `interval` nanoseconds, starting immediately
+    pub(crate) fn new(interval: u64) -> Self {
+        Self {
+            interval,
+            next: nanotime(),
+        }
+    }
+
+    /// wait for the next tick, if the previous one took longer than the interval it is due immediately
+    pub(crate) async fn tick(&mut self) {
+        let now = nanotime();
+        if now < self.next {
+            task::sleep(Duration::from_nanos(self.next - now)).await;
+        }
+        self.next = nanotime() + self.interval;
+    }
+}
+
+/// Load the state persisted at `path`, a missing file results in the initial state
+pub(crate) async fn load_state<T>(path: &Path) -> Result<T>
+where
+    T: DeserializeOwned + Default,
+{
+    if path.exists().await {
+        let mut data = async_std::fs::read(path).await?;
+        Ok(simd_json::from_slice(&mut data)?)
+    } else {
+        Ok(T::default())
+    }
+}
+
+/// Persist `state` at `path`, replacing the file atomically
+pub(crate) async fn save_state<T>(path: &Path, state: &T) -> Result<()>
+where
+    T: Serialize,
+{
+    let data = simd_json::to_vec(state)?;
+    let path = path.to_path_buf();
+    task::spawn_blocking(move || file::replace(&path, &data)).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    #[async_std::test]
+    async fn state_roundtrip() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("state.json");
+        let path = Path::new(path.as_os_str());
+        let state: BTreeSet<String> = load_state(path).await?;
+        assert!(state.is_empty());
+
+        let state: BTreeSet<String> = ["snot".to_string(), "badger".to_string()].into();
+        save_state(path, &state).await?;
+        assert_eq!(state, load_state(path).await?);
+        assert!(!path.with_extension("json.tmp").exists().await);
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn interval() {
+        let mut interval = Interval::new(10_000_000);
+        let start = nanotime();
+        interval.tick().await;
+        interval.tick().await;
+        interval.tick().await;
+        assert!(nanotime() - start >= 20_000_000);
+    }
+}
@@ -369,6 +369,12 @@ impl ReconnectRuntime {
         }
     }
 
+    /// start over with the reconnect strategy, as if there were no failed attempts
+    pub(crate) fn restart(&mut self) {
+        self.attempt.since_last_success = 0;
+        self.interval_ms = None;
+    }
+
     /// reset internal state after successful connect attempt
     fn reset(&mut self) {
         self.attempt.on_success();
@@ -465,6 +471,12 @@ mod tests {
         );
         async_std::task::sleep(Duration::from_millis(100)).await;
         assert!(rx.is_empty()); // no reconnect attempt has been made
+
+        // a restart allows another attempt
+        runtime.restart();
+        assert!(runtime.enqueue_retry(&ctx).await);
+        runtime.await_retry().await;
+        assert!(matches!(rx.try_recv(), Ok(Msg::Reconnect)));
         Ok(())
     }
 
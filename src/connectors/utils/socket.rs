@@ -17,6 +17,7 @@ use crate::connectors::utils::url::{Defaults, Url};
 use crate::errors::{Error, Result};
 use async_std::net::{TcpListener, TcpStream, ToSocketAddrs, UdpSocket};
 use socket2::{Domain, Protocol, SockAddr, Socket, Type};
+use std::net::{IpAddr, Ipv4Addr};
 
 #[derive(Debug, Clone, Deserialize)]
 #[serde(deny_unknown_fields, rename_all = "UPPERCASE")]
@@ -25,6 +26,21 @@ pub(crate) struct UdpSocketOptions {
     so_reuseport: bool,
     #[serde(default = "default_true")]
     so_reuseaddr: bool,
+    /// allow sending datagrams to broadcast addresses
+    #[serde(default = "default_false")]
+    so_broadcast: bool,
+    /// TTL of outgoing multicast datagrams, the hop limit for IPv6
+    #[serde(default)]
+    ip_multicast_ttl: Option<u32>,
+    /// whether outgoing multicast datagrams are looped back to local sockets
+    #[serde(default)]
+    ip_multicast_loop: Option<bool>,
+    /// address of the local interface to send IPv4 multicast datagrams from
+    #[serde(default)]
+    ip_multicast_if: Option<Ipv4Addr>,
+    /// index of the local interface to send IPv6 multicast datagrams from
+    #[serde(default)]
+    ipv6_multicast_if: Option<u32>,
     // TODO: add more options
 }
 
@@ -33,15 +49,99 @@ impl Default for UdpSocketOptions {
         Self {
             so_reuseport: false,
             so_reuseaddr: true,
+            so_broadcast: false,
+            ip_multicast_ttl: None,
+            ip_multicast_loop: None,
+            ip_multicast_if: None,
+            ipv6_multicast_if: None,
         }
     }
 }
 
 impl UdpSocketOptions {
     /// apply the given config to `sock`
-    fn apply_to(&self, sock: &Socket) -> Result<()> {
+    fn apply_to(&self, sock: &Socket, domain: Domain) -> Result<()> {
         sock.set_reuse_port(self.so_reuseport)?;
         sock.set_reuse_address(self.so_reuseaddr)?;
+        if self.so_broadcast {
+            sock.set_broadcast(true)?;
+        }
+        if domain == Domain::IPV6 {
+            if self.ip_multicast_if.is_some() {
+                return Err("IP_MULTICAST_IF is only supported for IPv4 sockets".into());
+            }
+            if let Some(hops) = self.ip_multicast_ttl {
+                sock.set_multicast_hops_v6(hops)?;
+            }
+            if let Some(multicast_loop) = self.ip_multicast_loop {
+                sock.set_multicast_loop_v6(multicast_loop)?;
+            }
+            if let Some(interface) = self.ipv6_multicast_if {
+                sock.set_multicast_if_v6(interface)?;
+            }
+        } else {
+            if self.ipv6_multicast_if.is_some() {
+                return Err("IPV6_MULTICAST_IF is only supported for IPv6 sockets".into());
+            }
+            if let Some(ttl) = self.ip_multicast_ttl {
+                sock.set_multicast_ttl_v4(ttl)?;
+            }
+            if let Some(multicast_loop) = self.ip_multicast_loop {
+                sock.set_multicast_loop_v4(multicast_loop)?;
+            }
+            if let Some(interface) = self.ip_multicast_if.as_ref() {
+                sock.set_multicast_if_v4(interface)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A multicast group for a UDP socket to join
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct MulticastGroup {
+    /// address of the group
+    group: IpAddr,
+    /// address of the local interface to join an IPv4 group on, any interface if not set
+    #[serde(default)]
+    interface: Option<Ipv4Addr>,
+    /// index of the local interface to join an IPv6 group on, any interface if not set
+    #[serde(default)]
+    interface_index: Option<u32>,
+}
+
+impl MulticastGroup {
+    /// check that the group is a multicast address with an interface of its family
+    pub(crate) fn validate(&self) -> Result<()> {
+        if !self.group.is_multicast() {
+            return Err(format!("{} is not a multicast address", self.group).into());
+        }
+        match self.group {
+            IpAddr::V4(_) if self.interface_index.is_some() => Err(format!(
+                "`interface_index` is only supported for IPv6 groups, use `interface` for {}",
+                self.group
+            )
+            .into()),
+            IpAddr::V6(_) if self.interface.is_some() => Err(format!(
+                "`interface` is only supported for IPv4 groups, use `interface_index` for {}",
+                self.group
+            )
+            .into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// join the group on `socket`
+    pub(crate) fn join(&self, socket: &UdpSocket) -> Result<()> {
+        match self.group {
+            IpAddr::V4(group) => {
+                socket.join_multicast_v4(group, self.interface.unwrap_or(Ipv4Addr::UNSPECIFIED))?;
+            }
+            IpAddr::V6(group) => {
+                socket.join_multicast_v6(&group, self.interface_index.unwrap_or_default())?;
+            }
+        }
         Ok(())
     }
 }
@@ -58,14 +158,11 @@ pub(crate) async fn udp_socket<D: Defaults>(
         let socket_addr = sock_addr
             .as_socket()
             .ok_or_else(|| format!("Invalid address {}:{}", host_port.0, host_port.1))?;
-        let sock = Socket::new(
-            Domain::for_address(socket_addr),
-            Type::DGRAM,
-            Some(Protocol::UDP),
-        )?;
+        let domain = Domain::for_address(socket_addr);
+        let sock = Socket::new(domain, Type::DGRAM, Some(Protocol::UDP))?;
 
         // apply socket options
-        options.apply_to(&sock)?;
+        options.apply_to(&sock, domain)?;
 
         match sock.bind(&sock_addr) {
             Ok(()) => {
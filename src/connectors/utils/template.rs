@@ -0,0 +1,269 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Templates for names of things connectors create, e.g. object keys or rotated files
+//!
+//! Placeholders:
+//!
+//! * `{yyyy}`, `{MM}`, `{dd}`, `{HH}`, `{mm}` and `{ss}`: date and time in UTC
+//! * `{uuid}`: a unique id
+//! * `{meta.<path>}`: a (nested) field of the connector metadata
+//! * `{name}`: the name of the thing the templated one derives from, e.g. a file name
+//! * `{n}`: a counter
+//!
+//! Every user decides which placeholders make sense for it.
+
+use crate::errors::{Error, Result};
+use chrono::{Datelike, TimeZone, Timelike, Utc};
+use regex::Regex;
+use tremor_value::Value;
+use value_trait::ValueAccess;
+
+/// A part of a [`Template`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Part {
+    Literal(String),
+    /// `{yyyy}`
+    Year,
+    /// `{MM}`
+    Month,
+    /// `{dd}`
+    Day,
+    /// `{HH}`
+    Hour,
+    /// `{mm}`
+    Minute,
+    /// `{ss}`
+    Second,
+    /// `{uuid}`
+    Uuid,
+    /// `{meta.<path>}`
+    Meta(Vec<String>),
+    /// `{name}`
+    Name,
+    /// `{n}`
+    N,
+}
+
+/// Template for names, e.g. `logs/{yyyy}/{MM}/{dd}/{HH}/{uuid}.json.gz`
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(try_from = "String")]
+pub(crate) struct Template {
+    parts: Vec<Part>,
+}
+
+/// Values for the placeholders other than date and time
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct Values<'v> {
+    pub(crate) uuid: &'v str,
+    pub(crate) meta: Option<&'v Value<'v>>,
+    /// connector type the metadata of `{meta.<path>}` belongs to, for errors
+    pub(crate) connector_type: &'v str,
+    pub(crate) name: &'v str,
+    pub(crate) n: u64,
+}
+
+impl TryFrom<String> for Template {
+    type Error = Error;
+
+    fn try_from(template: String) -> Result<Self> {
+        Self::parse(&template)
+    }
+}
+
+impl Template {
+    pub(crate) fn parse(template: &str) -> Result<Self> {
+        let mut parts = Vec::new();
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            if start > 0 {
+                parts.push(Part::Literal(rest[..start].to_string()));
+            }
+            let end = rest[start..]
+                .find('}')
+                .ok_or_else(|| format!("Unterminated placeholder in template `{template}`"))?
+                + start;
+            let part = match &rest[start + 1..end] {
+                "yyyy" => Part::Year,
+                "MM" => Part::Month,
+                "dd" => Part::Day,
+                "HH" => Part::Hour,
+                "mm" => Part::Minute,
+                "ss" => Part::Second,
+                "uuid" => Part::Uuid,
+                "name" => Part::Name,
+                "n" => Part::N,
+                placeholder => match placeholder.strip_prefix("meta.") {
+                    Some(path) if !path.is_empty() => {
+                        Part::Meta(path.split('.').map(ToString::to_string).collect())
+                    }
+                    _ => {
+                        return Err(format!(
+                            "Unknown placeholder `{{{placeholder}}}` in template `{template}`"
+                        )
+                        .into())
+                    }
+                },
+            };
+            parts.push(part);
+            rest = &rest[end + 1..];
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Literal(rest.to_string()));
+        }
+        Ok(Self { parts })
+    }
+
+    pub(crate) fn contains(&self, part: &Part) -> bool {
+        self.parts.contains(part)
+    }
+
+    /// Returns `true` if the template has `{meta.<path>}` placeholders
+    pub(crate) fn has_meta(&self) -> bool {
+        self.parts.iter().any(|part| matches!(part, Part::Meta(_)))
+    }
+
+    /// Render the template at `time_ns`
+    pub(crate) fn render(&self, time_ns: u64, values: &Values) -> Result<String> {
+        #[allow(clippy::cast_possible_wrap)] // timestamps are far off i64::MAX
+        let time = Utc.timestamp_nanos(time_ns as i64);
+        let mut rendered = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Literal(literal) => rendered.push_str(literal),
+                Part::Year => rendered.push_str(&format!("{:04}", time.year())),
+                Part::Month => rendered.push_str(&format!("{:02}", time.month())),
+                Part::Day => rendered.push_str(&format!("{:02}", time.day())),
+                Part::Hour => rendered.push_str(&format!("{:02}", time.hour())),
+                Part::Minute => rendered.push_str(&format!("{:02}", time.minute())),
+                Part::Second => rendered.push_str(&format!("{:02}", time.second())),
+                Part::Uuid => rendered.push_str(values.uuid),
+                Part::Meta(path) => {
+                    let value = path
+                        .iter()
+                        .fold(values.meta, |value, field| value.get(field.as_str()))
+                        .ok_or_else(|| {
+                            format!(
+                                "`${}.{}` metadata is missing",
+                                values.connector_type,
+                                path.join(".")
+                            )
+                        })?;
+                    if let Some(s) = value.as_str() {
+                        rendered.push_str(s);
+                    } else {
+                        rendered.push_str(&value.encode());
+                    }
+                }
+                Part::Name => rendered.push_str(values.name),
+                Part::N => rendered.push_str(&values.n.to_string()),
+            }
+        }
+        Ok(rendered)
+    }
+
+    /// Regex matching everything rendered from this template with the given `{name}`, with an optional `extension`
+    ///
+    /// Templates with `{meta.<path>}` placeholders can't be matched.
+    pub(crate) fn matcher(&self, name: &str, extension: Option<&str>) -> Result<Regex> {
+        let mut re = String::from("^");
+        for part in &self.parts {
+            match part {
+                Part::Literal(literal) => re.push_str(&regex::escape(literal)),
+                Part::Name => re.push_str(&regex::escape(name)),
+                Part::Year => re.push_str(r"\d{4}"),
+                Part::Month | Part::Day | Part::Hour | Part::Minute | Part::Second => {
+                    re.push_str(r"\d{2}");
+                }
+                Part::N => re.push_str(r"\d+"),
+                Part::Uuid => re.push_str(r"[0-9a-fA-F-]+"),
+                Part::Meta(_) => return Err("Templates with metadata can't be matched".into()),
+            }
+        }
+        if let Some(extension) = extension {
+            re.push_str(&format!(r"(\.{})?", regex::escape(extension)));
+        }
+        re.push('$');
+        Ok(Regex::new(&re)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tremor_value::literal;
+
+    // 2022-10-18T13:37:42Z
+    const TIME_NS: u64 = 1_666_100_262_000_000_000;
+
+    #[test]
+    fn render() -> Result<()> {
+        let template =
+            Template::parse("logs/{yyyy}/{MM}/{dd}/{HH}/{mm}{ss}/{meta.tenant.name}-{uuid}.gz")?;
+        let meta = literal!({"tenant": {"name": "snot"}});
+        let values = Values {
+            uuid: "badger",
+            meta: Some(&meta),
+            connector_type: "s3_streamer",
+            ..Values::default()
+        };
+        assert_eq!(
+            "logs/2022/10/18/13/3742/snot-badger.gz",
+            template.render(TIME_NS, &values)?
+        );
+        let meta = literal!({"tenant": {"name": 42}});
+        let values = Values {
+            meta: Some(&meta),
+            ..values
+        };
+        assert_eq!(
+            "logs/2022/10/18/13/3742/42-badger.gz",
+            template.render(TIME_NS, &values)?
+        );
+        let values = Values {
+            meta: None,
+            ..values
+        };
+        let e = template.render(TIME_NS, &values).expect_err("missing meta");
+        assert!(e.to_string().contains("`$s3_streamer.tenant.name`"));
+
+        let template = Template::parse("{name}.{n}")?;
+        let values = Values {
+            name: "out.log",
+            n: 3,
+            ..Values::default()
+        };
+        assert_eq!("out.log.3", template.render(TIME_NS, &values)?);
+
+        assert!(Template::parse("{yyyy").is_err());
+        assert!(Template::parse("{snot}").is_err());
+        assert!(Template::parse("{meta.}").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn matcher() -> Result<()> {
+        let template = Template::parse("{name}-{yyyy}-{MM}-{dd}T{HH}:{mm}:{ss}.{n}")?;
+        let matcher = template.matcher("out.log", Some("gz"))?;
+        assert!(matcher.is_match("out.log-2022-10-18T13:37:42.3"));
+        assert!(matcher.is_match("out.log-2022-10-18T13:37:42.12.gz"));
+        assert!(!matcher.is_match("out.log"));
+        assert!(!matcher.is_match("outxlog-2022-10-18T13:37:42.3"));
+        assert!(!matcher.is_match("out.log-2022-10-18T13:37:42.3.xz"));
+        assert!(Template::parse("{meta.snot}")?
+            .matcher("out.log", None)
+            .is_err());
+        Ok(())
+    }
+}
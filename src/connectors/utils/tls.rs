@@ -15,13 +15,27 @@
 //! TLS utilities
 
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, SystemTime};
 
+use crate::connectors::utils::metrics::make_tls_reload_metrics_payload;
+use crate::connectors::{Alias, HANDSHAKE_TIMEOUT};
 use crate::errors::{Error, Kind as ErrorKind, Result};
+use async_rustls::TlsAcceptor;
+use async_std::{net::TcpStream, prelude::FutureExt, task};
 use async_tls::TlsConnector;
 use rustls::internal::pemfile::{certs, pkcs8_private_keys, rsa_private_keys};
-use rustls::{Certificate, ClientConfig, NoClientAuth, PrivateKey, RootCertStore, ServerConfig};
+use rustls::{
+    AllowAnyAnonymousOrAuthenticatedClient, AllowAnyAuthenticatedClient, Certificate, ClientConfig,
+    NoClientAuth, PrivateKey, RootCertStore, ServerConfig, Session,
+};
 use rustls_native_certs::load_native_certs;
+use sha2::{Digest, Sha256};
 use std::io::{BufReader, Cursor};
+use std::net::{IpAddr, SocketAddr};
+use tremor_script::EventPayload;
+use tremor_value::prelude::*;
+use x509_parser::extensions::GeneralName;
 
 lazy_static! {
     static ref SYSTEM_ROOT_CERTS: RootCertStore = {
@@ -37,10 +51,50 @@ lazy_static! {
     };
 }
 
+/// Whether TLS servers ask clients for certificates
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ClientAuth {
+    /// clients are not asked for certificates
+    None,
+    /// clients may authenticate with a certificate, verified against the `cafile`
+    Optional,
+    /// clients need to authenticate with a certificate, verified against the `cafile`
+    Required,
+}
+
+/// TLS configuration of servers
 #[derive(Debug, Clone, Deserialize)]
-pub(crate) struct TLSServerConfig {
-    pub(crate) cert: PathBuf,
-    pub(crate) key: PathBuf,
+pub struct TLSServerConfig {
+    /// Path to the pem-encoded certificate (-chain) of the server
+    pub cert: PathBuf,
+    /// Path to the private key of the server
+    pub key: PathBuf,
+    /// Path to the pem-encoded certificate file of the CA to use for verifying client certificates.
+    /// If not provided, clients are not asked for certificates.
+    #[serde(default = "Default::default")]
+    pub cafile: Option<PathBuf>,
+    /// Whether clients are asked for certificates, one of `none`, `optional` or `required`.
+    /// Defaults to `optional` if a `cafile` is provided, `none` otherwise.
+    #[serde(default = "Default::default")]
+    pub client_auth: Option<ClientAuth>,
+    /// Interval in nanoseconds to check the certificate, key and CA files for changes.
+    /// New connections use the reloaded files, existing connections are kept.
+    /// If not provided, the files are only read once.
+    #[serde(default = "Default::default")]
+    pub reload_interval: Option<u64>,
+}
+
+impl TLSServerConfig {
+    /// The effective client auth mode
+    #[must_use]
+    pub fn client_auth(&self) -> ClientAuth {
+        self.client_auth.unwrap_or(if self.cafile.is_some() {
+            ClientAuth::Optional
+        } else {
+            ClientAuth::None
+        })
+    }
 }
 
 #[derive(Deserialize, Debug, Default, Clone)]
@@ -54,6 +108,11 @@ pub(crate) struct TLSClientConfig {
     pub(crate) cert: Option<PathBuf>,
     /// Path to the private key to use for TLS with client-side certificate
     pub(crate) key: Option<PathBuf>,
+    /// Interval in nanoseconds to check the certificate, key and CA files for changes.
+    /// New connections use the reloaded files, existing connections are kept.
+    /// If not provided, the files are only read once.
+    #[serde(default = "Default::default")]
+    pub(crate) reload_interval: Option<u64>,
 }
 
 /// Load the passed certificates file
@@ -117,12 +176,39 @@ pub(crate) fn load_keys(path: &Path) -> Result<PrivateKey> {
     }
 }
 
-pub(crate) fn load_server_config(config: &TLSServerConfig) -> Result<ServerConfig> {
+/// Load the rustls server config for the given TLS config
+///
+/// # Errors
+///   * if the certificates or key can't be loaded
+pub fn load_server_config(config: &TLSServerConfig) -> Result<ServerConfig> {
     let certs = load_certs(&config.cert)?;
 
     let keys = load_keys(&config.key)?;
 
-    let mut server_config = ServerConfig::new(NoClientAuth::new());
+    let client_auth = config.client_auth();
+    let mut server_config = if client_auth == ClientAuth::None {
+        ServerConfig::new(NoClientAuth::new())
+    } else {
+        let cafile = config.cafile.as_ref().ok_or_else(|| {
+            Error::from(ErrorKind::TLSError(
+                "Verifying client certificates requires a `cafile`".to_string(),
+            ))
+        })?;
+        let mut roots = RootCertStore::empty();
+        for ca_cert in load_certs(cafile)? {
+            roots.add(&ca_cert).map_err(|e| {
+                Error::from(ErrorKind::TLSError(format!(
+                    "Invalid CA certificate in {}: {e}",
+                    cafile.display()
+                )))
+            })?;
+        }
+        if client_auth == ClientAuth::Required {
+            ServerConfig::new(AllowAnyAuthenticatedClient::new(roots))
+        } else {
+            ServerConfig::new(AllowAnyAnonymousOrAuthenticatedClient::new(roots))
+        }
+    };
     server_config
         // set this server to use one cert together with the loaded private key
         .set_single_cert(certs, keys)?;
@@ -130,6 +216,249 @@ pub(crate) fn load_server_config(config: &TLSServerConfig) -> Result<ServerConfi
     Ok(server_config)
 }
 
+/// lower bound of the interval to check TLS files for changes
+const MIN_RELOAD_INTERVAL: Duration = Duration::from_millis(10);
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// A TLS config that is loaded again from its files once they change
+///
+/// The files are checked every `interval` nanoseconds by a background task, which stops once
+/// the last clone of the config is dropped. If loading fails, the previous config is kept until
+/// the files change again. Handing out the current config never touches the filesystem.
+pub(crate) struct Reloadable<C> {
+    inner: Arc<ReloadableInner<C>>,
+}
+
+struct ReloadableInner<C> {
+    files: Vec<PathBuf>,
+    load: Box<dyn Fn() -> Result<C> + Send + Sync>,
+    state: Mutex<ReloadState<C>>,
+}
+
+struct ReloadState<C> {
+    current: Arc<C>,
+    modified: Vec<Option<SystemTime>>,
+    reloads: u64,
+    failures: u64,
+}
+
+impl<C> Clone for Reloadable<C> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<C> Reloadable<C> {
+    fn new<F>(files: Vec<PathBuf>, interval: Option<u64>, load: F) -> Result<Self>
+    where
+        F: Fn() -> Result<C> + Send + Sync + 'static,
+        C: Send + Sync + 'static,
+    {
+        let modified = files.iter().map(|f| modified(f)).collect();
+        let current = Arc::new(load()?);
+        let reloadable = Self {
+            inner: Arc::new(ReloadableInner {
+                files,
+                load: Box::new(load),
+                state: Mutex::new(ReloadState {
+                    current,
+                    modified,
+                    reloads: 0,
+                    failures: 0,
+                }),
+            }),
+        };
+        if let Some(interval) = interval {
+            let interval = Duration::from_nanos(interval).max(MIN_RELOAD_INTERVAL);
+            let inner = Arc::downgrade(&reloadable.inner);
+            task::spawn(async move {
+                loop {
+                    task::sleep(interval).await;
+                    if let Some(inner) = inner.upgrade() {
+                        let reloadable = Self { inner };
+                        task::spawn_blocking(move || reloadable.check()).await;
+                    } else {
+                        break;
+                    }
+                }
+            });
+        }
+        Ok(reloadable)
+    }
+
+    fn state(&self) -> MutexGuard<'_, ReloadState<C>> {
+        match self.inner.state.lock() {
+            Ok(state) => state,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    /// Checks the files for changes and loads the config again if they changed
+    ///
+    /// This blocks on the filesystem, it is only called from the reload task
+    fn check(&self) {
+        let inner = &self.inner;
+        let modified: Vec<_> = inner.files.iter().map(|f| modified(f)).collect();
+        if modified == self.state().modified {
+            return;
+        }
+        let loaded = (inner.load)();
+        let mut state = self.state();
+        state.modified = modified;
+        match loaded {
+            Ok(config) => {
+                info!("Reloaded TLS config from {:?}", inner.files);
+                state.current = Arc::new(config);
+                state.reloads += 1;
+            }
+            Err(e) => {
+                error!("Error reloading TLS config from {:?}: {e}", inner.files);
+                state.failures += 1;
+            }
+        }
+    }
+
+    /// The current config
+    pub(crate) fn current(&self) -> Arc<C> {
+        self.state().current.clone()
+    }
+
+    /// Changes every time a new config got loaded, to tell if `current` changed
+    pub(crate) fn generation(&self) -> u64 {
+        self.state().reloads
+    }
+
+    /// Metrics of successful and failed reloads
+    pub(crate) fn metrics(&self, timestamp: u64, alias: &Alias) -> EventPayload {
+        let state = self.state();
+        make_tls_reload_metrics_payload(timestamp, state.reloads, state.failures, alias)
+    }
+}
+
+impl Reloadable<ServerConfig> {
+    pub(crate) fn server(config: &TLSServerConfig) -> Result<Self> {
+        let files = [
+            Some(&config.cert),
+            Some(&config.key),
+            config.cafile.as_ref(),
+        ]
+        .into_iter()
+        .flatten()
+        .cloned()
+        .collect();
+        let config = config.clone();
+        Self::new(files, config.reload_interval, move || {
+            load_server_config(&config)
+        })
+    }
+}
+
+impl Reloadable<ClientConfig> {
+    pub(crate) fn client(config: &TLSClientConfig) -> Result<Self> {
+        let files = [
+            config.cafile.as_ref(),
+            config.cert.as_ref(),
+            config.key.as_ref(),
+        ]
+        .into_iter()
+        .flatten()
+        .cloned()
+        .collect();
+        let config = config.clone();
+        Self::new(files, config.reload_interval, move || {
+            load_client_config(&config)
+        })
+    }
+}
+
+/// Identity of the verified certificate chain of a TLS peer, for connection metadata
+///
+/// Contains the `subject`, `issuer` and subject alternative names (`san`) of the peer certificate
+/// and its SHA-256 `fingerprint`, `None` if the peer did not present a certificate.
+#[must_use]
+pub fn peer_identity(certs: &[Certificate]) -> Option<Value<'static>> {
+    let cert = certs.first()?;
+    let mut identity = literal!({
+        "fingerprint": hex::encode(Sha256::digest(&cert.0)),
+    });
+    // the certificate was verified during the handshake, so this only fails for unsupported content
+    if let Ok((_, parsed)) = x509_parser::parse_x509_certificate(&cert.0) {
+        let mut dns = Vec::new();
+        let mut email = Vec::new();
+        let mut uri = Vec::new();
+        let mut ip = Vec::new();
+        if let Ok(Some(san)) = parsed.subject_alternative_name() {
+            for name in &san.value.general_names {
+                match name {
+                    GeneralName::DNSName(name) => dns.push(Value::from(name.to_string())),
+                    GeneralName::RFC822Name(name) => email.push(Value::from(name.to_string())),
+                    GeneralName::URI(name) => uri.push(Value::from(name.to_string())),
+                    GeneralName::IPAddress(bytes) => {
+                        let addr = <[u8; 4]>::try_from(*bytes)
+                            .map(IpAddr::from)
+                            .or_else(|_| <[u8; 16]>::try_from(*bytes).map(IpAddr::from));
+                        if let Ok(addr) = addr {
+                            ip.push(Value::from(addr.to_string()));
+                        }
+                    }
+                    _ => (),
+                }
+            }
+        }
+        identity.try_insert("subject", parsed.subject().to_string());
+        identity.try_insert("issuer", parsed.issuer().to_string());
+        identity.try_insert(
+            "san",
+            literal!({ "dns": dns, "email": email, "uri": uri, "ip": ip }),
+        );
+    }
+    Some(identity)
+}
+
+/// A verified client certificate chain, attached to requests over TLS connections
+#[derive(Clone, Debug)]
+pub struct PeerCertificates(pub Vec<Certificate>);
+
+/// Serve HTTP on a single TLS connection, making the verified client certificates
+/// available to the request handlers as [`PeerCertificates`]
+///
+/// # Errors
+///   * if the TLS handshake fails or doesn't finish within `HANDSHAKE_TIMEOUT`
+///   * if serving HTTP on the connection fails
+pub async fn serve_tls<State: Clone + Send + Sync + 'static>(
+    stream: TcpStream,
+    peer_addr: SocketAddr,
+    acceptor: TlsAcceptor,
+    server: tide::Server<State>,
+) -> http_types::Result<()> {
+    let local_addr = stream.local_addr().ok();
+    let stream = acceptor.accept(stream).timeout(HANDSHAKE_TIMEOUT).await??;
+    let peer_certificates = stream
+        .get_ref()
+        .1
+        .get_peer_certificates()
+        .map(PeerCertificates);
+    let stream = async_dup::Arc::new(async_dup::Mutex::new(stream));
+    async_h1::accept(stream, |mut req| {
+        let server = server.clone();
+        let peer_certificates = peer_certificates.clone();
+        async move {
+            req.set_local_addr(local_addr);
+            req.set_peer_addr(Some(peer_addr));
+            if let Some(peer_certificates) = peer_certificates {
+                req.ext_mut().insert(peer_certificates);
+            }
+            server.respond(req).await
+        }
+    })
+    .await
+}
+
 /// if we have a cafile configured, we only load it, and no other ca certificates
 /// if there is no cafile configured, we load the default webpki-roots from Mozilla
 pub(crate) async fn tls_client_connector(config: &TLSClientConfig) -> Result<TlsConnector> {
@@ -138,10 +467,15 @@ pub(crate) async fn tls_client_connector(config: &TLSClientConfig) -> Result<Tls
 }
 
 pub(crate) async fn tls_client_config(tremor_config: &TLSClientConfig) -> Result<ClientConfig> {
+    let tremor_config = tremor_config.clone();
+    async_std::task::spawn_blocking(move || load_client_config(&tremor_config)).await
+}
+
+pub(crate) fn load_client_config(tremor_config: &TLSClientConfig) -> Result<ClientConfig> {
     let mut tls_config = ClientConfig::new();
     // load server cert verification stuff
     if let Some(cafile) = tremor_config.cafile.as_ref() {
-        let file = async_std::fs::read(cafile).await?;
+        let file = std::fs::read(cafile)?;
         let mut pem = Cursor::new(file);
         tls_config.root_store.add_pem_file(&mut pem).map_err(|_e| {
             Error::from(ErrorKind::TLSError(format!(
@@ -166,6 +500,7 @@ mod tests {
     use std::io::Write;
 
     use crate::connectors::tests::setup_for_tls;
+    use filetime::{set_file_mtime, FileTime};
 
     use super::*;
 
@@ -205,6 +540,120 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn server_config_client_auth() -> Result<()> {
+        setup_for_tls();
+
+        let mut config = TLSServerConfig {
+            cert: Path::new("./tests/localhost.cert").to_path_buf(),
+            key: Path::new("./tests/localhost.key").to_path_buf(),
+            cafile: None,
+            client_auth: None,
+            reload_interval: None,
+        };
+        assert_eq!(ClientAuth::None, config.client_auth());
+        load_server_config(&config)?;
+
+        config.client_auth = Some(ClientAuth::Required);
+        assert!(load_server_config(&config).is_err());
+
+        config.cafile = Some(Path::new("./tests/localhost.cert").to_path_buf());
+        load_server_config(&config)?;
+        config.client_auth = None;
+        assert_eq!(ClientAuth::Optional, config.client_auth());
+        load_server_config(&config)?;
+        Ok(())
+    }
+
+    #[test]
+    fn reload_server_config() -> Result<()> {
+        setup_for_tls();
+
+        let dir = tempfile::tempdir()?;
+        let cert = dir.path().join("server.cert");
+        let key = dir.path().join("server.key");
+        std::fs::copy("./tests/localhost.cert", &cert)?;
+        std::fs::copy("./tests/localhost.key", &key)?;
+        // without an interval there is no reload task, the checks are driven by the test
+        let config = TLSServerConfig {
+            cert: cert.clone(),
+            key,
+            cafile: None,
+            client_auth: None,
+            reload_interval: None,
+        };
+        let reloadable = Reloadable::server(&config)?;
+        let first = reloadable.current();
+        reloadable.check();
+        assert!(Arc::ptr_eq(&first, &reloadable.current()));
+
+        // a broken file keeps the previous config
+        std::fs::write(&cert, b"Brueghelflinsch\n")?;
+        set_file_mtime(&cert, FileTime::from_unix_time(1, 0))?;
+        reloadable.check();
+        assert!(Arc::ptr_eq(&first, &reloadable.current()));
+
+        // a valid file is picked up
+        std::fs::copy("./tests/localhost.cert", &cert)?;
+        set_file_mtime(&cert, FileTime::from_unix_time(2, 0))?;
+        reloadable.check();
+        let second = reloadable.current();
+        assert!(!Arc::ptr_eq(&first, &second));
+        reloadable.check();
+        assert!(Arc::ptr_eq(&second, &reloadable.current()));
+
+        let metrics = reloadable.metrics(0, &Alias::new("flow", "snot"));
+        let fields = metrics.suffix().value().get("fields").ok_or("no fields")?;
+        assert_eq!(Some(1), fields.get_u64("reloads"));
+        assert_eq!(Some(1), fields.get_u64("reload_failures"));
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn reload_task() -> Result<()> {
+        setup_for_tls();
+
+        let dir = tempfile::tempdir()?;
+        let cert = dir.path().join("server.cert");
+        let key = dir.path().join("server.key");
+        std::fs::copy("./tests/localhost.cert", &cert)?;
+        std::fs::copy("./tests/localhost.key", &key)?;
+        let config = TLSServerConfig {
+            cert: cert.clone(),
+            key,
+            cafile: None,
+            client_auth: None,
+            reload_interval: Some(1_000_000),
+        };
+        let reloadable = Reloadable::server(&config)?;
+        let first = reloadable.current();
+        set_file_mtime(&cert, FileTime::from_unix_time(1, 0))?;
+        let start = std::time::Instant::now();
+        while Arc::ptr_eq(&first, &reloadable.current()) {
+            assert!(
+                start.elapsed() < Duration::from_secs(5),
+                "Timeout waiting for the reload"
+            );
+            task::sleep(MIN_RELOAD_INTERVAL).await;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn peer_identity_meta() -> Result<()> {
+        setup_for_tls();
+
+        assert_eq!(None, peer_identity(&[]));
+        let certs = load_certs(Path::new("./tests/localhost.cert"))?;
+        let identity = peer_identity(&certs).ok_or("no identity")?;
+        assert_eq!(Some("CN=localhost"), identity.get_str("subject"));
+        assert_eq!(Some("CN=localhost"), identity.get_str("issuer"));
+        assert_eq!(literal!(["localhost"]), identity["san"]["dns"]);
+        assert_eq!(literal!(["127.0.0.1", "::1"]), identity["san"]["ip"]);
+        assert_eq!(Some(64), identity.get_str("fingerprint").map(str::len));
+        Ok(())
+    }
+
     #[async_std::test]
     async fn client_config() -> Result<()> {
         setup_for_tls();
@@ -214,6 +663,7 @@ mod tests {
             domain: Some("hostenschmirtz".to_string()),
             cert: Some(Path::new("./tests/localhost.cert").to_path_buf()),
             key: Some(Path::new("./tests/localhost.key").to_path_buf()),
+            reload_interval: None,
         };
         let client_config = tls_client_config(&tls_config).await?;
         assert_eq!(1, client_config.root_store.roots.len());
@@ -150,6 +150,7 @@ error_chain! {
     foreign_links {
         AddrParseError(std::net::AddrParseError);
         AnyhowError(anyhow::Error);
+        ArrowError(arrow::error::ArrowError);
         AsyncChannelRecvError(async_std::channel::RecvError);
         AsyncChannelTryRecvError(async_std::channel::TryRecvError);
         Base64Error(base64::DecodeError);
@@ -175,6 +176,7 @@ error_chain! {
         ModeParseError(file_mode::ModeParseError);
         MsgPackDecoderError(rmp_serde::decode::Error);
         MsgPackEncoderError(rmp_serde::encode::Error);
+        ParquetError(parquet::errors::ParquetError);
         ParseIntError(std::num::ParseIntError);
         ParseFloatError(std::num::ParseFloatError);
         //Postgres(postgres::Error);
@@ -340,6 +342,18 @@ error_chain! {
             description("Connector not found")
                 display("Connector \"{}\" not found in Flow \"{}\"", alias, flow_id)
         }
+        PipelineNotFound(flow_id: String, alias: String) {
+            description("Pipeline not found")
+                display("Pipeline \"{}\" not found in Flow \"{}\"", alias, flow_id)
+        }
+        FlowDrainTimeout(flow_id: String, connectors: String) {
+            description("Timeout draining Flow")
+                display("Timeout draining Flow \"{}\", connectors not drained: {}", flow_id, connectors)
+        }
+        InvalidTap(target: String, msg: String) {
+            description("Invalid tap")
+                display("Invalid tap on {}: {}", target, msg)
+        }
         InvalidInputData(msg: &'static str) {
             description("Invalid Input data")
                 display("Invalid Input data: {}", msg)
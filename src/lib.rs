@@ -67,6 +67,8 @@ pub mod preprocessor;
 pub mod connectors;
 /// Tremor runtime system
 pub mod system;
+/// Taps on connector and pipeline ports of running flows
+pub mod tap;
 /// Utility functions
 pub mod utils;
 /// Tremor runtime version tools
@@ -13,10 +13,11 @@
 // limitations under the License.
 use crate::{
     connectors::{self, sink::SinkMsg, source::SourceMsg},
-    errors::{pipe_send_e, Result},
+    errors::{pipe_send_e, Error, Kind as ErrorKind, Result},
     instance::State,
     primerge::PriorityMerge,
     system::flow,
+    tap::{Tap, Taps},
 };
 use async_std::{
     channel::{bounded, unbounded, Receiver, Sender},
@@ -24,12 +25,19 @@ use async_std::{
     task::{self, JoinHandle},
 };
 use beef::Cow;
-use std::{fmt, sync::atomic::Ordering, time::Duration};
+use std::{
+    collections::VecDeque,
+    fmt,
+    sync::{atomic::Ordering, Arc},
+    time::Duration,
+};
 use tremor_common::{ids::OperatorIdGen, time::nanotime};
 use tremor_pipeline::{
-    errors::ErrorKind as PipelineErrorKind, CbAction, Event, ExecutableGraph, SignalKind,
+    errors::ErrorKind as PipelineErrorKind, trace::Tracer, CbAction, Event, ExecutableGraph,
+    SignalKind,
 };
 use tremor_script::{ast::DeployEndpoint, highlighter::Dumb};
+use tremor_value::Value;
 
 const TICK_MS: u64 = 100;
 type Inputs = halfbrown::HashMap<DeployEndpoint, (bool, InputTarget)>;
@@ -115,12 +123,49 @@ impl Addr {
     pub(crate) async fn start(&self) -> Result<()> {
         self.send_mgmt(MgmtMsg::Start).await
     }
-    pub(crate) async fn pause(&self) -> Result<()> {
+
+    /// Pause this pipeline, holding back events until it is resumed
+    ///
+    /// Signals still pass, a drain signal resumes the pipeline so the flow can drain.
+    ///
+    /// # Errors
+    /// if the pipeline can't be reached
+    pub async fn pause(&self) -> Result<()> {
         self.send_mgmt(MgmtMsg::Pause).await
     }
-    pub(crate) async fn resume(&self) -> Result<()> {
+
+    /// Resume this pipeline from pause
+    ///
+    /// # Errors
+    /// if the pipeline can't be reached
+    pub async fn resume(&self) -> Result<()> {
         self.send_mgmt(MgmtMsg::Resume).await
     }
+
+    /// report status of the pipeline instance
+    ///
+    /// # Errors
+    /// if the pipeline can't be reached
+    pub async fn report_status(&self) -> Result<report::StatusReport> {
+        let (tx, rx) = bounded(1);
+        self.send_mgmt(MgmtMsg::Report(tx)).await?;
+        Ok(rx.recv().await?)
+    }
+
+    /// Update the operator `operator` of this pipeline at runtime
+    ///
+    /// # Errors
+    /// if the pipeline can't be reached, the operator doesn't exist or rejects the update
+    pub async fn update_operator(&self, operator: String, update: Value<'static>) -> Result<()> {
+        let (tx, rx) = bounded(1);
+        self.send_mgmt(MgmtMsg::UpdateOperator {
+            operator,
+            update,
+            tx,
+        })
+        .await?;
+        rx.recv().await?
+    }
 }
 
 impl fmt::Debug for Addr {
@@ -180,10 +225,12 @@ pub(crate) fn spawn(
     pipeline_alias: Alias,
     config: &tremor_pipeline::query::Query,
     operator_id_gen: &mut OperatorIdGen,
+    tracer: Arc<Tracer>,
 ) -> Result<Addr> {
     let qsize = crate::QSIZE.load(Ordering::Relaxed);
     let mut pipeline = config.to_executable_graph(operator_id_gen)?;
     pipeline.optimize();
+    pipeline.set_tracer(tracer);
 
     let (tx, rx) = bounded::<Box<Msg>>(qsize);
     // We use a unbounded channel for counterflow, while an unbounded channel seems dangerous
@@ -254,32 +301,110 @@ pub(crate) enum MgmtMsg {
     },
     /// start the pipeline
     Start,
-    /// pause the pipeline - currently a no-op
+    /// pause the pipeline, events are held back until it is resumed or drained
     Pause,
-    /// resume from pause - currently a no-op
+    /// resume from pause
     Resume,
     /// stop the pipeline
     Stop,
-    #[cfg(test)]
-    Inspect(Sender<report::StatusReport>),
+    /// update an operator at runtime
+    UpdateOperator {
+        /// alias of the operator
+        operator: String,
+        /// the update to apply
+        update: Value<'static>,
+        /// sends the result
+        tx: Sender<Result<()>>,
+    },
+    /// request a status report
+    Report(Sender<report::StatusReport>),
+    /// tap an input or output port
+    Tap {
+        /// the tap, its port decides where it is attached
+        tap: Tap,
+        /// sends the result
+        tx: Sender<Result<()>>,
+    },
 }
 
-#[cfg(test)]
-#[allow(dead_code)]
-mod report {
+/// Status reports of pipeline instances
+pub mod report {
     use super::{DeployEndpoint, InputTarget, OutputTarget, State};
-
-    #[derive(Debug, Clone)]
-    pub(crate) struct StatusReport {
+    use tremor_pipeline::GraphReport;
+
+    /// Pipeline instance status report
+    #[derive(Debug, Clone, Serialize)]
+    pub struct StatusReport {
+        /// pipeline alias
+        pub(crate) alias: String,
+        /// state of the pipeline
         pub(crate) state: State,
+        /// number of events waiting in the pipeline queue
+        pub(crate) queue_size: usize,
+        /// connected inputs
         pub(crate) inputs: Vec<InputReport>,
+        /// connected outputs by output port
         pub(crate) outputs: halfbrown::HashMap<String, Vec<OutputReport>>,
+        /// nodes with their event counts and edges of the pipeline graph
+        pub(crate) graph: GraphReport,
     }
 
-    #[derive(Debug, Clone, PartialEq)]
-    pub(crate) enum InputReport {
-        Pipeline { alias: String, port: String },
-        Source { alias: String, port: String },
+    impl StatusReport {
+        /// the pipeline alias
+        #[must_use]
+        pub fn alias(&self) -> &str {
+            &self.alias
+        }
+
+        /// pipeline state
+        #[must_use]
+        pub fn state(&self) -> &State {
+            &self.state
+        }
+
+        /// number of events waiting in the pipeline queue
+        #[must_use]
+        pub fn queue_size(&self) -> usize {
+            self.queue_size
+        }
+
+        /// connected inputs
+        #[must_use]
+        pub fn inputs(&self) -> &[InputReport] {
+            &self.inputs
+        }
+
+        /// connected outputs by output port
+        #[must_use]
+        pub fn outputs(&self) -> &halfbrown::HashMap<String, Vec<OutputReport>> {
+            &self.outputs
+        }
+
+        /// the pipeline graph
+        #[must_use]
+        pub fn graph(&self) -> &GraphReport {
+            &self.graph
+        }
+    }
+
+    /// An instance connected to an input port of a pipeline
+    #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+    #[serde(tag = "type", rename_all = "lowercase")]
+    pub enum InputReport {
+        /// another pipeline
+        Pipeline {
+            /// alias of the pipeline
+            alias: String,
+            /// its output port
+            port: String,
+        },
+        /// a connector
+        Source {
+            /// alias of the connector
+            alias: String,
+            /// its output port
+            port: String,
+        },
     }
 
     impl InputReport {
@@ -308,10 +433,24 @@ mod report {
         }
     }
 
-    #[derive(Debug, Clone, PartialEq)]
-    pub(crate) enum OutputReport {
-        Pipeline { alias: String, port: String },
-        Sink { alias: String, port: String },
+    /// An instance connected to an output port of a pipeline
+    #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+    #[serde(tag = "type", rename_all = "lowercase")]
+    pub enum OutputReport {
+        /// another pipeline
+        Pipeline {
+            /// alias of the pipeline
+            alias: String,
+            /// its input port
+            port: String,
+        },
+        /// a connector
+        Sink {
+            /// alias of the connector
+            alias: String,
+            /// its input port
+            port: String,
+        },
     }
 
     impl OutputReport {
@@ -403,8 +542,9 @@ impl OutputTarget {
 }
 
 #[inline]
-async fn send_events(eventset: &mut EventSet, dests: &mut Dests) -> Result<()> {
+async fn send_events(eventset: &mut EventSet, dests: &mut Dests, taps: &mut Taps) -> Result<()> {
     for (output, event) in eventset.drain(..) {
+        taps.send(&output, &event);
         if let Some(destinations) = dests.get_mut(&output) {
             if let Some((last, rest)) = destinations.split_last_mut() {
                 for (id, dest) in rest {
@@ -548,27 +688,60 @@ pub(crate) async fn pipeline_task(
     let mut dests: Dests = halfbrown::HashMap::new();
     let mut inputs: Inputs = halfbrown::HashMap::new();
     let mut eventset = Vec::new();
+    let mut input_taps = Taps::default();
+    let mut output_taps = Taps::default();
 
     let mut state: State = State::Initializing;
 
     info!("{ctx} Starting Pipeline.");
 
+    // only used for reporting the queue size
+    let queue = rx.clone();
+    // events held back while paused, followed by the signal that resumed us, if any
+    let mut held: VecDeque<Msg> = VecDeque::new();
+
     let ff = rx.map(|e| AnyMsg::Flow(*e));
     let cf = cf_rx.map(AnyMsg::Contraflow);
     let mf = mgmt_rx.map(AnyMsg::Mgmt);
 
     // prioritize management flow over contra flow over forward event flow
     let mut s = PriorityMerge::new(mf, PriorityMerge::new(cf, ff));
-    while let Some(msg) = s.next().await {
+    loop {
+        // once resumed, work off the held back messages first
+        let held_msg = if state == State::Paused {
+            None
+        } else {
+            held.pop_front()
+        };
+        let msg = if let Some(msg) = held_msg {
+            AnyMsg::Flow(msg)
+        } else if let Some(msg) = s.next().await {
+            msg
+        } else {
+            break;
+        };
         match msg {
             AnyMsg::Contraflow(msg) => {
                 handle_cf_msg(msg, &mut pipeline, &inputs).await?;
             }
+            AnyMsg::Flow(msg @ Msg::Event { .. }) if state == State::Paused => {
+                held.push_back(msg);
+            }
+            AnyMsg::Flow(Msg::Signal(signal))
+                if state == State::Paused && matches!(signal.kind, Some(SignalKind::Drain(_))) =>
+            {
+                // a paused pipeline would keep the flow from draining
+                info!("{ctx} Resuming to drain.");
+                handle_insight(None, Event::cb_restore(nanotime()), &mut pipeline, &inputs).await;
+                state = State::Running;
+                held.push_back(Msg::Signal(signal));
+            }
             AnyMsg::Flow(Msg::Event { input, event }) => {
+                input_taps.send(&input, &event);
                 match pipeline.enqueue(input.clone(), event, &mut eventset).await {
                     Ok(()) => {
                         handle_insights(&mut pipeline, &inputs).await;
-                        maybe_send(send_events(&mut eventset, &mut dests).await);
+                        maybe_send(send_events(&mut eventset, &mut dests, &mut output_taps).await);
                     }
                     Err(e) => {
                         let err_str = if let PipelineErrorKind::Script(script_kind) = e.0 {
@@ -595,7 +768,7 @@ pub(crate) async fn pipeline_task(
                 } else {
                     maybe_send(send_signal(&id, signal, &mut dests).await);
                     handle_insights(&mut pipeline, &inputs).await;
-                    maybe_send(send_events(&mut eventset, &mut dests).await);
+                    maybe_send(send_events(&mut eventset, &mut dests, &mut output_taps).await);
                 }
             }
             AnyMsg::Mgmt(MgmtMsg::ConnectInput {
@@ -662,25 +835,60 @@ pub(crate) async fn pipeline_task(
                 info!("{ctx} Ignoring Start Msg. Current state: {state}",);
             }
             AnyMsg::Mgmt(MgmtMsg::Pause) if state == State::Running => {
-                // No-op
+                // stop the sources, so the held back events don't pile up
+                handle_insight(None, Event::cb_trigger(nanotime()), &mut pipeline, &inputs).await;
                 state = State::Paused;
             }
             AnyMsg::Mgmt(MgmtMsg::Pause) => {
                 info!("{ctx} Ignoring Pause Msg. Current state: {state}",);
             }
             AnyMsg::Mgmt(MgmtMsg::Resume) if state == State::Paused => {
-                // No-op
+                handle_insight(None, Event::cb_restore(nanotime()), &mut pipeline, &inputs).await;
                 state = State::Running;
             }
             AnyMsg::Mgmt(MgmtMsg::Resume) => {
                 info!("{ctx} Ignoring Resume Msg. Current state: {state}",);
             }
+            AnyMsg::Mgmt(MgmtMsg::UpdateOperator {
+                operator,
+                update,
+                tx,
+            }) => {
+                info!("{ctx} Updating operator '{operator}'");
+                let res = pipeline
+                    .update_operator(&operator, &update)
+                    .map_err(Error::from);
+                if let Err(e) = &res {
+                    error!("{ctx} Error updating operator '{operator}': {e}");
+                }
+                if tx.send(res).await.is_err() {
+                    error!("{ctx} Error sending update result.");
+                }
+            }
+            AnyMsg::Mgmt(MgmtMsg::Tap { tap, tx }) => {
+                let port = Cow::from(tap.port().to_string());
+                let res = if input_does_exist(&port, &pipeline) {
+                    input_taps.add(tap);
+                    Ok(())
+                } else if output_doesnt_exist(&port, &pipeline) {
+                    Err(ErrorKind::InvalidTap(
+                        format!("pipeline {}", id.pipeline_alias()),
+                        format!("unknown port {port}"),
+                    )
+                    .into())
+                } else {
+                    output_taps.add(tap);
+                    Ok(())
+                };
+                if tx.send(res).await.is_err() {
+                    error!("{ctx} Error sending tap result.");
+                }
+            }
             AnyMsg::Mgmt(MgmtMsg::Stop) => {
                 info!("{ctx} Stopping...");
                 break;
             }
-            #[cfg(test)]
-            AnyMsg::Mgmt(MgmtMsg::Inspect(tx)) => {
+            AnyMsg::Mgmt(MgmtMsg::Report(tx)) => {
                 use report::{InputReport, OutputReport, StatusReport};
                 let inputs: Vec<InputReport> = inputs
                     .iter()
@@ -697,9 +905,12 @@ pub(crate) async fn pipeline_task(
                     .collect();
 
                 let report = StatusReport {
+                    alias: id.pipeline_alias().to_string(),
                     state,
+                    queue_size: queue.len() + held.len(),
                     inputs,
                     outputs,
+                    graph: pipeline.report(),
                 };
                 if tx.send(report).await.is_err() {
                     error!("{ctx} Error sending status report.");
@@ -731,6 +942,7 @@ mod tests {
         connectors::{prelude::SinkAddr, source::SourceAddr},
         pipeline::report::{InputReport, OutputReport},
     };
+    use async_std::prelude::FutureExt;
     use std::time::Instant;
     use tremor_common::{
         ids::Id as _,
@@ -753,16 +965,19 @@ mod tests {
             Alias::new("report", "test-pipe1"),
             &query,
             &mut operator_id_gen,
+            Arc::default(),
         )?;
         let addr2 = spawn(
             Alias::new("report", "test-pipe2"),
             &query,
             &mut operator_id_gen,
+            Arc::default(),
         )?;
         let addr3 = spawn(
             Alias::new("report", "test-pipe3"),
             &query,
             &mut operator_id_gen,
+            Arc::default(),
         )?;
         println!("{:?}", addr); // coverage
         let yolo_mid = NodeMeta::new(Location::yolo(), Location::yolo());
@@ -809,9 +1024,11 @@ mod tests {
             .await?;
         rx.recv().await??;
         // get a status report from every single one
-        let (tx, rx) = unbounded();
-        addr.send_mgmt(MgmtMsg::Inspect(tx.clone())).await?;
-        let mut report1 = rx.recv().await?;
+        let mut report1 = addr.report_status().await?;
+        assert_eq!("test-pipe1", report1.alias());
+        assert_eq!(State::Initializing, *report1.state());
+        assert!(!report1.graph().nodes.is_empty());
+        assert!(!report1.graph().edges.is_empty());
         assert!(report1.inputs.is_empty());
         let mut output1 = report1
             .outputs
@@ -821,8 +1038,7 @@ mod tests {
         assert_eq!(1, output1.len());
         let output1 = output1.pop().ok_or("no data")?;
         assert_eq!(output1, OutputReport::pipeline("snot2", "in"));
-        addr2.send_mgmt(MgmtMsg::Inspect(tx.clone())).await?;
-        let mut report2 = rx.recv().await?;
+        let mut report2 = addr2.report_status().await?;
         let input2 = report2.inputs.pop().expect("no input at port in");
         assert_eq!(input2, InputReport::pipeline("snot", "out"));
         let mut output2 = report2
@@ -834,8 +1050,7 @@ mod tests {
         let output2 = output2.pop().ok_or("no data")?;
         assert_eq!(output2, OutputReport::pipeline("snot3", "in"));
 
-        addr3.send_mgmt(MgmtMsg::Inspect(tx.clone())).await?;
-        let mut report3 = rx.recv().await?;
+        let mut report3 = addr3.report_status().await?;
         assert!(report3.outputs.is_empty());
         let input3 = report3.inputs.pop().expect("no inputs");
         assert_eq!(input3, InputReport::pipeline("snot2", "out"));
@@ -855,18 +1070,15 @@ mod tests {
         let pipeline_id = Alias::new("flow", "test-pipe");
         let query =
             tremor_pipeline::query::Query::parse(trickle, &*FN_REGISTRY.read()?, &aggr_reg)?;
-        let addr = spawn(pipeline_id, &query, &mut operator_id_gen)?;
+        let addr = spawn(pipeline_id, &query, &mut operator_id_gen, Arc::default())?;
 
-        let (tx, rx) = unbounded();
-        addr.send_mgmt(MgmtMsg::Inspect(tx.clone())).await?;
-        let report = rx.recv().await?;
+        let report = addr.report_status().await?;
         assert_eq!(State::Initializing, report.state);
         assert!(report.inputs.is_empty());
         assert!(report.outputs.is_empty());
 
         addr.start().await?;
-        addr.send_mgmt(MgmtMsg::Inspect(tx.clone())).await?;
-        let report = rx.recv().await?;
+        let report = addr.report_status().await?;
         assert_eq!(State::Running, report.state);
         assert!(report.inputs.is_empty());
         assert!(report.outputs.is_empty());
@@ -887,9 +1099,7 @@ mod tests {
         .await?;
         rx.recv().await??;
 
-        let (tx, rx) = unbounded();
-        addr.send_mgmt(MgmtMsg::Inspect(tx.clone())).await?;
-        let report = rx.recv().await?;
+        let report = addr.report_status().await?;
         assert_eq!(1, report.inputs.len());
         assert_eq!(
             report::InputReport::source("source_01", &OUT),
@@ -910,9 +1120,7 @@ mod tests {
         .await?;
         rx.recv().await??;
 
-        let (tx, rx) = unbounded();
-        addr.send_mgmt(MgmtMsg::Inspect(tx.clone())).await?;
-        let report = rx.recv().await?;
+        let report = addr.report_status().await?;
         assert_eq!(1, report.outputs.len());
         assert_eq!(
             Some(&vec![report::OutputReport::sink("sink_01", &IN)]),
@@ -976,19 +1184,63 @@ mod tests {
 
         // test pause and resume
         addr.pause().await?;
-        addr.send_mgmt(MgmtMsg::Inspect(tx.clone())).await?;
-        let report = rx.recv().await?;
+        let report = addr.report_status().await?;
         assert_eq!(State::Paused, report.state);
+        // the source is told to stop sending
+        let source_msg = source_rx.recv().await?;
+        assert!(
+            matches!(source_msg, SourceMsg::Cb(CbAction::Trigger, _)),
+            "Expected SourceMsg::Cb(Trigger), got: {source_msg:?}"
+        );
         assert_eq!(1, report.inputs.len());
         assert_eq!(1, report.outputs.len());
 
+        // events are held back while paused
+        let event = Event {
+            data: (Value::from(23_u64), Value::null()).into(),
+            ..Event::default()
+        };
+        addr.send(Box::new(Msg::Event { event, input: IN })).await?;
+        task::sleep(Duration::from_millis(200)).await;
+        while let Ok(sink_msg) = sink_rx.try_recv() {
+            assert!(
+                !matches!(sink_msg, SinkMsg::Event { .. }),
+                "Paused pipeline emitted an event"
+            );
+        }
+        let report = addr.report_status().await?;
+        assert!(report.queue_size() >= 1);
+
         addr.resume().await?;
-        addr.send_mgmt(MgmtMsg::Inspect(tx.clone())).await?;
-        let report = rx.recv().await?;
+        let report = addr.report_status().await?;
         assert_eq!(State::Running, report.state);
+        let source_msg = source_rx.recv().await?;
+        assert!(
+            matches!(source_msg, SourceMsg::Cb(CbAction::Restore, _)),
+            "Expected SourceMsg::Cb(Restore), got: {source_msg:?}"
+        );
         assert_eq!(1, report.inputs.len());
         assert_eq!(1, report.outputs.len());
 
+        let mut sink_msg = sink_rx.recv().await?;
+        while let SinkMsg::Signal { .. } = sink_msg {
+            sink_msg = sink_rx.recv().await?;
+        }
+        match sink_msg {
+            SinkMsg::Event { event, port: _ } => {
+                assert_eq!(Value::from(23_usize), event.data.suffix().value());
+            }
+            other => panic!("Expected Event, got: {:?}", other),
+        }
+        let report = addr.report_status().await?;
+        let out = report
+            .graph()
+            .nodes
+            .iter()
+            .find(|node| node.kind == "output")
+            .ok_or("no output node")?;
+        assert_eq!(Some(&2), out.inputs.get("in"));
+
         // finally stop
         addr.stop().await?;
         // verify we cannot send to the pipeline after it is stopped
@@ -1007,4 +1259,74 @@ mod tests {
         }
         Ok(())
     }
+
+    #[async_std::test]
+    async fn drain_paused_pipeline() -> Result<()> {
+        let _ = env_logger::try_init();
+        let mut operator_id_gen = OperatorIdGen::new();
+        let trickle = r#"select event from in into out;"#;
+        let aggr_reg = aggr_registry();
+        let pipeline_id = Alias::new("flow", "test-pipe");
+        let query =
+            tremor_pipeline::query::Query::parse(trickle, &*FN_REGISTRY.read()?, &aggr_reg)?;
+        let addr = spawn(pipeline_id, &query, &mut operator_id_gen, Arc::default())?;
+        addr.start().await?;
+
+        let (sink_tx, sink_rx) = unbounded();
+        let mid = NodeMeta::new(Location::yolo(), Location::yolo());
+        let (tx, rx) = bounded(1);
+        addr.send_mgmt(MgmtMsg::ConnectOutput {
+            endpoint: DeployEndpoint::new(&"sink_01", &IN, &mid),
+            port: OUT,
+            tx,
+            target: OutputTarget::Sink(SinkAddr { addr: sink_tx }),
+        })
+        .await?;
+        rx.recv().await??;
+
+        addr.pause().await?;
+        // fill up more than the pipeline queue, nothing may block
+        let qsize = crate::QSIZE.load(Ordering::Relaxed);
+        for i in 0..=qsize {
+            let event = Event {
+                data: (Value::from(i), Value::null()).into(),
+                ..Event::default()
+            };
+            addr.send(Box::new(Msg::Event { event, input: IN }))
+                .timeout(Duration::from_secs(2))
+                .await??;
+        }
+        let report = addr.report_status().await?;
+        assert_eq!(State::Paused, report.state);
+        assert!(report.queue_size() > qsize);
+
+        // draining resumes the pipeline, the held back events are emitted before the drain signal
+        addr.send(Box::new(Msg::Signal(Event::signal_drain(SourceId::new(
+            42,
+        )))))
+        .await?;
+        let mut expected = 0;
+        loop {
+            match sink_rx.recv().timeout(Duration::from_secs(4)).await?? {
+                SinkMsg::Event { event, .. } => {
+                    assert_eq!(Value::from(expected), event.data.suffix().value());
+                    expected += 1;
+                }
+                SinkMsg::Signal {
+                    signal:
+                        Event {
+                            kind: Some(SignalKind::Drain(id)),
+                            ..
+                        },
+                } if id.id() == 42 => break,
+                _ => (),
+            }
+        }
+        assert_eq!(qsize + 1, expected);
+        let report = addr.report_status().await?;
+        assert_eq!(State::Running, report.state);
+
+        addr.stop().await?;
+        Ok(())
+    }
 }
@@ -13,7 +13,7 @@
 // limitations under the License.
 
 mod chunk;
-mod compress;
+pub(crate) mod compress;
 mod gelf;
 pub(crate) mod separate;
 
@@ -68,6 +68,7 @@ pub fn lookup_with_config(config: &PostprocessorConfig) -> Result<Box<dyn Postpr
         "length-prefixed" => Ok(Box::new(LengthPrefix::default())),
         "gelf-chunking" => Ok(Box::new(Gelf::default())),
         "textual-length-prefix" => Ok(Box::new(TextualLength::default())),
+        "octet-counting" => Ok(Box::new(OctetCounting::default())),
         name => Err(format!("Postprocessor '{}' not found.", name).into()),
     }
 }
@@ -219,19 +220,41 @@ impl Postprocessor for TextualLength {
     }
 }
 
+/// Octet-counting syslog framing as in RFC 6587 and RFC 5425: `<len> <msg>`
+///
+/// Empty messages are dropped, as frames need a non-zero length.
+#[derive(Clone, Default)]
+pub(crate) struct OctetCounting {}
+impl Postprocessor for OctetCounting {
+    fn name(&self) -> &str {
+        "octet-counting"
+    }
+
+    fn process(&mut self, _ingres_ns: u64, _egress_ns: u64, data: &[u8]) -> Result<Vec<Vec<u8>>> {
+        if data.is_empty() {
+            return Ok(vec![]);
+        }
+        let mut res = Vec::with_capacity(data.len() + 8);
+        write!(res, "{} ", data.len())?;
+        res.write_all(data)?;
+        Ok(vec![res])
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
     use crate::config::NameWithConfig;
     use tremor_value::literal;
 
-    const LOOKUP_TABLE: [&str; 6] = [
+    const LOOKUP_TABLE: [&str; 7] = [
         "separate",
         "base64",
         "gelf-chunking",
         "ingest-ns",
         "length-prefixed",
         "textual-length-prefix",
+        "octet-counting",
     ];
     const COMPRESSION: [&str; 6] = ["gzip", "zlib", "xz2", "snappy", "lz4", "zstd"];
 
@@ -282,4 +305,14 @@ mod test {
         assert!(post.finish(None)?.is_empty());
         Ok(())
     }
+
+    #[test]
+    fn octet_counting_postp() -> Result<()> {
+        let mut post = OctetCounting {};
+        let encoded = post.process(42, 23, b"<13>snot")?.pop().unwrap_or_default();
+        assert_eq!("8 <13>snot", str::from_utf8(&encoded)?);
+        assert!(post.process(42, 23, b"")?.is_empty());
+        assert!(post.finish(None)?.is_empty());
+        Ok(())
+    }
 }
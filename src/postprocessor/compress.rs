@@ -16,7 +16,7 @@ use super::Postprocessor;
 use crate::errors::Result;
 use simd_json::ValueAccess;
 use std::{
-    io::Write,
+    io::{self, Read, Write},
     str::{self, FromStr},
 };
 use tremor_script::Value;
@@ -222,6 +222,71 @@ impl Postprocessor for Zstd {
         Ok(vec![compressed])
     }
 }
+/// Compress everything read from `reader` into `writer`, without holding it in memory
+///
+/// Takes the same `algorithm` and `level` as the `compress` postprocessor and returns `writer` once the
+/// compressed stream is finished.
+pub(crate) fn compress_stream<R, W>(
+    algorithm: &str,
+    level: Option<i64>,
+    reader: &mut R,
+    writer: W,
+) -> Result<W>
+where
+    R: Read + ?Sized,
+    W: Write,
+{
+    let algorithm = Algorithm::from_str(algorithm)?;
+    if level.is_some()
+        && matches!(
+            algorithm,
+            Algorithm::Gzip | Algorithm::Zlib | Algorithm::Snappy
+        )
+    {
+        return Err("compression level not supported for given algorithm".into());
+    }
+    let writer = match algorithm {
+        Algorithm::Gzip => {
+            let mut encoder = libflate::gzip::Encoder::new(writer)?;
+            io::copy(reader, &mut encoder)?;
+            encoder.finish().into_result()?
+        }
+        Algorithm::Zlib => {
+            let mut encoder = libflate::zlib::Encoder::new(writer)?;
+            io::copy(reader, &mut encoder)?;
+            encoder.finish().into_result()?
+        }
+        Algorithm::Xz2 => {
+            let level = level.map_or(Ok(Xz2::default().compression_level), u32::try_from)?;
+            let mut encoder = xz2::write::XzEncoder::new(writer, level);
+            io::copy(reader, &mut encoder)?;
+            encoder.finish()?
+        }
+        Algorithm::Zstd => {
+            let level = level.map_or(Ok(Zstd::default().compression_level), i32::try_from)?;
+            let mut encoder = zstd::stream::write::Encoder::new(writer, level)?;
+            io::copy(reader, &mut encoder)?;
+            encoder.finish()?
+        }
+        Algorithm::Snappy => {
+            let mut encoder = snap::write::FrameEncoder::new(writer);
+            io::copy(reader, &mut encoder)?;
+            encoder
+                .into_inner()
+                .map_err(|e| format!("Snappy compression postprocessor error: {}", e.error()))?
+        }
+        Algorithm::Lz4 => {
+            let level = level.map_or(Ok(Lz4::default().compression_level), u32::try_from)?;
+            let mut encoder = lz4::EncoderBuilder::new().level(level).build(writer)?;
+            io::copy(reader, &mut encoder)?;
+            let (writer, res) = encoder.finish();
+            res?;
+            writer
+        }
+    };
+    Ok(writer)
+}
+
 pub(crate) struct Compress {
     codec: Box<dyn Postprocessor>,
 }
@@ -310,4 +375,23 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn stream() -> Result<()> {
+        use crate::preprocessor::decompress;
+
+        let data = b"snot badger ".repeat(1024);
+        for algorithm in ["gzip", "zlib", "xz2", "zstd", "snappy", "lz4"] {
+            let compressed = compress_stream(algorithm, None, &mut data.as_slice(), Vec::new())?;
+            let mut decompressed = Vec::new();
+            decompress::decoder(algorithm, compressed.as_slice())?
+                .read_to_end(&mut decompressed)?;
+            assert_eq!(data, decompressed, "{algorithm}");
+        }
+        let compressed = compress_stream("zstd", Some(3), &mut data.as_slice(), Vec::new())?;
+        assert!(compressed.len() < data.len());
+        assert!(compress_stream("gzip", Some(3), &mut data.as_slice(), Vec::new()).is_err());
+        assert!(compress_stream("snot", None, &mut data.as_slice(), Vec::new()).is_err());
+        Ok(())
+    }
 }
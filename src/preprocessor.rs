@@ -12,8 +12,9 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-mod decompress;
+pub(crate) mod decompress;
 pub(crate) mod gelf;
+pub(crate) mod octet_counting;
 pub(crate) mod separate;
 
 use crate::config::Preprocessor as PreprocessorConfig;
@@ -49,6 +50,12 @@ pub trait Preprocessor: Sync + Send {
     fn finish(&mut self, _data: Option<&[u8]>) -> Result<Vec<Vec<u8>>> {
         Ok(vec![])
     }
+
+    /// Errors about data the previous calls to `process` or `finish` discarded
+    /// while still returning the rest of the data.
+    fn take_errors(&mut self) -> Vec<Error> {
+        vec![]
+    }
 }
 
 /// Lookup a preprocessor implementation via its configuration
@@ -69,6 +76,9 @@ pub fn lookup_with_config(config: &PreprocessorConfig) -> Result<Box<dyn Preproc
         "ingest-ns" => Ok(Box::new(ExtractIngestTs {})),
         "length-prefixed" => Ok(Box::new(LengthPrefix::default())),
         "textual-length-prefix" => Ok(Box::new(TextualLength::default())),
+        "octet-counting" => Ok(Box::new(octet_counting::OctetCounting::from_config(
+            &config.config,
+        )?)),
         name => Err(format!("Preprocessor '{}' not found.", name).into()),
     }
 }
@@ -96,6 +106,8 @@ pub fn make_preprocessors(preprocessors: &[PreprocessorConfig]) -> Result<Prepro
 /// Preprocessors might split up the given data in multiple chunks. Each of those
 /// chunks must be seperately decoded by a `Codec`.
 ///
+/// Returns the chunks along with the errors about data the preprocessors discarded.
+///
 /// # Errors
 ///
 ///   * If a preprocessor failed
@@ -104,9 +116,10 @@ pub fn preprocess(
     ingest_ns: &mut u64,
     data: Vec<u8>,
     alias: &Alias,
-) -> Result<Vec<Vec<u8>>> {
+) -> Result<(Vec<Vec<u8>>, Vec<Error>)> {
     let mut data = vec![data];
     let mut data1 = Vec::new();
+    let mut errors = Vec::new();
     for pp in preprocessors {
         data1.clear();
         for (i, d) in data.iter().enumerate() {
@@ -117,18 +130,25 @@ pub fn preprocess(
                     return Err(e);
                 }
             }
+            take_errors(pp.as_mut(), &mut errors, alias);
         }
         std::mem::swap(&mut data, &mut data1);
     }
-    Ok(data)
+    Ok((data, errors))
 }
 
 /// Canonical way to finish preprocessors up
 ///
+/// Returns the remaining chunks along with the errors about data the preprocessors discarded.
+///
 /// # Errors
 ///
 /// * If a preprocessor failed
-pub fn finish(preprocessors: &mut [Box<dyn Preprocessor>], alias: &Alias) -> Result<Vec<Vec<u8>>> {
+pub fn finish(
+    preprocessors: &mut [Box<dyn Preprocessor>],
+    alias: &Alias,
+) -> Result<(Vec<Vec<u8>>, Vec<Error>)> {
+    let mut errors = Vec::new();
     if let Some((head, tail)) = preprocessors.split_first_mut() {
         let mut data = match head.finish(None) {
             Ok(d) => d,
@@ -140,6 +160,7 @@ pub fn finish(preprocessors: &mut [Box<dyn Preprocessor>], alias: &Alias) -> Res
                 return Err(e);
             }
         };
+        take_errors(head.as_mut(), &mut errors, alias);
         let mut data1 = Vec::new();
         for pp in tail {
             data1.clear();
@@ -154,12 +175,24 @@ pub fn finish(preprocessors: &mut [Box<dyn Preprocessor>], alias: &Alias) -> Res
                         return Err(e);
                     }
                 }
+                take_errors(pp.as_mut(), &mut errors, alias);
             }
             std::mem::swap(&mut data, &mut data1);
         }
-        Ok(data)
+        Ok((data, errors))
     } else {
-        Ok(vec![])
+        Ok((vec![], errors))
+    }
+}
+
+/// Log and collect the errors about data `pp` discarded
+fn take_errors(pp: &mut dyn Preprocessor, errors: &mut Vec<Error>, alias: &Alias) {
+    for e in pp.take_errors() {
+        warn!(
+            "[Connector::{alias}] Preprocessor '{}' discarded data: {e}",
+            pp.name()
+        );
+        errors.push(e);
     }
 }
 
@@ -465,22 +498,42 @@ mod test {
         let (start, end) = wire[0].split_at(7);
         let alias = Alias::new("test", "test");
         let mut pps: Vec<Box<dyn Preprocessor>> = vec![Box::new(pre_p)];
-        let recv = preprocess(pps.as_mut_slice(), &mut it, start.to_vec(), &alias)?;
+        let (recv, _) = preprocess(pps.as_mut_slice(), &mut it, start.to_vec(), &alias)?;
         assert!(recv.is_empty());
-        let recv = preprocess(pps.as_mut_slice(), &mut it, end.to_vec(), &alias)?;
+        let (recv, _) = preprocess(pps.as_mut_slice(), &mut it, end.to_vec(), &alias)?;
         assert_eq!(recv[0], data);
 
         // incomplete data
-        let processed = preprocess(pps.as_mut_slice(), &mut it, start.to_vec(), &alias)?;
+        let (processed, _) = preprocess(pps.as_mut_slice(), &mut it, start.to_vec(), &alias)?;
         assert!(processed.is_empty());
         // not emitted upon finish
-        let finished = finish(pps.as_mut_slice(), &alias)?;
+        let (finished, _) = finish(pps.as_mut_slice(), &alias)?;
         assert!(finished.is_empty());
 
         Ok(())
     }
 
-    const LOOKUP_TABLE: [&str; 8] = [
+    #[test]
+    fn discarded_data() -> Result<()> {
+        let alias = Alias::new("test", "test");
+        let mut pps: Vec<Box<dyn Preprocessor>> =
+            vec![Box::new(octet_counting::OctetCounting::new(10))];
+        let mut it = 0;
+        let (recv, errors) = preprocess(
+            pps.as_mut_slice(),
+            &mut it,
+            b"8 <13>snot11 <13>snot bad8 <13>snot".to_vec(),
+            &alias,
+        )?;
+        assert_eq!(vec![b"<13>snot".to_vec(), b"<13>snot".to_vec()], recv);
+        assert_eq!(1, errors.len());
+        let (finished, errors) = finish(pps.as_mut_slice(), &alias)?;
+        assert!(finished.is_empty());
+        assert!(errors.is_empty());
+        Ok(())
+    }
+
+    const LOOKUP_TABLE: [&str; 9] = [
         "separate",
         "base64",
         "decompress",
@@ -489,6 +542,7 @@ mod test {
         "ingest-ns",
         "length-prefixed",
         "textual-length-prefix",
+        "octet-counting",
     ];
 
     #[test]
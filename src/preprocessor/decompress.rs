@@ -165,6 +165,25 @@ impl Preprocessor for Fingerprinted {
     }
 }
 
+/// Streaming decoder for `algorithm`, decompressing the data read from `reader` while it is read
+///
+/// Unlike the preprocessor it doesn't need the whole compressed data up front.
+pub(crate) fn decoder<'r, R>(algorithm: &str, reader: R) -> Result<Box<dyn Read + 'r>>
+where
+    R: Read + 'r,
+{
+    let decoder: Box<dyn Read + 'r> = match algorithm {
+        "gzip" => Box::new(libflate::gzip::MultiDecoder::new(reader)?),
+        "zlib" => Box::new(libflate::zlib::Decoder::new(reader)?),
+        "xz2" => Box::new(xz2::read::XzDecoder::new(reader)),
+        "snappy" => Box::new(snap::read::FrameDecoder::new(reader)),
+        "lz4" => Box::new(lz4::Decoder::new(reader)?),
+        "zstd" => Box::new(zstd::stream::read::Decoder::new(reader)?),
+        other => return Err(format!("Unknown decompression algorithm: {other}").into()),
+    };
+    Ok(decoder)
+}
+
 pub(crate) struct Decompress {
     codec: Box<dyn Preprocessor>,
 }
@@ -232,6 +251,11 @@ mod test {
         assert_eq!(&internal, &out);
         // assert empty finish, no leftovers
         assert!(pre.finish(None)?.is_empty());
+
+        // the streaming decoder gets to the same result
+        let mut streamed = Vec::new();
+        super::decoder(algo, ext)?.read_to_end(&mut streamed)?;
+        assert_eq!(internal, streamed.as_slice());
         Ok(())
     }
 
@@ -0,0 +1,384 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Syslog framing as in [RFC 6587](https://datatracker.ietf.org/doc/html/rfc6587) and
+//! [RFC 5425](https://datatracker.ietf.org/doc/html/rfc5425).
+//!
+//! Frames starting with a non-zero digit are octet-counted (`<len> <msg>`), all other frames
+//! are delimited by `\n` (non-transparent framing), so both can be mixed on one connection.
+//! Stray `\r`, `\n` and `\0` bytes between frames are skipped.
+//!
+//! Frames longer than `max_length` are skipped, octet-counted ones by their length, others up to
+//! the next `\n`. Frames with an invalid length are skipped up to the next `\n` as well. Those
+//! are reported as errors via [`Preprocessor::take_errors`], the other frames are still emitted.
+
+use super::Preprocessor;
+use crate::errors::{Error, Kind as ErrorKind, Result};
+use memchr::memchr;
+use tremor_pipeline::{ConfigImpl, ConfigMap};
+
+/// Default maximum length of a frame
+pub(crate) const DEFAULT_MAX_LENGTH: usize = 64 * 1024;
+/// digits of the longest supported `MSG-LEN`
+const MAX_LEN_DIGITS: usize = 10;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(default = "default_max_length")]
+    max_length: usize,
+}
+
+fn default_max_length() -> usize {
+    DEFAULT_MAX_LENGTH
+}
+
+impl ConfigImpl for Config {}
+
+/// Rest of a discarded frame still to be skipped
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Skip {
+    /// the given number of bytes
+    Bytes(usize),
+    /// everything up to and including the next `\n`
+    Line,
+}
+
+#[derive(Debug)]
+pub(crate) struct OctetCounting {
+    max_length: usize,
+    buffer: Vec<u8>,
+    skip: Option<Skip>,
+    errors: Vec<Error>,
+}
+
+impl Default for OctetCounting {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_LENGTH)
+    }
+}
+
+impl OctetCounting {
+    pub(crate) fn from_config(config: &ConfigMap) -> Result<Self> {
+        if let Some(raw_config) = config {
+            let config = Config::new(raw_config)?;
+            if config.max_length == 0 {
+                return Err(ErrorKind::InvalidConfiguration(
+                    String::from("octet-counting preprocessor"),
+                    String::from("'max_length' must be > 0"),
+                )
+                .into());
+            }
+            Ok(Self::new(config.max_length))
+        } else {
+            Ok(Self::default())
+        }
+    }
+
+    pub(crate) fn new(max_length: usize) -> Self {
+        Self {
+            max_length,
+            buffer: Vec::new(),
+            skip: None,
+            errors: Vec::new(),
+        }
+    }
+
+    fn too_long(&self, len: usize) -> Error {
+        format!(
+            "Discarded syslog frame of length {len} exceeding the maximum allowed length of {}",
+            self.max_length
+        )
+        .into()
+    }
+
+    /// Split all complete frames off the buffer, returns the number of bytes consumed
+    fn split_frames(&mut self, frames: &mut Vec<Vec<u8>>) -> usize {
+        let mut start = 0;
+        while let Some(rest) = self.buffer.get(start..) {
+            if rest.is_empty() {
+                break;
+            }
+            match self.skip {
+                Some(Skip::Bytes(len)) => {
+                    let skipped = len.min(rest.len());
+                    start += skipped;
+                    self.skip = (skipped < len).then_some(Skip::Bytes(len - skipped));
+                    continue;
+                }
+                Some(Skip::Line) => {
+                    if let Some(idx) = memchr(b'\n', rest) {
+                        start += idx + 1;
+                        self.skip = None;
+                        continue;
+                    }
+                    start += rest.len();
+                    break;
+                }
+                None => (),
+            }
+            match rest.first() {
+                None => break,
+                Some(b'\r' | b'\n' | b'\0') => start += 1,
+                Some(b'1'..=b'9') => {
+                    // octet-counting: MSG-LEN SP SYSLOG-MSG
+                    let digits = rest.iter().take_while(|c| c.is_ascii_digit()).count();
+                    if digits > MAX_LEN_DIGITS {
+                        // we lost track of the frame boundaries, resync at the next newline
+                        self.errors.push(
+                            ErrorKind::InvalidInputData("Invalid syslog frame length").into(),
+                        );
+                        self.skip = Some(Skip::Line);
+                        continue;
+                    }
+                    match rest.get(digits) {
+                        None => break,
+                        Some(b' ') => (),
+                        Some(_) => {
+                            self.errors.push(
+                                ErrorKind::InvalidInputData(
+                                    "Invalid syslog frame, expected a space after the frame length",
+                                )
+                                .into(),
+                            );
+                            self.skip = Some(Skip::Line);
+                            continue;
+                        }
+                    }
+                    let len = std::str::from_utf8(rest.get(..digits).unwrap_or_default())
+                        .ok()
+                        .and_then(|digits| digits.parse::<usize>().ok());
+                    let len = if let Some(len) = len {
+                        len
+                    } else {
+                        self.errors.push(
+                            ErrorKind::InvalidInputData("Invalid syslog frame length").into(),
+                        );
+                        self.skip = Some(Skip::Line);
+                        continue;
+                    };
+                    if len > self.max_length {
+                        let e = self.too_long(len);
+                        self.errors.push(e);
+                        start += digits + 1;
+                        self.skip = Some(Skip::Bytes(len));
+                        continue;
+                    }
+                    let end = digits + 1 + len;
+                    if let Some(frame) = rest.get(digits + 1..end) {
+                        frames.push(frame.to_vec());
+                        start += end;
+                    } else {
+                        break;
+                    }
+                }
+                Some(_) => {
+                    // non-transparent framing: SYSLOG-MSG LF
+                    if let Some(idx) = memchr(b'\n', rest) {
+                        let mut frame = rest.get(..idx).unwrap_or_default();
+                        if let Some(stripped) = frame.strip_suffix(b"\r") {
+                            frame = stripped;
+                        }
+                        if frame.len() > self.max_length {
+                            let e = self.too_long(frame.len());
+                            self.errors.push(e);
+                        } else {
+                            frames.push(frame.to_vec());
+                        }
+                        start += idx + 1;
+                    } else if rest.len() > self.max_length {
+                        let e = self.too_long(rest.len());
+                        self.errors.push(e);
+                        start += rest.len();
+                        self.skip = Some(Skip::Line);
+                        break;
+                    } else {
+                        break;
+                    }
+                }
+            }
+        }
+        start
+    }
+}
+
+impl Preprocessor for OctetCounting {
+    fn name(&self) -> &str {
+        "octet-counting"
+    }
+
+    fn process(&mut self, _ingest_ns: &mut u64, data: &[u8]) -> Result<Vec<Vec<u8>>> {
+        self.buffer.extend_from_slice(data);
+        let mut frames = Vec::new();
+        let consumed = self.split_frames(&mut frames);
+        self.buffer.drain(..consumed);
+        Ok(frames)
+    }
+
+    fn finish(&mut self, data: Option<&[u8]>) -> Result<Vec<Vec<u8>>> {
+        let mut tmp = 0_u64;
+        let mut frames = self.process(&mut tmp, data.unwrap_or_default())?;
+        let rest = std::mem::take(&mut self.buffer);
+        // the stream ended, a new one starts with a new frame
+        self.skip = None;
+        // a non-transparent frame is complete at the end of the stream, an octet-counted one is not
+        match rest.first() {
+            None | Some(b'1'..=b'9') => (),
+            Some(_) => frames.push(rest),
+        }
+        Ok(frames)
+    }
+
+    fn take_errors(&mut self) -> Vec<Error> {
+        std::mem::take(&mut self.errors)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tremor_value::literal;
+
+    fn frames(pp: &mut OctetCounting, data: &[&[u8]]) -> Result<Vec<String>> {
+        let mut ingest_ns = 0_u64;
+        let mut res = Vec::new();
+        for d in data {
+            for frame in pp.process(&mut ingest_ns, d)? {
+                res.push(String::from_utf8(frame)?);
+            }
+        }
+        Ok(res)
+    }
+
+    #[test]
+    fn octet_counted() -> Result<()> {
+        let mut pp = OctetCounting::default();
+        assert_eq!(
+            vec!["<13>1 - - - - - snot", "<13>1 - - - - - badger"],
+            frames(
+                &mut pp,
+                &[b"20 <13>1 - - - - - snot22 <13>1 - - - - - badger"]
+            )?
+        );
+        // split across reads, also within the length
+        assert_eq!(
+            vec!["<13>1 - - - - - snot", "<13>1 - - - - - snot"],
+            frames(
+                &mut pp,
+                &[
+                    b"2",
+                    b"0 <13>1 - - ",
+                    b"- - - snot",
+                    b"20 <13>1 - - - - - snot"
+                ]
+            )?
+        );
+        // the frame may contain newlines
+        assert_eq!(
+            vec!["snot\nbadger"],
+            frames(&mut pp, &[b"11 snot\nbadger"])?
+        );
+        assert!(pp.finish(None)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn mixed() -> Result<()> {
+        let mut pp = OctetCounting::default();
+        assert_eq!(
+            vec![
+                "<13>snot",
+                "<13>badger",
+                "<13>1 - - - - - grmpf",
+                "<13>quagga"
+            ],
+            frames(
+                &mut pp,
+                &[
+                    b"<13>snot\n<13>bad",
+                    b"ger\r\n21 <13>1 - - - - - grmpf\n<13>quagga\n"
+                ]
+            )?
+        );
+        // an unterminated non-transparent frame is emitted on finish
+        assert!(frames(&mut pp, &[b"<13>wombat"])?.is_empty());
+        assert_eq!(vec![b"<13>wombat".to_vec()], pp.finish(None)?);
+        // an incomplete octet-counted frame is not
+        assert!(frames(&mut pp, &[b"30 <13>capy"])?.is_empty());
+        assert!(pp.finish(None)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn invalid() -> Result<()> {
+        let mut pp = OctetCounting::new(10);
+        // the frames around an oversized one are kept, its bytes are skipped
+        assert_eq!(
+            vec!["<13>snot", "<13>snot"],
+            frames(&mut pp, &[b"8 <13>snot11 <13>snot bad8 <13>snot"])?
+        );
+        assert_eq!(1, pp.take_errors().len());
+        // also if it is split across reads
+        assert_eq!(
+            vec!["<13>snot"],
+            frames(&mut pp, &[b"11 <13>snot", b" bad8 <13>snot"])?
+        );
+        assert_eq!(1, pp.take_errors().len());
+        // invalid lengths are skipped up to the next newline
+        assert_eq!(
+            vec!["<13>snot"],
+            frames(&mut pp, &[b"8x<13>snot\n8 <13>snot"])?
+        );
+        assert_eq!(1, pp.take_errors().len());
+        assert_eq!(
+            vec!["<13>snot"],
+            frames(&mut pp, &[b"12345678901 snot\n", b"<13>snot\n"])?
+        );
+        assert_eq!(1, pp.take_errors().len());
+        // as are oversized non-transparent frames
+        assert_eq!(
+            vec!["<13>snot"],
+            frames(&mut pp, &[b"<13>snot badger", b" grmpf\n<13>snot\n"])?
+        );
+        assert_eq!(1, pp.take_errors().len());
+        assert!(pp.finish(None)?.is_empty());
+        assert!(pp.take_errors().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn pre_post() -> Result<()> {
+        use crate::postprocessor::{self as post, Postprocessor};
+        let mut post = post::OctetCounting::default();
+        let mut pp = OctetCounting::default();
+        let msg =
+            b"<165>1 2003-10-11T22:14:15.003Z mymachine.example.com evntslog - ID47 - snot\nbadger";
+        let mut ingest_ns = 0_u64;
+        for encoded in post.process(0, 0, msg)? {
+            assert_eq!(vec![msg.to_vec()], pp.process(&mut ingest_ns, &encoded)?);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn from_config() -> Result<()> {
+        let pp = OctetCounting::from_config(&Some(literal!({"max_length": 42})))?;
+        assert_eq!(42, pp.max_length);
+        let pp = OctetCounting::from_config(&None)?;
+        assert_eq!(DEFAULT_MAX_LENGTH, pp.max_length);
+        assert!(OctetCounting::from_config(&Some(literal!({"max_length": 0}))).is_err());
+        assert!(OctetCounting::from_config(&Some(literal!({"snot": 0}))).is_err());
+        Ok(())
+    }
+}
@@ -16,15 +16,17 @@
 pub mod flow;
 /// contains the runtime actor starting and maintaining flows
 pub mod flow_supervisor;
+/// sampled tracing of events flowing through the runtime and the export of their spans
+pub mod trace;
 
 use self::flow::Flow;
 use crate::errors::{Error, Kind as ErrorKind, Result};
 use crate::{connectors, QSIZE};
 use async_std::channel::{bounded, Sender};
-use async_std::prelude::*;
 use async_std::task::JoinHandle;
-use std::sync::atomic::Ordering;
+use std::sync::{atomic::Ordering, Arc};
 use std::time::Duration;
+use tremor_pipeline::trace::Tracer;
 use tremor_script::{ast, highlighter::Highlighter};
 
 /// Configuration for the runtime
@@ -33,12 +35,19 @@ pub struct WorldConfig {
     pub qsize: usize,
     /// if debug connectors should be loaded
     pub debug_connectors: bool,
+    /// time to wait for flows to drain during a graceful shutdown,
+    /// deployments can override it with their `drain_timeout` setting
+    pub graceful_shutdown_timeout: Duration,
+    /// sampled tracing of events, disabled if `None`
+    pub trace: Option<trace::Config>,
 }
 impl Default for WorldConfig {
     fn default() -> Self {
         Self {
             qsize: QSIZE.load(Ordering::Relaxed),
             debug_connectors: false,
+            graceful_shutdown_timeout: DEFAULT_GRACEFUL_SHUTDOWN_TIMEOUT,
+            trace: None,
         }
     }
 }
@@ -70,15 +79,9 @@ impl KillSwitch {
         if mode == ShutdownMode::Graceful {
             let (tx, rx) = bounded(1);
             self.0.send(flow_supervisor::Msg::Drain(tx)).await?;
-            if let Ok(res) = rx.recv().timeout(DEFAULT_GRACEFUL_SHUTDOWN_TIMEOUT).await {
-                if let Err(e) | Ok(Err(e)) = res.map_err(Error::from) {
-                    error!("Error draining all Flows: {}", e);
-                }
-            } else {
-                warn!(
-                    "Timeout draining all Flows after {}s",
-                    DEFAULT_GRACEFUL_SHUTDOWN_TIMEOUT.as_secs()
-                );
+            // every flow enforces its own drain timeout, so we always get a response
+            if let Err(e) | Ok(Err(e)) = rx.recv().await.map_err(Error::from) {
+                error!("Error draining all Flows: {}", e);
             }
         }
         let res = self.0.send(flow_supervisor::Msg::Stop).await;
@@ -190,9 +193,19 @@ impl World {
     ///
     /// # Errors
     ///  * if the world manager can't be started
+    ///  * if the tracing config is invalid or its exporter can't be created
     pub async fn start(config: WorldConfig) -> Result<(Self, JoinHandle<Result<()>>)> {
-        let (system_h, system, kill_switch) =
-            flow_supervisor::FlowSupervisor::new(config.qsize).start();
+        // every world samples and exports its own traces
+        let tracer = Arc::new(Tracer::default());
+        if let Some(trace) = &config.trace {
+            trace::start(trace, &tracer).await?;
+        }
+        let (system_h, system, kill_switch) = flow_supervisor::FlowSupervisor::new(
+            config.qsize,
+            config.graceful_shutdown_timeout,
+            tracer,
+        )
+        .start();
 
         let world = Self {
             system,
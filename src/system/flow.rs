@@ -12,6 +12,9 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+pub mod supervision;
+
+use self::supervision::{Action, Supervisor};
 use crate::{
     connectors::{self, ConnectorResult, Known},
     errors::{Error, Kind as ErrorKind, Result},
@@ -20,6 +23,7 @@ use crate::{
     pipeline::{self, InputTarget},
     primerge::PriorityMerge,
     system::KillSwitch,
+    tap::{self, Tap, TapRx},
 };
 use async_std::prelude::*;
 use async_std::{
@@ -28,12 +32,18 @@ use async_std::{
 };
 use hashbrown::HashMap;
 use std::collections::HashSet;
-use std::{sync::atomic::Ordering, time::Duration};
+use std::{
+    sync::{atomic::Ordering, Arc},
+    time::Duration,
+};
 use tremor_common::ids::{ConnectorIdGen, OperatorIdGen};
+use tremor_common::time::nanotime;
+use tremor_pipeline::trace::Tracer;
 use tremor_script::{
     ast::{self, ConnectStmt, DeployFlow, Helper},
     errors::{error_generic, not_defined_err},
 };
+use value_trait::ValueAccess;
 
 /// unique identifier of a flow instance within a tremor instance
 #[derive(Debug, PartialEq, PartialOrd, Eq, Hash, Clone, Serialize, Deserialize)]
@@ -97,14 +107,24 @@ pub(crate) enum Msg {
     GetConnector(connectors::Alias, Sender<Result<connectors::Addr>>),
     /// Get the addresses for all connectors of this flow
     GetConnectors(Sender<Result<Vec<connectors::Addr>>>),
+    /// Get the addr for a single pipeline
+    GetPipeline(String, Sender<Result<pipeline::Addr>>),
+    /// Get the addresses for all pipelines of this flow
+    GetPipelines(Sender<Result<Vec<pipeline::Addr>>>),
+    /// Attach a tap to a connector or pipeline of this flow
+    Tap(tap::Target, Tap, Sender<Result<()>>),
 }
 type Addr = Sender<Msg>;
 
+/// deploy setting overriding the drain timeout of the runtime
+const DRAIN_TIMEOUT_SETTING: &str = "drain_timeout";
+
 /// A deployed Flow instance
 #[derive(Debug, Clone)]
 pub struct Flow {
     alias: Alias,
     addr: Addr,
+    drain_timeout: Duration,
 }
 
 /// Status Report for a Flow instance
@@ -116,12 +136,20 @@ pub struct StatusReport {
     pub status: State,
     /// the crated connectors
     pub connectors: Vec<connectors::Alias>,
+    /// the connectors that did not yet reach quiescence while draining
+    pub draining: Vec<connectors::Alias>,
+    /// restarts of failed connectors, oldest first
+    pub restarts: Vec<supervision::Restart>,
 }
 
 impl Flow {
     pub(crate) fn id(&self) -> &Alias {
         &self.alias
     }
+    /// the time to wait for all connectors of this flow to drain
+    pub(crate) fn drain_timeout(&self) -> Duration {
+        self.drain_timeout
+    }
     pub(crate) async fn stop(&self, tx: Sender<Result<()>>) -> Result<()> {
         self.addr.send(Msg::Stop(tx)).await.map_err(Error::from)
     }
@@ -162,6 +190,41 @@ impl Flow {
         rx.recv().await?
     }
 
+    /// get the Address used to send messages of a pipeline within this flow, identified by `pipeline_alias`
+    ///
+    /// # Errors
+    /// if the flow is not running anymore and can't be reached or if the pipeline is not part of the flow
+    pub async fn get_pipeline(&self, pipeline_alias: String) -> Result<pipeline::Addr> {
+        let (tx, rx) = bounded(1);
+        self.addr.send(Msg::GetPipeline(pipeline_alias, tx)).await?;
+        rx.recv().await?
+    }
+
+    /// Get the Addresses of all pipelines of this flow
+    ///
+    /// # Errors
+    /// if the flow is not running anymore and can't be reached
+    pub async fn get_pipelines(&self) -> Result<Vec<pipeline::Addr>> {
+        let (tx, rx) = bounded(1);
+        self.addr.send(Msg::GetPipelines(tx)).await?;
+        rx.recv().await?
+    }
+
+    /// Tap a port of a connector or pipeline of this flow, receiving copies of the events passing it
+    ///
+    /// # Errors
+    /// if the flow is not running anymore and can't be reached, if the tapped instance or port is not part of the flow
+    /// or if the tap config is invalid
+    pub async fn tap(&self, config: &tap::Config) -> Result<TapRx> {
+        let (tap, tap_rx) = Tap::new(config)?;
+        let (tx, rx) = bounded(1);
+        self.addr
+            .send(Msg::Tap(config.target.clone(), tap, tx))
+            .await?;
+        rx.recv().await??;
+        Ok(tap_rx)
+    }
+
     /// Pause this flow and all connectors in it.
     ///
     /// # Errors
@@ -186,10 +249,14 @@ impl Flow {
         connector_id_gen: &mut ConnectorIdGen,
         known_connectors: &Known,
         kill_switch: &KillSwitch,
+        default_drain_timeout: Duration,
+        tracer: &Arc<Tracer>,
     ) -> Result<Self> {
         let mut pipelines = HashMap::new();
         let mut connectors = HashMap::new();
         let flow_alias = Alias::from(&flow);
+        let drain_timeout = drain_timeout(&flow)?.unwrap_or(default_drain_timeout);
+        let supervisor = Supervisor::from_flow(&flow)?;
 
         for create in &flow.defn.creates {
             let alias: &str = &create.instance_alias;
@@ -213,6 +280,7 @@ impl Flow {
                             builder.as_ref(),
                             config,
                             kill_switch,
+                            tracer.clone(),
                         )
                         .await?,
                     );
@@ -229,7 +297,12 @@ impl Flow {
                     let pipeline = tremor_pipeline::query::Query(
                         tremor_script::query::Query::from_query(query),
                     );
-                    let addr = pipeline::spawn(pipeline_alias, &pipeline, operator_id_gen)?;
+                    let addr = pipeline::spawn(
+                        pipeline_alias,
+                        &pipeline,
+                        operator_id_gen,
+                        tracer.clone(),
+                    )?;
                     pipelines.insert(alias.to_string(), addr);
                 }
             }
@@ -245,6 +318,8 @@ impl Flow {
             pipelines,
             connectors,
             &flow.defn.connections,
+            drain_timeout,
+            supervisor,
         )
         .await?;
 
@@ -253,12 +328,54 @@ impl Flow {
         let this = Flow {
             alias: flow_alias.clone(),
             addr,
+            drain_timeout,
         };
 
         Ok(this)
     }
 }
 
+/// send stop to all connectors and pipelines,
+/// returns the number of connectors to expect a stop result from
+async fn stop_children(
+    prefix: &str,
+    connectors: impl Iterator<Item = &connectors::Addr>,
+    pipelines: &HashMap<String, pipeline::Addr>,
+    stop_tx: &Sender<ConnectorResult<()>>,
+) -> usize {
+    let mut expected_stops = 0;
+    for connector in connectors {
+        if !log_error!(
+            connector.stop(stop_tx.clone()).await,
+            "{prefix} Error stopping connector {connector}: {e}"
+        ) {
+            expected_stops += 1;
+        }
+    }
+    for pipeline in pipelines.values() {
+        if let Err(e) = pipeline.stop().await {
+            error!("{prefix} Error stopping pipeline {pipeline:?}: {e}");
+        }
+    }
+    expected_stops
+}
+
+/// the drain timeout from the `drain_timeout` setting of the deployment, in nanoseconds
+fn drain_timeout(flow: &DeployFlow) -> Result<Option<Duration>> {
+    flow.settings
+        .get(DRAIN_TIMEOUT_SETTING)
+        .map(|timeout| {
+            timeout.as_u64().map(Duration::from_nanos).ok_or_else(|| {
+                ErrorKind::InvalidConfiguration(
+                    format!("flow {}", flow.instance_alias),
+                    format!("'{DRAIN_TIMEOUT_SETTING}' must be a positive integer of nanoseconds"),
+                )
+                .into()
+            })
+        })
+        .transpose()
+}
+
 fn key_list<K: ToString, V>(h: &HashMap<K, V>) -> String {
     h.keys()
         .map(ToString::to_string)
@@ -398,6 +515,8 @@ async fn spawn_task(
     pipelines: HashMap<String, pipeline::Addr>,
     connectors: HashMap<String, connectors::Addr>,
     links: &[ConnectStmt],
+    drain_timeout: Duration,
+    mut supervisor: Supervisor,
 ) -> Result<Addr> {
     #[derive(Debug)]
     /// wrapper for all possible messages handled by the flow task
@@ -405,18 +524,23 @@ async fn spawn_task(
         Msg(Msg),
         StartResult(ConnectorResult<()>),
         DrainResult(ConnectorResult<()>),
+        DrainTimeout,
         StopResult(ConnectorResult<()>),
     }
 
     let (msg_tx, msg_rx) = bounded(crate::QSIZE.load(Ordering::Relaxed));
     let (drain_tx, drain_rx) = unbounded();
+    let (drain_timeout_tx, drain_timeout_rx) = bounded(1);
     let (stop_tx, stop_rx) = unbounded();
     let (start_tx, start_rx) = unbounded();
 
     let mut input_channel = PriorityMerge::new(
         msg_rx.map(MsgWrapper::Msg),
         PriorityMerge::new(
-            drain_rx.map(MsgWrapper::DrainResult),
+            PriorityMerge::new(
+                drain_rx.map(MsgWrapper::DrainResult),
+                drain_timeout_rx.map(|()| MsgWrapper::DrainTimeout),
+            ),
             PriorityMerge::new(
                 stop_rx.map(MsgWrapper::StopResult),
                 start_rx.map(MsgWrapper::StartResult),
@@ -449,8 +573,6 @@ async fn spawn_task(
         })
         .collect();
 
-    let pipelines: Vec<_> = pipelines.values().cloned().collect();
-
     let start_points: Vec<_> = source_connectors
         .difference(&sink_connectors)
         .filter_map(|p| connectors.get(p))
@@ -468,8 +590,10 @@ async fn spawn_task(
         .collect();
 
     // for receiving drain/stop completion notifications from connectors
-    let mut expected_drains: usize = 0;
+    let mut draining: HashSet<connectors::Alias> = HashSet::new();
     let mut expected_stops: usize = 0;
+    // if stop has been sent to all connectors and pipelines already
+    let mut children_stopped = false;
 
     // for storing senders that have been sent to us
     let mut drain_senders = Vec::with_capacity(1);
@@ -484,7 +608,7 @@ async fn spawn_task(
                 MsgWrapper::Msg(Msg::Start) if state == State::Initializing => {
                     info!("{prefix} Starting...");
                     // start all pipelines first - order doesnt matter as connectors aren't started yet
-                    for pipe in &pipelines {
+                    for pipe in pipelines.values() {
                         pipe.start().await?;
                     }
 
@@ -511,7 +635,7 @@ async fn spawn_task(
                     for source in start_points.iter().chain(&mixed_pickles).chain(&end_points) {
                         source.pause().await?;
                     }
-                    for pipeline in &pipelines {
+                    for pipeline in pipelines.values() {
                         pipeline.pause().await?;
                     }
                     state = State::Paused;
@@ -523,7 +647,7 @@ async fn spawn_task(
                 MsgWrapper::Msg(Msg::Resume) if state == State::Paused => {
                     info!("{prefix} Resuming...");
 
-                    for pipeline in &pipelines {
+                    for pipeline in pipelines.values() {
                         pipeline.resume().await?;
                     }
                     for sink in end_points.iter().chain(&mixed_pickles).chain(&start_points) {
@@ -565,39 +689,39 @@ async fn spawn_task(
                                 addr.drain(drain_tx.clone()).await,
                                 "{prefix} Error starting Draining Connector {addr:?}: {e}"
                             ) {
-                                expected_drains += 1;
+                                draining.insert(addr.alias.clone());
                             }
                         }
+                        let drain_timeout_tx = drain_timeout_tx.clone();
+                        task::spawn(async move {
+                            task::sleep(drain_timeout).await;
+                            // the flow might be stopped already
+                            drain_timeout_tx.send(()).await.ok();
+                        });
                     }
                 }
                 MsgWrapper::Msg(Msg::Stop(sender)) => {
                     info!("{prefix} Stopping...");
-                    if connectors.is_empty() {
+                    if !children_stopped {
+                        expected_stops += stop_children(
+                            &prefix,
+                            end_points.iter().chain(&start_points).chain(&mixed_pickles),
+                            &pipelines,
+                            &stop_tx,
+                        )
+                        .await;
+                        children_stopped = true;
+                    }
+                    state = State::Stopped;
+                    if expected_stops == 0 {
+                        // e.g. the children were already stopped by the supervision
                         log_error!(
                             sender.send(Ok(())).await,
                             "{prefix} Error sending Stop result: {e}"
                         );
-                    } else {
-                        stop_senders.push(sender);
-                        for connector in
-                            end_points.iter().chain(&start_points).chain(&mixed_pickles)
-                        {
-                            if !log_error!(
-                                connector.stop(stop_tx.clone()).await,
-                                "{prefix} Error stopping connector {connector}: {e}"
-                            ) {
-                                expected_stops += 1;
-                            }
-                        }
-                    }
-
-                    for pipeline in &pipelines {
-                        if let Err(e) = pipeline.stop().await {
-                            error!("{prefix} Error stopping pipeline {pipeline:?}: {e}");
-                        }
+                        break;
                     }
-
-                    state = State::Stopped;
+                    stop_senders.push(sender);
                 }
                 MsgWrapper::Msg(Msg::Report(sender)) => {
                     // TODO: aggregate states of all containing instances
@@ -609,6 +733,8 @@ async fn spawn_task(
                         alias: id.clone(),
                         status: state,
                         connectors,
+                        draining: draining.iter().cloned().collect(),
+                        restarts: supervisor.history(),
                     };
                     log_error!(
                         sender.send(Ok(report)).await,
@@ -641,6 +767,57 @@ async fn spawn_task(
                         "{prefix} Error sending GetConnectors response: {e}"
                     );
                 }
+                MsgWrapper::Msg(Msg::GetPipeline(pipeline_alias, reply_tx)) => {
+                    let res = pipelines.get(&pipeline_alias).cloned().ok_or_else(|| {
+                        ErrorKind::PipelineNotFound(id.to_string(), pipeline_alias).into()
+                    });
+                    log_error!(
+                        reply_tx.send(res).await,
+                        "{prefix} Error sending GetPipeline response: {e}"
+                    );
+                }
+                MsgWrapper::Msg(Msg::Tap(target, tap, reply_tx)) => {
+                    let res = match &target {
+                        tap::Target::Connector(alias) => {
+                            if let Some(connector) = connectors.get(alias) {
+                                let msg = connectors::Msg::Tap {
+                                    tap,
+                                    result_tx: reply_tx.clone(),
+                                };
+                                connector.send(msg).await
+                            } else {
+                                Err(ErrorKind::ConnectorNotFound(id.to_string(), alias.clone())
+                                    .into())
+                            }
+                        }
+                        tap::Target::Pipeline(alias) => {
+                            if let Some(pipeline) = pipelines.get(alias) {
+                                let msg = pipeline::MgmtMsg::Tap {
+                                    tap,
+                                    tx: reply_tx.clone(),
+                                };
+                                pipeline.send_mgmt(msg).await
+                            } else {
+                                Err(ErrorKind::PipelineNotFound(id.to_string(), alias.clone())
+                                    .into())
+                            }
+                        }
+                    };
+                    // on success the tapped instance replies
+                    if let Err(e) = res {
+                        log_error!(
+                            reply_tx.send(Err(e)).await,
+                            "{prefix} Error sending Tap response: {e}"
+                        );
+                    }
+                }
+                MsgWrapper::Msg(Msg::GetPipelines(reply_tx)) => {
+                    let res = pipelines.values().cloned().collect::<Vec<_>>();
+                    log_error!(
+                        reply_tx.send(Ok(res)).await,
+                        "{prefix} Error sending GetPipelines response: {e}"
+                    );
+                }
 
                 MsgWrapper::DrainResult(conn_res) => {
                     info!("[Flow::{}] Connector {} drained.", &id, &conn_res.alias);
@@ -651,9 +828,7 @@ async fn spawn_task(
                         &conn_res.alias
                     );
 
-                    let old = expected_drains;
-                    expected_drains = expected_drains.saturating_sub(1);
-                    if expected_drains == 0 && old > 0 {
+                    if draining.remove(&conn_res.alias) && draining.is_empty() {
                         info!("{prefix} All connectors are drained.");
                         // upon last drain
                         for drain_sender in drain_senders.drain(..) {
@@ -664,6 +839,38 @@ async fn spawn_task(
                         }
                     }
                 }
+                MsgWrapper::DrainTimeout if draining.is_empty() => {
+                    // all connectors drained already or none could be asked to drain
+                    for drain_sender in drain_senders.drain(..) {
+                        log_error!(
+                            drain_sender.send(Ok(())).await,
+                            "{prefix} Error sending Drain result: {e}"
+                        );
+                    }
+                }
+                MsgWrapper::DrainTimeout => {
+                    let pending = draining
+                        .iter()
+                        .map(ToString::to_string)
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    warn!(
+                        "{prefix} Timeout draining after {}ms. Connectors not drained: {pending}",
+                        drain_timeout.as_millis()
+                    );
+                    for drain_sender in drain_senders.drain(..) {
+                        log_error!(
+                            drain_sender
+                                .send(Err(ErrorKind::FlowDrainTimeout(
+                                    id.to_string(),
+                                    pending.clone()
+                                )
+                                .into()))
+                                .await,
+                            "{prefix} Error sending Drain timeout result: {e}"
+                        );
+                    }
+                }
                 MsgWrapper::StopResult(conn_res) => {
                     info!("[Flow::{}] Connector {} stopped.", &id, &conn_res.alias);
 
@@ -675,7 +882,8 @@ async fn spawn_task(
 
                     let old = expected_stops;
                     expected_stops = expected_stops.saturating_sub(1);
-                    if expected_stops == 0 && old > 0 {
+                    // a flow stopped by its supervision keeps on reporting its status
+                    if expected_stops == 0 && old > 0 && !stop_senders.is_empty() {
                         info!("{prefix} All connectors are stopped.");
                         // upon last stop
                         for stop_sender in stop_senders.drain(..) {
@@ -687,6 +895,58 @@ async fn spawn_task(
                         break;
                     }
                 }
+                MsgWrapper::StartResult(ConnectorResult { alias, res: Err(e) })
+                    if matches!(state, State::Running | State::Paused) =>
+                {
+                    // the connector failed after it started
+                    error!("{prefix} Connector {alias} failed: {e}");
+                    let action =
+                        supervisor.on_failure(alias.connector_alias(), e.to_string(), nanotime());
+                    match action {
+                        Action::None => (),
+                        Action::RestartConnector => {
+                            info!("{prefix} Restarting Connector {alias}...");
+                            if let Some(connector) =
+                                connectors.get(&alias.connector_alias().to_string())
+                            {
+                                log_error!(
+                                    connector.restart().await,
+                                    "{prefix} Error restarting Connector {alias}: {e}"
+                                );
+                            }
+                        }
+                        Action::RestartFlow => {
+                            info!("{prefix} Restarting all connectors...");
+                            for connector in
+                                end_points.iter().chain(&mixed_pickles).chain(&start_points)
+                            {
+                                log_error!(
+                                    connector.restart().await,
+                                    "{prefix} Error restarting Connector {connector}: {e}"
+                                );
+                            }
+                        }
+                        Action::StopFlow { escalated } => {
+                            if escalated {
+                                error!("{prefix} Too many restarts, stopping...");
+                            } else {
+                                info!("{prefix} Stopping...");
+                            }
+                            if !children_stopped {
+                                expected_stops += stop_children(
+                                    &prefix,
+                                    end_points.iter().chain(&start_points).chain(&mixed_pickles),
+                                    &pipelines,
+                                    &stop_tx,
+                                )
+                                .await;
+                                children_stopped = true;
+                            }
+                            state = State::Failed;
+                            info!("{prefix} Failed.");
+                        }
+                    }
+                }
                 MsgWrapper::StartResult(conn_res) => {
                     if let Err(e) = conn_res.res {
                         error!(
@@ -836,11 +1096,115 @@ mod tests {
         }
     }
 
+    fn deploy_flow(src: &str) -> Result<DeployFlow<'static>> {
+        let aggr_reg = tremor_script::aggr_registry();
+        let deployable = Deploy::parse(&src, &*FN_REGISTRY.read()?, &aggr_reg)?;
+        Ok(deployable
+            .deploy
+            .stmts
+            .into_iter()
+            .find_map(|stmt| match stmt {
+                DeployStmt::DeployFlowStmt(deploy_flow) => Some((*deploy_flow).clone()),
+                _other => None,
+            })
+            .expect("No deploy in the given troy file"))
+    }
+
+    #[test]
+    fn drain_timeout_setting() -> Result<()> {
+        let src = r#"
+        define flow test
+        flow
+            define pipeline main
+            pipeline
+                select event from in into out;
+            end;
+        end;
+        deploy flow test with drain_timeout = 30000000000 end;
+        "#;
+        assert_eq!(
+            Some(Duration::from_secs(30)),
+            drain_timeout(&deploy_flow(src)?)?
+        );
+        let src = r#"
+        define flow test
+        flow
+            define pipeline main
+            pipeline
+                select event from in into out;
+            end;
+        end;
+        deploy flow test with drain_timeout = "snot" end;
+        "#;
+        assert!(drain_timeout(&deploy_flow(src)?).is_err());
+        let src = r#"
+        define flow test
+        flow
+            define pipeline main
+            pipeline
+                select event from in into out;
+            end;
+        end;
+        deploy flow test;
+        "#;
+        assert_eq!(None, drain_timeout(&deploy_flow(src)?)?);
+        // settings are no flow arguments, they don't need to be declared and don't clash with them
+        let src = r#"
+        define flow test
+        args
+            snot = "badger"
+        flow
+            define pipeline main
+            pipeline
+                select event from in into out;
+            end;
+        end;
+        deploy flow test with snot = "meh", drain_timeout = 1000 end;
+        "#;
+        let flow = deploy_flow(src)?;
+        assert_eq!(Some(Duration::from_nanos(1000)), drain_timeout(&flow)?);
+        assert!(flow.defn.params.render()?.get("drain_timeout").is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn supervision_setting() -> Result<()> {
+        let src = r#"
+        define flow test
+        flow
+            define pipeline main
+            pipeline
+                select event from in into out;
+            end;
+        end;
+        deploy flow test
+        with
+            supervision = {"policy": "restart_connector", "max_restarts": 1}
+        end;
+        "#;
+        let mut supervisor = Supervisor::from_flow(&deploy_flow(src)?)?;
+        assert_eq!(
+            Action::RestartConnector,
+            supervisor.on_failure("snot", String::new(), 0)
+        );
+        let src = r#"
+        define flow test
+        flow
+            define pipeline main
+            pipeline
+                select event from in into out;
+            end;
+        end;
+        deploy flow test with supervision = {"policy": "restart_snot"} end;
+        "#;
+        assert!(Supervisor::from_flow(&deploy_flow(src)?).is_err());
+        Ok(())
+    }
+
     #[async_std::test]
     async fn flow_spawn() -> Result<()> {
         let mut operator_id_gen = OperatorIdGen::default();
         let mut connector_id_gen = ConnectorIdGen::default();
-        let aggr_reg = tremor_script::aggr_registry();
         let src = r#"
         define flow test
         flow
@@ -865,16 +1229,7 @@ mod tests {
         "#;
         let (tx, _rx) = bounded(1);
         let kill_switch = KillSwitch(tx);
-        let deployable = Deploy::parse(&src, &*FN_REGISTRY.read()?, &aggr_reg)?;
-        let deploy = deployable
-            .deploy
-            .stmts
-            .into_iter()
-            .find_map(|stmt| match stmt {
-                DeployStmt::DeployFlowStmt(deploy_flow) => Some((*deploy_flow).clone()),
-                _other => None,
-            })
-            .expect("No deploy in the given troy file");
+        let deploy = deploy_flow(src)?;
         let mut known_connectors = Known::new();
         let (connector_tx, connector_rx) = unbounded();
         let builder = connector::FakeBuilder { tx: connector_tx };
@@ -885,8 +1240,14 @@ mod tests {
             &mut connector_id_gen,
             &known_connectors,
             &kill_switch,
+            crate::system::DEFAULT_GRACEFUL_SHUTDOWN_TIMEOUT,
+            &Arc::default(),
         )
         .await?;
+        assert_eq!(
+            crate::system::DEFAULT_GRACEFUL_SHUTDOWN_TIMEOUT,
+            flow.drain_timeout()
+        );
 
         let connector = flow.get_connector("foo".to_string()).await?;
         assert_eq!(String::from("test::foo"), connector.alias.to_string());
@@ -895,6 +1256,10 @@ mod tests {
         assert_eq!(1, connectors.len());
         assert_eq!(String::from("test::foo"), connectors[0].alias.to_string());
 
+        let pipelines = flow.get_pipelines().await?;
+        assert_eq!(1, pipelines.len());
+        assert_eq!("main", pipelines[0].report_status().await?.alias());
+
         // assert the flow has started and events are flowing
         let event = connector_rx.recv().await?;
         assert_eq!(
@@ -912,6 +1277,36 @@ mod tests {
         assert_eq!(instance::State::Running, report.status);
         assert_eq!(1, report.connectors.len());
 
+        // tap the pipeline output and the connector input
+        let pipeline_tap = tap::Target::Pipeline("main".to_string());
+        let tap_rx = flow
+            .tap(&tap::Config::new(pipeline_tap.clone(), "out"))
+            .await?;
+        let record = tap_rx.recv().await?;
+        assert_eq!(Some("out"), record.get_str("port"));
+        assert_eq!(Some("badger"), record.get("data").get_str("snot"));
+        let connector_tap = tap::Target::Connector("foo".to_string());
+        let tap_rx = flow
+            .tap(&tap::Config::new(connector_tap.clone(), "in"))
+            .await?;
+        let record = tap_rx.recv().await?;
+        assert_eq!(Some("in"), record.get_str("port"));
+        assert!(flow
+            .tap(&tap::Config::new(pipeline_tap, "snot"))
+            .await
+            .is_err());
+        assert!(flow
+            .tap(&tap::Config::new(connector_tap, "badger"))
+            .await
+            .is_err());
+        assert!(flow
+            .tap(&tap::Config::new(
+                tap::Target::Connector("bar".to_string()),
+                "out"
+            ))
+            .await
+            .is_err());
+
         flow.pause().await?;
         let report = flow.report_status().await?;
         assert_eq!(instance::State::Paused, report.status);
@@ -926,6 +1321,10 @@ mod tests {
         let (tx, rx) = bounded(1);
         flow.drain(tx.clone()).await?;
         rx.recv().await??;
+        let report = flow.report_status().await?;
+        assert_eq!(instance::State::Draining, report.status);
+        assert!(report.draining.is_empty());
+        assert!(report.restarts.is_empty());
 
         flow.stop(tx).await?;
         rx.recv().await??;
@@ -0,0 +1,285 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Supervision of the connectors of a flow
+//!
+//! Connectors failing after they started, e.g. because they gave up reconnecting,
+//! are handled according to the `policy` in the `supervision` setting of the deployment:
+//!
+//! ```troy
+//! deploy flow main
+//! with
+//!   supervision = {
+//!     "policy": "restart_connector",
+//!     "max_restarts": 3,
+//!     "within": 60000000000
+//!   }
+//! end;
+//! ```
+//!
+//! If a flow needs more than `max_restarts` restarts `within` the given nanoseconds,
+//! the failure is escalated and the flow is stopped.
+
+use crate::errors::{Error, Kind as ErrorKind, Result};
+use std::collections::VecDeque;
+use tremor_script::ast::DeployFlow;
+use value_trait::ValueAccess;
+
+/// deploy setting containing the supervision config
+const SUPERVISION_SETTING: &str = "supervision";
+
+/// number of restarts kept in the history of a flow, in addition to those within the restart window
+const HISTORY_SIZE: usize = 64;
+
+/// What to do when a connector of a flow fails
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Policy {
+    /// leave the connector in its failed state
+    #[default]
+    None,
+    /// restart only the failed connector
+    RestartConnector,
+    /// restart all connectors of the flow
+    RestartFlow,
+    /// stop the flow
+    StopFlow,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Config {
+    #[serde(default)]
+    policy: Policy,
+    /// maximum number of restarts `within` the time window before the flow is stopped
+    #[serde(default = "default_max_restarts")]
+    max_restarts: usize,
+    /// time window for `max_restarts` in nanoseconds
+    #[serde(default = "default_within")]
+    within: u64,
+}
+
+fn default_max_restarts() -> usize {
+    3
+}
+
+fn default_within() -> u64 {
+    60_000_000_000
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            policy: Policy::default(),
+            max_restarts: default_max_restarts(),
+            within: default_within(),
+        }
+    }
+}
+
+/// A restart done due to a failed connector
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Restart {
+    /// alias of the failed connector
+    pub connector: String,
+    /// why the connector failed
+    pub reason: String,
+    /// the applied policy
+    pub policy: Policy,
+    /// time of the restart in nanoseconds since epoch
+    pub timestamp: u64,
+}
+
+/// Action to take upon a failed connector
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Action {
+    /// leave the connector in its failed state
+    None,
+    /// restart the failed connector
+    RestartConnector,
+    /// restart all connectors
+    RestartFlow,
+    /// stop the flow, `escalated` if the restart limit was hit
+    StopFlow { escalated: bool },
+}
+
+/// keeps track of the restarts of a flow
+#[derive(Debug)]
+pub(crate) struct Supervisor {
+    config: Config,
+    history: VecDeque<Restart>,
+}
+
+impl Supervisor {
+    pub(crate) fn new(config: Config) -> Self {
+        Self {
+            config,
+            history: VecDeque::with_capacity(HISTORY_SIZE),
+        }
+    }
+
+    /// create a supervisor from the `supervision` setting of the given deployment
+    pub(crate) fn from_flow(flow: &DeployFlow) -> Result<Self> {
+        let config = if let Some(config) = flow.settings.get(SUPERVISION_SETTING) {
+            tremor_value::structurize(config.clone()).map_err(|e| {
+                let kind = ErrorKind::InvalidConfiguration(
+                    format!("supervision of flow {}", flow.instance_alias),
+                    e.to_string(),
+                );
+                Error::with_chain(e, kind)
+            })?
+        } else {
+            Config::default()
+        };
+        Ok(Self::new(config))
+    }
+
+    /// the restarts done so far, oldest first
+    pub(crate) fn history(&self) -> Vec<Restart> {
+        self.history.iter().cloned().collect()
+    }
+
+    /// decide what to do about the `connector` that failed at `now`
+    pub(crate) fn on_failure(&mut self, connector: &str, reason: String, now: u64) -> Action {
+        let action = match self.config.policy {
+            Policy::None => return Action::None,
+            Policy::StopFlow => return Action::StopFlow { escalated: false },
+            Policy::RestartConnector => Action::RestartConnector,
+            Policy::RestartFlow => Action::RestartFlow,
+        };
+        let window_start = now.saturating_sub(self.config.within);
+        let recent = self
+            .history
+            .iter()
+            .filter(|restart| restart.timestamp >= window_start)
+            .count();
+        if recent >= self.config.max_restarts {
+            return Action::StopFlow { escalated: true };
+        }
+        // restarts within the window are needed to decide upon escalation
+        while self.history.len() >= HISTORY_SIZE
+            && self
+                .history
+                .front()
+                .map_or(false, |restart| restart.timestamp < window_start)
+        {
+            self.history.pop_front();
+        }
+        self.history.push_back(Restart {
+            connector: connector.to_string(),
+            reason,
+            policy: self.config.policy,
+            timestamp: now,
+        });
+        action
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tremor_value::literal;
+
+    fn supervisor(config: tremor_value::Value<'static>) -> Result<Supervisor> {
+        Ok(Supervisor::new(tremor_value::structurize(config)?))
+    }
+
+    #[test]
+    fn restart_intensity() -> Result<()> {
+        let mut s = supervisor(literal!({
+            "policy": "restart_connector",
+            "max_restarts": 2,
+            "within": 100
+        }))?;
+        assert_eq!(
+            Action::RestartConnector,
+            s.on_failure("snot", "failed".to_string(), 1000)
+        );
+        assert_eq!(
+            Action::RestartConnector,
+            s.on_failure("badger", "failed".to_string(), 1050)
+        );
+        assert_eq!(
+            Action::StopFlow { escalated: true },
+            s.on_failure("snot", "failed".to_string(), 1099)
+        );
+        // the first restart is out of the window now
+        assert_eq!(
+            Action::RestartConnector,
+            s.on_failure("snot", "failed".to_string(), 1101)
+        );
+        let history = s.history();
+        assert_eq!(3, history.len());
+        assert_eq!("badger", history[1].connector);
+        assert_eq!(Policy::RestartConnector, history[1].policy);
+        assert_eq!(1101, history[2].timestamp);
+        Ok(())
+    }
+
+    #[test]
+    fn policies() -> Result<()> {
+        let mut s = Supervisor::new(Config::default());
+        assert_eq!(Action::None, s.on_failure("snot", String::new(), 0));
+        let mut s = supervisor(literal!({"policy": "stop_flow"}))?;
+        assert_eq!(
+            Action::StopFlow { escalated: false },
+            s.on_failure("snot", String::new(), 0)
+        );
+        let mut s = supervisor(literal!({"policy": "restart_flow", "max_restarts": 0}))?;
+        assert_eq!(
+            Action::StopFlow { escalated: true },
+            s.on_failure("snot", String::new(), 0)
+        );
+        assert!(s.history().is_empty());
+        assert!(supervisor(literal!({"policy": "snot"})).is_err());
+        assert!(supervisor(literal!({"badger": 1})).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn bounded_history() {
+        let mut s = Supervisor::new(Config {
+            policy: Policy::RestartFlow,
+            max_restarts: usize::MAX,
+            within: 0,
+        });
+        for i in 0..(HISTORY_SIZE as u64 * 2) {
+            assert_eq!(Action::RestartFlow, s.on_failure("snot", String::new(), i));
+        }
+        let history = s.history();
+        assert_eq!(HISTORY_SIZE, history.len());
+        assert_eq!(HISTORY_SIZE as u64, history[0].timestamp);
+    }
+
+    #[test]
+    fn more_restarts_than_history() {
+        let max_restarts = HISTORY_SIZE * 2;
+        let mut s = Supervisor::new(Config {
+            policy: Policy::RestartConnector,
+            max_restarts,
+            within: u64::MAX,
+        });
+        for i in 0..max_restarts as u64 {
+            assert_eq!(
+                Action::RestartConnector,
+                s.on_failure("snot", String::new(), i)
+            );
+        }
+        assert_eq!(
+            Action::StopFlow { escalated: true },
+            s.on_failure("snot", String::new(), max_restarts as u64)
+        );
+        assert_eq!(max_restarts, s.history().len());
+    }
+}
@@ -24,11 +24,16 @@ use async_std::channel::{bounded, Sender};
 use async_std::prelude::*;
 use async_std::task::{self, JoinHandle};
 use hashbrown::{hash_map::Entry, HashMap};
+use std::{sync::Arc, time::Duration};
 use tremor_common::ids::{ConnectorIdGen, OperatorIdGen};
+use tremor_pipeline::trace::Tracer;
 use tremor_script::ast::DeployFlow;
 
 pub(crate) type Channel = Sender<Msg>;
 
+/// additional time given to flows to report back after their drain timeout
+const DRAIN_RESPONSE_GRACE: Duration = Duration::from_secs(1);
+
 /// This is control plane
 #[derive(Debug)]
 pub(crate) enum Msg {
@@ -60,16 +65,20 @@ pub(crate) struct FlowSupervisor {
     connector_id_gen: ConnectorIdGen,
     known_connectors: connectors::Known,
     qsize: usize,
+    drain_timeout: Duration,
+    tracer: Arc<Tracer>,
 }
 
 impl FlowSupervisor {
-    pub fn new(qsize: usize) -> Self {
+    pub fn new(qsize: usize, drain_timeout: Duration, tracer: Arc<Tracer>) -> Self {
         Self {
             flows: HashMap::new(),
             known_connectors: connectors::Known::new(),
             operator_id_gen: OperatorIdGen::new(),
             connector_id_gen: ConnectorIdGen::new(),
             qsize,
+            drain_timeout,
+            tracer,
         }
     }
 
@@ -98,6 +107,8 @@ impl FlowSupervisor {
                 &mut self.connector_id_gen,
                 &self.known_connectors,
                 kill_switch,
+                self.drain_timeout,
+                &self.tracer,
             )
             .await
             .map(|deploy| {
@@ -167,6 +178,7 @@ impl FlowSupervisor {
             let num_flows = self.flows.len();
             info!("Draining all {num_flows} Flows ...");
             let mut alive_flows = 0_usize;
+            let mut timeout = Duration::default();
             let (tx, rx) = bounded(num_flows);
             for (_, flow) in &self.flows {
                 if !log_error!(
@@ -175,19 +187,29 @@ impl FlowSupervisor {
                     alias = flow.id()
                 ) {
                     alive_flows += 1;
+                    timeout = timeout.max(flow.drain_timeout());
                 }
             }
             task::spawn::<_, Result<()>>(async move {
                 let rx_futures = std::iter::repeat_with(|| rx.recv()).take(alive_flows);
-                for result in futures::future::join_all(rx_futures).await {
-                    match result {
-                        Ok(Err(e)) => {
-                            error!("Error during Draining: {}", e);
+                // flows report back after their drain timeout at the latest,
+                // this only guards against flows that can't respond anymore
+                if let Ok(results) = futures::future::join_all(rx_futures)
+                    .timeout(timeout + DRAIN_RESPONSE_GRACE)
+                    .await
+                {
+                    for result in results {
+                        match result {
+                            Ok(Err(e)) => {
+                                error!("Error during Draining: {}", e);
+                            }
+                            Err(_) | Ok(Ok(())) => {}
                         }
-                        Err(_) | Ok(Ok(())) => {}
                     }
+                    info!("Flows drained.");
+                } else {
+                    warn!("Timeout draining all Flows after {}s", timeout.as_secs());
                 }
-                info!("Flows drained.");
                 sender.send(Ok(())).await?;
                 Ok(())
             });
@@ -0,0 +1,230 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::connectors::{
+    impls::otel::{common::OtelDefaults, trace::resource_spans_to_pb},
+    utils::url::Url,
+};
+use crate::errors::Result;
+use crate::version::VERSION;
+use async_broadcast::{RecvError, TryRecvError};
+use async_std::{
+    fs::{File, OpenOptions},
+    io::WriteExt,
+    task,
+};
+use std::path::PathBuf;
+use tonic::transport::{Channel as TonicChannel, Endpoint as TonicEndpoint};
+use tremor_otelapis::opentelemetry::proto::collector::trace::v1::{
+    trace_service_client::TraceServiceClient, ExportTraceServiceRequest,
+};
+use tremor_pipeline::trace::{Span, Tracer};
+use tremor_value::{literal, Value};
+use value_trait::Writable;
+
+/// maximum number of spans exported at once
+const BATCH_SIZE: usize = 128;
+
+/// Where the spans of traced events are exported to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Exporter {
+    /// `OpenTelemetry` collector url, spans are sent via OTLP/gRPC like with the `otel_client` connector
+    Otel(String),
+    /// local file, every batch of spans is appended as one line in the json mapping of the `otel_client` connector
+    File(PathBuf),
+}
+
+/// Configuration for sampled tracing of events flowing through the runtime
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Config {
+    /// trace every `sample`th event
+    pub sample: u64,
+    /// where to export the spans to
+    pub exporter: Exporter,
+}
+
+enum Output {
+    Otel {
+        url: String,
+        client: Option<TraceServiceClient<TonicChannel>>,
+    },
+    File(File),
+}
+
+impl Output {
+    async fn new(exporter: &Exporter) -> Result<Self> {
+        match exporter {
+            Exporter::Otel(url) => Ok(Self::Otel {
+                url: Url::<OtelDefaults>::parse(url)?.to_string(),
+                client: None,
+            }),
+            Exporter::File(path) => {
+                let file = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .await?;
+                Ok(Self::File(file))
+            }
+        }
+    }
+
+    async fn export(&mut self, batch: &Value<'static>) -> Result<()> {
+        match self {
+            Self::Otel { url, client } => {
+                let request = ExportTraceServiceRequest {
+                    resource_spans: resource_spans_to_pb(Some(batch))?,
+                };
+                // (re-)connect lazily, an unavailable collector must not keep the runtime from starting
+                let mut remote = if let Some(remote) = client.take() {
+                    remote
+                } else {
+                    let channel = TonicEndpoint::from_shared(url.clone())
+                        .map_err(|e| format!("Unable to connect to remote otel endpoint: {e}"))?
+                        .connect()
+                        .await?;
+                    TraceServiceClient::new(channel)
+                };
+                remote.export(request).await?;
+                *client = Some(remote);
+            }
+            Self::File(file) => {
+                let mut line = batch.encode();
+                line.push('\n');
+                file.write_all(line.as_bytes()).await?;
+                file.flush().await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// the spans in the json mapping of the `otel_client` connector
+fn to_batch(spans: &[Span]) -> Value<'static> {
+    let spans: Value = spans.iter().map(Span::to_value).collect();
+    literal!({
+        "trace": [{
+            "resource": {
+                "attributes": { "service.name": "tremor" },
+                "dropped_attributes_count": 0
+            },
+            "schema_url": "",
+            "instrumentation_library_spans": [{
+                "instrumentation_library": { "name": "tremor", "version": VERSION },
+                "schema_url": "",
+                "spans": spans
+            }]
+        }]
+    })
+}
+
+/// Starts sampling events and exporting the spans of the sampled ones
+pub(crate) async fn start(config: &Config, tracer: &Tracer) -> Result<()> {
+    if config.sample == 0 {
+        return Err("Tracing `sample` must be greater than 0".into());
+    }
+    let mut output = Output::new(&config.exporter).await?;
+    let mut rx = tracer.rx();
+    tracer.set_sample(config.sample);
+    task::spawn(async move {
+        loop {
+            let mut spans = match rx.recv().await {
+                Ok(span) => vec![span],
+                Err(RecvError::Overflowed(n)) => {
+                    warn!("[Tracer] Dropped {n} spans, exporting them can't keep up");
+                    continue;
+                }
+                Err(RecvError::Closed) => break,
+            };
+            while spans.len() < BATCH_SIZE {
+                match rx.try_recv() {
+                    Ok(span) => spans.push(span),
+                    Err(TryRecvError::Overflowed(_)) => continue,
+                    Err(_) => break,
+                }
+            }
+            if let Err(e) = output.export(&to_batch(&spans)).await {
+                warn!("[Tracer] Failed to export {} spans: {e}", spans.len());
+            }
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tremor_pipeline::trace::SpanContext;
+
+    #[test]
+    fn batch_to_pb() -> Result<()> {
+        let root = SpanContext::new_trace();
+        let child = root.child();
+        let spans = vec![
+            Span {
+                context: root,
+                parent: None,
+                name: "source in".to_string(),
+                start_ns: 1,
+                end_ns: 2,
+                attributes: literal!({"connector": "flow::in"}),
+            },
+            Span {
+                context: child,
+                parent: Some(root),
+                name: "sink out".to_string(),
+                start_ns: 3,
+                end_ns: 4,
+                attributes: literal!({"connector": "flow::out"}),
+            },
+        ];
+        let batch = to_batch(&spans);
+        let resource_spans = resource_spans_to_pb(Some(&batch))?;
+        assert_eq!(1, resource_spans.len());
+        let pb_spans = &resource_spans[0].instrumentation_library_spans[0].spans;
+        assert_eq!(2, pb_spans.len());
+        assert!(pb_spans[0].parent_span_id.is_empty());
+        assert_eq!(pb_spans[0].span_id, pb_spans[1].parent_span_id);
+        assert_eq!(pb_spans[0].trace_id, pb_spans[1].trace_id);
+        assert_eq!(16, pb_spans[1].trace_id.len());
+        assert_eq!("sink out", pb_spans[1].name);
+        assert_eq!(3, pb_spans[1].start_time_unix_nano);
+        assert!(resource_spans[0].resource.is_some());
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn file_exporter() -> Result<()> {
+        let dir = tempfile::Builder::new().tempdir()?;
+        let path = dir.path().join("spans.json");
+        let mut output = Output::new(&Exporter::File(path.clone())).await?;
+        let span = Span {
+            context: SpanContext::new_trace(),
+            parent: None,
+            name: "source in".to_string(),
+            start_ns: 1,
+            end_ns: 2,
+            attributes: Value::object(),
+        };
+        output.export(&to_batch(&[span.clone()])).await?;
+        output.export(&to_batch(&[span])).await?;
+        let content = async_std::fs::read_to_string(&path).await?;
+        let lines: Vec<_> = content.lines().collect();
+        assert_eq!(2, lines.len());
+        let mut line = lines[0].as_bytes().to_vec();
+        let batch = tremor_value::parse_to_value(&mut line)?;
+        assert_eq!(1, resource_spans_to_pb(Some(&batch))?.len());
+        Ok(())
+    }
+}
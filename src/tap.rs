@@ -0,0 +1,249 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::errors::{Error, Kind as ErrorKind, Result};
+use async_std::channel::{bounded, Receiver, Sender, TrySendError};
+use tremor_pipeline::{Event, KeyExpr};
+use tremor_script::EventContext;
+use tremor_value::{literal, Value};
+use value_trait::ValueAccess;
+
+/// number of copies buffered for a tap before further copies are dropped
+const TAP_QSIZE: usize = 128;
+
+/// Instance within a flow to tap
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Target {
+    /// connector alias
+    Connector(String),
+    /// pipeline alias
+    Pipeline(String),
+}
+
+impl std::fmt::Display for Target {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Connector(alias) => write!(f, "connector {alias}"),
+            Self::Pipeline(alias) => write!(f, "pipeline {alias}"),
+        }
+    }
+}
+
+/// Tap configuration
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// the instance to tap
+    pub target: Target,
+    /// the port to tap
+    pub port: String,
+    /// only send every `sample`th event passing the filter
+    pub sample: u64,
+    /// tremor-script expression, only events it evaluates to `true` for are sent
+    ///
+    /// It is evaluated on the tapped events in place, so it can't modify them.
+    pub filter: Option<String>,
+}
+
+impl Config {
+    /// tap all events passing `port` of `target`
+    #[must_use]
+    pub fn new(target: Target, port: impl Into<String>) -> Self {
+        Self {
+            target,
+            port: port.into(),
+            sample: 1,
+            filter: None,
+        }
+    }
+}
+
+/// Receiving end of a tap, yielding `{"port": ..., "ingest_ns": ..., "data": ..., "meta": ...}` records
+pub type TapRx = Receiver<Value<'static>>;
+
+/// Sending end of a tap, living in the tapped instance
+///
+/// Taps never apply backpressure, copies a slow tap can't keep up with are dropped.
+#[derive(Debug)]
+pub(crate) struct Tap {
+    port: String,
+    sample: u64,
+    seen: u64,
+    filter: Option<KeyExpr>,
+    tx: Sender<Value<'static>>,
+}
+
+impl Tap {
+    /// create a new tap from the given config
+    pub(crate) fn new(config: &Config) -> Result<(Self, TapRx)> {
+        if config.sample == 0 {
+            return Err(ErrorKind::InvalidTap(
+                config.target.to_string(),
+                "`sample` must be greater than 0".to_string(),
+            )
+            .into());
+        }
+        let filter = config
+            .filter
+            .as_deref()
+            .map(KeyExpr::parse)
+            .transpose()
+            .map_err(|e| {
+                let kind = ErrorKind::InvalidTap(
+                    config.target.to_string(),
+                    format!("invalid filter: {e}"),
+                );
+                Error::with_chain(e, kind)
+            })?;
+        let (tx, rx) = bounded(TAP_QSIZE);
+        let tap = Self {
+            port: config.port.clone(),
+            sample: config.sample,
+            seen: 0,
+            filter,
+            tx,
+        };
+        Ok((tap, rx))
+    }
+
+    /// the tapped port
+    pub(crate) fn port(&self) -> &str {
+        &self.port
+    }
+
+    /// check if the filter evaluates to `true` for the given event value and meta
+    fn matches(&self, event: &Event, data: &Value, meta: &Value) -> bool {
+        self.filter.as_ref().map_or(true, |filter| {
+            let ctx = EventContext::new(event.ingest_ns, event.origin_uri.as_ref());
+            filter
+                .eval(&ctx, data, meta)
+                .map_or(false, |value| value.as_bool() == Some(true))
+        })
+    }
+
+    /// send copies of all values of `event` passing the filter and sampling
+    ///
+    /// Only the copies actually sent are cloned, nothing is evaluated while the tap is full.
+    ///
+    /// returns `false` if the receiving end is gone
+    pub(crate) fn send(&mut self, event: &Event) -> bool {
+        for (value, meta) in event.value_meta_iter() {
+            if self.tx.is_closed() {
+                return false;
+            }
+            // a slow tap misses copies, but never slows down the flow
+            if self.tx.is_full() {
+                return true;
+            }
+            if !self.matches(event, value, meta) {
+                continue;
+            }
+            self.seen += 1;
+            if (self.seen - 1) % self.sample != 0 {
+                continue;
+            }
+            let record = literal!({
+                "port": self.port.clone(),
+                "ingest_ns": event.ingest_ns,
+                "data": value.clone_static(),
+                "meta": meta.clone_static()
+            });
+            if let Err(TrySendError::Closed(_)) = self.tx.try_send(record) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// All taps of a connector or pipeline
+#[derive(Debug, Default)]
+pub(crate) struct Taps(Vec<Tap>);
+
+impl Taps {
+    pub(crate) fn add(&mut self, tap: Tap) {
+        self.0.push(tap);
+    }
+
+    /// send copies of `event` passing `port` to the taps on it, removing closed taps
+    pub(crate) fn send(&mut self, port: &str, event: &Event) {
+        if !self.0.is_empty() {
+            self.0
+                .retain_mut(|tap| !tap.port.eq_ignore_ascii_case(port) || tap.send(event));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(value: Value<'static>) -> Event {
+        Event {
+            ingest_ns: 42,
+            data: (value, Value::object()).into(),
+            ..Event::default()
+        }
+    }
+
+    #[test]
+    fn sample_and_filter() -> Result<()> {
+        let mut config = Config::new(Target::Pipeline("main".to_string()), "out");
+        config.sample = 2;
+        config.filter = Some("event.snot == \"badger\"".to_string());
+        let (tap, rx) = Tap::new(&config)?;
+        let mut taps = Taps::default();
+        taps.add(tap);
+
+        for i in 0..4_u64 {
+            taps.send("out", &event(literal!({"snot": "badger", "i": i})));
+            taps.send("out", &event(literal!({"snot": "meh", "i": i})));
+            taps.send("in", &event(literal!({"snot": "badger", "i": i})));
+        }
+        let first = rx.try_recv()?;
+        assert_eq!(Some("out"), first.get_str("port"));
+        assert_eq!(Some(42), first.get_u64("ingest_ns"));
+        assert_eq!(Some(0), first.get("data").get_u64("i"));
+        assert_eq!(Some(2), rx.try_recv()?.get("data").get_u64("i"));
+        assert!(rx.try_recv().is_err());
+
+        // closed taps are removed
+        drop(rx);
+        taps.send("out", &event(literal!({"snot": "badger"})));
+        assert!(taps.0.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn full_tap_drops_copies() -> Result<()> {
+        let (mut tap, rx) = Tap::new(&Config::new(Target::Connector("foo".to_string()), "in"))?;
+        for i in 0..(TAP_QSIZE as u64 * 2) {
+            assert!(tap.send(&event(Value::from(i))));
+        }
+        assert_eq!(TAP_QSIZE, rx.len());
+        assert_eq!(Some(0), rx.try_recv()?.get_u64("data"));
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_config() {
+        let mut config = Config::new(Target::Pipeline("main".to_string()), "out");
+        config.sample = 0;
+        assert!(Tap::new(&config).is_err());
+        config.sample = 1;
+        config.filter = Some("event.snot ==".to_string());
+        assert!(Tap::new(&config).is_err());
+        config.filter = Some("let event.snot = true; true".to_string());
+        assert!(Tap::new(&config).is_err());
+    }
+}
@@ -78,4 +78,5 @@ test_cases!(
     unknown_argument,
     unknown_argument_no_args,
     missing_required_argument,
+    reserved_argument,
 );
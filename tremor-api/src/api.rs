@@ -14,23 +14,27 @@
 
 // #![cfg_attr(coverage, no_coverage)]
 
-use std::time::Duration;
+use std::{sync::Arc, time::Duration};
 
 use crate::errors::Error;
-use async_std::{prelude::*, task::JoinHandle};
+use async_rustls::TlsAcceptor;
+use async_std::{net::TcpListener, prelude::*, task::JoinHandle};
 use http_types::{
     headers::{self, HeaderValue, ToHeaderValues},
     StatusCode,
 };
 use serde::{Deserialize, Serialize};
 use tide::Response;
+use tremor_runtime::connectors::utils::tls::{load_server_config, serve_tls, TLSServerConfig};
 use tremor_runtime::instance::State as InstanceState;
 use tremor_runtime::system::World;
 
+pub mod auth;
 pub mod flow;
 pub mod model;
 pub mod prelude;
 pub mod status;
+pub mod tap;
 pub mod version;
 
 pub type Request = tide::Request<State>;
@@ -39,9 +43,23 @@ pub type Result<T> = std::result::Result<T, Error>;
 /// Default API timeout applied to operations triggered by the API. E.g. get flow status
 pub const DEFAULT_API_TIMEOUT: Duration = Duration::from_secs(5);
 
+/// Pause after a failed accept, e.g. when running out of file descriptors
+const ACCEPT_ERROR_BACKOFF: Duration = Duration::from_millis(100);
+
 #[derive(Clone)]
 pub struct State {
     pub world: World,
+    /// authentication config, if `None` all requests are allowed
+    pub auth: Option<Arc<auth::Config>>,
+}
+
+/// Configuration of the API listener
+#[derive(Debug, Clone, Default)]
+pub struct ServerConfig {
+    /// serve the API via HTTPS, required for authenticating clients via certificates
+    pub tls: Option<TLSServerConfig>,
+    /// authenticate and authorize clients, if not provided all requests are allowed
+    pub auth: Option<auth::Config>,
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -155,27 +173,85 @@ async fn handle_api_request<
     // Handle request. If any api error is returned, serialize it into a tide response
     // as well, respecting the requested resource type. (and if there's error during
     // this serialization, fall back to the error's conversion into tide response)
-    let r = match handler_func(req).timeout(DEFAULT_API_TIMEOUT).await {
-        Err(e) => {
-            error!("[API {method} {path}] Timeout");
-            Err(e.into())
-        }
-        Ok(Err(e)) => {
-            error!("[API {method} {path}] Error: {e}");
-            Err(e)
+    let r = if let Err(e) = auth::authorize(&req) {
+        // already logged to the audit log
+        Err(e)
+    } else {
+        match handler_func(req).timeout(DEFAULT_API_TIMEOUT).await {
+            Err(e) => {
+                error!("[API {method} {path}] Timeout");
+                Err(e.into())
+            }
+            Ok(Err(e)) => {
+                error!("[API {method} {path}] Error: {e}");
+                Err(e)
+            }
+            Ok(Ok(r)) => Ok(r),
         }
-        Ok(Ok(r)) => Ok(r),
     };
     r.or_else(|api_error| {
-        serialize_error(resource_type, api_error).or_else(|e| Ok(Into::<tide::Response>::into(e)))
+        let mut response =
+            serialize_error(resource_type, api_error).unwrap_or_else(Into::<tide::Response>::into);
+        if response.status() == StatusCode::Unauthorized {
+            response.insert_header(headers::WWW_AUTHENTICATE, "Bearer realm=\"tremor\"");
+        }
+        Ok(response)
     })
 }
 
+/// accept TLS connections to the API
+async fn listen_tls(
+    host: String,
+    app: tide::Server<()>,
+    acceptor: TlsAcceptor,
+) -> std::io::Result<()> {
+    let listener = TcpListener::bind(&host).await?;
+    info!(
+        "API listening for HTTPS requests on {}",
+        listener.local_addr()?
+    );
+    loop {
+        let (stream, peer_addr) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                // e.g. running out of file descriptors, keep serving the existing connections
+                error!("[API] Error Accepting: {e}");
+                async_std::task::sleep(ACCEPT_ERROR_BACKOFF).await;
+                continue;
+            }
+        };
+        let acceptor = acceptor.clone();
+        let app = app.clone();
+        async_std::task::spawn(async move {
+            if let Err(e) = serve_tls(stream, peer_addr, acceptor, app).await {
+                debug!("[API] Error serving HTTPS connection from {peer_addr}: {e}");
+            }
+        });
+    }
+}
+
 /// server the tremor API in a separately spawned task
-#[must_use]
-pub fn serve(host: String, world: &World) -> JoinHandle<Result<()>> {
+///
+/// # Errors
+///  * if the TLS config is invalid
+pub fn serve(host: String, world: &World, config: ServerConfig) -> Result<JoinHandle<Result<()>>> {
+    let acceptor = config
+        .tls
+        .as_ref()
+        .map(|tls| {
+            load_server_config(tls)
+                .map(|server_config| TlsAcceptor::from(Arc::new(server_config)))
+                .map_err(|e| {
+                    Error::new(
+                        StatusCode::InternalServerError,
+                        format!("Invalid API TLS config: {e}"),
+                    )
+                })
+        })
+        .transpose()?;
     let mut v1_app = tide::Server::with_state(State {
         world: world.clone(),
+        auth: config.auth.map(Arc::new),
     });
     v1_app
         .at("/version")
@@ -197,13 +273,30 @@ pub fn serve(host: String, world: &World) -> JoinHandle<Result<()>> {
         .at("/flows/:id/connectors/:connector")
         .get(|r| handle_api_request(r, flow::get_flow_connector_status))
         .patch(|r| handle_api_request(r, flow::patch_flow_connector_status));
+    v1_app
+        .at("/flows/:id/pipelines")
+        .get(|r| handle_api_request(r, flow::get_flow_pipelines));
+    v1_app
+        .at("/flows/:id/pipelines/:pipeline")
+        .get(|r| handle_api_request(r, flow::get_flow_pipeline_status))
+        .patch(|r| handle_api_request(r, flow::patch_flow_pipeline_status));
+    v1_app
+        .at("/flows/:id/pipelines/:pipeline/operators/:operator")
+        .patch(|r| handle_api_request(r, flow::patch_flow_pipeline_operator));
+    v1_app
+        .at("/flows/:id/tap")
+        .get(|r| handle_api_request(r, tap::tap_flow));
 
     let mut app = tide::Server::new();
     app.at("/v1").nest(v1_app);
 
     // spawn API listener
-    async_std::task::spawn(async move {
-        let res = app.listen(host).await;
+    Ok(async_std::task::spawn(async move {
+        let res = if let Some(acceptor) = acceptor {
+            listen_tls(host, app, acceptor).await
+        } else {
+            app.listen(host).await
+        };
         warn!("API stopped.");
         if let Err(e) = res {
             error!("API Error: {}", e);
@@ -211,7 +304,7 @@ pub fn serve(host: String, world: &World) -> JoinHandle<Result<()>> {
         } else {
             Ok(())
         }
-    })
+    }))
 }
 
 #[cfg(test)]
@@ -224,21 +317,42 @@ mod tests {
     use tremor_runtime::{
         errors::Result as RuntimeResult,
         instance::State as InstanceState,
+        pipeline::report::InputReport,
         system::{ShutdownMode, WorldConfig},
     };
-    use tremor_script::{aggr_registry, ast::DeployStmt, deploy::Deploy, FN_REGISTRY};
+    use tremor_script::{
+        aggr_registry,
+        ast::{DeployFlow, DeployStmt},
+        deploy::Deploy,
+        FN_REGISTRY,
+    };
     use tremor_value::{literal, value::StaticValue};
 
-    use crate::api::model::{ApiFlowStatusReport, PatchStatus};
+    use crate::api::model::{ApiFlowStatusReport, ApiPipelineStatusReport, PatchStatus};
 
     use super::*;
 
+    fn deploy_flow(src: &str) -> RuntimeResult<DeployFlow<'static>> {
+        let aggr_reg = aggr_registry();
+        let deployable = Deploy::parse(&src, &*FN_REGISTRY.read()?, &aggr_reg)?;
+        Ok(deployable
+            .deploy
+            .stmts
+            .into_iter()
+            .find_map(|stmt| match stmt {
+                DeployStmt::DeployFlowStmt(deploy_flow) => Some((*deploy_flow).clone()),
+                _other => None,
+            })
+            .expect("No deploy in the given troy file"))
+    }
+
     #[async_std::test]
     async fn test_api() -> RuntimeResult<()> {
         let _ = env_logger::try_init();
         let config = WorldConfig {
             qsize: 16,
             debug_connectors: true,
+            ..WorldConfig::default()
         };
         let (world, world_handle) = World::start(config).await?;
 
@@ -249,7 +363,8 @@ mod tests {
             port
         };
         let host = format!("127.0.0.1:{free_port}");
-        let api_handle = serve(host.clone(), &world);
+        let api_handle =
+            serve(host.clone(), &world, ServerConfig::default()).map_err(|e| e.to_string())?;
         info!("Listening on: {}", host);
 
         let src = r#"
@@ -269,18 +384,58 @@ mod tests {
         end;
         deploy flow api_test;
         "#;
-        let aggr_reg = aggr_registry();
-        let deployable = Deploy::parse(&src, &*FN_REGISTRY.read()?, &aggr_reg)?;
-        let deploy = deployable
-            .deploy
-            .stmts
-            .into_iter()
-            .find_map(|stmt| match stmt {
-                DeployStmt::DeployFlowStmt(deploy_flow) => Some((*deploy_flow).clone()),
-                _other => None,
-            })
-            .expect("No deploy in the given troy file");
-        world.start_flow(&deploy).await?;
+        world.start_flow(&deploy_flow(src)?).await?;
+        // a flow with an operator that can be updated at runtime
+        let src = r#"
+        define flow operator_test
+        flow
+            define pipeline main
+            pipeline
+                define operator limit from qos::ratelimit
+                with
+                    rate = 1000.0,
+                    burst = 1000
+                end;
+                create operator limit;
+                select event from in into limit;
+                select event from limit into out;
+            end;
+            create pipeline main;
+
+            define connector my_null from `null`;
+            create connector my_null;
+
+            connect /connector/my_null to /pipeline/main;
+            connect /pipeline/main to /connector/my_null;
+        end;
+        deploy flow operator_test;
+        "#;
+        world.start_flow(&deploy_flow(src)?).await?;
+        // a flow with events flowing, for tapping
+        let src = r#"
+        define flow tap_test
+        flow
+            define connector tick from metronome
+            with
+                config = {"interval": 10000000}
+            end;
+            create connector tick;
+
+            define pipeline main
+            pipeline
+                select event from in into out;
+            end;
+            create pipeline main;
+
+            define connector my_null from `null`;
+            create connector my_null;
+
+            connect /connector/tick to /pipeline/main;
+            connect /pipeline/main to /connector/my_null;
+        end;
+        deploy flow tap_test;
+        "#;
+        world.start_flow(&deploy_flow(src)?).await?;
 
         // check the status endpoint
         let start = Instant::now();
@@ -334,11 +489,14 @@ mod tests {
             .await?
             .body_json::<Vec<ApiFlowStatusReport>>()
             .await?;
-        assert_eq!(1, body.len());
-        assert_eq!("api_test", body[0].alias.as_str());
-        assert_eq!(InstanceState::Running, body[0].status);
-        assert_eq!(1, body[0].connectors.len());
-        assert_eq!(String::from("my_null"), body[0].connectors[0]);
+        assert_eq!(3, body.len());
+        let flow = body
+            .iter()
+            .find(|flow| flow.alias.as_str() == "api_test")
+            .expect("api_test flow");
+        assert_eq!(InstanceState::Running, flow.status);
+        assert_eq!(1, flow.connectors.len());
+        assert_eq!(String::from("my_null"), flow.connectors[0]);
 
         // get flow
         let res = client.get("/v1/flows/i_do_not_exist").await?;
@@ -354,6 +512,8 @@ mod tests {
         assert_eq!(InstanceState::Running, body.status);
         assert_eq!(1, body.connectors.len());
         assert_eq!(String::from("my_null"), body.connectors[0]);
+        assert!(body.draining.is_empty());
+        assert!(body.restarts.is_empty());
 
         // patch flow status
         let body = client
@@ -540,6 +700,261 @@ mod tests {
             body
         );
 
+        // update a pipeline operator
+        let body = client
+            .patch("/v1/flows/operator_test/pipelines/main/operators/limit")
+            .body_json(&literal!({"burst": 10}))?
+            .await?
+            .body_json::<StaticValue>()
+            .await?
+            .into_value();
+        assert_eq!(literal!({"burst": 10}), body);
+
+        // invalid update
+        let mut res = client
+            .patch("/v1/flows/operator_test/pipelines/main/operators/limit")
+            .body_json(&literal!({"rate": -1.0}))?
+            .await?;
+        assert_eq!(StatusCode::BadRequest, res.status());
+        res.body_bytes().await?; // consume the body
+
+        // unknown operator and pipeline
+        let mut res = client
+            .patch("/v1/flows/operator_test/pipelines/main/operators/i_do_not_exist")
+            .body_json(&literal!({"burst": 10}))?
+            .await?;
+        assert_eq!(StatusCode::NotFound, res.status());
+        res.body_bytes().await?; // consume the body
+        let mut res = client
+            .patch("/v1/flows/operator_test/pipelines/i_do_not_exist/operators/limit")
+            .body_json(&literal!({"burst": 10}))?
+            .await?;
+        assert_eq!(StatusCode::NotFound, res.status());
+        res.body_bytes().await?; // consume the body
+
+        // list flow pipelines
+        let body = client
+            .get("/v1/flows/operator_test/pipelines")
+            .await?
+            .body_json::<Vec<ApiPipelineStatusReport>>()
+            .await?;
+        assert_eq!(1, body.len());
+        let pipeline = &body[0];
+        assert_eq!("main", pipeline.alias);
+        assert_eq!(InstanceState::Running, pipeline.status);
+        assert_eq!(1, pipeline.inputs.len());
+        assert!(matches!(pipeline.inputs[0], InputReport::Source { .. }));
+        assert_eq!(Some(1), pipeline.outputs.get("out").map(Vec::len));
+        assert!(pipeline
+            .graph
+            .nodes
+            .iter()
+            .any(|node| node.id == "limit" && node.op_type == "trickle::operator"));
+        assert!(pipeline
+            .graph
+            .edges
+            .iter()
+            .any(|edge| edge.to == "limit" || edge.from == "limit"));
+
+        // get flow pipeline
+        let mut res = client
+            .get("/v1/flows/api_test/pipelines/i_do_not_exist")
+            .await?;
+        assert_eq!(StatusCode::NotFound, res.status());
+        res.body_bytes().await?; // consume the body
+
+        let body = client
+            .get("/v1/flows/api_test/pipelines/main")
+            .await?
+            .body_json::<ApiPipelineStatusReport>()
+            .await?;
+        assert_eq!("main", body.alias);
+        assert_eq!(InstanceState::Running, body.status);
+
+        // pause and resume a flow pipeline
+        let body = client
+            .patch("/v1/flows/api_test/pipelines/main")
+            .body_json(&PatchStatus {
+                status: InstanceState::Paused,
+            })?
+            .await?
+            .body_json::<ApiPipelineStatusReport>()
+            .await?;
+        assert_eq!(InstanceState::Paused, body.status);
+
+        let mut res = client
+            .patch("/v1/flows/api_test/pipelines/main")
+            .body_json(&PatchStatus {
+                status: InstanceState::Failed,
+            })?
+            .await?;
+        assert_eq!(StatusCode::BadRequest, res.status());
+        res.body_bytes().await?; // consume the body
+
+        let body = client
+            .patch("/v1/flows/api_test/pipelines/main")
+            .body_json(&PatchStatus {
+                status: InstanceState::Running,
+            })?
+            .await?
+            .body_json::<ApiPipelineStatusReport>()
+            .await?;
+        assert_eq!(InstanceState::Running, body.status);
+
+        // tap a flow
+        let mut res = client
+            .get("/v1/flows/api_test/tap?pipeline=main&port=out")
+            .await?;
+        assert_eq!(StatusCode::BadRequest, res.status());
+        res.body_bytes().await?; // consume the body
+
+        let mut res = client
+            .get("/v1/flows/api_test/tap?pipeline=main&connector=my_null&port=out")
+            .header(headers::UPGRADE, "websocket")
+            .header(headers::CONNECTION, "Upgrade")
+            .header("Sec-WebSocket-Key", "dGhlIHNhbXBsZSBub25jZQ==")
+            .await?;
+        assert_eq!(StatusCode::BadRequest, res.status());
+        res.body_bytes().await?; // consume the body
+
+        let mut res = client
+            .get("/v1/flows/api_test/tap?connector=i_do_not_exist&port=in")
+            .header(headers::UPGRADE, "websocket")
+            .header(headers::CONNECTION, "Upgrade")
+            .header("Sec-WebSocket-Key", "dGhlIHNhbXBsZSBub25jZQ==")
+            .await?;
+        assert_eq!(StatusCode::NotFound, res.status());
+        res.body_bytes().await?; // consume the body
+
+        let (mut ws, res) = async_tungstenite::async_std::connect_async(format!(
+            "ws://{host}/v1/flows/api_test/tap?pipeline=main&port=out&sample=2"
+        ))
+        .await?;
+        assert_eq!(101, res.status().as_u16());
+        ws.close(None).await?;
+
+        let (mut ws, _) = async_tungstenite::async_std::connect_async(format!(
+            "ws://{host}/v1/flows/tap_test/tap?pipeline=main&port=out"
+        ))
+        .await?;
+        let msg = ws
+            .next()
+            .timeout(Duration::from_secs(5))
+            .await?
+            .ok_or("tap closed")??;
+        let text = msg.into_text()?;
+        let mut bytes = text.into_bytes();
+        let record = simd_json::to_owned_value(&mut bytes)?;
+        assert_eq!(Some("out"), record.get_str("port"));
+        assert!(record.get("data").get_u64("id").is_some());
+        ws.close(None).await?;
+
+        // cleanup
+        world.stop(ShutdownMode::Graceful).await?;
+        world_handle.cancel().await;
+        api_handle.cancel().await;
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn test_api_auth() -> RuntimeResult<()> {
+        let _ = env_logger::try_init();
+        let (world, world_handle) = World::start(WorldConfig::default()).await?;
+
+        let free_port = {
+            let listener = TcpListener::bind("127.0.0.1:0").await?;
+            let port = listener.local_addr()?.port();
+            drop(listener);
+            port
+        };
+        let host = format!("127.0.0.1:{free_port}");
+        let auth: auth::Config = serde_yaml::from_str(
+            r#"
+tokens:
+  - name: ci
+    token: snot
+    role: reader
+  - name: ops
+    token: badger
+    role: operator
+"#,
+        )
+        .map_err(|e| e.to_string())?;
+        let config = ServerConfig {
+            tls: None,
+            auth: Some(auth),
+        };
+        let api_handle = serve(host.clone(), &world, config).map_err(|e| e.to_string())?;
+
+        let client: surf::Client = surf::Config::new()
+            .set_base_url(Url::parse(&format!("http://{host}/"))?)
+            .try_into()
+            .expect("Could not create surf client");
+
+        // wait for the API to come up
+        let start = Instant::now();
+        let mut res = client.get("/v1/version").await;
+        while res.is_err() {
+            if start.elapsed() > Duration::from_secs(2) {
+                panic!("Timeout waiting for the API: {res:?}");
+            }
+            res = client.get("/v1/version").await;
+        }
+
+        // no credentials
+        let mut res = res?;
+        assert_eq!(StatusCode::Unauthorized, res.status());
+        assert!(res.header(headers::WWW_AUTHENTICATE).is_some());
+        res.body_bytes().await?; // consume the body
+
+        // wrong token
+        let mut res = client
+            .get("/v1/version")
+            .header(headers::AUTHORIZATION, "Bearer snotty")
+            .await?;
+        assert_eq!(StatusCode::Unauthorized, res.status());
+        res.body_bytes().await?; // consume the body
+
+        // readers may read
+        let body = client
+            .get("/v1/version")
+            .header(headers::AUTHORIZATION, "Bearer snot")
+            .await?
+            .body_json::<StaticValue>()
+            .await?
+            .into_value();
+        assert!(body.contains_key("version"));
+
+        // but not act
+        let mut res = client
+            .patch("/v1/flows/i_do_not_exist")
+            .header(headers::AUTHORIZATION, "Bearer snot")
+            .body_json(&PatchStatus {
+                status: InstanceState::Paused,
+            })?
+            .await?;
+        assert_eq!(StatusCode::Forbidden, res.status());
+        res.body_bytes().await?; // consume the body
+
+        // nor tap live events
+        let mut res = client
+            .get("/v1/flows/i_do_not_exist/tap?connector=snot&port=out")
+            .header(headers::AUTHORIZATION, "Bearer snot")
+            .await?;
+        assert_eq!(StatusCode::Forbidden, res.status());
+        res.body_bytes().await?; // consume the body
+
+        // operators may act
+        let mut res = client
+            .patch("/v1/flows/i_do_not_exist")
+            .header(headers::AUTHORIZATION, "Bearer badger")
+            .body_json(&PatchStatus {
+                status: InstanceState::Paused,
+            })?
+            .await?;
+        assert_eq!(StatusCode::NotFound, res.status());
+        res.body_bytes().await?; // consume the body
+
         // cleanup
         world.stop(ShutdownMode::Graceful).await?;
         world_handle.cancel().await;
@@ -0,0 +1,239 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! API authentication and authorization
+
+use crate::api::prelude::*;
+use http_types::{headers, Method};
+use simd_json::ValueAccess;
+use std::path::Path;
+use tremor_runtime::connectors::utils::tls::{peer_identity, PeerCertificates};
+use tremor_value::Value;
+
+/// Log target of the audit log, every access decision is logged to it
+pub const AUDIT_TARGET: &str = "tremor_api::audit";
+
+/// Role of an API client
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// may read the status of the runtime, its flows, connectors and pipelines
+    Reader,
+    /// may additionally act on flows, connectors and pipelines, e.g. pause, resume, deploy or delete them,
+    /// and tap flows to see the events passing through them
+    Operator,
+}
+
+impl Role {
+    /// the role required for requests with the given method to the given path
+    ///
+    /// Taps stream live event payloads, so they are not part of the read-only status.
+    fn required_for(method: Method, path: &str) -> Self {
+        match method {
+            Method::Get | Method::Head | Method::Options if !path.ends_with("/tap") => Self::Reader,
+            _ => Self::Operator,
+        }
+    }
+}
+
+impl std::fmt::Display for Role {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Reader => write!(f, "reader"),
+            Self::Operator => write!(f, "operator"),
+        }
+    }
+}
+
+/// A bearer token and the role it grants
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TokenGrant {
+    /// name of the token holder, used in the audit log instead of the token
+    pub name: String,
+    /// the token clients send as `Authorization: Bearer <token>`
+    pub token: String,
+    /// the role granted
+    pub role: Role,
+}
+
+/// A client certificate and the role it grants
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CertificateGrant {
+    /// the subject (e.g. `CN=ops`) or the hex encoded SHA-256 fingerprint of the client certificate
+    pub identity: String,
+    /// the role granted
+    pub role: Role,
+}
+
+/// Authentication config of the API
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// bearer tokens
+    #[serde(default)]
+    pub tokens: Vec<TokenGrant>,
+    /// client certificates, verified via mTLS
+    #[serde(default)]
+    pub certificates: Vec<CertificateGrant>,
+}
+
+impl Config {
+    /// Load the config from a YAML file
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        Ok(serde_yaml::from_str(&content)?)
+    }
+
+    /// The principal and role granted to the given credentials
+    ///
+    /// A bearer token takes precedence over a client certificate.
+    fn authenticate(
+        &self,
+        authorization: Option<&str>,
+        peer: Option<&Value<'static>>,
+    ) -> Option<(String, Role)> {
+        if let Some(token) = authorization.and_then(|auth| auth.strip_prefix("Bearer ")) {
+            self.tokens
+                .iter()
+                .find(|grant| constant_time_eq(grant.token.as_bytes(), token.trim().as_bytes()))
+                .map(|grant| (format!("token {}", grant.name), grant.role))
+        } else {
+            let peer = peer?;
+            let subject = peer.get_str("subject");
+            let fingerprint = peer.get_str("fingerprint");
+            self.certificates
+                .iter()
+                .find(|grant| {
+                    subject == Some(grant.identity.as_str())
+                        || fingerprint.map_or(false, |f| f.eq_ignore_ascii_case(&grant.identity))
+                })
+                .map(|grant| (format!("certificate {}", grant.identity), grant.role))
+        }
+    }
+}
+
+/// compare two byte slices without short-circuiting, so the time taken doesn't leak how much matched
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0_u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Authenticates and authorizes a request, logging the decision to the audit log
+///
+/// All requests are allowed if no auth is configured.
+pub(crate) fn authorize(req: &Request) -> Result<()> {
+    let auth = if let Some(auth) = &req.state().auth {
+        auth
+    } else {
+        return Ok(());
+    };
+    let method = req.method();
+    let path = req.url().path();
+    let peer_addr = req.peer_addr().unwrap_or("unknown");
+    let required = Role::required_for(method, path);
+    let authorization = req
+        .header(headers::AUTHORIZATION)
+        .map(|values| values.last().as_str());
+    let peer = req
+        .ext::<PeerCertificates>()
+        .and_then(|certs| peer_identity(&certs.0));
+    match auth.authenticate(authorization, peer.as_ref()) {
+        Some((principal, role)) if role >= required => {
+            info!(
+                target: AUDIT_TARGET,
+                "allowed {method} {path} for {principal} ({role}) from {peer_addr}"
+            );
+            Ok(())
+        }
+        Some((principal, role)) => {
+            warn!(target: AUDIT_TARGET, "denied {method} {path} for {principal} ({role}) from {peer_addr}: {required} role required");
+            Err(Error::new(
+                StatusCode::Forbidden,
+                format!("The {required} role is required"),
+            ))
+        }
+        None => {
+            warn!(
+                target: AUDIT_TARGET,
+                "denied {method} {path} from {peer_addr}: not authenticated"
+            );
+            Err(Error::new(
+                StatusCode::Unauthorized,
+                "Authentication required".to_string(),
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tremor_value::literal;
+
+    #[test]
+    fn authenticate() -> std::result::Result<(), serde_yaml::Error> {
+        let config: Config = serde_yaml::from_str(
+            r#"
+tokens:
+  - name: ci
+    token: snot
+    role: reader
+  - name: ops
+    token: badger
+    role: operator
+certificates:
+  - identity: CN=ops
+    role: operator
+  - identity: ABCDEF
+    role: reader
+"#,
+        )?;
+        assert_eq!(
+            Some(("token ci".to_string(), Role::Reader)),
+            config.authenticate(Some("Bearer snot"), None)
+        );
+        assert_eq!(
+            Some(("token ops".to_string(), Role::Operator)),
+            config.authenticate(Some("Bearer badger "), None)
+        );
+        let peer = literal!({"subject": "CN=ops", "fingerprint": "012345"});
+        // a wrong token is not made up for by a certificate
+        assert_eq!(
+            None,
+            config.authenticate(Some("Bearer snotty"), Some(&peer))
+        );
+        assert_eq!(None, config.authenticate(Some("Basic snot"), None));
+        assert_eq!(
+            Some(("certificate CN=ops".to_string(), Role::Operator)),
+            config.authenticate(None, Some(&peer))
+        );
+        let peer = literal!({"subject": "CN=ci", "fingerprint": "abcdef"});
+        assert_eq!(
+            Some(("certificate ABCDEF".to_string(), Role::Reader)),
+            config.authenticate(None, Some(&peer))
+        );
+        let peer = literal!({"subject": "CN=other", "fingerprint": "012345"});
+        assert_eq!(None, config.authenticate(None, Some(&peer)));
+        assert_eq!(None, config.authenticate(None, None));
+
+        assert!(Role::Operator >= Role::required_for(Method::Patch, "/v1/flows/snot"));
+        assert!(Role::Reader < Role::required_for(Method::Delete, "/v1/flows/snot"));
+        assert!(Role::Reader >= Role::required_for(Method::Get, "/v1/flows/snot"));
+        assert!(Role::Reader < Role::required_for(Method::Get, "/v1/flows/snot/tap"));
+        assert!(Role::Operator >= Role::required_for(Method::Get, "/v1/flows/snot/tap"));
+        assert!(serde_yaml::from_str::<Config>("tokens: [{token: snot, role: admin}]").is_err());
+        Ok(())
+    }
+}
@@ -16,8 +16,10 @@
 
 use crate::{
     api::prelude::*,
-    model::{ApiConnectorStatusReport, ApiFlowStatusReport, PatchStatus},
+    model::{ApiConnectorStatusReport, ApiFlowStatusReport, ApiPipelineStatusReport, PatchStatus},
 };
+use tremor_pipeline::errors::ErrorKind as PipelineErrorKind;
+use tremor_runtime::errors::Kind as ErrorKind;
 
 pub(crate) async fn list_flows(req: Request) -> Result<Response> {
     let world = &req.state().world;
@@ -124,3 +126,82 @@ pub(crate) async fn patch_flow_connector_status(mut req: Request) -> Result<Resp
     };
     reply(&req, ApiConnectorStatusReport::from(report), StatusCode::Ok)
 }
+
+pub(crate) async fn get_flow_pipelines(req: Request) -> Result<Response> {
+    let world = &req.state().world;
+    let flow_id = req.param("id")?.to_string();
+    let flow = world.get_flow(flow_id).await?;
+    let pipelines = flow.get_pipelines().await?;
+    let mut result: Vec<ApiPipelineStatusReport> = Vec::with_capacity(pipelines.len());
+    for pipeline in pipelines {
+        let status = pipeline.report_status().await?;
+        result.push(status.into());
+    }
+    reply(&req, result, StatusCode::Ok)
+}
+
+pub(crate) async fn get_flow_pipeline_status(req: Request) -> Result<Response> {
+    let world = &req.state().world;
+    let flow_id = req.param("id")?.to_string();
+    let pipeline_id = req.param("pipeline")?.to_string();
+    let flow = world.get_flow(flow_id).await?;
+
+    let pipeline = flow.get_pipeline(pipeline_id).await?;
+    let report = pipeline.report_status().await?;
+    reply(&req, ApiPipelineStatusReport::from(report), StatusCode::Ok)
+}
+
+pub(crate) async fn patch_flow_pipeline_status(mut req: Request) -> Result<Response> {
+    let patch_status_payload: PatchStatus = req.body_json().await?;
+    let flow_id = req.param("id")?.to_string();
+    let pipeline_id = req.param("pipeline")?.to_string();
+
+    let world = &req.state().world;
+    let flow = world.get_flow(flow_id.clone()).await?;
+    let pipeline = flow.get_pipeline(pipeline_id.clone()).await?;
+    let current_status = pipeline.report_status().await?;
+    let report = match (current_status.state(), patch_status_payload.status) {
+        (state1, state2) if *state1 == state2 => {
+            // desired status == current status
+            current_status
+        }
+        (InstanceState::Running, InstanceState::Paused) => {
+            pipeline.pause().await?;
+            pipeline.report_status().await?
+        }
+
+        (InstanceState::Paused, InstanceState::Running) => {
+            pipeline.resume().await?;
+            pipeline.report_status().await?
+        }
+        (current, desired) => {
+            // throw error
+            return Err(Error::bad_request(format!("Cannot patch status of pipeline {pipeline_id} in flow {flow_id} from {current} to {desired}")));
+        }
+    };
+    reply(&req, ApiPipelineStatusReport::from(report), StatusCode::Ok)
+}
+
+pub(crate) async fn patch_flow_pipeline_operator(mut req: Request) -> Result<Response> {
+    let update: simd_json::OwnedValue = req.body_json().await?;
+    let flow_id = req.param("id")?.to_string();
+    let pipeline_id = req.param("pipeline")?.to_string();
+    let operator_id = req.param("operator")?.to_string();
+
+    let world = &req.state().world;
+    let flow = world.get_flow(flow_id).await?;
+    let pipeline = flow.get_pipeline(pipeline_id).await?;
+    pipeline
+        .update_operator(operator_id, tremor_value::Value::from(update.clone()))
+        .await
+        .map_err(|e| match &e.0 {
+            // the operator rejected the update
+            ErrorKind::Pipeline(kind)
+                if !matches!(kind, PipelineErrorKind::OperatorNotFound(..)) =>
+            {
+                Error::bad_request(format!("Invalid operator update: {kind}"))
+            }
+            _ => Error::from(e),
+        })?;
+    reply(&req, update, StatusCode::Ok)
+}
@@ -14,10 +14,13 @@
 
 use crate::api::prelude::*;
 use halfbrown::HashMap;
+use tremor_pipeline::GraphReport;
 use tremor_runtime::{
     connectors::{Connectivity, StatusReport as ConnectorStatusReport},
     instance::State,
-    system::flow::{Alias as FlowAlias, StatusReport as FlowStatusReport},
+    pipeline::report::{InputReport, OutputReport, StatusReport as PipelineStatusReport},
+    system::flow::{supervision::Restart, Alias as FlowAlias, StatusReport as FlowStatusReport},
+    tap::{Config as TapConfig, Target as TapTarget},
 };
 use tremor_script::ast::DeployEndpoint;
 
@@ -26,6 +29,8 @@ pub(crate) struct ApiFlowStatusReport {
     pub(crate) alias: FlowAlias,
     pub(crate) status: State,
     pub(crate) connectors: Vec<String>,
+    pub(crate) draining: Vec<String>,
+    pub(crate) restarts: Vec<Restart>,
 }
 
 impl From<FlowStatusReport> for ApiFlowStatusReport {
@@ -38,6 +43,12 @@ impl From<FlowStatusReport> for ApiFlowStatusReport {
                 .into_iter()
                 .map(|ca| ca.connector_alias().to_string())
                 .collect(),
+            draining: sr
+                .draining
+                .into_iter()
+                .map(|ca| ca.connector_alias().to_string())
+                .collect(),
+            restarts: sr.restarts,
         }
     }
 }
@@ -85,7 +96,60 @@ impl From<ConnectorStatusReport> for ApiConnectorStatusReport {
     }
 }
 
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub(crate) struct ApiPipelineStatusReport {
+    pub(crate) alias: String,
+    pub(crate) status: State,
+    pub(crate) queue_size: usize,
+    pub(crate) inputs: Vec<InputReport>,
+    pub(crate) outputs: HashMap<String, Vec<OutputReport>>,
+    pub(crate) graph: GraphReport,
+}
+
+impl From<PipelineStatusReport> for ApiPipelineStatusReport {
+    fn from(psr: PipelineStatusReport) -> Self {
+        Self {
+            alias: psr.alias().to_string(),
+            status: *psr.state(),
+            queue_size: psr.queue_size(),
+            inputs: psr.inputs().to_vec(),
+            outputs: psr.outputs().clone(),
+            graph: psr.graph().clone(),
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub(crate) struct PatchStatus {
     pub(crate) status: InstanceState,
 }
+
+/// Query parameters of a tap request
+#[derive(Deserialize, Debug)]
+pub(crate) struct TapQuery {
+    pub(crate) connector: Option<String>,
+    pub(crate) pipeline: Option<String>,
+    pub(crate) port: String,
+    pub(crate) sample: Option<u64>,
+    pub(crate) filter: Option<String>,
+}
+
+impl TapQuery {
+    pub(crate) fn into_config(self) -> Result<TapConfig> {
+        let target = match (self.connector, self.pipeline) {
+            (Some(connector), None) => TapTarget::Connector(connector),
+            (None, Some(pipeline)) => TapTarget::Pipeline(pipeline),
+            _ => {
+                return Err(Error::bad_request(
+                    "Invalid tap: exactly one of `connector` or `pipeline` is required".to_string(),
+                ))
+            }
+        };
+        let mut config = TapConfig::new(target, self.port);
+        if let Some(sample) = self.sample {
+            config.sample = sample;
+        }
+        config.filter = self.filter;
+        Ok(config)
+    }
+}
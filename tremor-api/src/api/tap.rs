@@ -0,0 +1,102 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Flow taps streamed over `WebSocket`
+
+use crate::{api::prelude::*, model::TapQuery};
+use async_std::{
+    stream::{self, StreamExt},
+    task,
+};
+use async_tungstenite::{
+    tungstenite::{handshake::derive_accept_key, protocol::Role, Message},
+    WebSocketStream,
+};
+use http_types::headers::{CONNECTION, UPGRADE};
+use tremor_runtime::tap::TapRx;
+use tremor_value::Value;
+
+enum TapMsg {
+    Record(Value<'static>),
+    Closed,
+}
+
+pub(crate) async fn tap_flow(req: Request) -> Result<Response> {
+    let query: TapQuery = req
+        .query()
+        .map_err(|e| Error::bad_request(format!("Invalid tap: {e}")))?;
+    let config = query.into_config()?;
+    let is_upgrade = req
+        .header(UPGRADE)
+        .map_or(false, |h| h.as_str().eq_ignore_ascii_case("websocket"));
+    let key = match req.header("Sec-WebSocket-Key") {
+        Some(key) if is_upgrade => derive_accept_key(key.as_str().as_bytes()),
+        _ => {
+            return Err(Error::bad_request(
+                "Taps need a WebSocket upgrade request".to_string(),
+            ))
+        }
+    };
+    let world = &req.state().world;
+    let flow_id = req.param("id")?.to_string();
+    let flow = world.get_flow(flow_id).await?;
+    let tap_rx = flow.tap(&config).await?;
+
+    let mut res = Response::new(StatusCode::SwitchingProtocols);
+    res.insert_header(UPGRADE, "websocket");
+    res.insert_header(CONNECTION, "Upgrade");
+    res.insert_header("Sec-WebSocket-Accept", key);
+    let http_res: &mut http_types::Response = res.as_mut();
+    let upgrade = http_res.recv_upgrade().await;
+    task::spawn(async move {
+        if let Some(connection) = upgrade.await {
+            let ws = WebSocketStream::from_raw_socket(connection, Role::Server, None).await;
+            stream_tap(ws, tap_rx).await;
+        }
+    });
+    Ok(res)
+}
+
+/// send the records of the tap until the client or the flow goes away
+async fn stream_tap<S>(ws: WebSocketStream<S>, tap_rx: TapRx)
+where
+    S: futures::AsyncRead + futures::AsyncWrite + Unpin,
+{
+    let (mut ws_tx, ws_rx) = futures::StreamExt::split(ws);
+    let records = tap_rx
+        .map(TapMsg::Record)
+        .chain(stream::once(TapMsg::Closed));
+    let client = ws_rx
+        .filter_map(|msg| matches!(msg, Err(_) | Ok(Message::Close(_))).then_some(TapMsg::Closed));
+    let mut msgs = records.merge(client);
+    while let Some(TapMsg::Record(record)) = msgs.next().await {
+        let text = match simd_json::to_string(&record) {
+            Ok(text) => text,
+            Err(e) => {
+                error!("[API Tap] Error encoding tapped event: {e}");
+                continue;
+            }
+        };
+        if futures::SinkExt::send(&mut ws_tx, Message::Text(text))
+            .await
+            .is_err()
+        {
+            break;
+        }
+    }
+    // dropping the tap receiver detaches the tap
+    if let Err(e) = futures::SinkExt::close(&mut ws_tx).await {
+        debug!("[API Tap] Error closing WebSocket: {e}");
+    }
+}
@@ -17,6 +17,7 @@ use http_types::{headers, StatusCode};
 use serde::Serialize;
 use std::sync::{MutexGuard, PoisonError};
 use tide::Response;
+use tremor_pipeline::errors::ErrorKind as PipelineErrorKind;
 use tremor_runtime::errors::{Error as TremorError, Kind as ErrorKind};
 
 /// Tremor API error
@@ -140,6 +141,20 @@ impl From<TremorError> for Error {
                 StatusCode::NotFound,
                 format!("Connector {id} not found in Flow {flow_id}"),
             ),
+            ErrorKind::PipelineNotFound(flow_id, id) => Error::new(
+                StatusCode::NotFound,
+                format!("Pipeline {id} not found in Flow {flow_id}"),
+            ),
+            ErrorKind::Pipeline(PipelineErrorKind::OperatorNotFound(pipeline_id, id)) => {
+                Error::new(
+                    StatusCode::NotFound,
+                    format!("Operator {id} not found in Pipeline {pipeline_id}"),
+                )
+            }
+            ErrorKind::InvalidTap(target, msg) => Error::new(
+                StatusCode::BadRequest,
+                format!("Invalid tap on {target}: {msg}"),
+            ),
             _e => Error::new(
                 StatusCode::InternalServerError,
                 "Internal server error".into(),
@@ -260,6 +260,30 @@ pub(crate) struct ServerRun {
     /// function tail-recursion stack depth limit
     #[clap(short, long, default_value = "1024", value_parser = clap::value_parser!(u32))]
     pub(crate) recursion_limit: u32,
+    /// Nanoseconds to wait for flows to drain on shutdown, unless overridden by the `drain_timeout` setting of a deployment
+    #[clap(long, default_value = "5000000000", value_parser = clap::value_parser!(u64))]
+    pub(crate) graceful_shutdown_timeout: u64,
+    /// Trace every n-th event flowing through the runtime, requires `--trace-otel` or `--trace-file`
+    #[clap(long, value_parser = clap::value_parser!(u64))]
+    pub(crate) trace_sample: Option<u64>,
+    /// OpenTelemetry collector url to export the spans of traced events to via OTLP/gRPC
+    #[clap(long, value_parser = clap::value_parser!(String))]
+    pub(crate) trace_otel: Option<String>,
+    /// File to write the spans of traced events to, one json line per batch of spans
+    #[clap(long, value_parser = clap::value_parser!(String))]
+    pub(crate) trace_file: Option<String>,
+    /// Certificate (-chain) to serve the API via HTTPS, requires `--api-tls-key`
+    #[clap(long, value_parser = clap::value_parser!(String))]
+    pub(crate) api_tls_cert: Option<String>,
+    /// Private key to serve the API via HTTPS, requires `--api-tls-cert`
+    #[clap(long, value_parser = clap::value_parser!(String))]
+    pub(crate) api_tls_key: Option<String>,
+    /// CA certificate to verify API client certificates against, enables authentication via mTLS
+    #[clap(long, value_parser = clap::value_parser!(String))]
+    pub(crate) api_tls_cafile: Option<String>,
+    /// YAML file with the bearer tokens and client certificates allowed to access the API and their roles
+    #[clap(long, value_parser = clap::value_parser!(String))]
+    pub(crate) api_auth: Option<String>,
 }
 
 // TODO: since the API will change this isn't translated yet
@@ -23,10 +23,13 @@ use signal_hook::consts::signal::{SIGINT, SIGQUIT, SIGTERM};
 use signal_hook::low_level::signal_name;
 use signal_hook_async_std::Signals;
 use std::io::Write;
+use std::path::Path;
 use std::sync::atomic::Ordering;
+use std::time::Duration;
 use tremor_api as api;
 use tremor_common::file;
-use tremor_runtime::system::{ShutdownMode, World};
+use tremor_runtime::connectors::utils::tls::TLSServerConfig;
+use tremor_runtime::system::{trace, ShutdownMode, World};
 use tremor_runtime::{self, version};
 
 macro_rules! log_and_print_error {
@@ -38,6 +41,8 @@ macro_rules! log_and_print_error {
 
 async fn handle_signals(signals: Signals, world: World) {
     let mut signals = signals.fuse();
+    let mut draining = false;
+    let mut graceful = None;
 
     while let Some(signal) = signals.next().await {
         info!(
@@ -45,14 +50,36 @@ async fn handle_signals(signals: Signals, world: World) {
             signal_name(signal).unwrap_or(&signal.to_string())
         );
         match signal {
+            SIGINT | SIGTERM if !draining => {
+                // drain in the background, so we can still react to a second signal
+                draining = true;
+                let world = world.clone();
+                graceful = Some(async_std::task::spawn(async move {
+                    if let Err(_e) = world.stop(ShutdownMode::Graceful).await {
+                        if let Err(e) = signal_hook::low_level::emulate_default_handler(signal) {
+                            error!("Error handling signal {}: {}", signal, e);
+                        }
+                    }
+                }));
+            }
             SIGINT | SIGTERM => {
-                if let Err(_e) = world.stop(ShutdownMode::Graceful).await {
+                warn!(
+                    "Received second shutdown signal, stopping without waiting for flows to drain."
+                );
+                // the graceful stop must not race the forceful one
+                if let Some(graceful) = graceful.take() {
+                    graceful.cancel().await;
+                }
+                if let Err(_e) = world.stop(ShutdownMode::Forceful).await {
                     if let Err(e) = signal_hook::low_level::emulate_default_handler(signal) {
                         error!("Error handling signal {}: {}", signal, e);
                     }
                 }
             }
             SIGQUIT => {
+                if let Some(graceful) = graceful.take() {
+                    graceful.cancel().await;
+                }
                 if let Err(_e) = world.stop(ShutdownMode::Forceful).await {
                     if let Err(e) = signal_hook::low_level::emulate_default_handler(signal) {
                         error!("Error handling signal {}: {}", signal, e);
@@ -68,6 +95,51 @@ async fn handle_signals(signals: Signals, world: World) {
     }
 }
 impl ServerRun {
+    /// tracing config from the `--trace-*` arguments
+    fn trace_config(&self) -> Result<Option<trace::Config>> {
+        let exporter = match (&self.trace_otel, &self.trace_file) {
+            (Some(url), None) => Some(trace::Exporter::Otel(url.clone())),
+            (None, Some(path)) => Some(trace::Exporter::File(path.into())),
+            (None, None) => None,
+            (Some(_), Some(_)) => {
+                return Err("Only one of `--trace-otel` and `--trace-file` can be used".into())
+            }
+        };
+        match (self.trace_sample, exporter) {
+            (Some(sample), Some(exporter)) => Ok(Some(trace::Config { sample, exporter })),
+            (None, None) => Ok(None),
+            (Some(_), None) => {
+                Err("`--trace-sample` requires `--trace-otel` or `--trace-file`".into())
+            }
+            (None, Some(_)) => {
+                Err("`--trace-otel` and `--trace-file` require `--trace-sample`".into())
+            }
+        }
+    }
+
+    fn api_config(&self) -> Result<api::ServerConfig> {
+        let tls = match (&self.api_tls_cert, &self.api_tls_key) {
+            (Some(cert), Some(key)) => Some(TLSServerConfig {
+                cert: cert.into(),
+                key: key.into(),
+                cafile: self.api_tls_cafile.as_ref().map(Into::into),
+                client_auth: None,
+                reload_interval: None,
+            }),
+            (None, None) if self.api_tls_cafile.is_none() => None,
+            _ => return Err("`--api-tls-cert` and `--api-tls-key` are required for HTTPS".into()),
+        };
+        let auth = self
+            .api_auth
+            .as_ref()
+            .map(|path| {
+                api::auth::Config::load(Path::new(path))
+                    .map_err(|e| Error::from(format!("Invalid API auth config {path}: {e}")))
+            })
+            .transpose()?;
+        Ok(api::ServerConfig { tls, auth })
+    }
+
     #[allow(clippy::too_many_lines)]
     async fn run_dun(&self) -> Result<i32> {
         use tremor_runtime::system::WorldConfig;
@@ -99,9 +171,18 @@ impl ServerRun {
 
         tremor_script::RECURSION_LIMIT.store(self.recursion_limit, Ordering::Relaxed);
 
+        let api_config = self.api_config()?;
+        let scheme = if api_config.tls.is_some() {
+            "https"
+        } else {
+            "http"
+        };
+
         // TODO: Allow configuring this for offramps and pipelines
         let config = WorldConfig {
             debug_connectors: self.debug_connectors,
+            graceful_shutdown_timeout: Duration::from_nanos(self.graceful_shutdown_timeout),
+            trace: self.trace_config()?,
             ..WorldConfig::default()
         };
 
@@ -139,9 +220,10 @@ impl ServerRun {
                 Ok(())
             })
         } else {
-            eprintln!("Listening at: http://{}", &self.api_host);
-            info!("Listening at: http://{}", &self.api_host);
-            api::serve(self.api_host.clone(), &world)
+            eprintln!("Listening at: {scheme}://{}", &self.api_host);
+            info!("Listening at: {scheme}://{}", &self.api_host);
+            api::serve(self.api_host.clone(), &world, api_config)
+                .map_err(|e| Error::from(format!("Failed to start the API: {e}")))?
         };
         // waiting for either
         match future::select(handle, api_handle).await {
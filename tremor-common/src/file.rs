@@ -75,6 +75,25 @@ where
         Error::Cwd(e, p.to_string_lossy().to_string())
     })
 }
+/// Writes `data` to `path`, replacing the file atomically
+///
+/// The data is written next to it into `<path>.tmp` first and then moved into place,
+/// so a crash never leaves a partially written file behind.
+///
+/// # Errors
+///   * if the file couldn't be written or moved into place
+pub fn replace<S>(path: &S, data: &[u8]) -> Result<(), Error>
+where
+    S: AsRef<Path> + ?Sized,
+{
+    let path: &Path = path.as_ref();
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    std::fs::write(&tmp, data)
+        .and_then(|_| std::fs::rename(&tmp, path))
+        .map_err(|e| Error::FileCreate(e, path.to_string_lossy().to_string()))
+}
+
 /// Gets the extesion for a filename
 pub fn extension(path: &str) -> Option<&str> {
     Path::new(path).extension().and_then(OsStr::to_str)
@@ -128,6 +147,23 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn replace() -> Result<(), Error> {
+        let path = std::env::current_dir().unwrap();
+        let path = path.join(".a.replaced.file.that.will.get.deleted");
+        super::replace(&path, b"snot")?;
+        super::replace(&path, b"badger")?;
+        assert_eq!(b"badger".to_vec(), std::fs::read(&path).unwrap());
+        std::fs::remove_file(&path).unwrap();
+        let path = path.join("this.does.not.work");
+        let p = path.to_string_lossy().to_string();
+        let r = super::replace(&path, b"snot");
+        assert!(r.is_err());
+        let err = r.err().unwrap();
+        assert!(matches!(err, Error::FileCreate(_, bad) if bad == p));
+        Ok(())
+    }
+
     #[test]
     fn open() -> Result<(), Error> {
         let path = std::env::current_dir().unwrap();
@@ -41,6 +41,7 @@ error_chain! {
     }
     foreign_links {
         YamlError(serde_yaml::Error) #[doc = "Error during yaml parsing"];
+        CsvError(csv::Error);
         JsonError(simd_json::Error);
         JsonAccessError(value_trait::AccessError);
         UrlParserError(url::ParseError);
@@ -51,6 +52,7 @@ error_chain! {
         ParseFloatError(std::num::ParseFloatError);
         Sled(sled::Error);
         TremorValue(tremor_value::Error);
+        Common(tremor_common::Error);
     }
 
     errors {
@@ -82,6 +84,10 @@ error_chain! {
             description("Unknown operator")
                 display("Unknown operator: {}::{}", n, o)
         }
+        OperatorNotFound(pipeline: String, operator: String) {
+            description("Operator not found")
+                display("Operator \"{}\" not found in pipeline \"{}\"", operator, pipeline)
+        }
         UnknownNamespace(n: String) {
             description("Unknown namespace")
                 display("Unknown namespace: {}", n)
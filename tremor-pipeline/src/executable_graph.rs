@@ -19,13 +19,17 @@ use crate::{
     errors::Result,
     errors::{Error, ErrorKind},
     metrics::value_count,
+    trace::Tracer,
     ConfigMap, ExecPortIndexMap, MetricsMsg, MetricsSender, NodeLookupFn,
 };
 use crate::{op::EventAndInsights, Event, NodeKind, Operator};
 use beef::Cow;
 use halfbrown::HashMap;
-use tremor_common::{ids::OperatorId, stry};
+use std::{collections::BTreeMap, sync::Arc};
+use tremor_common::{ids::OperatorId, stry, time::nanotime};
 use tremor_script::{ast::Helper, ast::Stmt, Value};
+use tremor_value::literal;
+use value_trait::Mutable;
 
 /// Configuration for a node
 #[derive(Debug, Clone, Default)]
@@ -160,6 +164,10 @@ impl Operator for OperatorNode {
         self.op.metrics(tags, timestamp)
     }
 
+    fn on_update(&mut self, update: &Value) -> Result<()> {
+        self.op.on_update(update)
+    }
+
     fn skippable(&self) -> bool {
         self.op.skippable()
     }
@@ -240,6 +248,43 @@ impl NodeMetrics {
     }
 }
 
+/// Report of a node of an `ExecutableGraph`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NodeReport {
+    /// id of the node
+    pub id: String,
+    /// kind of the node: `input`, `output`, `operator`, `select` or `script`
+    pub kind: String,
+    /// type of the operator
+    pub op_type: String,
+    /// number of events received per input port
+    pub inputs: BTreeMap<String, u64>,
+    /// number of events sent per output port
+    pub outputs: BTreeMap<String, u64>,
+}
+
+/// Report of an edge between two nodes of an `ExecutableGraph`
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct EdgeReport {
+    /// id of the node the edge starts at
+    pub from: String,
+    /// output port of the `from` node
+    pub from_port: String,
+    /// id of the node the edge ends at
+    pub to: String,
+    /// input port of the `to` node
+    pub to_port: String,
+}
+
+/// Report of the nodes and edges of an `ExecutableGraph`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GraphReport {
+    /// all nodes with their event counts
+    pub nodes: Vec<NodeReport>,
+    /// all edges, sorted
+    pub edges: Vec<EdgeReport>,
+}
+
 /// An executable graph, this is the executable
 /// form of a pipeline
 #[derive(Debug)]
@@ -257,6 +302,7 @@ pub struct ExecutableGraph {
     pub(crate) last_metrics: u64,
     pub(crate) metric_interval: Option<u64>,
     pub(crate) metrics_channel: MetricsSender,
+    pub(crate) tracer: Option<Arc<Tracer>>,
     /// outputs in pipeline
     pub outputs: HashMap<Cow<'static, str>, usize>,
     /// snot
@@ -269,11 +315,64 @@ pub struct ExecutableGraph {
 pub type Returns = Vec<(Cow<'static, str>, Event)>;
 
 impl ExecutableGraph {
+    /// Sets the tracer recording the spans of traced events passing the operators
+    pub fn set_tracer(&mut self, tracer: Arc<Tracer>) {
+        self.tracer = Some(tracer);
+    }
+
     /// returns inputs of the `ExecutableGraph`
     #[must_use]
     pub fn inputs(&self) -> &HashMap<Cow<'static, str>, usize> {
         &self.inputs
     }
+
+    /// Reports the nodes, with their event counts, and the edges of the graph
+    #[must_use]
+    pub fn report(&self) -> GraphReport {
+        let counts = |ports: &HashMap<Cow<'static, str>, u64>| {
+            ports
+                .iter()
+                .map(|(port, count)| (port.to_string(), *count))
+                .collect()
+        };
+        let nodes = self
+            .graph
+            .iter()
+            .zip(&self.metrics)
+            .map(|(node, metrics)| NodeReport {
+                id: node.id.clone(),
+                kind: match node.kind {
+                    NodeKind::Input => "input",
+                    NodeKind::Output(_) => "output",
+                    NodeKind::Operator => "operator",
+                    NodeKind::Select => "select",
+                    NodeKind::Script => "script",
+                }
+                .to_string(),
+                op_type: node.op_type.clone(),
+                inputs: counts(&metrics.inputs),
+                outputs: counts(&metrics.outputs),
+            })
+            .collect();
+        let id = |idx: &usize| self.graph.get(*idx).map(|node| node.id.clone());
+        let mut edges: Vec<EdgeReport> = self
+            .port_indexes
+            .iter()
+            .flat_map(|((from, from_port), targets)| {
+                targets.iter().filter_map(move |(to, to_port)| {
+                    Some(EdgeReport {
+                        from: id(from)?,
+                        from_port: from_port.to_string(),
+                        to: id(to)?,
+                        to_port: to_port.to_string(),
+                    })
+                })
+            })
+            .collect();
+        edges.sort();
+        GraphReport { nodes, edges }
+    }
+
     /// Tries to optimise a pipeline
     pub fn optimize(&mut self) -> Option<()> {
         let mut i = 0;
@@ -485,8 +584,33 @@ impl ExecutableGraph {
                 } else {
                     // ALLOW: We know the state was initiated
                     let state = unsafe { self.states.ops.get_unchecked_mut(idx) };
-                    let EventAndInsights { events, insights } =
-                        stry!(node.on_event(node.uid, &port, state, event));
+                    let trace = self
+                        .tracer
+                        .as_ref()
+                        .zip(event.id.trace())
+                        .filter(|_| node.kind.skippable());
+                    let start = trace.map(|_| nanotime());
+                    let mut res = node.on_event(node.uid, &port, state, event);
+                    if let (Some((tracer, parent)), Some(start)) = (trace, start) {
+                        let mut attributes = literal!({
+                            "pipeline": self.id.clone(),
+                            "node": node.id.clone(),
+                            "op_type": node.op_type.clone(),
+                            "port": port.to_string()
+                        });
+                        if let Err(e) = &res {
+                            attributes.try_insert("error", e.to_string());
+                        }
+                        let name = format!("pipeline {}/{}", self.id, node.id);
+                        let span = tracer.child_span(&parent, name, start, nanotime(), attributes);
+                        // the events leaving the operator continue the trace from its span
+                        if let Ok(EventAndInsights { events, .. }) = &mut res {
+                            for (_, event) in events {
+                                event.id.set_trace(Some(span));
+                            }
+                        }
+                    }
+                    let EventAndInsights { events, insights } = stry!(res);
 
                     for (out_port, _) in &events {
                         unsafe { self.metrics.get_unchecked_mut(idx) }.inc_output(out_port);
@@ -564,6 +688,18 @@ impl ExecutableGraph {
             }
         }
     }
+    /// Update the operator `id` at runtime
+    ///
+    /// # Errors
+    /// if there is no operator `id` or it fails to apply the update
+    pub fn update_operator(&mut self, id: &str, update: &Value) -> Result<()> {
+        let op = self
+            .graph
+            .iter_mut()
+            .find(|op| op.id == id)
+            .ok_or_else(|| ErrorKind::OperatorNotFound(self.id.clone(), id.to_string()))?;
+        op.on_update(update)
+    }
     /// Enque a contraflow insight
     pub fn contraflow(&mut self, mut skip_to: Option<usize>, mut insight: Event) -> Event {
         for idx in &self.contraflow {
@@ -616,6 +752,7 @@ mod test {
             identity::PassthroughFactory,
             prelude::{IN, OUT},
         },
+        trace::{SpanContext, Tracer},
         Result, METRICS_CHANNEL,
     };
     use tremor_common::ids::Id;
@@ -838,6 +975,7 @@ mod test {
             insights: vec![],
             dot: String::new(),
             metrics_channel: METRICS_CHANNEL.tx(),
+            tracer: None,
         };
 
         // Test with one event
@@ -873,6 +1011,63 @@ mod test {
             metrics.push(m);
         }
         test_metrics(metrics, 3)?;
+
+        let report = g.report();
+        let kinds: Vec<_> = report.nodes.iter().map(|n| n.kind.as_str()).collect();
+        assert_eq!(vec!["input", "operator", "operator", "output"], kinds);
+        let all_1 = &report.nodes[1];
+        assert_eq!("all-1", all_1.id);
+        assert_eq!("test", all_1.op_type);
+        assert_eq!(Some(&3), all_1.inputs.get("in"));
+        assert_eq!(Some(&3), all_1.outputs.get("out"));
+        assert_eq!(Some(&3), report.nodes[3].inputs.get("in"));
+        assert_eq!(
+            vec![
+                EdgeReport {
+                    from: "all-1".to_string(),
+                    from_port: "out".to_string(),
+                    to: "all-2".to_string(),
+                    to_port: "in".to_string(),
+                },
+                EdgeReport {
+                    from: "all-2".to_string(),
+                    from_port: "out".to_string(),
+                    to: "out".to_string(),
+                    to_port: "in".to_string(),
+                },
+                EdgeReport {
+                    from: "in".to_string(),
+                    from_port: "out".to_string(),
+                    to: "all-1".to_string(),
+                    to_port: "in".to_string(),
+                },
+            ],
+            report.edges
+        );
+
+        // traced events get a span per operator, continuing the trace of the previous one
+        let tracer = Arc::new(Tracer::new(64));
+        g.set_tracer(tracer.clone());
+        let mut rx = tracer.rx();
+        let root = SpanContext::new_trace();
+        let mut e = Event::default();
+        e.id.set_trace(Some(root));
+        let mut returns = vec![];
+        g.enqueue(IN, e, &mut returns).await?;
+        let (_, e) = returns.pop().ok_or("no event")?;
+        let mut spans = Vec::new();
+        while let Ok(span) = rx.try_recv() {
+            if span.context.trace_id() == root.trace_id() {
+                spans.push(span);
+            }
+        }
+        assert_eq!(2, spans.len());
+        assert_eq!("pipeline flow::pipe/all-1", spans[0].name);
+        assert_eq!(Some(root), spans[0].parent);
+        assert_eq!(Some("all-1"), spans[0].attributes.get_str("node"));
+        assert_eq!("pipeline flow::pipe/all-2", spans[1].name);
+        assert_eq!(Some(spans[0].context), spans[1].parent);
+        assert_eq!(Some(spans[1].context), e.id.trace());
         Ok(())
     }
 
@@ -940,6 +1135,7 @@ mod test {
             insights: vec![],
             dot: String::new(),
             metrics_channel: METRICS_CHANNEL.tx(),
+            tracer: None,
         };
         assert!(g.optimize().is_some());
         // Test with one event
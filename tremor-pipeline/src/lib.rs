@@ -31,6 +31,7 @@ extern crate serde_derive;
 extern crate log;
 
 use crate::errors::{ErrorKind, Result};
+use crate::trace::SpanContext;
 use async_broadcast::{broadcast, Receiver, Sender};
 use beef::Cow;
 use either::Either;
@@ -66,9 +67,14 @@ pub(crate) mod op;
 
 /// Tools to turn tremor query into pipelines
 pub mod query;
+/// Sampled self-tracing of events flowing through tremor
+pub mod trace;
 pub use crate::event::{Event, ValueIter, ValueMetaIter};
-pub use crate::executable_graph::{ExecutableGraph, OperatorNode};
+pub use crate::executable_graph::{
+    EdgeReport, ExecutableGraph, GraphReport, NodeReport, OperatorNode,
+};
 pub(crate) use crate::executable_graph::{NodeMetrics, State};
+pub use op::path::KeyExpr;
 pub use op::{ConfigImpl, InitializableOperator, Operator};
 pub use tremor_script::prelude::EventOriginUri;
 pub(crate) type ExecPortIndexMap =
@@ -337,9 +343,10 @@ impl CbAction {
 ///
 /// `EventId` also tracks min and max event ids for other events in order to support batched and grouped events
 /// and facilitate CB mechanics
-#[derive(
-    Debug, Clone, PartialEq, Default, simd_json_derive::Serialize, simd_json_derive::Deserialize, Eq,
-)]
+///
+/// The trace context of a sampled event only lives while the event is in flight,
+/// it is neither compared nor serialized, e.g. into a WAL.
+#[derive(Debug, Clone, Default)]
 pub struct EventId {
     /// can be a `SourceId` or an `OperatorId`
     source_id: u64,
@@ -347,6 +354,68 @@ pub struct EventId {
     event_id: u64,
     pull_id: u64,
     tracked_pull_ids: Vec<TrackedPullIds>,
+    trace: Option<SpanContext>,
+}
+
+/// the serialized form of an `EventId`
+#[derive(simd_json_derive::Serialize, simd_json_derive::Deserialize)]
+struct EventIdRepr {
+    source_id: u64,
+    stream_id: u64,
+    event_id: u64,
+    pull_id: u64,
+    tracked_pull_ids: Vec<TrackedPullIds>,
+}
+
+impl PartialEq for EventId {
+    fn eq(&self, other: &Self) -> bool {
+        self.source_id == other.source_id
+            && self.stream_id == other.stream_id
+            && self.event_id == other.event_id
+            && self.pull_id == other.pull_id
+            && self.tracked_pull_ids == other.tracked_pull_ids
+    }
+}
+
+impl Eq for EventId {}
+
+impl simd_json_derive::Serialize for EventId {
+    fn json_write<W>(&self, writer: &mut W) -> std::io::Result<()>
+    where
+        W: std::io::Write,
+    {
+        let repr = EventIdRepr {
+            source_id: self.source_id,
+            stream_id: self.stream_id,
+            event_id: self.event_id,
+            pull_id: self.pull_id,
+            tracked_pull_ids: self.tracked_pull_ids.clone(),
+        };
+        simd_json_derive::Serialize::json_write(&repr, writer)
+    }
+}
+
+impl<'input> simd_json_derive::Deserialize<'input> for EventId {
+    fn from_tape(tape: &mut simd_json_derive::Tape<'input>) -> simd_json::Result<Self>
+    where
+        Self: Sized + 'input,
+    {
+        let EventIdRepr {
+            source_id,
+            stream_id,
+            event_id,
+            pull_id,
+            tracked_pull_ids,
+        } = simd_json_derive::Deserialize::from_tape(tape)?;
+        Ok(Self {
+            source_id,
+            stream_id,
+            event_id,
+            pull_id,
+            tracked_pull_ids,
+            trace: None,
+        })
+    }
 }
 
 /// default stream id if streams dont make sense
@@ -364,6 +433,7 @@ impl EventId {
             event_id,
             pull_id,
             tracked_pull_ids: Vec::with_capacity(0),
+            trace: None,
         }
     }
 
@@ -421,6 +491,17 @@ impl EventId {
         self.pull_id = pull_id;
     }
 
+    #[must_use]
+    /// the trace context if this event is sampled for tracing
+    pub fn trace(&self) -> Option<SpanContext> {
+        self.trace
+    }
+
+    /// set the trace context, events carrying one get a span in every operator and sink they pass
+    pub fn set_trace(&mut self, trace: Option<SpanContext>) {
+        self.trace = trace;
+    }
+
     /// track the min and max of the given `event_id`
     /// and also include all event ids `event_id` was tracking
     pub fn track(&mut self, other: &EventId) {
@@ -888,14 +969,19 @@ fn factory(node: &NodeConfig) -> Result<Box<dyn InitializableOperator>> {
     #[cfg(feature = "bert")]
     use op::bert::{SequenceClassificationFactory, SummerizationFactory};
     use op::debug::EventHistoryFactory;
-    use op::generic::{BatchFactory, CounterFactory};
+    use op::enrich::LookupFactory;
+    use op::generic::{BatchFactory, CounterFactory, DedupFactory};
     use op::grouper::BucketGrouperFactory;
     use op::identity::PassthroughFactory;
-    use op::qos::{BackpressureFactory, PercentileFactory, RoundRobinFactory};
+    use op::qos::{
+        BackpressureFactory, PercentileFactory, RateLimitFactory, ReservoirFactory,
+        RoundRobinFactory, SampleFactory, TailSampleFactory,
+    };
     let name_parts: Vec<&str> = node.op_type.split("::").collect();
     let factory = match name_parts.as_slice() {
         ["passthrough"] => PassthroughFactory::new_boxed(),
         ["debug", "history"] => EventHistoryFactory::new_boxed(),
+        ["enrich", "lookup"] => LookupFactory::new_boxed(),
         ["grouper", "bucket"] => BucketGrouperFactory::new_boxed(),
         ["generic", "batch"] => BatchFactory::new_boxed(),
         ["generic", "backpressure"] => {
@@ -903,9 +989,14 @@ fn factory(node: &NodeConfig) -> Result<Box<dyn InitializableOperator>> {
             BackpressureFactory::new_boxed()
         }
         ["generic", "counter"] => CounterFactory::new_boxed(),
+        ["generic", "dedup"] => DedupFactory::new_boxed(),
         ["qos", "backpressure"] => BackpressureFactory::new_boxed(),
         ["qos", "roundrobin"] => RoundRobinFactory::new_boxed(),
         ["qos", "percentile"] => PercentileFactory::new_boxed(),
+        ["qos", "ratelimit"] => RateLimitFactory::new_boxed(),
+        ["qos", "sample"] => SampleFactory::new_boxed(),
+        ["qos", "reservoir"] => ReservoirFactory::new_boxed(),
+        ["qos", "tailsample"] => TailSampleFactory::new_boxed(),
         #[cfg(feature = "bert")]
         ["bert", "sequence_classification"] => SequenceClassificationFactory::new_boxed(),
         #[cfg(feature = "bert")]
@@ -1011,6 +1102,24 @@ mod test {
         assert!(CbAction::Trigger.is_cb());
     }
 
+    #[test]
+    fn event_id_trace() -> Result<()> {
+        let id = EventId::from_id(1, 2, 3);
+        let mut traced = id.clone();
+        traced.set_trace(Some(SpanContext::new_trace()));
+        assert_eq!(id, traced);
+
+        // the trace context is not persisted
+        let json = traced.json_string()?;
+        assert_eq!(id.json_string()?, json);
+        assert!(!json.contains("trace"));
+        let mut bytes = json.into_bytes();
+        let read = EventId::from_slice(&mut bytes)?;
+        assert_eq!(id, read);
+        assert_eq!(None, read.trace());
+        Ok(())
+    }
+
     #[test]
     fn event_ids() {
         let mut ids1 = EventId::from_id(1, 1, 1);
@@ -40,6 +40,26 @@ pub fn value_count(
     })
 }
 
+/// Generate an influx-compatible metrics value based on a count for each value of the tag `tag`,
+/// e.g. the number of events an operator passed and dropped
+#[must_use]
+pub fn value_counts(
+    metric_name: Cow<'static, str>,
+    tags: &HashMap<Cow<'static, str>, Value<'static>>,
+    tag: Cow<'static, str>,
+    counts: &[(Cow<'static, str>, u64)],
+    timestamp: u64,
+) -> Vec<Value<'static>> {
+    counts
+        .iter()
+        .map(|(tag_value, count)| {
+            let mut tags = tags.clone();
+            tags.insert(tag.clone(), Value::from(tag_value.clone()));
+            value_count(metric_name.clone(), tags, *count, timestamp)
+        })
+        .collect()
+}
+
 /// Generate an influx-compatible metrics value based on a named value, which will be encoded into a field
 #[must_use]
 pub fn value_named(
@@ -95,4 +115,24 @@ mod test {
         assert_eq!("tag-value", t.get_str("tag").expect("no tag"));
         assert_eq!(None, t.get_str("no-tag"));
     }
+
+    #[test]
+    fn value_counts_test() {
+        let mut t = HashMap::new();
+        t.insert("tag".into(), "tag-value".into());
+
+        let m = value_counts(
+            "name".into(),
+            &t,
+            "action".into(),
+            &[("pass".into(), 1), ("drop".into(), 2)],
+            42,
+        );
+        assert_eq!(2, m.len());
+        assert_eq!(m[0]["tags"]["action"], "pass");
+        assert_eq!(m[0]["tags"]["tag"], "tag-value");
+        assert_eq!(m[0]["fields"]["count"], 1);
+        assert_eq!(m[1]["tags"]["action"], "drop");
+        assert_eq!(m[1]["fields"]["count"], 2);
+    }
 }
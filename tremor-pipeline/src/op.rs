@@ -15,16 +15,21 @@
 #[cfg(feature = "bert")]
 pub mod bert;
 pub mod debug;
+pub mod enrich;
 pub mod generic;
 pub mod grouper;
 pub mod identity;
+pub(crate) mod path;
 pub mod prelude;
 pub mod qos;
 pub mod trickle;
 
+#[cfg(test)]
+pub(crate) mod test_utils;
+
 use self::prelude::OUT;
 use super::{Event, NodeConfig};
-use crate::errors::Result;
+use crate::errors::{ErrorKind, Result};
 use beef::Cow;
 use halfbrown::HashMap;
 use regex::Regex;
@@ -136,6 +141,14 @@ pub trait Operator: std::fmt::Debug + Send + Sync {
         Ok(Vec::new())
     }
 
+    /// Updates the operator at runtime, defaults to not supporting updates.
+    ///
+    /// # Errors
+    /// if the operator can't be updated or the update is invalid
+    fn on_update(&mut self, _update: &Value) -> Result<()> {
+        Err(ErrorKind::BadOpConfig("Operator can't be updated at runtime".to_string()).into())
+    }
+
     /// An operator is skippable and doesn't need to be executed
 
     fn skippable(&self) -> bool {
@@ -0,0 +1,419 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Enrichment with reference data
+//!
+//! Looks up events in a reference table loaded from a CSV, JSON or YAML file and merges
+//! the found record into the event. The file is checked for changes and loaded again in the background,
+//! events are looked up in the previous table until the new one is loaded.
+//!
+//! Only local files are supported as reference tables. The database of the `kv` connector can't back them,
+//! as it can't be opened a second time while the connector is running. For reference data in the `kv`
+//! connector, query it from the flow and join the responses instead.
+//!
+//! ## Configuration
+//!
+//! See [Config](struct.Config.html) for details.
+//!
+//! ## Reference tables
+//!
+//! * CSV files need a header, every row is a record of its columns, keyed by the `key_field` column.
+//! * JSON and YAML files are either an object of records by key,
+//!   or an array of records, keyed by their `key_field`.
+//!
+//! Keys keep their type, e.g. the number `1` doesn't find the record keyed by the string `"1"`.
+//! As CSV fields and object keys are strings, the `key` expression needs to produce strings for those tables.
+//!
+//! ## Outputs
+//!
+//! All events are sent to `out`, events without a record unchanged.
+//!
+//! # Example
+//!
+//! ```trickle
+//! define operator teams from enrich::lookup
+//! with
+//!   path = "hosts.csv",
+//!   key = "event.host",
+//!   key_field = "hostname",
+//!   target = "owner"
+//! end;
+//! ```
+
+use crate::errors::{ErrorKind, Result};
+use crate::metrics::value_counts;
+use crate::op::path::{key_of, merge, FieldPath, KeyExpr};
+use crate::op::prelude::*;
+use crate::ConfigImpl;
+use async_std::channel::{bounded, Receiver, Sender};
+use async_std::task;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+use tremor_script::{prelude::*, EventContext};
+
+const LOOKUP: Cow<'static, str> = Cow::const_str("lookup");
+const RESULT: Cow<'static, str> = Cow::const_str("result");
+const HIT: Cow<'static, str> = Cow::const_str("hit");
+const MISS: Cow<'static, str> = Cow::const_str("miss");
+
+/// Format of the reference table
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    /// CSV with a header
+    Csv,
+    /// JSON
+    Json,
+    /// YAML
+    Yaml,
+}
+
+impl Format {
+    fn from_path(path: &str) -> Option<Self> {
+        match Path::new(path).extension()?.to_str()? {
+            "csv" => Some(Self::Csv),
+            "json" => Some(Self::Json),
+            "yaml" | "yml" => Some(Self::Yaml),
+            _ => None,
+        }
+    }
+}
+
+fn default_key_field() -> String {
+    "key".to_string()
+}
+
+fn default_refresh_interval() -> u64 {
+    1_000_000_000
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Path of the reference table
+    pub path: String,
+    /// Format of the reference table, derived from the file extension if not set
+    #[serde(default = "Default::default")]
+    pub format: Option<Format>,
+    /// Expression for the lookup key, e.g. `event.host` or `$kafka.key`
+    pub key: String,
+    /// Field of records holding their key
+    ///
+    /// default: `key`
+    #[serde(default = "default_key_field")]
+    pub key_field: String,
+    /// Path of the field to put the found record into, e.g. `owner.info`,
+    /// the record is merged into the event if not set
+    #[serde(default = "Default::default")]
+    pub target: Option<String>,
+    /// Interval between checks for changes of the file in nanoseconds
+    ///
+    /// default: 1s
+    #[serde(default = "default_refresh_interval")]
+    pub refresh_interval: u64,
+}
+
+impl ConfigImpl for Config {}
+
+type Table = HashMap<String, Value<'static>>;
+
+fn load(path: &str, format: Format, key_field: &str) -> Result<Table> {
+    let mut data = std::fs::read(path)?;
+    let records: Value<'static> = match format {
+        Format::Csv => {
+            let mut reader = csv::Reader::from_reader(data.as_slice());
+            let headers = reader.headers()?.clone();
+            let mut records = Vec::new();
+            for row in reader.records() {
+                let row = row?;
+                let mut record = Value::object_with_capacity(headers.len());
+                for (header, field) in headers.iter().zip(row.iter()) {
+                    record.try_insert(header.to_string(), field.to_string());
+                }
+                records.push(record);
+            }
+            Value::from(records)
+        }
+        Format::Json => Value::from(simd_json::to_owned_value(&mut data)?),
+        Format::Yaml => Value::from(serde_yaml::from_slice::<OwnedValue>(&data)?),
+    };
+    let mut table = Table::new();
+    match records {
+        Value::Object(records) => {
+            for (key, record) in *records {
+                table.insert(key_of(&Value::from(key)), record);
+            }
+        }
+        Value::Array(records) => {
+            for record in records {
+                let key = record
+                    .get(key_field)
+                    .map(key_of)
+                    .ok_or_else(|| format!("Record without `{key_field}` in {path}"))?;
+                table.insert(key, record);
+            }
+        }
+        _ => return Err(format!("Expected an object or array of records in {path}").into()),
+    }
+    Ok(table)
+}
+
+fn modified(path: &str) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Checks the file for changes every `refresh_interval` and sends the reloaded table to `tx`
+///
+/// Stops once `tx` is closed. If loading fails the current table is kept until the file changes again.
+async fn reload(
+    config: Config,
+    format: Format,
+    mut loaded_mtime: Option<SystemTime>,
+    tx: Sender<Table>,
+) {
+    let interval = Duration::from_nanos(config.refresh_interval);
+    while !tx.is_closed() {
+        task::sleep(interval).await;
+        let path = config.path.clone();
+        let key_field = config.key_field.clone();
+        let (current, loaded) = task::spawn_blocking(move || {
+            let current = modified(&path);
+            if current == loaded_mtime {
+                (current, None)
+            } else {
+                (current, Some(load(&path, format, &key_field)))
+            }
+        })
+        .await;
+        loaded_mtime = current;
+        match loaded {
+            Some(Ok(table)) => {
+                if tx.send(table).await.is_err() {
+                    break;
+                }
+            }
+            Some(Err(e)) => error!("Failed to reload {}: {e}", config.path),
+            None => (),
+        }
+    }
+}
+
+op!(LookupFactory(_uid, node) {
+    if let Some(map) = &node.config {
+        let config: Config = Config::new(map)?;
+        Ok(Box::new(Lookup::new(config)?))
+    } else {
+        Err(ErrorKind::MissingOpConfig(node.id.clone()).into())
+    }
+});
+
+#[derive(Debug)]
+struct Lookup {
+    key: KeyExpr,
+    target: Option<FieldPath>,
+    table: Table,
+    /// tables reloaded in the background
+    reloaded: Receiver<Table>,
+    hits: u64,
+    misses: u64,
+}
+
+impl Lookup {
+    fn new(config: Config) -> Result<Self> {
+        let format = config
+            .format
+            .or_else(|| Format::from_path(&config.path))
+            .ok_or("Unknown file extension, `format` needs to be set")?;
+        if config.refresh_interval == 0 {
+            return Err("`refresh_interval` needs to be greater than 0".into());
+        }
+        let modified = modified(&config.path);
+        let table = load(&config.path, format, &config.key_field)?;
+        let (tx, reloaded) = bounded(1);
+        let key = KeyExpr::parse(&config.key)?;
+        let target = config.target.as_deref().map(FieldPath::parse);
+        task::spawn(reload(config, format, modified, tx));
+        Ok(Self {
+            key,
+            target,
+            table,
+            reloaded,
+            hits: 0,
+            misses: 0,
+        })
+    }
+}
+
+impl Operator for Lookup {
+    fn on_event(
+        &mut self,
+        _uid: OperatorId,
+        _port: &str,
+        _state: &mut Value<'static>,
+        mut event: Event,
+    ) -> Result<EventAndInsights> {
+        while let Ok(table) = self.reloaded.try_recv() {
+            self.table = table;
+        }
+        let ctx = EventContext::new(event.ingest_ns, event.origin_uri.as_ref());
+        let hit = event.data.rent_mut(|data| {
+            let (value, meta) = data.parts_mut();
+            let key = self.key.key(&ctx, value, meta);
+            if let Some(record) = key.and_then(|key| self.table.get(&key)) {
+                let record = record.clone();
+                match &self.target {
+                    Some(target) => target.set(value, record),
+                    None => merge(value, record),
+                }
+                true
+            } else {
+                false
+            }
+        });
+        if hit {
+            self.hits += 1;
+        } else {
+            self.misses += 1;
+        }
+        Ok(event.into())
+    }
+
+    fn metrics(
+        &self,
+        tags: &HashMap<Cow<'static, str>, Value<'static>>,
+        timestamp: u64,
+    ) -> Result<Vec<Value<'static>>> {
+        let counts = [(HIT, self.hits), (MISS, self.misses)];
+        Ok(value_counts(LOOKUP, tags, RESULT, &counts, timestamp))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::op::test_utils::{count, send};
+
+    fn config(path: &Path, key: &str, target: Option<&str>) -> Config {
+        Config {
+            path: path.display().to_string(),
+            format: None,
+            key: key.to_string(),
+            key_field: "host".to_string(),
+            target: target.map(ToString::to_string),
+            refresh_interval: 1_000_000,
+        }
+    }
+
+    fn run(op: &mut Lookup, value: Value<'static>, meta: Value<'static>) -> Result<Value<'static>> {
+        let event = Event {
+            data: (value, meta).into(),
+            ..Event::default()
+        };
+        let mut r = send(op, event)?;
+        let (port, e) = r.events.pop().ok_or("no data")?;
+        assert_eq!(port, "out");
+        Ok(e.data.suffix().value().clone_static())
+    }
+
+    #[test]
+    fn csv() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("hosts.csv");
+        std::fs::write(&path, "host,team\nsnot,badger\n")?;
+        let mut op = Lookup::new(config(&path, "event.host", Some("owner.info")))?;
+
+        assert_eq!(
+            literal!({"host": "snot", "owner": {"info": {"host": "snot", "team": "badger"}}}),
+            run(&mut op, literal!({"host": "snot"}), Value::object())?
+        );
+        assert_eq!(
+            literal!({"host": "grmpf"}),
+            run(&mut op, literal!({"host": "grmpf"}), Value::object())?
+        );
+
+        assert_eq!(1, count(&op, "result", "hit")?);
+        assert_eq!(1, count(&op, "result", "miss")?);
+        Ok(())
+    }
+
+    #[test]
+    fn json_refresh() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("hosts.json");
+        std::fs::write(&path, r#"[{"host": 1, "dc": "snot"}]"#)?;
+        filetime::set_file_mtime(&path, filetime::FileTime::zero())?;
+        let mut op = Lookup::new(config(&path, "$host.id", None))?;
+
+        assert_eq!(
+            literal!({"host": 1, "dc": "snot", "event": true}),
+            run(
+                &mut op,
+                literal!({"event": true}),
+                literal!({"host": {"id": 1}})
+            )?
+        );
+
+        std::fs::write(&path, r#"[{"host": 1, "dc": "badger"}]"#)?;
+        // the table is reloaded in the background
+        let mut reloaded = Value::null();
+        for _ in 0..500 {
+            reloaded = run(&mut op, Value::object(), literal!({"host": {"id": 1}}))?;
+            if reloaded.get_str("dc") == Some("badger") {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        assert_eq!(literal!({"host": 1, "dc": "badger"}), reloaded);
+        Ok(())
+    }
+
+    #[test]
+    fn yaml_and_errors() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("hosts.yml");
+        std::fs::write(&path, "snot:\n  team: badger\n")?;
+        let mut op = Lookup::new(config(&path, "event.host", Some("owner")))?;
+        assert_eq!(
+            literal!({"host": "snot", "owner": {"team": "badger"}}),
+            run(&mut op, literal!({"host": "snot"}), Value::object())?
+        );
+
+        // keys keep their type
+        let path = dir.path().join("ids.yml");
+        std::fs::write(&path, "\"1\":\n  team: badger\n")?;
+        let mut op = Lookup::new(config(&path, "event.id", None))?;
+        assert_eq!(
+            literal!({"id": 1}),
+            run(&mut op, literal!({"id": 1}), Value::object())?
+        );
+        let mut op = Lookup::new(config(&path, r##""#{event.id}""##, None))?;
+        assert_eq!(
+            literal!({"id": 1, "team": "badger"}),
+            run(&mut op, literal!({"id": 1}), Value::object())?
+        );
+
+        let path = dir.path().join("hosts.txt");
+        std::fs::write(&path, "snot")?;
+        assert!(Lookup::new(config(&path, "event.host", None)).is_err());
+        let path = dir.path().join("hosts.json");
+        std::fs::write(&path, r#"[{"team": "badger"}]"#)?;
+        assert!(Lookup::new(config(&path, "event.host", None)).is_err());
+        std::fs::write(&path, r#"[{"host": "snot"}]"#)?;
+        assert!(Lookup::new(config(&path, "event.", None)).is_err());
+        let mut no_refresh = config(&path, "event.host", None);
+        no_refresh.refresh_interval = 0;
+        assert!(Lookup::new(no_refresh).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,388 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Deduplication of events
+//!
+//! Events with a key seen within `ttl` are duplicates. The seen keys are kept in an LRU cache
+//! of at most `capacity` keys, so a key evicted by newer keys is not recognized again.
+//!
+//! With a `state_file` the seen keys are written to it in the background, at most every
+//! `persist_interval` nanoseconds, and loaded from it on start.
+//!
+//! ## Configuration
+//!
+//! See [Config](struct.Config.html) for details.
+//!
+//! Keys keep the type of the value they are computed from, e.g. the string `"1"` and the number `1` are different keys.
+//!
+//! ## Outputs
+//!
+//! The 1st additional output `duplicate` is used to route duplicate events to. Duplicates are acknowledged
+//! right away, as the first event with their key was already passed on, and leave the operator as
+//! non-transactional events.
+//!
+//! # Example
+//!
+//! ```trickle
+//! define operator dedup from generic::dedup
+//! with
+//!   key = "$kafka_consumer.offset",
+//!   ttl = 60000000000,
+//!   capacity = 100000
+//! end;
+//! ```
+
+use crate::errors::{ErrorKind, Result};
+use crate::metrics::value_counts;
+use crate::op::path::KeyExpr;
+use crate::op::prelude::*;
+use crate::{ConfigImpl, SignalKind};
+use async_std::channel::{bounded, Receiver, Sender, TrySendError};
+use async_std::task::{self, JoinHandle};
+use lru::LruCache;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use tremor_script::{prelude::*, EventContext};
+
+const DEDUP: Cow<'static, str> = Cow::const_str("dedup");
+const ACTION: Cow<'static, str> = Cow::const_str("action");
+const PASS: Cow<'static, str> = Cow::const_str("pass");
+const DUPLICATE: Cow<'static, str> = Cow::const_str("duplicate");
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Expression for the key of an event, e.g. `$kafka_consumer.offset`
+    pub key: String,
+    /// Time in nanoseconds after the first event with a key during which events with the same key are duplicates,
+    /// keys are only forgotten by eviction if not set
+    #[serde(default = "Default::default")]
+    pub ttl: Option<u64>,
+    /// Maximum number of keys to remember
+    pub capacity: NonZeroUsize,
+    /// File to persist the seen keys in, so they survive restarts
+    #[serde(default = "Default::default")]
+    pub state_file: Option<String>,
+    /// Minimum time in nanoseconds between two writes of the `state_file`
+    ///
+    /// default: 10s
+    #[serde(default = "default_persist_interval")]
+    pub persist_interval: u64,
+}
+
+fn default_persist_interval() -> u64 {
+    10_000_000_000
+}
+
+impl ConfigImpl for Config {}
+
+op!(DedupFactory(_uid, node) {
+    if let Some(map) = &node.config {
+        let config: Config = Config::new(map)?;
+        Ok(Box::new(Dedup::new(config)?))
+    } else {
+        Err(ErrorKind::MissingOpConfig(node.id.clone()).into())
+    }
+});
+
+/// Seen keys and the time they were first seen, oldest first
+type Snapshot = Vec<(String, u64)>;
+
+/// Background writer of the `state_file`
+#[derive(Debug)]
+struct Persist {
+    tx: Sender<Snapshot>,
+    writer: JoinHandle<()>,
+    interval: u64,
+    /// earliest time of the next write
+    next: u64,
+}
+
+#[derive(Debug)]
+struct Dedup {
+    key: KeyExpr,
+    ttl: Option<u64>,
+    persist: Option<Persist>,
+    /// seen keys and the time they were first seen
+    seen: LruCache<String, u64>,
+    /// keys changed since they were persisted
+    dirty: bool,
+    pass: u64,
+    duplicates: u64,
+}
+
+/// Load seen keys from `path`, stored as an array of `[key, seen]` pairs, oldest first
+fn load(path: &Path, seen: &mut LruCache<String, u64>) -> Result<()> {
+    let mut data = std::fs::read(path)?;
+    let entries = tremor_value::parse_to_value(&mut data)?;
+    for entry in entries.as_array().ok_or("Invalid dedup state")? {
+        let key = entry.get_idx(0).and_then(|v| v.as_str());
+        let ns = entry.get_idx(1).and_then(|v| v.as_u64());
+        if let Some((key, ns)) = key.zip(ns) {
+            seen.put(key.to_string(), ns);
+        }
+    }
+    Ok(())
+}
+
+/// Store the seen keys at `path`, replacing the file atomically
+fn save(path: &Path, seen: &[(String, u64)]) -> Result<()> {
+    let entries: Vec<Value> = seen
+        .iter()
+        .map(|(key, ns)| literal!([key.as_str(), *ns]))
+        .collect();
+    tremor_common::file::replace(path, Value::from(entries).encode().as_bytes())?;
+    Ok(())
+}
+
+/// Writes the snapshots received on `rx` to `path`, until `rx` is closed
+async fn persist(path: PathBuf, rx: Receiver<Snapshot>) {
+    while let Ok(seen) = rx.recv().await {
+        let target = path.clone();
+        if let Err(e) = task::spawn_blocking(move || save(&target, &seen)).await {
+            error!("Failed to persist dedup state to {}: {e}", path.display());
+        }
+    }
+}
+
+impl Dedup {
+    fn new(config: Config) -> Result<Self> {
+        let mut seen = LruCache::new(config.capacity);
+        if let Some(path) = config.state_file.as_deref().map(Path::new) {
+            if path.exists() {
+                load(path, &mut seen)?;
+            }
+        }
+        let key = KeyExpr::parse(&config.key)?;
+        let persist = config.state_file.map(|path| {
+            // one snapshot at a time, the next one is taken once it is written
+            let (tx, rx) = bounded(1);
+            Persist {
+                tx,
+                writer: task::spawn(persist(PathBuf::from(path), rx)),
+                interval: config.persist_interval,
+                next: 0,
+            }
+        });
+        Ok(Self {
+            key,
+            ttl: config.ttl,
+            persist,
+            seen,
+            dirty: false,
+            pass: 0,
+            duplicates: 0,
+        })
+    }
+
+    /// Returns `true` if `key` has been seen within the ttl before `now`, and records it as seen
+    fn is_duplicate(&mut self, key: String, now: u64) -> bool {
+        let ttl = self.ttl;
+        if let Some(seen) = self.seen.get(&key) {
+            if ttl.map_or(true, |ttl| now < seen.saturating_add(ttl)) {
+                return true;
+            }
+        }
+        self.seen.put(key, now);
+        self.dirty = true;
+        false
+    }
+}
+
+impl Operator for Dedup {
+    fn on_event(
+        &mut self,
+        _uid: OperatorId,
+        _port: &str,
+        _state: &mut Value<'static>,
+        mut event: Event,
+    ) -> Result<EventAndInsights> {
+        let ctx = EventContext::new(event.ingest_ns, event.origin_uri.as_ref());
+        let (value, meta) = event.data.parts();
+        // events without a key can't be duplicates
+        let key = self.key.key(&ctx, value, meta);
+        if key.map_or(false, |key| self.is_duplicate(key, event.ingest_ns)) {
+            self.duplicates += 1;
+            let insights = if event.transactional {
+                event.transactional = false;
+                vec![event.insight_ack()]
+            } else {
+                vec![]
+            };
+            Ok(EventAndInsights {
+                events: vec![(DUPLICATE, event)],
+                insights,
+            })
+        } else {
+            self.pass += 1;
+            Ok(event.into())
+        }
+    }
+
+    fn handles_signal(&self) -> bool {
+        self.persist.is_some()
+    }
+
+    fn on_signal(
+        &mut self,
+        _uid: OperatorId,
+        _state: &mut Value<'static>,
+        signal: &mut Event,
+    ) -> Result<EventAndInsights> {
+        let now = signal.ingest_ns;
+        if let Some(persist) = self.persist.as_mut() {
+            // while the writer is busy with the previous snapshot, this is retried on the next tick
+            if signal.kind == Some(SignalKind::Tick)
+                && self.dirty
+                && now >= persist.next
+                && !persist.tx.is_full()
+            {
+                let snapshot = self
+                    .seen
+                    .iter()
+                    .rev()
+                    .map(|(key, ns)| (key.clone(), *ns))
+                    .collect();
+                match persist.tx.try_send(snapshot) {
+                    Ok(()) => {
+                        self.dirty = false;
+                        persist.next = now.saturating_add(persist.interval);
+                    }
+                    Err(TrySendError::Full(_)) => (),
+                    Err(TrySendError::Closed(_)) => return Err("Dedup state writer stopped".into()),
+                }
+            }
+        }
+        Ok(EventAndInsights::default())
+    }
+
+    fn metrics(
+        &self,
+        tags: &HashMap<Cow<'static, str>, Value<'static>>,
+        timestamp: u64,
+    ) -> Result<Vec<Value<'static>>> {
+        let counts = [(PASS, self.pass), (DUPLICATE, self.duplicates)];
+        Ok(value_counts(DEDUP, tags, ACTION, &counts, timestamp))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::op::test_utils::{self, count, tick};
+
+    fn config(ttl: Option<u64>, capacity: usize, state_file: Option<&Path>) -> Result<Config> {
+        Ok(Config {
+            key: "$id".to_string(),
+            ttl,
+            capacity: NonZeroUsize::new(capacity).ok_or("zero capacity")?,
+            state_file: state_file.map(|p| p.display().to_string()),
+            persist_interval: 10,
+        })
+    }
+
+    /// Wait for the pending writes of the state file
+    fn flush(op: &mut Dedup) -> Result<()> {
+        let Persist { tx, writer, .. } = op.persist.take().ok_or("not persisted")?;
+        drop(tx);
+        task::block_on(writer);
+        Ok(())
+    }
+
+    fn send(
+        op: &mut Dedup,
+        meta: Value<'static>,
+        ingest_ns: u64,
+        transactional: bool,
+    ) -> Result<EventAndInsights> {
+        let event = Event {
+            ingest_ns,
+            data: (Value::from("snot"), meta).into(),
+            transactional,
+            ..Event::default()
+        };
+        test_utils::send(op, event)
+    }
+
+    fn port(op: &mut Dedup, id: Option<u64>, ingest_ns: u64) -> Result<Cow<'static, str>> {
+        let meta = id.map_or_else(Value::object, |id| literal!({ "id": id }));
+        let mut r = send(op, meta, ingest_ns, false)?;
+        let (port, _) = r.events.pop().ok_or("no data")?;
+        Ok(port)
+    }
+
+    #[test]
+    fn ttl_and_capacity() -> Result<()> {
+        let mut op = Dedup::new(config(Some(10), 2, None)?)?;
+        assert_eq!("out", port(&mut op, Some(1), 0)?);
+        assert_eq!("duplicate", port(&mut op, Some(1), 9)?);
+        // expired
+        assert_eq!("out", port(&mut op, Some(1), 10)?);
+        // no key
+        assert_eq!("out", port(&mut op, None, 10)?);
+        assert_eq!("out", port(&mut op, None, 10)?);
+        // 1 is evicted
+        assert_eq!("out", port(&mut op, Some(2), 11)?);
+        assert_eq!("out", port(&mut op, Some(3), 11)?);
+        assert_eq!("out", port(&mut op, Some(1), 12)?);
+
+        // keys keep their type
+        let r = send(&mut op, literal!({"id": "1"}), 12, false)?;
+        assert_eq!("out", r.events[0].0);
+
+        assert_eq!(8, count(&op, "action", "pass")?);
+        assert_eq!(1, count(&op, "action", "duplicate")?);
+        Ok(())
+    }
+
+    #[test]
+    fn ack_duplicates() -> Result<()> {
+        let mut op = Dedup::new(config(None, 2, None)?)?;
+        let r = send(&mut op, literal!({"id": 1}), 0, true)?;
+        assert!(r.insights.is_empty());
+        assert!(r.events[0].1.transactional);
+        let r = send(&mut op, literal!({"id": 1}), 1, true)?;
+        let (port, duplicate) = &r.events[0];
+        assert_eq!("duplicate", *port);
+        assert!(!duplicate.transactional);
+        assert_eq!(1, r.insights.len());
+        assert_eq!(CbAction::Ack, r.insights[0].cb);
+        assert_eq!(duplicate.id, r.insights[0].id);
+        Ok(())
+    }
+
+    #[test]
+    fn persist() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("dedup.json");
+        let mut op = Dedup::new(config(None, 2, Some(&path))?)?;
+        assert!(op.handles_signal());
+        assert_eq!("out", port(&mut op, Some(1), 0)?);
+        assert_eq!("out", port(&mut op, Some(2), 0)?);
+        assert_eq!("out", port(&mut op, Some(3), 0)?);
+        tick(&mut op, 0)?;
+        assert!(!op.dirty);
+        // written at most once per interval
+        assert_eq!("out", port(&mut op, Some(4), 1)?);
+        tick(&mut op, 5)?;
+        assert!(op.dirty);
+        flush(&mut op)?;
+
+        let mut op = Dedup::new(config(None, 2, Some(&path))?)?;
+        assert_eq!("duplicate", port(&mut op, Some(2), 1)?);
+        assert_eq!("duplicate", port(&mut op, Some(3), 1)?);
+        assert_eq!("out", port(&mut op, Some(1), 1)?);
+        Ok(())
+    }
+}
@@ -0,0 +1,206 @@
+// Copyright 2020-2021, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Paths to values in events and keys of events used in operator configs
+
+use crate::errors::Result;
+use tremor_script::{
+    ast::Expr,
+    interpreter::{Env, ExecOpts, LocalStack},
+    prelude::*,
+    AggrType, EventContext, Script, FN_REGISTRY, NO_AGGRS, NO_CONSTS, NULL,
+};
+
+/// Path to a value in an event, e.g. `owner.team` or `$kafka.key`
+///
+/// Segments are separated by `.`, a leading `$` starts at the event metadata instead of its value.
+#[derive(Debug, Clone)]
+pub(crate) struct FieldPath {
+    meta: bool,
+    segments: Vec<String>,
+}
+
+impl FieldPath {
+    pub(crate) fn parse(path: &str) -> Self {
+        let (meta, path) = match path.strip_prefix('$') {
+            Some(path) => (true, path),
+            None => (false, path),
+        };
+        let segments = path
+            .split('.')
+            .filter(|s| !s.is_empty())
+            .map(ToString::to_string)
+            .collect();
+        Self { meta, segments }
+    }
+
+    fn get<'v, 'value>(&self, value: &'v Value<'value>) -> Option<&'v Value<'value>> {
+        self.segments
+            .iter()
+            .try_fold(value, |v, segment| v.get(segment.as_str()))
+    }
+
+    /// The value at the path in the event `value` or `meta`
+    pub(crate) fn resolve<'v, 'value>(
+        &self,
+        value: &'v Value<'value>,
+        meta: &'v Value<'value>,
+    ) -> Option<&'v Value<'value>> {
+        if self.meta {
+            self.get(meta)
+        } else {
+            self.get(value)
+        }
+    }
+
+    /// Set `data` at the path, creating missing objects
+    pub(crate) fn set<'value>(&self, value: &mut Value<'value>, data: Value<'value>) {
+        let (last, parents) = if let Some(split) = self.segments.split_last() {
+            split
+        } else {
+            return merge(value, data);
+        };
+        let mut target = value;
+        for segment in parents {
+            if target
+                .get(segment.as_str())
+                .map_or(true, |v| !v.is_object())
+            {
+                target.try_insert(segment.clone(), Value::object());
+            }
+            target = match target.get_mut(segment.as_str()) {
+                Some(target) => target,
+                None => return,
+            };
+        }
+        target.try_insert(last.clone(), data);
+    }
+}
+
+/// Merge the fields of `data` into `value`, replaces `value` if any of them is not an object
+pub(crate) fn merge<'value>(value: &mut Value<'value>, data: Value<'value>) {
+    if let Value::Object(data) = data {
+        if let Some(value) = value.as_object_mut() {
+            for (k, v) in *data {
+                value.insert(k, v);
+            }
+            return;
+        }
+        *value = Value::Object(data);
+    } else {
+        *value = data;
+    }
+}
+
+/// Key of a value, its JSON representation
+///
+/// Keys keep the type of the value, the string `"1"` and the number `1` are different keys.
+pub(crate) fn key_of(value: &Value) -> String {
+    value.encode()
+}
+
+/// Key of an event, a tremor-script expression evaluated against the event,
+/// e.g. `event.host`, `$kafka.key` or `"#{event.tenant}/#{event.host}"`
+///
+/// Also used by the runtime for other per-event expressions, like tap filters.
+#[derive(Debug)]
+pub struct KeyExpr {
+    script: Script,
+}
+
+impl KeyExpr {
+    /// Parse `src` as a single tremor-script expression
+    ///
+    /// # Errors
+    ///  * if `src` is not valid tremor-script
+    ///  * if `src` is not a single expression
+    pub fn parse(src: &str) -> Result<Self> {
+        let script = Script::parse(src, &*FN_REGISTRY.read()?)?;
+        if matches!(script.script.exprs.as_slice(), [Expr::Imut(_)]) {
+            Ok(Self { script })
+        } else {
+            Err(format!("`{src}` must be a single expression").into())
+        }
+    }
+
+    /// The key of the event `value` and `meta`, see [`key_of`],
+    /// `None` if the expression fails, e.g. because a field is missing
+    pub(crate) fn key(&self, ctx: &EventContext, value: &Value, meta: &Value) -> Option<String> {
+        self.eval(ctx, value, meta).map(|key| key_of(&key))
+    }
+
+    /// The value of the expression for the event `value` and `meta`,
+    /// `None` if the expression fails, e.g. because a field is missing
+    #[must_use]
+    pub fn eval(&self, ctx: &EventContext, value: &Value, meta: &Value) -> Option<Value<'static>> {
+        let script = &self.script.script;
+        if let [Expr::Imut(expr)] = script.exprs.as_slice() {
+            let env = Env {
+                context: ctx,
+                consts: NO_CONSTS.run(),
+                aggrs: &NO_AGGRS,
+                recursion_limit: tremor_script::recursion_limit(),
+            };
+            let opts = ExecOpts {
+                result_needed: true,
+                aggr: AggrType::Tick,
+            };
+            let local = LocalStack::with_size(script.locals);
+            expr.run(opts, &env, value, &NULL, meta, &local)
+                .ok()
+                .map(|v| v.into_owned().into_static())
+        } else {
+            // rejected by `parse`
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn key_expr() -> Result<()> {
+        let ctx = EventContext::new(0, None);
+        let value = literal!({"host": "snot", "id": 1, "sid": "1"});
+        let meta = literal!({"kafka": {"key": "badger"}});
+        let key = |src: &str| -> Result<Option<String>> {
+            Ok(KeyExpr::parse(src)?.key(&ctx, &value, &meta))
+        };
+        assert_eq!(Some(r#""snot""#.to_string()), key("event.host")?);
+        assert_eq!(Some(r#""badger""#.to_string()), key("$kafka.key")?);
+        assert_eq!(
+            Some(r#""snot/badger""#.to_string()),
+            key(r##""#{event.host}/#{$kafka.key}""##)?
+        );
+        assert_ne!(key("event.id")?, key("event.sid")?);
+        assert_eq!(None, key("event.missing")?);
+        assert!(KeyExpr::parse("let a = 1; a").is_err());
+        assert!(KeyExpr::parse("event.").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn field_path() {
+        let mut value = literal!({"owner": "snot"});
+        let meta = literal!({"host": {"id": 1}});
+        assert_eq!(
+            Some(&Value::from(1)),
+            FieldPath::parse("$host.id").resolve(&value, &meta)
+        );
+        FieldPath::parse("owner.team").set(&mut value, Value::from("badger"));
+        assert_eq!(literal!({"owner": {"team": "badger"}}), value);
+    }
+}
@@ -14,11 +14,19 @@
 
 pub mod backpressure;
 pub mod percentile;
+pub mod ratelimit;
+pub mod reservoir;
 pub mod rr;
+pub mod sample;
+pub mod tail;
 
 pub use backpressure::BackpressureFactory;
 pub use percentile::PercentileFactory;
+pub use ratelimit::RateLimitFactory;
+pub use reservoir::ReservoirFactory;
 pub use rr::RoundRobinFactory;
+pub use sample::SampleFactory;
+pub use tail::TailSampleFactory;
 
 use crate::op::prelude::*;
 use tremor_script::prelude::*;
@@ -0,0 +1,518 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Token bucket rate limiter
+//!
+//! Every event takes a token from the bucket of its key, buckets hold up to `burst` tokens
+//! and are refilled with `rate` tokens per second. Keys can have their own quota in `quotas`,
+//! quotas can be changed at runtime by updating the operator via the API.
+//!
+//! ## Configuration
+//!
+//! See [Config](struct.Config.html) for details.
+//!
+//! ## Outputs
+//!
+//! With the `discard` method the 1st additional output `overflow` is used to route events
+//! exceeding their quota to. With the `pause` method those events are delayed: the circuit breaker
+//! is triggered upstream and the operator holds them, and all events after them to keep their order,
+//! until their buckets have been refilled. They are sent to `out` on the next tick with a token
+//! in their bucket, once all held events are sent the circuit breaker is restored. Events arriving
+//! while `max_delayed` events are held already are routed to `overflow`.
+//!
+//! # Example
+//!
+//! ```trickle
+//! define operator limit from qos::ratelimit
+//! with
+//!   rate = 100.0,
+//!   burst = 200,
+//!   key = "event.tenant",
+//!   quotas = { "big_tenant": { "rate": 1000.0, "burst": 2000 } }
+//! end;
+//! ```
+
+use super::backpressure::Method;
+use crate::errors::{ErrorKind, Result};
+use crate::metrics::value_counts;
+use crate::op::path::{key_of, KeyExpr};
+use crate::op::prelude::*;
+use crate::{ConfigImpl, SignalKind};
+use lru::LruCache;
+use std::collections::{HashMap as StdHashMap, VecDeque};
+use std::num::NonZeroUsize;
+use tremor_script::{prelude::*, EventContext};
+
+const RATELIMIT: Cow<'static, str> = Cow::const_str("ratelimit");
+const ACTION: Cow<'static, str> = Cow::const_str("action");
+const PASS: Cow<'static, str> = Cow::const_str("pass");
+const OVERFLOW: Cow<'static, str> = Cow::const_str("overflow");
+
+/// Quota of a bucket
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Quota {
+    /// Tokens added to the bucket per second
+    pub rate: f64,
+    /// Maximum number of tokens in the bucket
+    pub burst: u64,
+}
+
+impl Quota {
+    fn validate(&self) -> Result<()> {
+        if self.rate.is_finite() && self.rate > 0.0 && self.burst > 0 {
+            Ok(())
+        } else {
+            Err(ErrorKind::BadOpConfig("`rate` and `burst` need to be positive".to_string()).into())
+        }
+    }
+}
+
+#[allow(clippy::unwrap_used)]
+fn default_max_keys() -> NonZeroUsize {
+    // ALLOW: 1000 is not zero
+    NonZeroUsize::new(1000).unwrap()
+}
+
+fn default_max_delayed() -> usize {
+    1000
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Tokens added to a bucket per second
+    pub rate: f64,
+    /// Maximum number of tokens in a bucket
+    pub burst: u64,
+    /// Expression for the key of an event, e.g. `event.tenant`.
+    /// All events share one bucket if not set
+    #[serde(default = "Default::default")]
+    pub key: Option<String>,
+    /// Quotas for individual keys, as keys keep their type these apply to string keys
+    #[serde(default = "Default::default")]
+    pub quotas: StdHashMap<String, Quota>,
+    /// Defines what happens to events exceeding their quota
+    ///
+    /// - `discard`: events are sent to `overflow`
+    /// - `pause`: events are delayed until their bucket is refilled, the circuit breaker is triggered meanwhile
+    #[serde(default)]
+    pub method: Method,
+    /// Maximum number of buckets, the least recently used buckets are dropped
+    ///
+    /// default: 1000
+    #[serde(default = "default_max_keys")]
+    pub max_keys: NonZeroUsize,
+    /// Maximum number of events delayed by the `pause` method, further events are sent to `overflow`
+    ///
+    /// default: 1000
+    #[serde(default = "default_max_delayed")]
+    pub max_delayed: usize,
+}
+
+impl ConfigImpl for Config {}
+
+/// Runtime update of the quotas, a `null` quota removes the quota of that key
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Update {
+    /// Tokens added to a bucket per second
+    #[serde(default = "Default::default")]
+    pub rate: Option<f64>,
+    /// Maximum number of tokens in a bucket
+    #[serde(default = "Default::default")]
+    pub burst: Option<u64>,
+    /// Quotas for individual keys
+    #[serde(default = "Default::default")]
+    pub quotas: StdHashMap<String, Option<Quota>>,
+}
+
+impl ConfigImpl for Update {}
+
+#[derive(Debug, Clone)]
+struct Bucket {
+    quota: Quota,
+    tokens: f64,
+    /// last refill
+    last: u64,
+}
+
+impl Bucket {
+    fn new(quota: Quota, now: u64) -> Self {
+        #[allow(clippy::cast_precision_loss)]
+        let tokens = quota.burst as f64;
+        Self {
+            quota,
+            tokens,
+            last: now,
+        }
+    }
+
+    fn refill(&mut self, now: u64) {
+        #[allow(clippy::cast_precision_loss)]
+        let elapsed = now.saturating_sub(self.last) as f64 / 1_000_000_000.0;
+        #[allow(clippy::cast_precision_loss)]
+        let burst = self.quota.burst as f64;
+        self.tokens = (self.tokens + elapsed * self.quota.rate).min(burst);
+        self.last = self.last.max(now);
+    }
+
+    fn take(&mut self, now: u64) -> bool {
+        self.refill(now);
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn set_quota(&mut self, quota: Quota) {
+        self.quota = quota;
+        #[allow(clippy::cast_precision_loss)]
+        let burst = quota.burst as f64;
+        self.tokens = self.tokens.min(burst);
+    }
+}
+
+op!(RateLimitFactory(_uid, node) {
+    if let Some(map) = &node.config {
+        let config: Config = Config::new(map)?;
+        Ok(Box::new(RateLimit::new(config)?))
+    } else {
+        Err(ErrorKind::MissingOpConfig(node.id.clone()).into())
+    }
+});
+
+#[derive(Debug)]
+struct RateLimit {
+    default: Quota,
+    quotas: StdHashMap<String, Quota>,
+    key: Option<KeyExpr>,
+    method: Method,
+    buckets: LruCache<String, Bucket>,
+    /// events delayed by the `pause` method and their keys, the circuit breaker is triggered while there are any
+    delayed: VecDeque<(String, Event)>,
+    max_delayed: usize,
+    pass: u64,
+    overflow: u64,
+}
+
+impl RateLimit {
+    fn new(config: Config) -> Result<Self> {
+        let default = Quota {
+            rate: config.rate,
+            burst: config.burst,
+        };
+        default.validate()?;
+        for quota in config.quotas.values() {
+            quota.validate()?;
+        }
+        Ok(Self {
+            default,
+            quotas: config
+                .quotas
+                .into_iter()
+                .map(|(key, quota)| (key_of(&Value::from(key)), quota))
+                .collect(),
+            key: config.key.as_deref().map(KeyExpr::parse).transpose()?,
+            method: config.method,
+            buckets: LruCache::new(config.max_keys),
+            delayed: VecDeque::new(),
+            max_delayed: config.max_delayed,
+            pass: 0,
+            overflow: 0,
+        })
+    }
+
+    fn quota(&self, key: &str) -> Quota {
+        self.quotas.get(key).copied().unwrap_or(self.default)
+    }
+
+    fn take(&mut self, key: &str, now: u64) -> bool {
+        if let Some(bucket) = self.buckets.get_mut(key) {
+            return bucket.take(now);
+        }
+        let mut bucket = Bucket::new(self.quota(key), now);
+        let taken = bucket.take(now);
+        self.buckets.put(key.to_string(), bucket);
+        taken
+    }
+
+    /// Send the delayed events with a token in their bucket, in order
+    fn release(&mut self, now: u64) -> Vec<(Cow<'static, str>, Event)> {
+        let mut events = Vec::new();
+        while let Some(key) = self.delayed.front().map(|(key, _)| key.clone()) {
+            if !self.take(&key, now) {
+                break;
+            }
+            if let Some((_, event)) = self.delayed.pop_front() {
+                events.push((OUT, event));
+            }
+        }
+        events
+    }
+}
+
+impl Operator for RateLimit {
+    fn on_event(
+        &mut self,
+        _uid: OperatorId,
+        _port: &str,
+        _state: &mut Value<'static>,
+        event: Event,
+    ) -> Result<EventAndInsights> {
+        let ctx = EventContext::new(event.ingest_ns, event.origin_uri.as_ref());
+        let (value, meta) = event.data.parts();
+        // events without a key share the bucket of the empty key, no key expression evaluates to it
+        let key = self
+            .key
+            .as_ref()
+            .and_then(|key| key.key(&ctx, value, meta))
+            .unwrap_or_default();
+        // events after delayed ones are delayed as well, to keep their order
+        if self.delayed.is_empty() && self.take(&key, event.ingest_ns) {
+            self.pass += 1;
+            return Ok(event.into());
+        }
+        self.overflow += 1;
+        match self.method {
+            Method::Discard => Ok(vec![(OVERFLOW, event)].into()),
+            // the circuit breaker is triggered already, but events can still be in flight
+            Method::Pause if self.delayed.len() >= self.max_delayed => {
+                Ok(vec![(OVERFLOW, event)].into())
+            }
+            Method::Pause => {
+                let insights = if self.delayed.is_empty() {
+                    vec![Event::cb_trigger(event.ingest_ns)]
+                } else {
+                    vec![]
+                };
+                self.delayed.push_back((key, event));
+                Ok(EventAndInsights {
+                    insights,
+                    ..EventAndInsights::default()
+                })
+            }
+        }
+    }
+
+    fn handles_signal(&self) -> bool {
+        self.method == Method::Pause
+    }
+
+    fn on_signal(
+        &mut self,
+        _uid: OperatorId,
+        _state: &mut Value<'static>,
+        signal: &mut Event,
+    ) -> Result<EventAndInsights> {
+        let mut events = vec![];
+        let mut insights = vec![];
+        if signal.kind == Some(SignalKind::Tick) && !self.delayed.is_empty() {
+            let now = signal.ingest_ns;
+            events = self.release(now);
+            if self.delayed.is_empty() {
+                insights.push(Event::cb_restore(now));
+            }
+        }
+        Ok(EventAndInsights { events, insights })
+    }
+
+    fn on_update(&mut self, update: &Value) -> Result<()> {
+        let update = Update::new(update)?;
+        let mut default = self.default;
+        if let Some(rate) = update.rate {
+            default.rate = rate;
+        }
+        if let Some(burst) = update.burst {
+            default.burst = burst;
+        }
+        default.validate()?;
+        for quota in update.quotas.values().flatten() {
+            quota.validate()?;
+        }
+
+        self.default = default;
+        for (key, quota) in update.quotas {
+            let key = key_of(&Value::from(key));
+            if let Some(quota) = quota {
+                self.quotas.insert(key, quota);
+            } else {
+                self.quotas.remove(&key);
+            }
+        }
+        let quotas: Vec<_> = self
+            .buckets
+            .iter()
+            .map(|(key, _)| (key.clone(), self.quota(key)))
+            .collect();
+        for (key, quota) in quotas {
+            if let Some(bucket) = self.buckets.peek_mut(&key) {
+                bucket.set_quota(quota);
+            }
+        }
+        Ok(())
+    }
+
+    fn metrics(
+        &self,
+        tags: &HashMap<Cow<'static, str>, Value<'static>>,
+        timestamp: u64,
+    ) -> Result<Vec<Value<'static>>> {
+        let counts = [(PASS, self.pass), (OVERFLOW, self.overflow)];
+        Ok(value_counts(RATELIMIT, tags, ACTION, &counts, timestamp))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::op::test_utils::{self, count, tick};
+
+    fn config(method: &str) -> Result<Config> {
+        Ok(Config::new(&literal!({
+            "rate": 1.0,
+            "burst": 2,
+            "key": "event.tenant",
+            "quotas": { "big": { "rate": 10.0, "burst": 3 } },
+            "method": method,
+        }))?)
+    }
+
+    fn send(op: &mut RateLimit, tenant: &str, ingest_ns: u64) -> Result<EventAndInsights> {
+        let event = Event {
+            ingest_ns,
+            data: literal!({ "tenant": tenant.to_string() }).into(),
+            ..Event::default()
+        };
+        test_utils::send(op, event)
+    }
+
+    fn tenants(r: &EventAndInsights) -> Vec<String> {
+        r.events
+            .iter()
+            .filter_map(|(_, event)| event.data.suffix().value().get_str("tenant"))
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    fn port(op: &mut RateLimit, tenant: &str, ingest_ns: u64) -> Result<Cow<'static, str>> {
+        let mut r = send(op, tenant, ingest_ns)?;
+        Ok(r.events.pop().ok_or("no data")?.0)
+    }
+
+    #[test]
+    fn discard() -> Result<()> {
+        let mut op = RateLimit::new(config("discard")?)?;
+        // burst
+        assert_eq!("out", port(&mut op, "small", 0)?);
+        assert_eq!("out", port(&mut op, "small", 0)?);
+        assert_eq!("overflow", port(&mut op, "small", 0)?);
+        // other keys have their own buckets and quotas
+        assert_eq!("out", port(&mut op, "big", 0)?);
+        assert_eq!("out", port(&mut op, "big", 0)?);
+        assert_eq!("out", port(&mut op, "big", 0)?);
+        assert_eq!("overflow", port(&mut op, "big", 0)?);
+        // refill
+        assert_eq!("out", port(&mut op, "small", 1_000_000_000)?);
+        assert_eq!("overflow", port(&mut op, "small", 1_000_000_000)?);
+        assert_eq!("out", port(&mut op, "big", 100_000_000)?);
+
+        assert_eq!(7, count(&op, "action", "pass")?);
+        assert_eq!(3, count(&op, "action", "overflow")?);
+        Ok(())
+    }
+
+    #[test]
+    fn pause() -> Result<()> {
+        let mut op = RateLimit::new(config("pause")?)?;
+        assert!(op.handles_signal());
+        assert_eq!("out", port(&mut op, "small", 0)?);
+        assert_eq!("out", port(&mut op, "small", 0)?);
+        // over quota events are delayed
+        let r = send(&mut op, "small", 0)?;
+        assert!(r.events.is_empty());
+        assert_eq!(CbAction::Trigger, r.insights[0].cb);
+        // only triggered once, later events are delayed as well to keep their order
+        let r = send(&mut op, "big", 0)?;
+        assert!(r.events.is_empty());
+        assert!(r.insights.is_empty());
+        assert!(send(&mut op, "small", 0)?.insights.is_empty());
+
+        let r = tick(&mut op, 500_000_000)?;
+        assert!(r.events.is_empty());
+        assert!(r.insights.is_empty());
+        // one token refilled
+        let r = tick(&mut op, 1_000_000_000)?;
+        assert_eq!(vec!["small", "big"], tenants(&r));
+        assert!(r.events.iter().all(|(port, _)| port == "out"));
+        assert!(r.insights.is_empty());
+        let r = tick(&mut op, 2_000_000_000)?;
+        assert_eq!(vec!["small"], tenants(&r));
+        assert_eq!(CbAction::Restore, r.insights[0].cb);
+        // nothing delayed anymore
+        assert!(tick(&mut op, 3_000_000_000)?.insights.is_empty());
+        assert_eq!("out", port(&mut op, "small", 3_000_000_000)?);
+        Ok(())
+    }
+
+    #[test]
+    fn max_delayed() -> Result<()> {
+        let mut config = config("pause")?;
+        config.max_delayed = 2;
+        let mut op = RateLimit::new(config)?;
+        assert_eq!("out", port(&mut op, "small", 0)?);
+        assert_eq!("out", port(&mut op, "small", 0)?);
+        assert!(send(&mut op, "small", 0)?.events.is_empty());
+        assert!(send(&mut op, "big", 0)?.events.is_empty());
+        // events past the limit are not held
+        assert_eq!("overflow", port(&mut op, "small", 0)?);
+        assert_eq!("overflow", port(&mut op, "big", 0)?);
+        assert_eq!(2, op.delayed.len());
+        let r = tick(&mut op, 1_000_000_000)?;
+        assert_eq!(vec!["small", "big"], tenants(&r));
+        assert_eq!(CbAction::Restore, r.insights[0].cb);
+        assert_eq!(4, count(&op, "action", "overflow")?);
+        Ok(())
+    }
+
+    #[test]
+    fn update() -> Result<()> {
+        let mut op = RateLimit::new(config("discard")?)?;
+        assert_eq!("out", port(&mut op, "big", 0)?);
+        assert_eq!("out", port(&mut op, "small", 0)?);
+        op.on_update(&literal!({
+            "burst": 1,
+            "quotas": { "big": null, "other": { "rate": 1.0, "burst": 5 } }
+        }))?;
+        // existing buckets are capped to the new burst
+        assert_eq!("out", port(&mut op, "big", 0)?);
+        assert_eq!("overflow", port(&mut op, "big", 0)?);
+        assert_eq!("overflow", port(&mut op, "small", 0)?);
+        for _ in 0..5 {
+            assert_eq!("out", port(&mut op, "other", 0)?);
+        }
+        assert_eq!("overflow", port(&mut op, "other", 0)?);
+
+        // invalid updates are rejected as a whole
+        assert!(op.on_update(&literal!({"rate": 0.0})).is_err());
+        assert!(op.on_update(&literal!({"snot": 1})).is_err());
+        assert!(op
+            .on_update(&literal!({"quotas": {"big": {"rate": 1.0, "burst": 0}}}))
+            .is_err());
+        assert_eq!(op.default.burst, 1);
+        Ok(())
+    }
+}
@@ -0,0 +1,282 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Reservoir sampling
+//!
+//! Keeps a uniform random sample of at most `size` events of every window of `interval`
+//! nanoseconds, other events are dropped. Windows start with their first event, the sample
+//! of a window is emitted in order of ingestion once the window is over, either with the
+//! first event after it or with the next tick.
+//!
+//! The ids of dropped events are tracked by a kept event of their window, so acknowledging
+//! or failing the sample acknowledges or fails all events of the window.
+//!
+//! ## Configuration
+//!
+//! See [Config](struct.Config.html) for details.
+//!
+//! # Example
+//!
+//! ```trickle
+//! define operator reservoir from qos::reservoir
+//! with
+//!   size = 100,
+//!   interval = 1000000000
+//! end;
+//! ```
+
+use crate::errors::{ErrorKind, Result};
+use crate::metrics::value_counts;
+use crate::op::prelude::*;
+use crate::{ConfigImpl, SignalKind};
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+use std::mem;
+use std::num::NonZeroUsize;
+use tremor_script::prelude::*;
+
+const RESERVOIR: Cow<'static, str> = Cow::const_str("reservoir");
+const ACTION: Cow<'static, str> = Cow::const_str("action");
+const KEEP: Cow<'static, str> = Cow::const_str("keep");
+const DROP: Cow<'static, str> = Cow::const_str("drop");
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Maximum number of events to keep per window
+    pub size: NonZeroUsize,
+    /// Length of a window in nanoseconds
+    pub interval: u64,
+}
+
+impl ConfigImpl for Config {}
+
+op!(ReservoirFactory(_uid, node) {
+    if let Some(map) = &node.config {
+        let config: Config = Config::new(map)?;
+        Ok(Box::new(Reservoir::new(config, SmallRng::from_entropy())))
+    } else {
+        Err(ErrorKind::MissingOpConfig(node.id.clone()).into())
+    }
+});
+
+#[derive(Debug)]
+struct Reservoir {
+    config: Config,
+    rng: SmallRng,
+    /// start of the current window, `None` if no event arrived since the last window
+    start: Option<u64>,
+    /// events seen in the current window
+    seen: u64,
+    sample: Vec<Event>,
+    kept: u64,
+    dropped: u64,
+}
+
+/// Track `dropped` by `kept`
+fn absorb(kept: &mut Event, dropped: &Event) {
+    kept.id.track(&dropped.id);
+    kept.transactional |= dropped.transactional;
+}
+
+impl Reservoir {
+    fn new(config: Config, rng: SmallRng) -> Self {
+        Self {
+            sample: Vec::with_capacity(config.size.get()),
+            config,
+            rng,
+            start: None,
+            seen: 0,
+            kept: 0,
+            dropped: 0,
+        }
+    }
+
+    /// Emit the sample of the current window if it is over at `now`
+    fn flush(&mut self, now: u64) -> EventAndInsights {
+        match self.start {
+            Some(start) if now >= start.saturating_add(self.config.interval) => {
+                let mut sample = mem::take(&mut self.sample);
+                sample.sort_by_key(|event| event.ingest_ns);
+                self.kept += sample.len() as u64;
+                self.dropped += self.seen - sample.len() as u64;
+                self.start = None;
+                self.seen = 0;
+                sample
+                    .into_iter()
+                    .map(|event| (OUT, event))
+                    .collect::<Vec<_>>()
+                    .into()
+            }
+            _ => EventAndInsights::default(),
+        }
+    }
+}
+
+impl Operator for Reservoir {
+    fn on_event(
+        &mut self,
+        _uid: OperatorId,
+        _port: &str,
+        _state: &mut Value<'static>,
+        event: Event,
+    ) -> Result<EventAndInsights> {
+        let flushed = self.flush(event.ingest_ns);
+        self.start.get_or_insert(event.ingest_ns);
+        self.seen += 1;
+        if self.sample.len() < self.config.size.get() {
+            self.sample.push(event);
+        } else {
+            // replace a random event with the probability `size / seen`
+            let idx = self.rng.gen_range(0..self.seen);
+            if let Some(slot) = usize::try_from(idx)
+                .ok()
+                .and_then(|idx| self.sample.get_mut(idx))
+            {
+                let replaced = mem::replace(slot, event);
+                absorb(slot, &replaced);
+            } else if let Some(kept) = self.sample.last_mut() {
+                absorb(kept, &event);
+            }
+        }
+        Ok(flushed)
+    }
+
+    fn handles_signal(&self) -> bool {
+        true
+    }
+
+    fn on_signal(
+        &mut self,
+        _uid: OperatorId,
+        _state: &mut Value<'static>,
+        signal: &mut Event,
+    ) -> Result<EventAndInsights> {
+        if signal.kind == Some(SignalKind::Tick) {
+            Ok(self.flush(signal.ingest_ns))
+        } else {
+            Ok(EventAndInsights::default())
+        }
+    }
+
+    fn metrics(
+        &self,
+        tags: &HashMap<Cow<'static, str>, Value<'static>>,
+        timestamp: u64,
+    ) -> Result<Vec<Value<'static>>> {
+        let counts = [(KEEP, self.kept), (DROP, self.dropped)];
+        Ok(value_counts(RESERVOIR, tags, ACTION, &counts, timestamp))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::op::test_utils::{count, send, tick};
+    use crate::EventIdGenerator;
+    use tremor_common::ids::{Id, SourceId};
+
+    fn reservoir(size: usize) -> Result<Reservoir> {
+        let config = Config {
+            size: NonZeroUsize::new(size).ok_or("zero size")?,
+            interval: 100,
+        };
+        Ok(Reservoir::new(config, SmallRng::seed_from_u64(42)))
+    }
+
+    fn ingest_ns(r: &EventAndInsights) -> Vec<u64> {
+        r.events.iter().map(|(_, e)| e.ingest_ns).collect()
+    }
+
+    fn push(op: &mut Reservoir, ingest_ns: u64) -> Result<Vec<u64>> {
+        let event = Event {
+            ingest_ns,
+            data: (Value::from(ingest_ns), Value::object()).into(),
+            ..Event::default()
+        };
+        Ok(ingest_ns(&send(op, event)?))
+    }
+
+    fn flush(op: &mut Reservoir, now: u64) -> Result<Vec<u64>> {
+        Ok(ingest_ns(&tick(op, now)?))
+    }
+
+    #[test]
+    fn windows() -> Result<()> {
+        let mut op = reservoir(10)?;
+        for ns in 0..50 {
+            assert!(push(&mut op, ns)?.is_empty());
+        }
+        assert!(flush(&mut op, 99)?.is_empty());
+        let sample = flush(&mut op, 100)?;
+        assert_eq!(10, sample.len());
+        // in order of ingestion
+        assert!(sample.windows(2).all(|w| w[0] < w[1]));
+
+        // a small window is kept entirely and emitted by the next event
+        assert!(push(&mut op, 200)?.is_empty());
+        assert!(push(&mut op, 250)?.is_empty());
+        assert_eq!(vec![200, 250], push(&mut op, 300)?);
+        assert_eq!(vec![300], flush(&mut op, 400)?);
+        assert!(flush(&mut op, 500)?.is_empty());
+
+        assert_eq!(13, count(&op, "action", "keep")?);
+        assert_eq!(40, count(&op, "action", "drop")?);
+        Ok(())
+    }
+
+    #[test]
+    fn uniform() -> Result<()> {
+        // every event has the same chance to be part of the sample
+        let mut op = reservoir(10)?;
+        let mut counts = std::collections::HashMap::<u64, u32>::new();
+        for window in 0..1000 {
+            let start = window * 100;
+            for ns in start..start + 100 {
+                push(&mut op, ns)?;
+            }
+            for ns in flush(&mut op, start + 100)? {
+                *counts.entry(ns - start).or_default() += 1;
+            }
+        }
+        // expected: 100 per event
+        assert_eq!(100, counts.len());
+        assert!(counts.values().all(|c| (50..150).contains(c)), "{counts:?}");
+        Ok(())
+    }
+
+    #[test]
+    fn track_dropped() -> Result<()> {
+        let mut op = reservoir(2)?;
+        let mut idgen = EventIdGenerator::new(SourceId::new(1));
+        let ids: Vec<_> = (0..10).map(|_| idgen.next_id()).collect();
+        for (ns, id) in (0..).zip(&ids) {
+            let event = Event {
+                id: id.clone(),
+                ingest_ns: ns,
+                transactional: ns == 5,
+                ..Event::default()
+            };
+            assert!(send(&mut op, event)?.events.is_empty());
+        }
+        let r = tick(&mut op, 100)?;
+        assert_eq!(2, r.events.len());
+        // every event is tracked by one of the sample
+        for id in &ids {
+            assert!(r.events.iter().any(|(_, e)| e.id.is_tracking(id)));
+        }
+        assert!(r.events.iter().any(|(_, e)| e.transactional));
+        Ok(())
+    }
+}
@@ -0,0 +1,247 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Probabilistic sampling
+//!
+//! Keeps every event with the probability `rate`, other events are dropped. Dropped
+//! transactional events are acknowledged.
+//!
+//! With a `key` expression the decision is deterministic: an event is kept if its key hashes into the
+//! lower `rate` share of `10000` slots, the same as `chash::jump(key, 10000) < rate * 10000`,
+//! so all events with the same key are either kept or dropped, also across tremor instances.
+//! Events the key expression fails for, e.g. because of a missing field, are always kept.
+//!
+//! ## Configuration
+//!
+//! See [Config](struct.Config.html) for details.
+//!
+//! # Example
+//!
+//! ```trickle
+//! define operator sample from qos::sample
+//! with
+//!   rate = 0.1,
+//!   key = "event.trace_id"
+//! end;
+//! ```
+
+use crate::errors::{ErrorKind, Result};
+use crate::metrics::value_counts;
+use crate::op::path::{key_of, KeyExpr};
+use crate::op::prelude::*;
+use crate::ConfigImpl;
+use jumphash::JumpHasher;
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+use tremor_script::prelude::*;
+
+const SAMPLE: Cow<'static, str> = Cow::const_str("sample");
+const ACTION: Cow<'static, str> = Cow::const_str("action");
+const KEEP: Cow<'static, str> = Cow::const_str("keep");
+const DROP: Cow<'static, str> = Cow::const_str("drop");
+
+/// Number of slots keys are hashed into
+const SLOTS: u32 = 10_000;
+
+/// Returns `true` if `key` hashes into the lower `rate` share of the slots
+pub(crate) fn in_sample(key: &str, rate: f64) -> bool {
+    // This is 'tremor\0\0'  and '\0\0tremor' as integers, as used by `chash::jump`
+    let jh = JumpHasher::new_with_keys(8_390_880_576_440_238_080, 128_034_676_764_530);
+    f64::from(jh.slot(&key, SLOTS)) < rate * f64::from(SLOTS)
+}
+
+/// Key of a value to sample by, strings are hashed as they are, like `chash::jump` does
+pub(crate) fn sample_key(value: &Value) -> String {
+    value
+        .as_str()
+        .map_or_else(|| key_of(value), ToString::to_string)
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Share of events to keep, between `0.0` and `1.0`
+    pub rate: f64,
+    /// Expression for the key of an event, e.g. `event.trace_id`.
+    /// Events are sampled randomly if not set
+    #[serde(default = "Default::default")]
+    pub key: Option<String>,
+}
+
+impl ConfigImpl for Config {}
+
+op!(SampleFactory(_uid, node) {
+    if let Some(map) = &node.config {
+        let config: Config = Config::new(map)?;
+        Ok(Box::new(Sample::new(config, SmallRng::from_entropy())?))
+    } else {
+        Err(ErrorKind::MissingOpConfig(node.id.clone()).into())
+    }
+});
+
+#[derive(Debug)]
+struct Sample {
+    rate: f64,
+    key: Option<KeyExpr>,
+    rng: SmallRng,
+    kept: u64,
+    dropped: u64,
+}
+
+impl Sample {
+    fn new(config: Config, rng: SmallRng) -> Result<Self> {
+        if !(0.0..=1.0).contains(&config.rate) {
+            return Err(ErrorKind::BadOpConfig(
+                "`rate` needs to be between 0.0 and 1.0".to_string(),
+            )
+            .into());
+        }
+        Ok(Self {
+            rate: config.rate,
+            key: config.key.as_deref().map(KeyExpr::parse).transpose()?,
+            rng,
+            kept: 0,
+            dropped: 0,
+        })
+    }
+
+    fn keep(&mut self, event: &Event) -> bool {
+        if let Some(key) = &self.key {
+            let ctx = EventContext::new(event.ingest_ns, event.origin_uri.as_ref());
+            let (value, meta) = event.data.parts();
+            key.eval(&ctx, value, meta)
+                .map_or(true, |key| in_sample(&sample_key(&key), self.rate))
+        } else {
+            self.rng.gen_bool(self.rate)
+        }
+    }
+}
+
+impl Operator for Sample {
+    fn on_event(
+        &mut self,
+        _uid: OperatorId,
+        _port: &str,
+        _state: &mut Value<'static>,
+        event: Event,
+    ) -> Result<EventAndInsights> {
+        if self.keep(&event) {
+            self.kept += 1;
+            Ok(event.into())
+        } else {
+            self.dropped += 1;
+            let insights = if event.transactional {
+                vec![event.insight_ack()]
+            } else {
+                vec![]
+            };
+            Ok(EventAndInsights {
+                insights,
+                ..EventAndInsights::default()
+            })
+        }
+    }
+
+    fn metrics(
+        &self,
+        tags: &HashMap<Cow<'static, str>, Value<'static>>,
+        timestamp: u64,
+    ) -> Result<Vec<Value<'static>>> {
+        let counts = [(KEEP, self.kept), (DROP, self.dropped)];
+        Ok(value_counts(SAMPLE, tags, ACTION, &counts, timestamp))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::op::test_utils::{count, send};
+
+    fn sample(rate: f64, key: Option<&str>) -> Result<Sample> {
+        let config = Config {
+            rate,
+            key: key.map(ToString::to_string),
+        };
+        Sample::new(config, SmallRng::seed_from_u64(42))
+    }
+
+    fn kept(op: &mut Sample, value: Value<'static>) -> Result<bool> {
+        let event = Event {
+            data: (value, Value::object()).into(),
+            ..Event::default()
+        };
+        Ok(!send(op, event)?.events.is_empty())
+    }
+
+    #[test]
+    fn random() -> Result<()> {
+        let mut op = sample(0.5, None)?;
+        let mut n = 0;
+        for _ in 0..1000 {
+            if kept(&mut op, Value::object())? {
+                n += 1;
+            }
+        }
+        assert!((400..600).contains(&n), "{n} events kept");
+        assert_eq!(n, count(&op, "action", "keep")?);
+        assert_eq!(1000 - n, count(&op, "action", "drop")?);
+
+        let mut op = sample(0.0, None)?;
+        assert!(!kept(&mut op, Value::object())?);
+        let mut op = sample(1.0, None)?;
+        assert!(kept(&mut op, Value::object())?);
+        assert!(sample(1.5, None).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn by_key() -> Result<()> {
+        let mut op = sample(0.5, Some("event.trace_id"))?;
+        let mut n = 0;
+        for i in 0..1000 {
+            let id = format!("trace-{i}");
+            let first = kept(&mut op, literal!({ "trace_id": id.clone() }))?;
+            // the decision is the same for every event with the key
+            assert_eq!(first, kept(&mut op, literal!({ "trace_id": id }))?);
+            if first {
+                n += 1;
+            }
+        }
+        assert!((400..600).contains(&n), "{n} keys kept");
+        // events without the key are kept
+        assert!(kept(&mut op, Value::object())?);
+
+        let mut op = sample(0.0, Some("event.trace_id"))?;
+        assert!(!kept(&mut op, literal!({ "trace_id": "snot" }))?);
+        assert!(sample(0.5, Some("event.")).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn ack_dropped() -> Result<()> {
+        let mut op = sample(0.0, None)?;
+        let event = Event {
+            transactional: true,
+            ..Event::default()
+        };
+        let id = event.id.clone();
+        let r = send(&mut op, event)?;
+        assert!(r.events.is_empty());
+        assert_eq!(CbAction::Ack, r.insights[0].cb);
+        assert_eq!(id, r.insights[0].id);
+        // non transactional events are not acknowledged
+        assert!(send(&mut op, Event::default())?.insights.is_empty());
+        Ok(())
+    }
+}
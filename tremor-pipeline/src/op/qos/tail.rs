@@ -0,0 +1,567 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Tail based trace sampling
+//!
+//! Buffers the spans of trace events, as emitted by the `otel` connector, by their `trace_id`
+//! and decides `decision_wait` nanoseconds after the first span of a trace whether the whole
+//! trace is kept. A trace is kept if:
+//!
+//! * `errors` is set and one of its spans has an error status
+//! * its duration, from the earliest span start to the latest span end, is at least `latency`
+//! * its `trace_id` is part of the deterministic `rate` sample, see `qos::sample`
+//!
+//! Kept traces are emitted as one event in the `otel` trace layout, spans of a trace arriving
+//! after the decision follow it. Events without a `trace` pass unchanged.
+//!
+//! Trace events track the ids of all events with their spans. Dropped traces are acknowledged,
+//! as are events with only spans of already dropped traces.
+//!
+//! ## Configuration
+//!
+//! See [Config](struct.Config.html) for details.
+//!
+//! # Example
+//!
+//! ```trickle
+//! define operator tail from qos::tailsample
+//! with
+//!   decision_wait = 10000000000,
+//!   latency = 500000000,
+//!   rate = 0.01
+//! end;
+//! ```
+
+use super::sample::{in_sample, sample_key};
+use crate::errors::{ErrorKind, Result};
+use crate::metrics::value_counts;
+use crate::op::prelude::*;
+use crate::{ConfigImpl, EventId, EventIdGenerator, SignalKind};
+use lru::LruCache;
+use std::collections::{HashMap as StdHashMap, VecDeque};
+use std::num::NonZeroUsize;
+use tremor_script::prelude::*;
+
+const TAILSAMPLE: Cow<'static, str> = Cow::const_str("tailsample");
+const ACTION: Cow<'static, str> = Cow::const_str("action");
+const KEEP: Cow<'static, str> = Cow::const_str("keep");
+const DROP: Cow<'static, str> = Cow::const_str("drop");
+
+/// `STATUS_CODE_ERROR` of otel span statuses
+const STATUS_CODE_ERROR: i64 = 2;
+
+fn default_decision_wait() -> u64 {
+    10_000_000_000
+}
+
+#[allow(clippy::unwrap_used)]
+fn default_max_traces() -> NonZeroUsize {
+    // ALLOW: 10000 is not zero
+    NonZeroUsize::new(10_000).unwrap()
+}
+
+fn default_errors() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Time in nanoseconds to buffer the spans of a trace after its first span before deciding
+    ///
+    /// default: 10s
+    #[serde(default = "default_decision_wait")]
+    pub decision_wait: u64,
+    /// Maximum number of buffered traces, the oldest trace is decided early if exceeded
+    ///
+    /// default: 10000
+    #[serde(default = "default_max_traces")]
+    pub max_traces: NonZeroUsize,
+    /// Keep traces with a span with an error status
+    ///
+    /// default: true
+    #[serde(default = "default_errors")]
+    pub errors: bool,
+    /// Keep traces lasting at least this many nanoseconds
+    #[serde(default = "Default::default")]
+    pub latency: Option<u64>,
+    /// Share of the other traces to keep, between `0.0` and `1.0`
+    ///
+    /// default: 0.0
+    #[serde(default = "Default::default")]
+    pub rate: f64,
+}
+
+impl ConfigImpl for Config {}
+
+op!(TailSampleFactory(uid, node) {
+    if let Some(map) = &node.config {
+        let config: Config = Config::new(map)?;
+        Ok(Box::new(TailSample::new(config, uid)?))
+    } else {
+        Err(ErrorKind::MissingOpConfig(node.id.clone()).into())
+    }
+});
+
+/// Spans of a single trace
+#[derive(Debug, Default)]
+struct Spans {
+    /// resource spans holding only spans of this trace
+    resource_spans: Vec<Value<'static>>,
+    error: bool,
+    start: Option<u64>,
+    end: Option<u64>,
+}
+
+impl Spans {
+    fn observe(&mut self, span: &Value) {
+        let code = span.get("status").and_then(|status| status.get_i64("code"));
+        self.error |= code == Some(STATUS_CODE_ERROR);
+        if let Some(start) = span.get_u64("start_time_unix_nano") {
+            self.start = Some(self.start.map_or(start, |s| s.min(start)));
+        }
+        if let Some(end) = span.get_u64("end_time_unix_nano") {
+            self.end = Some(self.end.map_or(end, |e| e.max(end)));
+        }
+    }
+
+    fn absorb(&mut self, other: Spans) {
+        self.resource_spans.extend(other.resource_spans);
+        self.error |= other.error;
+        self.start = self.start.into_iter().chain(other.start).min();
+        self.end = self.end.into_iter().chain(other.end).max();
+    }
+
+    fn duration(&self) -> u64 {
+        self.start
+            .zip(self.end)
+            .map_or(0, |(start, end)| end.saturating_sub(start))
+    }
+}
+
+/// A copy of the object `obj` with `key` set to `data`
+fn with_field(obj: &Value, key: &'static str, data: Vec<Value<'static>>) -> Value<'static> {
+    let mut copy = Value::object();
+    for (k, v) in obj.as_object().into_iter().flatten() {
+        if &**k != key {
+            copy.try_insert(k.to_string(), v.clone_static());
+        }
+    }
+    copy.try_insert(key, Value::from(data));
+    copy
+}
+
+fn array<'v, 'value>(value: &'v Value<'value>, key: &str) -> &'v [Value<'value>] {
+    value.get_array(key).map_or(&[], Vec::as_slice)
+}
+
+/// Split the resource spans in the `otel` trace layout of `value` by trace id,
+/// `None` if `value` is no trace
+fn split(value: &Value) -> Option<StdHashMap<String, Spans>> {
+    let mut traces: StdHashMap<String, Spans> = StdHashMap::new();
+    for rs in value.get_array("trace")? {
+        let mut ils_by_trace: StdHashMap<String, Vec<Value<'static>>> = StdHashMap::new();
+        for ils in array(rs, "instrumentation_library_spans") {
+            let mut spans_by_trace: StdHashMap<String, Vec<Value<'static>>> = StdHashMap::new();
+            for span in array(ils, "spans") {
+                let trace_id = span.get("trace_id").map_or_else(String::new, sample_key);
+                traces.entry(trace_id.clone()).or_default().observe(span);
+                spans_by_trace
+                    .entry(trace_id)
+                    .or_default()
+                    .push(span.clone_static());
+            }
+            for (trace_id, spans) in spans_by_trace {
+                let ils = with_field(ils, "spans", spans);
+                ils_by_trace.entry(trace_id).or_default().push(ils);
+            }
+        }
+        for (trace_id, ils) in ils_by_trace {
+            if let Some(spans) = traces.get_mut(&trace_id) {
+                let rs = with_field(rs, "instrumentation_library_spans", ils);
+                spans.resource_spans.push(rs);
+            }
+        }
+    }
+    Some(traces)
+}
+
+#[derive(Debug)]
+struct Trace {
+    /// tracks the ids of all events with spans of this trace
+    id: EventId,
+    ingest_ns: u64,
+    origin_uri: Option<EventOriginUri>,
+    meta: Value<'static>,
+    transactional: bool,
+    spans: Spans,
+}
+
+impl Trace {
+    fn into_event(self) -> Event {
+        Event {
+            id: self.id,
+            data: (literal!({ "trace": self.spans.resource_spans }), self.meta).into(),
+            ingest_ns: self.ingest_ns,
+            origin_uri: self.origin_uri,
+            transactional: self.transactional,
+            ..Event::default()
+        }
+    }
+}
+
+#[derive(Debug)]
+struct TailSample {
+    config: Config,
+    idgen: EventIdGenerator,
+    traces: StdHashMap<String, Trace>,
+    /// buffered traces in order of their decision time
+    pending: VecDeque<(u64, String)>,
+    /// decisions of recent traces, to apply to late spans
+    decided: LruCache<String, bool>,
+    kept: u64,
+    dropped: u64,
+}
+
+impl TailSample {
+    fn new(config: Config, uid: OperatorId) -> Result<Self> {
+        if !(0.0..=1.0).contains(&config.rate) {
+            return Err(ErrorKind::BadOpConfig(
+                "`rate` needs to be between 0.0 and 1.0".to_string(),
+            )
+            .into());
+        }
+        Ok(Self {
+            idgen: EventIdGenerator::for_operator(uid),
+            traces: StdHashMap::new(),
+            pending: VecDeque::new(),
+            decided: LruCache::new(config.max_traces),
+            kept: 0,
+            dropped: 0,
+            config,
+        })
+    }
+
+    fn keep(&self, trace_id: &str, spans: &Spans) -> bool {
+        (self.config.errors && spans.error)
+            || self
+                .config
+                .latency
+                .map_or(false, |latency| spans.duration() >= latency)
+            || in_sample(trace_id, self.config.rate)
+    }
+
+    /// Decide the oldest buffered trace, kept traces are emitted and dropped ones acknowledged
+    fn decide_next(&mut self, decided: &mut EventAndInsights) {
+        let (trace_id, trace) = match self.pending.pop_front() {
+            Some((_, trace_id)) => match self.traces.remove(&trace_id) {
+                Some(trace) => (trace_id, trace),
+                None => return,
+            },
+            None => return,
+        };
+        let keep = self.keep(&trace_id, &trace.spans);
+        self.decided.put(trace_id, keep);
+        let event = trace.into_event();
+        if keep {
+            self.kept += 1;
+            decided.events.push((OUT, event));
+        } else {
+            self.dropped += 1;
+            if event.transactional {
+                decided.insights.push(event.insight_ack());
+            }
+        }
+    }
+
+    /// Decide all traces due at `now` or exceeding `max_traces`
+    fn decide(&mut self, now: u64, decided: &mut EventAndInsights) {
+        while let Some((due, _)) = self.pending.front() {
+            if *due > now && self.traces.len() <= self.config.max_traces.get() {
+                break;
+            }
+            self.decide_next(decided);
+        }
+    }
+}
+
+impl Operator for TailSample {
+    fn on_event(
+        &mut self,
+        _uid: OperatorId,
+        _port: &str,
+        _state: &mut Value<'static>,
+        event: Event,
+    ) -> Result<EventAndInsights> {
+        let (value, meta) = event.data.parts();
+        let traces = if let Some(traces) = split(value) {
+            traces
+        } else {
+            return Ok(event.into());
+        };
+        let mut out = EventAndInsights::default();
+        // if the event is tracked by a trace event
+        let mut tracked = false;
+        for (trace_id, spans) in traces {
+            match self.decided.get(&trace_id).copied() {
+                Some(true) => {
+                    tracked = true;
+                    let mut id = self.idgen.next_id();
+                    id.track(&event.id);
+                    let late = Trace {
+                        id,
+                        ingest_ns: event.ingest_ns,
+                        origin_uri: event.origin_uri.clone(),
+                        meta: meta.clone_static(),
+                        transactional: event.transactional,
+                        spans,
+                    };
+                    out.events.push((OUT, late.into_event()));
+                }
+                Some(false) => (),
+                None => {
+                    tracked = true;
+                    let trace = self.traces.entry(trace_id).or_insert_with_key(|trace_id| {
+                        let due = event.ingest_ns.saturating_add(self.config.decision_wait);
+                        self.pending.push_back((due, trace_id.clone()));
+                        Trace {
+                            id: self.idgen.next_id(),
+                            ingest_ns: event.ingest_ns,
+                            origin_uri: event.origin_uri.clone(),
+                            meta: meta.clone_static(),
+                            transactional: false,
+                            spans: Spans::default(),
+                        }
+                    });
+                    trace.id.track(&event.id);
+                    trace.transactional |= event.transactional;
+                    trace.spans.absorb(spans);
+                }
+            }
+        }
+        if !tracked && event.transactional {
+            out.insights.push(event.insight_ack());
+        }
+        self.decide(event.ingest_ns, &mut out);
+        Ok(out)
+    }
+
+    fn handles_signal(&self) -> bool {
+        true
+    }
+
+    fn on_signal(
+        &mut self,
+        _uid: OperatorId,
+        _state: &mut Value<'static>,
+        signal: &mut Event,
+    ) -> Result<EventAndInsights> {
+        let mut decided = EventAndInsights::default();
+        if signal.kind == Some(SignalKind::Tick) {
+            self.decide(signal.ingest_ns, &mut decided);
+        }
+        Ok(decided)
+    }
+
+    fn metrics(
+        &self,
+        tags: &HashMap<Cow<'static, str>, Value<'static>>,
+        timestamp: u64,
+    ) -> Result<Vec<Value<'static>>> {
+        let counts = [(KEEP, self.kept), (DROP, self.dropped)];
+        Ok(value_counts(TAILSAMPLE, tags, ACTION, &counts, timestamp))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::op::test_utils::{count, send, tick};
+    use tremor_common::ids::{Id, SourceId};
+
+    fn config() -> Config {
+        Config {
+            decision_wait: 100,
+            max_traces: default_max_traces(),
+            errors: true,
+            latency: None,
+            rate: 0.0,
+        }
+    }
+
+    fn span(trace_id: &str, code: i64, start: u64, end: u64) -> Value<'static> {
+        literal!({
+            "trace_id": trace_id.to_string(),
+            "span_id": "0000000000000001",
+            "start_time_unix_nano": start,
+            "end_time_unix_nano": end,
+            "status": { "code": code, "deprecated_code": 0, "message": "" }
+        })
+    }
+
+    fn otel(spans: Vec<Value<'static>>) -> Value<'static> {
+        literal!({
+            "trace": [{
+                "resource": { "attributes": {}, "dropped_attributes_count": 0 },
+                "schema_url": "",
+                "instrumentation_library_spans": [{ "spans": spans, "schema_url": "" }]
+            }]
+        })
+    }
+
+    fn values(r: EventAndInsights) -> Vec<Value<'static>> {
+        r.events
+            .into_iter()
+            .map(|(_, e)| e.data.suffix().value().clone_static())
+            .collect()
+    }
+
+    fn push(
+        op: &mut TailSample,
+        ingest_ns: u64,
+        value: Value<'static>,
+    ) -> Result<Vec<Value<'static>>> {
+        let event = Event {
+            ingest_ns,
+            data: (value, Value::object()).into(),
+            ..Event::default()
+        };
+        Ok(values(send(op, event)?))
+    }
+
+    fn decide(op: &mut TailSample, ingest_ns: u64) -> Result<Vec<Value<'static>>> {
+        Ok(values(tick(op, ingest_ns)?))
+    }
+
+    /// trace ids of all spans in an event
+    fn trace_ids(value: &Value) -> Vec<String> {
+        let mut ids = Vec::new();
+        for rs in array(value, "trace") {
+            for ils in array(rs, "instrumentation_library_spans") {
+                for span in array(ils, "spans") {
+                    ids.extend(span.get_str("trace_id").map(ToString::to_string));
+                }
+            }
+        }
+        ids
+    }
+
+    #[test]
+    fn errors() -> Result<()> {
+        let mut op = TailSample::new(config(), OperatorId::new(0))?;
+        let first = otel(vec![span("a", 0, 0, 10), span("b", 0, 0, 10)]);
+        assert!(push(&mut op, 0, first)?.is_empty());
+        let second = otel(vec![
+            span("a", STATUS_CODE_ERROR, 5, 10),
+            span("b", 0, 5, 10),
+        ]);
+        assert!(push(&mut op, 50, second)?.is_empty());
+        assert!(decide(&mut op, 99)?.is_empty());
+
+        let events = decide(&mut op, 100)?;
+        assert_eq!(1, events.len());
+        let trace = &events[0];
+        // one resource span per event
+        assert_eq!(2, array(trace, "trace").len());
+        assert_eq!(vec!["a", "a"], trace_ids(trace));
+        // other fields are kept
+        assert!(trace["trace"][0].get("resource").is_some());
+        assert_eq!(
+            Some(""),
+            trace["trace"][0]["instrumentation_library_spans"][0].get_str("schema_url")
+        );
+
+        // late spans follow the decision
+        let late = otel(vec![span("a", 0, 20, 30), span("b", 0, 20, 30)]);
+        let events = push(&mut op, 150, late)?;
+        assert_eq!(1, events.len());
+        assert_eq!(vec!["a"], trace_ids(&events[0]));
+
+        // no trace
+        let events = push(&mut op, 150, literal!({"snot": "badger"}))?;
+        assert_eq!(vec![literal!({"snot": "badger"})], events);
+
+        assert_eq!(1, count(&op, "action", "keep")?);
+        assert_eq!(1, count(&op, "action", "drop")?);
+        Ok(())
+    }
+
+    #[test]
+    fn latency_and_max_traces() -> Result<()> {
+        let mut config = config();
+        config.errors = false;
+        config.latency = Some(1000);
+        config.max_traces = NonZeroUsize::new(1).ok_or("zero max traces")?;
+        let mut op = TailSample::new(config, OperatorId::new(0))?;
+
+        assert!(push(
+            &mut op,
+            0,
+            otel(vec![span("slow", STATUS_CODE_ERROR, 0, 500)])
+        )?
+        .is_empty());
+        // exceeding max_traces decides the oldest trace
+        let events = push(
+            &mut op,
+            1,
+            otel(vec![span("fast", 0, 0, 10), span("slow", 0, 900, 1000)]),
+        )?;
+        assert_eq!(1, events.len());
+        assert_eq!(vec!["slow", "slow"], trace_ids(&events[0]));
+        assert!(decide(&mut op, 101)?.is_empty());
+
+        let mut config = self::config();
+        config.rate = 1.0;
+        assert!(
+            TailSample::new(config.clone(), OperatorId::new(0))?.keep("snot", &Spans::default())
+        );
+        config.rate = 2.0;
+        assert!(TailSample::new(config, OperatorId::new(0)).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn ack_dropped() -> Result<()> {
+        let mut op = TailSample::new(config(), OperatorId::new(0))?;
+        let mut idgen = EventIdGenerator::new(SourceId::new(1));
+        let mut event = |ingest_ns: u64, value: Value<'static>| Event {
+            id: idgen.next_id(),
+            ingest_ns,
+            data: (value, Value::object()).into(),
+            transactional: true,
+            ..Event::default()
+        };
+        let first = event(0, otel(vec![span("a", 0, 0, 10)]));
+        let first_id = first.id.clone();
+        let r = send(&mut op, first)?;
+        assert!(r.events.is_empty());
+        assert!(r.insights.is_empty());
+        // the dropped trace is acknowledged
+        let r = tick(&mut op, 100)?;
+        assert!(r.events.is_empty());
+        assert_eq!(CbAction::Ack, r.insights[0].cb);
+        assert!(r.insights[0].id.is_tracking(&first_id));
+        // as are events with spans of dropped traces only
+        let late = event(150, otel(vec![span("a", 0, 20, 30)]));
+        let late_id = late.id.clone();
+        let r = send(&mut op, late)?;
+        assert!(r.events.is_empty());
+        assert_eq!(CbAction::Ack, r.insights[0].cb);
+        assert_eq!(late_id, r.insights[0].id);
+        // events with spans of pending traces are not
+        let mixed = event(150, otel(vec![span("a", 0, 20, 30), span("b", 0, 20, 30)]));
+        assert!(send(&mut op, mixed)?.insights.is_empty());
+        Ok(())
+    }
+}
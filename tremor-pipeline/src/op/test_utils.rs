@@ -0,0 +1,42 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Helpers for operator tests
+
+use crate::op::prelude::*;
+use tremor_common::ids::Id;
+use tremor_script::prelude::*;
+
+/// Send `event` to the `in` port of `op`
+pub(crate) fn send(op: &mut impl Operator, event: Event) -> Result<EventAndInsights> {
+    op.on_event(OperatorId::new(0), "in", &mut Value::null(), event)
+}
+
+/// Send a tick signal at `ingest_ns` to `op`
+pub(crate) fn tick(op: &mut impl Operator, ingest_ns: u64) -> Result<EventAndInsights> {
+    let mut signal = Event {
+        ingest_ns,
+        ..Event::signal_tick()
+    };
+    op.on_signal(OperatorId::new(0), &mut Value::null(), &mut signal)
+}
+
+/// The count of the metric of `op` with the tag `tag` set to `value`
+pub(crate) fn count(op: &impl Operator, tag: &str, value: &str) -> Result<u64> {
+    op.metrics(&HashMap::new(), 0)?
+        .iter()
+        .find(|m| m.get("tags").and_then(|tags| tags.get_str(tag)) == Some(value))
+        .and_then(|m| m.get("fields").and_then(|fields| fields.get_u64("count")))
+        .ok_or_else(|| format!("no metric with {tag} {value}").into())
+}
@@ -100,6 +100,10 @@ impl Operator for TrickleOperator {
         self.op.metrics(tags, timestamp)
     }
 
+    fn on_update(&mut self, update: &Value) -> Result<()> {
+        self.op.on_update(update)
+    }
+
     fn skippable(&self) -> bool {
         self.op.skippable()
     }
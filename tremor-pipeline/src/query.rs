@@ -702,6 +702,7 @@ impl Query {
                 insights: Vec::new(),
                 dot: format!("{}", dot),
                 metrics_channel: METRICS_CHANNEL.tx(),
+                tracer: None,
                 outputs,
             })
         }
@@ -0,0 +1,257 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use async_broadcast::{broadcast, Receiver, Sender, TrySendError};
+use std::sync::atomic::{AtomicU64, Ordering};
+use tremor_value::{literal, Value};
+use value_trait::Mutable;
+
+/// `SPAN_KIND_INTERNAL` in the `OpenTelemetry` span model
+const SPAN_KIND_INTERNAL: u64 = 1;
+
+/// Trace context of a sampled event, propagated along with its `EventId`
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Default,
+    simd_json_derive::Serialize,
+    simd_json_derive::Deserialize,
+)]
+pub struct SpanContext {
+    trace_id_hi: u64,
+    trace_id_lo: u64,
+    span_id: u64,
+}
+
+impl SpanContext {
+    /// context of the root span of a new trace
+    #[must_use]
+    pub fn new_trace() -> Self {
+        Self {
+            trace_id_hi: rand::random(),
+            trace_id_lo: rand::random(),
+            span_id: rand::random(),
+        }
+    }
+
+    /// context of a new span within the same trace
+    #[must_use]
+    pub fn child(&self) -> Self {
+        Self {
+            span_id: rand::random(),
+            ..*self
+        }
+    }
+
+    /// hex encoded 16 byte trace id
+    #[must_use]
+    pub fn trace_id(&self) -> String {
+        format!("{:016x}{:016x}", self.trace_id_hi, self.trace_id_lo)
+    }
+
+    /// hex encoded 8 byte span id
+    #[must_use]
+    pub fn span_id(&self) -> String {
+        format!("{:016x}", self.span_id)
+    }
+}
+
+/// A finished span
+#[derive(Debug, Clone, PartialEq)]
+pub struct Span {
+    /// context of this span
+    pub context: SpanContext,
+    /// context of the parent span, `None` for the root span of a trace
+    pub parent: Option<SpanContext>,
+    /// span name
+    pub name: String,
+    /// start timestamp in nanoseconds
+    pub start_ns: u64,
+    /// end timestamp in nanoseconds
+    pub end_ns: u64,
+    /// span attributes as a record
+    pub attributes: Value<'static>,
+}
+
+impl Span {
+    /// The span in the `OpenTelemetry` json mapping used by the `otel_client` and `otel_server` connectors
+    #[must_use]
+    pub fn to_value(&self) -> Value<'static> {
+        let mut span = literal!({
+            "trace_id": self.context.trace_id(),
+            "span_id": self.context.span_id(),
+            "name": self.name.clone(),
+            "kind": SPAN_KIND_INTERNAL,
+            "start_time_unix_nano": self.start_ns,
+            "end_time_unix_nano": self.end_ns,
+            "attributes": self.attributes.clone()
+        });
+        if let Some(parent) = &self.parent {
+            span.try_insert("parent_span_id", parent.span_id());
+        }
+        span
+    }
+}
+
+/// Samples events for tracing and collects their spans
+///
+/// Every runtime has its own, shared by all its connectors and pipelines.
+#[derive(Debug)]
+pub struct Tracer {
+    sample: AtomicU64,
+    seen: AtomicU64,
+    tx: Sender<Span>,
+    rx: Receiver<Span>,
+}
+
+/// Spans buffered by default for slow receivers
+const DEFAULT_QSIZE: usize = 1024;
+
+impl Default for Tracer {
+    fn default() -> Self {
+        Self::new(DEFAULT_QSIZE)
+    }
+}
+
+impl Tracer {
+    /// A disabled tracer buffering up to `qsize` spans for slow receivers
+    #[must_use]
+    pub fn new(qsize: usize) -> Self {
+        let (mut tx, rx) = broadcast(qsize);
+        // spans nobody keeps up with are dropped, tracing must never slow down the events it traces
+        tx.set_overflow(true);
+        Self {
+            sample: AtomicU64::new(0),
+            seen: AtomicU64::new(0),
+            tx,
+            rx,
+        }
+    }
+
+    /// Trace every `sample`th event entering the runtime, `0` disables tracing
+    pub fn set_sample(&self, sample: u64) {
+        self.sample.store(sample, Ordering::Relaxed);
+    }
+
+    /// is tracing enabled at all
+    #[must_use]
+    pub fn is_enabled(&self) -> bool {
+        self.sample.load(Ordering::Relaxed) > 0
+    }
+
+    /// Samples the next event, returns `true` if it is to be traced
+    pub fn sample(&self) -> bool {
+        let sample = self.sample.load(Ordering::Relaxed);
+        sample > 0 && self.seen.fetch_add(1, Ordering::Relaxed) % sample == 0
+    }
+
+    /// Records the root span of a new trace, for an event that was sampled
+    ///
+    /// Returns the context to propagate with the event
+    pub fn root_span(
+        &self,
+        name: impl Into<String>,
+        start_ns: u64,
+        end_ns: u64,
+        attributes: Value<'static>,
+    ) -> SpanContext {
+        let context = SpanContext::new_trace();
+        self.record(Span {
+            context,
+            parent: None,
+            name: name.into(),
+            start_ns,
+            end_ns,
+            attributes,
+        });
+        context
+    }
+
+    /// Records a span as child of `parent`
+    ///
+    /// Returns the context of the recorded span
+    pub fn child_span(
+        &self,
+        parent: &SpanContext,
+        name: impl Into<String>,
+        start_ns: u64,
+        end_ns: u64,
+        attributes: Value<'static>,
+    ) -> SpanContext {
+        let context = parent.child();
+        self.record(Span {
+            context,
+            parent: Some(*parent),
+            name: name.into(),
+            start_ns,
+            end_ns,
+            attributes,
+        });
+        context
+    }
+
+    fn record(&self, span: Span) {
+        if let Err(TrySendError::Closed(_)) = self.tx.try_broadcast(span) {
+            error!("Tracer channel closed");
+        }
+    }
+
+    /// Get a receiver for all recorded spans
+    #[must_use]
+    pub fn rx(&self) -> Receiver<Span> {
+        self.rx.clone()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::errors::Result;
+    use value_trait::ValueAccess;
+
+    #[test]
+    fn sampling_and_spans() -> Result<()> {
+        let tracer = Tracer::new(8);
+        let mut rx = tracer.rx();
+        assert!(!tracer.is_enabled());
+        assert!(!tracer.sample());
+
+        tracer.set_sample(2);
+        assert!(tracer.sample());
+        assert!(!tracer.sample());
+        assert!(tracer.sample());
+        let root = tracer.root_span("source", 1, 2, Value::object());
+        let child = tracer.child_span(&root, "operator", 2, 3, literal!({"node": "limit"}));
+        assert_eq!(root.trace_id(), child.trace_id());
+        assert_ne!(root.span_id(), child.span_id());
+
+        let span = rx.try_recv().ok().ok_or("no root span")?;
+        assert_eq!(root, span.context);
+        assert_eq!(None, span.parent);
+        let span = rx.try_recv().ok().ok_or("no child span")?.to_value();
+        assert_eq!(Some(32), span.get_str("trace_id").map(str::len));
+        assert_eq!(
+            Some(root.span_id().as_str()),
+            span.get_str("parent_span_id")
+        );
+        assert_eq!(Some(child.span_id().as_str()), span.get_str("span_id"));
+        assert_eq!(Some("operator"), span.get_str("name"));
+        assert_eq!(Some("limit"), span.get("attributes").get_str("node"));
+        assert!(rx.try_recv().is_err());
+        Ok(())
+    }
+}
@@ -281,6 +281,10 @@ impl crate::ast::node_id::BaseRef for CreateStmt<'_> {
     }
 }
 
+/// Keys in the `with` of a `deploy` statement configuring the deployment itself,
+/// instead of being passed as arguments to the flow, flows can't declare arguments with these names
+pub const DEPLOY_SETTINGS: [&str; 2] = ["drain_timeout", "supervision"];
+
 /// A create statement
 #[derive(Clone, Debug, PartialEq, Serialize)]
 pub struct DeployFlow<'script> {
@@ -291,6 +295,8 @@ pub struct DeployFlow<'script> {
     pub instance_alias: String,
     /// Atomic unit of deployment
     pub defn: FlowDefinition<'script>,
+    /// Settings of the deployment, one of [`DEPLOY_SETTINGS`] each
+    pub settings: Value<'script>,
     /// Documentation comments
     #[serde(skip)]
     pub docs: Option<String>,
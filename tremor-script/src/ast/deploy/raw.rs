@@ -17,9 +17,9 @@
 
 use super::{
     BaseExpr, ConnectStmt, ConnectorDefinition, CreateStmt, CreateTargetDefinition, DeployEndpoint,
-    DeployFlow, FlowDefinition, Value,
+    DeployFlow, FlowDefinition, Value, DEPLOY_SETTINGS,
 };
-use crate::ast::optimizer::Optimizer;
+use crate::ast::{optimizer::Optimizer, visitors::ConstFolder};
 use crate::{
     ast::{
         base_expr::Ranged,
@@ -349,6 +349,19 @@ impl<'script> Upable<'script> for FlowDefinitionRaw<'script> {
         helper.leave_scope()?;
         // we need to evaluate args in the outer scope
         let params = self.params.up(helper)?;
+        // deploy settings are taken out of the `with` of a deployment, such an argument would never be set
+        if let Some((name, _)) = params
+            .args
+            .0
+            .iter()
+            .find(|(name, _)| DEPLOY_SETTINGS.contains(&name.as_str()))
+        {
+            return err_generic(
+                &params,
+                name,
+                &format!("Reserved argument: {name} is a deploy setting"),
+            );
+        }
 
         let flow_defn = FlowDefinition {
             mid,
@@ -500,7 +513,18 @@ impl<'script> Upable<'script> for DeployFlowRaw<'script> {
             )
             .into());
         };
-        let upped_params = self.params.up(helper)?;
+        let mut upped_params = self.params.up(helper)?;
+        // deployment settings are no arguments of the flow
+        let (settings, args): (Vec<_>, Vec<_>) = upped_params
+            .with
+            .0
+            .drain(..)
+            .partition(|(k, _)| DEPLOY_SETTINGS.contains(&k.as_str()));
+        upped_params.with.0 = args;
+        let settings = settings
+            .into_iter()
+            .map(|(k, v)| Ok((k.id, ConstFolder::reduce_to_val(helper, v)?)))
+            .collect::<Result<Value>>()?;
         defn.params.ingest_creational_with(&upped_params)?;
         Optimizer::new(helper).walk_definitional_args(&mut defn.params)?;
         let defn_args = defn.params.render()?;
@@ -514,6 +538,7 @@ impl<'script> Upable<'script> for DeployFlowRaw<'script> {
             instance_alias: self.id.id.to_string(),
             from_target: self.target,
             defn,
+            settings,
             docs: self
                 .docs
                 .map(|d| d.iter().map(|l| l.trim()).collect::<Vec<_>>().join("\n")),